@@ -0,0 +1,301 @@
+//! Accounting of the gas payments made for messages and the gas spent
+//! delivering them, so the costs of the relayer can be reconciled with what
+//! apps paid.
+//!
+//! Every payment and expenditure is kept in the gas ledger of the origin
+//! database. Payments are in native tokens of the origin and expenditures in
+//! native tokens of the destination, so amounts of tokens are reported along
+//! with the domain whose native token they are in, and payments and
+//! expenditures are totalled separately rather than netted.
+
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Serializer};
+
+use hyperlane_base::db::{GasLedgerEntry, GasLedgerEntryKind, HyperlaneRocksDB};
+use hyperlane_core::{H256, H512, U256};
+
+/// A gas ledger entry along with the message it is for.
+#[derive(Debug, Clone, Serialize)]
+pub struct LedgerRecord {
+    pub origin: u32,
+    /// `None` if the message has not been indexed yet.
+    pub destination: Option<u32>,
+    pub sender: Option<H256>,
+    pub recipient: Option<H256>,
+    pub message_id: H256,
+    pub kind: &'static str,
+    pub transaction_id: H512,
+    /// The domain whose native token `tokens` are in: the origin for payments
+    /// and the destination for expenditures. `None` if the message of an
+    /// expenditure has not been indexed yet.
+    pub token_domain: Option<u32>,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub tokens: U256,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub gas: U256,
+    pub timestamp: u64,
+}
+
+/// Totals of the payments or of the expenditures of a route or of a sender on
+/// a route.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub count: u64,
+    /// The domain whose native token `tokens` are in.
+    pub token_domain: u32,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub tokens: U256,
+    /// Destination gas paid for or used.
+    #[serde(serialize_with = "serialize_decimal")]
+    pub gas: U256,
+}
+
+impl Totals {
+    fn new(token_domain: u32) -> Self {
+        Self {
+            count: 0,
+            token_domain,
+            tokens: U256::zero(),
+            gas: U256::zero(),
+        }
+    }
+
+    fn add(&mut self, record: &LedgerRecord) {
+        self.count += 1;
+        self.tokens = self.tokens.saturating_add(record.tokens);
+        self.gas = self.gas.saturating_add(record.gas);
+    }
+}
+
+/// The payments made on the origin of a route and the expenditures on its
+/// destination. Only their gas can be compared, their tokens are of different
+/// chains.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Balance {
+    pub payments: Totals,
+    pub expenditures: Totals,
+}
+
+impl Balance {
+    fn new(origin: u32, destination: u32) -> Self {
+        Self {
+            payments: Totals::new(origin),
+            expenditures: Totals::new(destination),
+        }
+    }
+
+    fn add(&mut self, record: &LedgerRecord) {
+        match record.kind {
+            PAYMENT => self.payments.add(record),
+            _ => self.expenditures.add(record),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RouteBalance {
+    pub origin: u32,
+    pub destination: u32,
+    #[serde(flatten)]
+    pub balance: Balance,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SenderBalance {
+    pub origin: u32,
+    pub destination: u32,
+    pub sender: H256,
+    #[serde(flatten)]
+    pub balance: Balance,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Balances {
+    pub routes: Vec<RouteBalance>,
+    pub senders: Vec<SenderBalance>,
+}
+
+const PAYMENT: &str = "payment";
+const EXPENDITURE: &str = "expenditure";
+
+/// Read the gas ledgers of the given origin databases, oldest entries first.
+pub fn ledger<'a>(dbs: impl IntoIterator<Item = &'a HyperlaneRocksDB>) -> Vec<LedgerRecord> {
+    let mut records: Vec<_> = dbs
+        .into_iter()
+        .flat_map(|db| {
+            db.retrieve_gas_ledger()
+                .into_iter()
+                .map(move |entry| to_record(db, entry))
+        })
+        .collect();
+    records.sort_by_key(|r| r.timestamp);
+    records
+}
+
+fn to_record(db: &HyperlaneRocksDB, entry: GasLedgerEntry) -> LedgerRecord {
+    let message = db
+        .retrieve_indexed_message_by_id(&entry.message_id)
+        .ok()
+        .flatten();
+    let origin = db.domain().id();
+    let destination = message.as_ref().map(|m| m.destination);
+    LedgerRecord {
+        origin,
+        destination,
+        sender: message.as_ref().map(|m| m.sender),
+        recipient: message.as_ref().map(|m| m.recipient),
+        message_id: entry.message_id,
+        kind: match entry.kind {
+            GasLedgerEntryKind::Payment => PAYMENT,
+            GasLedgerEntryKind::Expenditure => EXPENDITURE,
+        },
+        transaction_id: entry.transaction_id,
+        token_domain: match entry.kind {
+            GasLedgerEntryKind::Payment => Some(origin),
+            GasLedgerEntryKind::Expenditure => destination,
+        },
+        tokens: entry.tokens,
+        gas: entry.gas,
+        timestamp: entry.timestamp,
+    }
+}
+
+/// Sum up ledger records by route and by sender on each route. Records for
+/// messages which have not been indexed yet are left out.
+pub fn balances(records: &[LedgerRecord]) -> Balances {
+    let mut routes: BTreeMap<(u32, u32), Balance> = BTreeMap::new();
+    let mut senders: BTreeMap<(u32, u32, H256), Balance> = BTreeMap::new();
+    for record in records {
+        let (Some(destination), Some(sender)) = (record.destination, record.sender) else {
+            continue;
+        };
+        let new_balance = || Balance::new(record.origin, destination);
+        routes
+            .entry((record.origin, destination))
+            .or_insert_with(new_balance)
+            .add(record);
+        senders
+            .entry((record.origin, destination, sender))
+            .or_insert_with(new_balance)
+            .add(record);
+    }
+    Balances {
+        routes: routes
+            .into_iter()
+            .map(|((origin, destination), balance)| RouteBalance {
+                origin,
+                destination,
+                balance,
+            })
+            .collect(),
+        senders: senders
+            .into_iter()
+            .map(|((origin, destination, sender), balance)| SenderBalance {
+                origin,
+                destination,
+                sender,
+                balance,
+            })
+            .collect(),
+    }
+}
+
+/// Format ledger records as CSV with a header row.
+pub fn to_csv(records: &[LedgerRecord]) -> String {
+    fn opt<T: std::fmt::Debug>(v: Option<T>) -> String {
+        v.map(|v| format!("{v:?}")).unwrap_or_default()
+    }
+
+    let mut csv = String::from(
+        "timestamp,kind,origin,destination,sender,recipient,message_id,transaction_id,token_domain,tokens,gas\n",
+    );
+    for r in records {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{:?},{:?},{},{},{}\n",
+            r.timestamp,
+            r.kind,
+            r.origin,
+            r.destination.map(|d| d.to_string()).unwrap_or_default(),
+            opt(r.sender),
+            opt(r.recipient),
+            r.message_id,
+            r.transaction_id,
+            r.token_domain.map(|d| d.to_string()).unwrap_or_default(),
+            r.tokens,
+            r.gas,
+        ));
+    }
+    csv
+}
+
+fn serialize_decimal<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(kind: &'static str, sender: u64, destination: Option<u32>) -> LedgerRecord {
+        LedgerRecord {
+            origin: 1,
+            destination,
+            sender: destination.map(|_| H256::from_low_u64_be(sender)),
+            recipient: destination.map(|_| H256::zero()),
+            message_id: H256::zero(),
+            kind,
+            transaction_id: H512::zero(),
+            token_domain: if kind == PAYMENT {
+                Some(1)
+            } else {
+                destination
+            },
+            tokens: U256::from(100),
+            gas: U256::from(10),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_balances_by_route_and_sender() {
+        let records = vec![
+            record(PAYMENT, 1, Some(2)),
+            record(PAYMENT, 1, Some(2)),
+            record(EXPENDITURE, 1, Some(2)),
+            record(PAYMENT, 2, Some(2)),
+            record(PAYMENT, 1, Some(3)),
+            // Not indexed yet
+            record(PAYMENT, 1, None),
+        ];
+        let balances = balances(&records);
+
+        assert_eq!(balances.routes.len(), 2);
+        let route = &balances.routes[0];
+        assert_eq!((route.origin, route.destination), (1, 2));
+        assert_eq!(route.balance.payments.count, 3);
+        assert_eq!(route.balance.payments.token_domain, 1);
+        assert_eq!(route.balance.payments.tokens, U256::from(300));
+        assert_eq!(route.balance.payments.gas, U256::from(30));
+        assert_eq!(route.balance.expenditures.token_domain, 2);
+        assert_eq!(route.balance.expenditures.tokens, U256::from(100));
+        assert_eq!(route.balance.expenditures.gas, U256::from(10));
+
+        assert_eq!(balances.senders.len(), 3);
+        let sender = &balances.senders[0];
+        assert_eq!(sender.sender, H256::from_low_u64_be(1));
+        assert_eq!(sender.balance.payments.count, 2);
+        assert_eq!(sender.balance.expenditures.count, 1);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let csv = to_csv(&[record(PAYMENT, 1, Some(2)), record(EXPENDITURE, 1, None)]);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("0,payment,1,2,0x"));
+        assert!(lines[1].ends_with(",1,100,10"));
+        assert!(lines[2].starts_with("0,expenditure,1,,,,0x"));
+        assert!(lines[2].ends_with(",,100,10"));
+    }
+}
@@ -0,0 +1,664 @@
+//! HTTP API for operators to inspect and manipulate the relayer's message
+//! queues. It is served alongside `/metrics` by the metrics server, and only
+//! answers requests carrying the configured token in an
+//! `Authorization: Bearer <token>` header.
+//!
+//! - `GET /queues`: list the operations in every submitter's prepare and
+//!   confirm queues, with why their last attempt failed.
+//! - `POST /messages/<id>/retry`: clear the retry count and backoff of a
+//!   message so it is attempted again as soon as possible.
+//! - `POST /messages/<id>/priority/<priority>`: set the priority of a message.
+//!   Messages with a higher priority are attempted before others that are
+//!   equally ready to be attempted.
+//! - `POST /messages/<id>/drop`: remove a message from the queues and record
+//!   it in the dead-letter queue.
+//! - `GET /dead_letters`: list the messages in the dead-letter queue of every
+//!   origin chain.
+//! - `POST /dead_letters/<origin domain id>/<nonce>/requeue`: remove a message
+//!   from the dead-letter queue and attempt to deliver it again, e.g. after its
+//!   recipient has been deployed.
+//! - `GET /messages/<id>/ism` or `GET /messages/<origin domain id>/<nonce>/ism`:
+//!   describe the tree of ISMs verifying a message, with the validators of each
+//!   multisig ISM and what would stop each ISM from verifying the message. Add
+//!   `/text` to the path for a readable tree rather than JSON.
+//! - `GET /ledger`: list the gas payments and expenditures of every origin
+//!   chain as JSON, or as CSV with `GET /ledger/csv`.
+//! - `GET /ledger/balances`: total the gas payments and expenditures by route
+//!   and by sender on each route.
+//! - `GET /equivocations`: list the checkpoints found to have been signed by a
+//!   validator for an index it had already signed a different checkpoint for,
+//!   with both signed checkpoints as evidence of the fraud. Legacy checkpoints
+//!   are listed without a message id.
+//!
+//! Routes taking an origin domain id refer to the default mailbox deployment
+//! of the origin chain. Add `?deployment=<name>` to refer to one of the
+//! additional deployments on it instead.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{info, warn};
+use warp::{
+    filters::BoxedFilter,
+    http::header::CONTENT_TYPE,
+    http::StatusCode,
+    reply::{json, with_header, with_status},
+    Filter, Reply,
+};
+
+use hyperlane_base::db::{Equivocation, HyperlaneRocksDB, LegacyEquivocation};
+use hyperlane_core::{DeploymentKey, HyperlaneDomain, HyperlaneMessage, H256};
+
+use crate::{
+    accounting,
+    msg::{
+        metadata::inspect_ism,
+        pending_message::MessageContext,
+        pending_operation::PendingOperation,
+        serial_submitter::{OpQueue, SubmitterQueues},
+    },
+};
+
+/// The queues of every running SerialSubmitter.
+#[derive(Debug, Clone, Default)]
+pub struct QueueRegistry(Arc<RwLock<Vec<SubmitterQueues>>>);
+
+impl QueueRegistry {
+    /// Make the queues of a submitter available to the API.
+    pub fn register(&self, queues: SubmitterQueues) {
+        self.0.write().unwrap().push(queues);
+    }
+
+    fn submitters(&self) -> Vec<SubmitterQueues> {
+        self.0.read().unwrap().clone()
+    }
+}
+
+/// The dead-letter queue of an origin chain and the channel its message
+/// processor takes the nonces of re-enqueued messages from.
+#[derive(Debug, Clone)]
+pub struct DeadLetterQueue {
+    pub db: HyperlaneRocksDB,
+    pub requeue_sender: UnboundedSender<u32>,
+}
+
+/// Dead-letter queues by origin deployment.
+pub type DeadLetterQueues = Arc<HashMap<DeploymentKey, DeadLetterQueue>>;
+
+/// Contexts of the messages of each route by origin deployment and
+/// destination domain id, used to inspect the ISMs verifying messages.
+pub type MessageContexts = Arc<HashMap<(DeploymentKey, u32), Arc<MessageContext>>>;
+
+#[derive(Debug, Serialize)]
+struct QueueStatus {
+    destination: String,
+    lane: usize,
+    queue: &'static str,
+    operations: Vec<OperationStatus>,
+}
+
+#[derive(Debug, Serialize)]
+struct OperationStatus {
+    id: H256,
+    num_retries: u32,
+    /// Seconds until the operation may next be attempted, `None` if it may be
+    /// attempted immediately.
+    next_attempt_after_s: Option<u64>,
+    priority: u32,
+    /// Why the last attempt failed, e.g. the decoded reason the simulated
+    /// process call reverted with.
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DeadLetterStatus {
+    origin: String,
+    nonce: u32,
+    id: H256,
+    destination: Option<u32>,
+    recipient: Option<H256>,
+    reason: String,
+    last_error: Option<String>,
+    timestamp: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum EquivocationStatus {
+    WithMessageId(Equivocation),
+    Legacy(LegacyEquivocation),
+}
+
+/// A change an operator can make to a queued operation.
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Retry,
+    SetPriority(u32),
+    Drop,
+}
+
+/// A message to inspect the ISMs of.
+#[derive(Debug, Clone)]
+enum MessageRef {
+    Id(H256),
+    Nonce {
+        origin: u32,
+        deployment: DeploymentQuery,
+        nonce: u32,
+    },
+}
+
+/// The mailbox deployment on an origin chain a route refers to.
+#[derive(Debug, Clone, Deserialize)]
+struct DeploymentQuery {
+    /// Name of an additional deployment, `None` for the default one.
+    deployment: Option<String>,
+}
+
+impl DeploymentQuery {
+    /// The key of this deployment on the `origin` chain.
+    fn key(&self, origin: u32) -> DeploymentKey {
+        DeploymentKey {
+            domain_id: origin,
+            deployment: self.deployment.clone(),
+        }
+    }
+}
+
+/// How the gas ledger is exported.
+#[derive(Debug, Clone, Copy)]
+enum LedgerFormat {
+    Json,
+    Csv,
+    Balances,
+}
+
+/// Build the routes of the admin API.
+pub fn routes(
+    registry: QueueRegistry,
+    dead_letters: DeadLetterQueues,
+    origin_dbs: Arc<Vec<HyperlaneRocksDB>>,
+    message_contexts: MessageContexts,
+) -> BoxedFilter<(Box<dyn Reply>,)> {
+    let with_registry = warp::any().map(move || registry.clone());
+    let with_dead_letters = warp::any().map(move || dead_letters.clone());
+    let with_origin_dbs = warp::any().map(move || origin_dbs.clone());
+    let with_message_contexts = warp::any().map(move || message_contexts.clone());
+
+    let list = warp::get()
+        .and(warp::path!("queues"))
+        .and(with_registry.clone())
+        .and_then(list_queues);
+    let retry = warp::post()
+        .and(warp::path!("messages" / H256 / "retry"))
+        .and(with_registry.clone())
+        .and_then(|id, registry| update_operation(registry, id, Action::Retry));
+    let set_priority = warp::post()
+        .and(warp::path!("messages" / H256 / "priority" / u32))
+        .and(with_registry.clone())
+        .and_then(|id, priority, registry| {
+            update_operation(registry, id, Action::SetPriority(priority))
+        });
+    let drop_op = warp::post()
+        .and(warp::path!("messages" / H256 / "drop"))
+        .and(with_registry)
+        .and_then(|id, registry| update_operation(registry, id, Action::Drop));
+
+    let list_dead = warp::get()
+        .and(warp::path!("dead_letters"))
+        .and(with_dead_letters.clone())
+        .and_then(list_dead_letters);
+    let requeue = warp::post()
+        .and(warp::path!("dead_letters" / u32 / u32 / "requeue"))
+        .and(warp::query::<DeploymentQuery>())
+        .and(with_dead_letters)
+        .and_then(requeue_dead_letter);
+
+    let inspect_by_id = warp::get()
+        .and(warp::path!("messages" / H256 / "ism"))
+        .and(with_origin_dbs.clone())
+        .and(with_message_contexts.clone())
+        .and_then(|id, dbs, ctxs| inspect_message(MessageRef::Id(id), false, dbs, ctxs));
+    let inspect_by_id_text = warp::get()
+        .and(warp::path!("messages" / H256 / "ism" / "text"))
+        .and(with_origin_dbs.clone())
+        .and(with_message_contexts.clone())
+        .and_then(|id, dbs, ctxs| inspect_message(MessageRef::Id(id), true, dbs, ctxs));
+    let inspect_by_nonce = warp::get()
+        .and(warp::path!("messages" / u32 / u32 / "ism"))
+        .and(warp::query::<DeploymentQuery>())
+        .and(with_origin_dbs.clone())
+        .and(with_message_contexts.clone())
+        .and_then(|origin, nonce, deployment, dbs, ctxs| {
+            let message = MessageRef::Nonce {
+                origin,
+                deployment,
+                nonce,
+            };
+            inspect_message(message, false, dbs, ctxs)
+        });
+    let inspect_by_nonce_text = warp::get()
+        .and(warp::path!("messages" / u32 / u32 / "ism" / "text"))
+        .and(warp::query::<DeploymentQuery>())
+        .and(with_origin_dbs.clone())
+        .and(with_message_contexts)
+        .and_then(|origin, nonce, deployment, dbs, ctxs| {
+            let message = MessageRef::Nonce {
+                origin,
+                deployment,
+                nonce,
+            };
+            inspect_message(message, true, dbs, ctxs)
+        });
+
+    let ledger = warp::get()
+        .and(warp::path!("ledger"))
+        .and(with_origin_dbs.clone())
+        .and_then(|dbs| export_ledger(dbs, LedgerFormat::Json));
+    let ledger_csv = warp::get()
+        .and(warp::path!("ledger" / "csv"))
+        .and(with_origin_dbs.clone())
+        .and_then(|dbs| export_ledger(dbs, LedgerFormat::Csv));
+    let balances = warp::get()
+        .and(warp::path!("ledger" / "balances"))
+        .and(with_origin_dbs.clone())
+        .and_then(|dbs| export_ledger(dbs, LedgerFormat::Balances));
+
+    let equivocations = warp::get()
+        .and(warp::path!("equivocations"))
+        .and(with_origin_dbs)
+        .and_then(list_equivocations);
+
+    list.or(retry)
+        .unify()
+        .or(set_priority)
+        .unify()
+        .or(drop_op)
+        .unify()
+        .or(list_dead)
+        .unify()
+        .or(requeue)
+        .unify()
+        .or(inspect_by_id)
+        .unify()
+        .or(inspect_by_id_text)
+        .unify()
+        .or(inspect_by_nonce)
+        .unify()
+        .or(inspect_by_nonce_text)
+        .unify()
+        .or(ledger)
+        .unify()
+        .or(ledger_csv)
+        .unify()
+        .or(balances)
+        .unify()
+        .or(equivocations)
+        .unify()
+        .boxed()
+}
+
+async fn list_queues(registry: QueueRegistry) -> Result<Box<dyn Reply>, Infallible> {
+    let now = Instant::now();
+    let mut statuses = vec![];
+    for submitter in registry.submitters() {
+        for (queue_name, queue) in queue_names(&submitter) {
+            let operations = queue
+                .lock()
+                .await
+                .iter()
+                .map(|Reverse(op)| OperationStatus {
+                    id: op.id(),
+                    num_retries: op.num_retries(),
+                    next_attempt_after_s: op
+                        ._next_attempt_after()
+                        .map(|a| a.saturating_duration_since(now).as_secs()),
+                    priority: op.priority(),
+                    last_error: op.last_error().map(str::to_owned),
+                })
+                .collect();
+            statuses.push(QueueStatus {
+                destination: submitter.domain.name().to_owned(),
+                lane: submitter.lane,
+                queue: queue_name,
+                operations,
+            });
+        }
+    }
+    Ok(Box::new(json(&statuses)))
+}
+
+/// Apply an action to the queued operation with the given id.
+async fn update_operation(
+    registry: QueueRegistry,
+    id: H256,
+    action: Action,
+) -> Result<Box<dyn Reply>, Infallible> {
+    let mut found = false;
+    for submitter in registry.submitters() {
+        for (queue_name, queue) in queue_names(&submitter) {
+            let mut queue = queue.lock().await;
+            if !queue.iter().any(|Reverse(op)| op.id() == id) {
+                continue;
+            }
+            // The heap has to be rebuilt since changing an operation may change
+            // its position in it.
+            let ops = std::mem::take(&mut *queue).into_vec();
+            *queue = ops
+                .into_iter()
+                .filter_map(|Reverse(mut op)| {
+                    if op.id() != id {
+                        return Some(Reverse(op));
+                    }
+                    info!(
+                        ?op,
+                        ?action,
+                        destination=%submitter.domain,
+                        lane=submitter.lane,
+                        queue=queue_name,
+                        "Updating operation at the request of an operator"
+                    );
+                    match action {
+                        Action::Retry => op.reset_attempts(),
+                        Action::SetPriority(priority) => op.set_priority(priority),
+                        Action::Drop => {
+                            op.on_drop("Dropped by an operator");
+                            return None;
+                        }
+                    }
+                    Some(Reverse(op))
+                })
+                .collect();
+            found = true;
+        }
+    }
+    Ok(if found {
+        Box::new(StatusCode::OK)
+    } else {
+        Box::new(with_status(
+            "No queued operation with that id",
+            StatusCode::NOT_FOUND,
+        ))
+    })
+}
+
+async fn list_dead_letters(dead_letters: DeadLetterQueues) -> Result<Box<dyn Reply>, Infallible> {
+    let mut statuses = vec![];
+    for queue in dead_letters.values() {
+        for dead_letter in queue.db.retrieve_dead_letters() {
+            let message = queue
+                .db
+                .retrieve_message_by_nonce(dead_letter.nonce)
+                .ok()
+                .flatten();
+            statuses.push(DeadLetterStatus {
+                origin: queue.db.domain().name().to_owned(),
+                nonce: dead_letter.nonce,
+                id: dead_letter.message_id,
+                destination: message.as_ref().map(|m| m.destination),
+                recipient: message.as_ref().map(|m| m.recipient),
+                reason: dead_letter.reason,
+                last_error: dead_letter.last_error,
+                timestamp: dead_letter.timestamp,
+            });
+        }
+    }
+    Ok(Box::new(json(&statuses)))
+}
+
+/// Remove a message from the dead-letter queue of its origin and hand it back
+/// to the origin's message processor.
+async fn requeue_dead_letter(
+    origin: u32,
+    nonce: u32,
+    deployment: DeploymentQuery,
+    dead_letters: DeadLetterQueues,
+) -> Result<Box<dyn Reply>, Infallible> {
+    let Some(queue) = dead_letters.get(&deployment.key(origin)) else {
+        return Ok(Box::new(with_status(
+            "Unknown origin domain",
+            StatusCode::NOT_FOUND,
+        )));
+    };
+    match queue.db.remove_dead_letter_by_nonce(nonce) {
+        Ok(Some(dead_letter)) => {
+            info!(
+                ?dead_letter,
+                origin = %queue.db.domain(),
+                "Requeueing dead-lettered message at the request of an operator"
+            );
+            if queue.requeue_sender.send(nonce).is_err() {
+                warn!(origin = %queue.db.domain(), nonce, "Message processor is not running, message will be picked up on restart");
+            }
+            Ok(Box::new(StatusCode::OK))
+        }
+        Ok(None) => Ok(Box::new(with_status(
+            "No dead-lettered message with that nonce",
+            StatusCode::NOT_FOUND,
+        ))),
+        Err(e) => Ok(Box::new(with_status(
+            e.to_string(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ))),
+    }
+}
+
+/// Describe the tree of ISMs verifying an indexed message, as JSON or as
+/// text.
+async fn inspect_message(
+    message: MessageRef,
+    as_text: bool,
+    origin_dbs: Arc<Vec<HyperlaneRocksDB>>,
+    message_contexts: MessageContexts,
+) -> Result<Box<dyn Reply>, Infallible> {
+    let Some((origin, message)) = find_message(&origin_dbs, &message) else {
+        return Ok(Box::new(with_status(
+            "No indexed message found",
+            StatusCode::NOT_FOUND,
+        )));
+    };
+    let Some(ctx) = message_contexts.get(&(origin.deployment_key(), message.destination)) else {
+        return Ok(Box::new(with_status(
+            "Messages are not relayed between the origin and destination of the message",
+            StatusCode::NOT_FOUND,
+        )));
+    };
+    let ism_address = match ctx
+        .destination_mailbox
+        .recipient_ism(message.recipient)
+        .await
+    {
+        Ok(ism_address) => ism_address,
+        Err(e) => {
+            return Ok(Box::new(with_status(
+                format!("Error when fetching the ISM of the recipient: {e}"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )))
+        }
+    };
+    let tree = inspect_ism(&ctx.metadata_builder, ism_address, &message).await;
+    Ok(if as_text {
+        Box::new(tree.to_string())
+    } else {
+        Box::new(json(&tree))
+    })
+}
+
+/// Find an indexed message and the domain of the origin DB it was found in.
+fn find_message<'a>(
+    origin_dbs: &'a [HyperlaneRocksDB],
+    message: &MessageRef,
+) -> Option<(&'a HyperlaneDomain, HyperlaneMessage)> {
+    origin_dbs.iter().find_map(|db| {
+        let message = match message {
+            MessageRef::Id(id) => db.retrieve_indexed_message_by_id(id).ok().flatten(),
+            MessageRef::Nonce {
+                origin,
+                deployment,
+                nonce,
+            } if db.domain().deployment_key() == deployment.key(*origin) => {
+                db.retrieve_message_by_nonce(*nonce).ok().flatten()
+            }
+            MessageRef::Nonce { .. } => None,
+        }?;
+        Some((db.domain(), message))
+    })
+}
+
+async fn export_ledger(
+    origin_dbs: Arc<Vec<HyperlaneRocksDB>>,
+    format: LedgerFormat,
+) -> Result<Box<dyn Reply>, Infallible> {
+    let records = accounting::ledger(origin_dbs.iter());
+    Ok(match format {
+        LedgerFormat::Json => Box::new(json(&records)),
+        LedgerFormat::Csv => Box::new(with_header(
+            accounting::to_csv(&records),
+            CONTENT_TYPE,
+            "text/csv",
+        )),
+        LedgerFormat::Balances => Box::new(json(&accounting::balances(&records))),
+    })
+}
+
+async fn list_equivocations(
+    origin_dbs: Arc<Vec<HyperlaneRocksDB>>,
+) -> Result<Box<dyn Reply>, Infallible> {
+    let mut equivocations = vec![];
+    for db in origin_dbs.iter() {
+        equivocations.extend(
+            db.retrieve_equivocations()
+                .into_iter()
+                .map(EquivocationStatus::WithMessageId),
+        );
+        equivocations.extend(
+            db.retrieve_legacy_equivocations()
+                .into_iter()
+                .map(EquivocationStatus::Legacy),
+        );
+    }
+    Ok(Box::new(json(&equivocations)))
+}
+
+fn queue_names(submitter: &SubmitterQueues) -> [(&'static str, &OpQueue); 2] {
+    [
+        ("prepare_queue", &submitter.prepare_queue),
+        ("confirm_queue", &submitter.confirm_queue),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use hyperlane_base::{
+        db::{test_utils, DeadLetter},
+        with_bearer_token,
+    };
+    use hyperlane_core::HyperlaneDomain;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    const TOKEN: &str = "secret";
+
+    fn authenticated_routes(dead_letters: DeadLetterQueues) -> BoxedFilter<(Box<dyn Reply>,)> {
+        with_bearer_token(
+            TOKEN,
+            routes(
+                QueueRegistry::default(),
+                dead_letters,
+                Arc::new(vec![]),
+                Arc::new(HashMap::new()),
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_requests_need_the_bearer_token() {
+        let routes = authenticated_routes(Arc::new(HashMap::new()));
+
+        let missing = warp::test::request()
+            .method("POST")
+            .path(&format!("/messages/{:?}/drop", H256::zero()))
+            .reply(&routes)
+            .await;
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+
+        let wrong = warp::test::request()
+            .path("/queues")
+            .header("authorization", "Bearer wrong")
+            .reply(&routes)
+            .await;
+        assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+
+        let listed = warp::test::request()
+            .path("/queues")
+            .header("authorization", format!("Bearer {TOKEN}"))
+            .reply(&routes)
+            .await;
+        assert_eq!(listed.status(), StatusCode::OK);
+        assert_eq!(listed.body().as_ref(), b"[]");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_operation_is_not_found() {
+        let routes = authenticated_routes(Arc::new(HashMap::new()));
+        let res = warp::test::request()
+            .method("POST")
+            .path(&format!("/messages/{:?}/retry", H256::zero()))
+            .header("authorization", format!("Bearer {TOKEN}"))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_requeue_dead_letter() {
+        test_utils::run_test_db(|db| async move {
+            let origin = HyperlaneDomain::new_test_domain("test1");
+            let db = HyperlaneRocksDB::new(&origin, db);
+            db.store_dead_letter_by_nonce(
+                &7,
+                &DeadLetter {
+                    message_id: H256::repeat_byte(1),
+                    nonce: 7,
+                    reason: "Recipient is not a contract".to_owned(),
+                    last_error: None,
+                    timestamp: 0,
+                },
+            )
+            .unwrap();
+            let (requeue_sender, mut requeue_receiver) = mpsc::unbounded_channel();
+            let key = DeploymentKey {
+                domain_id: origin.id(),
+                deployment: None,
+            };
+            let dead_letters = HashMap::from([(
+                key,
+                DeadLetterQueue {
+                    db: db.clone(),
+                    requeue_sender,
+                },
+            )]);
+            let routes = authenticated_routes(Arc::new(dead_letters));
+            let requeue = |nonce: u32| {
+                warp::test::request()
+                    .method("POST")
+                    .path(&format!("/dead_letters/{}/{nonce}/requeue", origin.id()))
+                    .header("authorization", format!("Bearer {TOKEN}"))
+                    .reply(&routes)
+            };
+
+            assert_eq!(requeue(7).await.status(), StatusCode::OK);
+            assert_eq!(requeue_receiver.try_recv().unwrap(), 7);
+            assert!(db.retrieve_dead_letters().is_empty());
+            assert_eq!(requeue(7).await.status(), StatusCode::NOT_FOUND);
+            assert!(requeue_receiver.try_recv().is_err());
+        })
+        .await;
+    }
+}
@@ -13,6 +13,8 @@ use hyperlane_base::agent_main;
 
 use crate::relayer::Relayer;
 
+mod accounting;
+mod admin;
 mod merkle_tree_builder;
 mod msg;
 mod prover;
@@ -10,7 +10,9 @@ use hyperlane_core::{
     U256,
 };
 
-use crate::msg::gas_payment::policies::GasPaymentPolicyOnChainFeeQuoting;
+use crate::msg::gas_payment::policies::{
+    GasPaymentPolicyOnChainFeeQuoting, GasPaymentPolicyProfitability,
+};
 use crate::settings::{
     matching_list::MatchingList, GasPaymentEnforcementConf, GasPaymentEnforcementPolicy,
 };
@@ -18,6 +20,7 @@ use crate::settings::{
 use self::policies::{GasPaymentPolicyMinimum, GasPaymentPolicyNone};
 
 mod policies;
+pub(crate) mod token_price;
 
 #[async_trait]
 pub trait GasPaymentPolicy: Debug + Send + Sync {
@@ -60,6 +63,13 @@ impl GasPaymentEnforcer {
                         gas_fraction_numerator: n,
                         gas_fraction_denominator: d,
                     } => Box::new(GasPaymentPolicyOnChainFeeQuoting::new(n, d)),
+                    GasPaymentEnforcementPolicy::Profitability {
+                        price_source,
+                        margin,
+                    } => Box::new(GasPaymentPolicyProfitability::new(
+                        price_source.into(),
+                        margin,
+                    )),
                 };
                 (p, cfg.matching_list)
             })
@@ -123,11 +133,14 @@ impl GasPaymentEnforcer {
     }
 
     pub fn record_tx_outcome(&self, message: &HyperlaneMessage, outcome: TxOutcome) -> Result<()> {
-        self.db.process_gas_expenditure(InterchainGasExpenditure {
-            message_id: message.id(),
-            gas_used: outcome.gas_used,
-            tokens_used: outcome.gas_used * outcome.gas_price,
-        })?;
+        self.db.process_gas_expenditure(
+            InterchainGasExpenditure {
+                message_id: message.id(),
+                gas_used: outcome.gas_used,
+                tokens_used: outcome.gas_used * outcome.gas_price,
+            },
+            outcome.transaction_id,
+        )?;
         Ok(())
     }
 }
@@ -1,7 +1,9 @@
 mod minimum;
 mod none;
 mod on_chain_fee_quoting;
+mod profitability;
 
 pub(crate) use minimum::GasPaymentPolicyMinimum;
 pub(crate) use none::GasPaymentPolicyNone;
 pub(crate) use on_chain_fee_quoting::GasPaymentPolicyOnChainFeeQuoting;
+pub(crate) use profitability::GasPaymentPolicyProfitability;
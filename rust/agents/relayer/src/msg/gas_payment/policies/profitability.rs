@@ -0,0 +1,184 @@
+use async_trait::async_trait;
+use eyre::Result;
+use tracing::{debug, warn};
+
+use hyperlane_core::{
+    HyperlaneMessage, InterchainGasExpenditure, InterchainGasPayment, TxCostEstimate, U256,
+};
+
+use crate::msg::gas_payment::{token_price::CachedTokenPrices, GasPaymentPolicy};
+
+#[derive(Debug)]
+pub struct GasPaymentPolicyProfitability {
+    prices: CachedTokenPrices,
+    /// Fraction of the delivery cost that must be paid on top of it.
+    margin: f64,
+}
+
+impl GasPaymentPolicyProfitability {
+    pub fn new(prices: CachedTokenPrices, margin: f64) -> Self {
+        Self { prices, margin }
+    }
+}
+
+#[async_trait]
+impl GasPaymentPolicy for GasPaymentPolicyProfitability {
+    /// Compares the USD value of what is left of the payment, in origin native
+    /// tokens, with the estimated USD cost of delivery, in destination native
+    /// tokens.
+    async fn message_meets_gas_payment_requirement(
+        &self,
+        message: &HyperlaneMessage,
+        current_payment: &InterchainGasPayment,
+        current_expenditure: &InterchainGasExpenditure,
+        tx_cost_estimate: &TxCostEstimate,
+    ) -> Result<Option<U256>> {
+        let prices = self.prices.get().await?;
+        let (Some(origin_price), Some(destination_price)) =
+            (prices.get(&message.origin), prices.get(&message.destination))
+        else {
+            warn!(
+                origin = message.origin,
+                destination = message.destination,
+                "Missing native token price for origin or destination"
+            );
+            return Ok(None);
+        };
+
+        let paid_usd = origin_price.usd_value(current_payment.payment);
+        let spent_usd = destination_price.usd_value(current_expenditure.tokens_used);
+        let cost_usd = destination_price.usd_value(
+            tx_cost_estimate
+                .gas_limit
+                .saturating_mul(tx_cost_estimate.gas_price),
+        );
+        let required_usd = cost_usd * (1. + self.margin);
+        debug!(
+            paid_usd,
+            spent_usd, cost_usd, required_usd, "Evaluating profitability of message"
+        );
+
+        if paid_usd - spent_usd >= required_usd {
+            Ok(Some(tx_cost_estimate.gas_limit))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyperlane_core::H256;
+
+    use super::*;
+    use crate::msg::gas_payment::token_price::{TokenPrice, TokenPriceSource, TokenPrices};
+
+    const ORIGIN: u32 = 1;
+    const DESTINATION: u32 = 137;
+    const WEI: u64 = 1_000_000_000_000_000_000;
+
+    #[derive(Debug)]
+    struct StaticPrices;
+
+    #[async_trait]
+    impl TokenPriceSource for StaticPrices {
+        async fn fetch_prices(&self) -> Result<TokenPrices> {
+            Ok(TokenPrices::from([
+                (
+                    ORIGIN,
+                    TokenPrice {
+                        price_usd: 2000.,
+                        decimals: 18,
+                    },
+                ),
+                (
+                    DESTINATION,
+                    TokenPrice {
+                        price_usd: 1.,
+                        decimals: 18,
+                    },
+                ),
+            ]))
+        }
+    }
+
+    fn policy(margin: f64) -> GasPaymentPolicyProfitability {
+        GasPaymentPolicyProfitability::new(CachedTokenPrices::new(Box::new(StaticPrices)), margin)
+    }
+
+    fn message(destination: u32) -> HyperlaneMessage {
+        HyperlaneMessage {
+            origin: ORIGIN,
+            destination,
+            ..Default::default()
+        }
+    }
+
+    /// A payment of 1/1000 of an origin token, worth $2.
+    fn current_payment() -> InterchainGasPayment {
+        InterchainGasPayment {
+            message_id: H256::zero(),
+            payment: U256::from(WEI / 1000),
+            gas_amount: U256::zero(),
+        }
+    }
+
+    fn current_expenditure(tokens_used: U256) -> InterchainGasExpenditure {
+        InterchainGasExpenditure {
+            message_id: H256::zero(),
+            tokens_used,
+            gas_used: U256::zero(),
+        }
+    }
+
+    /// A delivery costing `usd` destination tokens.
+    fn tx_cost_estimate(usd: f64) -> TxCostEstimate {
+        TxCostEstimate {
+            gas_limit: U256::from(100_000),
+            gas_price: U256::from((usd * WEI as f64 / 100_000.) as u64),
+            l2_gas_limit: None,
+        }
+    }
+
+    async fn meets_requirement(
+        policy: &GasPaymentPolicyProfitability,
+        message: &HyperlaneMessage,
+        tokens_used: U256,
+        cost_usd: f64,
+    ) -> bool {
+        policy
+            .message_meets_gas_payment_requirement(
+                message,
+                &current_payment(),
+                &current_expenditure(tokens_used),
+                &tx_cost_estimate(cost_usd),
+            )
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    #[tokio::test]
+    async fn test_payment_must_cover_cost_and_margin() {
+        let policy = policy(0.1);
+        let message = message(DESTINATION);
+        assert!(meets_requirement(&policy, &message, U256::zero(), 1.8).await);
+        assert!(!meets_requirement(&policy, &message, U256::zero(), 1.9).await);
+    }
+
+    #[tokio::test]
+    async fn test_previous_expenditure_is_deducted() {
+        let policy = policy(0.);
+        let message = message(DESTINATION);
+        // $1.50 of the $2 payment was already spent on an earlier attempt
+        let spent = U256::from(WEI * 3 / 2);
+        assert!(meets_requirement(&policy, &message, spent, 0.4).await);
+        assert!(!meets_requirement(&policy, &message, spent, 0.6).await);
+    }
+
+    #[tokio::test]
+    async fn test_missing_price_is_not_approved() {
+        let policy = policy(0.);
+        assert!(!meets_requirement(&policy, &message(42), U256::zero(), 0.).await);
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use eyre::{Context, Result};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use tokio::{sync::Mutex, time::Instant};
+use tracing::warn;
+
+use hyperlane_core::U256;
+
+use crate::settings::TokenPriceSourceConf;
+
+/// How long prices are used for before they are fetched again.
+const PRICE_CACHE_TTL: Duration = Duration::from_secs(60);
+/// How long prices are still used for when they cannot be fetched again.
+const PRICE_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+/// How long to wait for an HTTP price source to respond.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// USD price of the native token of a chain.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenPrice {
+    /// Price of one whole token in USD.
+    pub price_usd: f64,
+    /// Number of decimals of the token.
+    pub decimals: u8,
+}
+
+impl TokenPrice {
+    /// USD value of an amount of the token in its smallest unit, e.g. wei.
+    pub fn usd_value(&self, amount: U256) -> f64 {
+        amount.to_f64_lossy() / 10f64.powi(self.decimals as i32) * self.price_usd
+    }
+}
+
+/// Native token prices by domain id.
+pub type TokenPrices = HashMap<u32, TokenPrice>;
+
+/// Somewhere to get the prices of native tokens from.
+#[async_trait]
+pub trait TokenPriceSource: Debug + Send + Sync {
+    async fn fetch_prices(&self) -> Result<TokenPrices>;
+}
+
+/// Reads prices from a JSON file.
+#[derive(Debug)]
+pub struct FileTokenPriceSource {
+    path: PathBuf,
+}
+
+#[async_trait]
+impl TokenPriceSource for FileTokenPriceSource {
+    async fn fetch_prices(&self) -> Result<TokenPrices> {
+        let contents = tokio::fs::read(&self.path)
+            .await
+            .with_context(|| format!("Reading token prices from {}", self.path.display()))?;
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("Parsing token prices from {}", self.path.display()))
+    }
+}
+
+/// Fetches prices from an HTTP endpoint which responds with JSON.
+#[derive(Debug)]
+pub struct HttpTokenPriceSource {
+    url: Url,
+    client: Client,
+}
+
+#[async_trait]
+impl TokenPriceSource for HttpTokenPriceSource {
+    async fn fetch_prices(&self) -> Result<TokenPrices> {
+        self.client
+            .get(self.url.clone())
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .with_context(|| format!("Fetching token prices from {}", self.url))?
+            .json()
+            .await
+            .with_context(|| format!("Parsing token prices from {}", self.url))
+    }
+}
+
+/// Token prices which are fetched again from their source once they are
+/// older than `PRICE_CACHE_TTL`. If they cannot be fetched, the previous
+/// prices keep being used until they are older than `PRICE_MAX_AGE`.
+#[derive(Debug)]
+pub struct CachedTokenPrices {
+    source: Box<dyn TokenPriceSource>,
+    cache: RwLock<Option<(Instant, Arc<TokenPrices>)>>,
+    /// Held while fetching prices, so only one fetch is in flight at a time
+    fetching: Mutex<()>,
+}
+
+impl CachedTokenPrices {
+    pub fn new(source: Box<dyn TokenPriceSource>) -> Self {
+        Self {
+            source,
+            cache: Default::default(),
+            fetching: Default::default(),
+        }
+    }
+
+    /// The current prices, fetching them if the cached ones have expired.
+    ///
+    /// Callers do not wait for a fetch already in flight if the cached prices
+    /// are still usable.
+    pub async fn get(&self) -> Result<Arc<TokenPrices>> {
+        if let Some(prices) = self.cached(PRICE_CACHE_TTL) {
+            return Ok(prices);
+        }
+        let _fetching = match self.fetching.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => match self.cached(PRICE_MAX_AGE) {
+                Some(prices) => return Ok(prices),
+                None => self.fetching.lock().await,
+            },
+        };
+        // The prices may have been fetched while waiting
+        if let Some(prices) = self.cached(PRICE_CACHE_TTL) {
+            return Ok(prices);
+        }
+        match self.source.fetch_prices().await {
+            Ok(prices) => {
+                let prices = Arc::new(prices);
+                *self.cache.write().unwrap() = Some((Instant::now(), prices.clone()));
+                Ok(prices)
+            }
+            Err(err) => match self.cached(PRICE_MAX_AGE) {
+                Some(prices) => {
+                    warn!(
+                        ?err,
+                        "Failed to fetch token prices, using the previous ones"
+                    );
+                    Ok(prices)
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    /// The cached prices, if they are younger than `max_age`.
+    fn cached(&self, max_age: Duration) -> Option<Arc<TokenPrices>> {
+        self.cache
+            .read()
+            .unwrap()
+            .as_ref()
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < max_age)
+            .map(|(_, prices)| prices.clone())
+    }
+}
+
+impl From<TokenPriceSourceConf> for CachedTokenPrices {
+    fn from(conf: TokenPriceSourceConf) -> Self {
+        let source: Box<dyn TokenPriceSource> = match conf {
+            TokenPriceSourceConf::File { path } => Box::new(FileTokenPriceSource { path }),
+            TokenPriceSourceConf::Http { url } => Box::new(HttpTokenPriceSource {
+                url,
+                // Only fails if the TLS backend cannot be initialized, like
+                // `Client::new`
+                client: Client::builder()
+                    .timeout(FETCH_TIMEOUT)
+                    .build()
+                    .expect("Failed to build token price HTTP client"),
+            }),
+        };
+        Self::new(source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use eyre::eyre;
+
+    use super::*;
+
+    /// Serves a price of $1 for domain 1 on the first fetch, and fails on the
+    /// following ones.
+    #[derive(Debug, Default)]
+    struct FailingAfterFirstFetch {
+        fetches: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TokenPriceSource for FailingAfterFirstFetch {
+        async fn fetch_prices(&self) -> Result<TokenPrices> {
+            if self.fetches.fetch_add(1, Ordering::SeqCst) > 0 {
+                return Err(eyre!("Price source unavailable"));
+            }
+            Ok(HashMap::from([(
+                1,
+                TokenPrice {
+                    price_usd: 1.,
+                    decimals: 18,
+                },
+            )]))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_prices_are_used_until_max_age() {
+        tokio::time::pause();
+        let prices = CachedTokenPrices::new(Box::<FailingAfterFirstFetch>::default());
+        assert_eq!(prices.get().await.unwrap()[&1].price_usd, 1.);
+
+        // Fetching again fails, the previous prices are used meanwhile
+        tokio::time::advance(PRICE_CACHE_TTL).await;
+        assert_eq!(prices.get().await.unwrap()[&1].price_usd, 1.);
+
+        tokio::time::advance(PRICE_MAX_AGE).await;
+        assert!(prices.get().await.is_err());
+    }
+
+    #[test]
+    fn test_parse_token_prices() {
+        let prices: TokenPrices = serde_json::from_str(
+            r#"{"1": {"priceUsd": 2000, "decimals": 18}, "137": {"priceUsd": 0.5, "decimals": 18}}"#,
+        )
+        .unwrap();
+        assert_eq!(prices[&1].price_usd, 2000.);
+        assert_eq!(prices[&137].decimals, 18);
+    }
+
+    #[test]
+    fn test_usd_value() {
+        let price = TokenPrice {
+            price_usd: 2000.,
+            decimals: 6,
+        };
+        assert_eq!(price.usd_value(U256::from(1_500_000)), 3000.);
+    }
+}
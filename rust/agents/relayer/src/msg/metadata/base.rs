@@ -5,13 +5,15 @@ use derive_new::new;
 use eyre::{Context, Result};
 use hyperlane_base::{
     settings::{ChainConf, CheckpointSyncerConf},
-    CheckpointSyncer, CoreMetrics, MultisigCheckpointSyncer,
+    CheckpointFetchConf, CheckpointSyncer, CoreMetrics, EquivocationDetector,
+    MultisigCheckpointSyncer, ValidatorHealth,
 };
 use hyperlane_core::{
-    accumulator::merkle::Proof, AggregationIsm, CcipReadIsm, Checkpoint, HyperlaneDomain,
-    HyperlaneMessage, InterchainSecurityModule, ModuleType, MultisigIsm, RoutingIsm,
-    ValidatorAnnounce, H160, H256,
+    accumulator::merkle::Proof, AggregationIsm, CcipReadIsm, Checkpoint, HyperlaneChain,
+    HyperlaneContract, HyperlaneDomain, HyperlaneMessage, InterchainSecurityModule, ModuleType,
+    MultisigIsm, OptimisticIsm, RoutingIsm, ValidatorAnnounce, H160, H256,
 };
+use num_traits::FromPrimitive;
 use tokio::sync::RwLock;
 use tracing::{debug, info, instrument, warn};
 
@@ -22,7 +24,9 @@ use crate::{
             LegacyMultisigMetadataBuilder, MerkleRootMultisigMetadataBuilder,
             MessageIdMultisigMetadataBuilder,
         },
-        AggregationIsmMetadataBuilder, CcipReadIsmMetadataBuilder, NullMetadataBuilder,
+        AggregationIsmMetadataBuilder, CcipReadGateway, CcipReadIsmMetadataBuilder,
+        ExternalMetadataBuilder, ExternalMetadataProvider, ExternalMetadataProviders,
+        MetadataCache, NullMetadataBuilder, OptimisticIsmMetadataBuilder,
         RoutingIsmMetadataBuilder,
     },
 };
@@ -47,8 +51,23 @@ pub struct BaseMetadataBuilder {
     destination_chain_setup: ChainConf,
     origin_prover_sync: Arc<RwLock<MerkleTreeBuilder>>,
     origin_validator_announce: Arc<dyn ValidatorAnnounce>,
+    /// Health of the origin's validators, shared by the builders of every
+    /// message from the origin.
+    origin_validator_health: Arc<ValidatorHealth>,
+    /// Checkpoints fetched from the origin's validators, shared by the
+    /// builders of every message from the origin.
+    origin_equivocations: Arc<EquivocationDetector>,
+    /// How long to wait for the checkpoint syncers of validators.
+    checkpoint_fetch: Arc<CheckpointFetchConf>,
     allow_local_checkpoint_syncers: bool,
     metrics: Arc<CoreMetrics>,
+    /// Shared by the builders of every message going to the destination.
+    cache: Arc<MetadataCache>,
+    /// Client for CCIP-read gateways, shared by every builder.
+    ccip_read_gateway: Arc<CcipReadGateway>,
+    /// Services which build metadata for the ISMs they are configured for, in
+    /// place of the builders for their module types.
+    external_metadata_providers: Arc<ExternalMetadataProviders>,
     /// ISMs can be structured recursively. We keep track of the depth
     /// of the recursion to avoid infinite loops.
     #[new(default)]
@@ -75,26 +94,48 @@ impl MetadataBuilder for BaseMetadataBuilder {
         message: &HyperlaneMessage,
     ) -> Result<Option<Vec<u8>>> {
         const CTX: &str = "When fetching module type";
-        let ism = self.build_ism(ism_address).await.context(CTX)?;
-        let module_type = ism.module_type().await.context(CTX)?;
+        let raw_module_type = self.raw_module_type(ism_address).await.context(CTX)?;
+        let module_type = ModuleType::from_u8(raw_module_type).unwrap_or(ModuleType::Unused);
+        if let Some(metadata) = self.cache.metadata(ism_address, message.id()) {
+            debug!(ism=?ism_address, ?module_type, "Using cached metadata");
+            return Ok(Some(metadata));
+        }
         let base = self.clone_with_incremented_depth()?;
 
-        let metadata_builder: Box<dyn MetadataBuilder> = match module_type {
-            ModuleType::LegacyMultisig => Box::new(LegacyMultisigMetadataBuilder::new(base)),
-            ModuleType::MerkleRootMultisig => {
-                Box::new(MerkleRootMultisigMetadataBuilder::new(base))
+        let metadata_builder: Box<dyn MetadataBuilder> = if let Some(provider) =
+            self.external_metadata_provider(ism_address, raw_module_type)
+        {
+            Box::new(ExternalMetadataBuilder::new(
+                base,
+                provider.clone(),
+                raw_module_type,
+            ))
+        } else {
+            match module_type {
+                ModuleType::LegacyMultisig => Box::new(LegacyMultisigMetadataBuilder::new(base)),
+                ModuleType::MerkleRootMultisig => {
+                    Box::new(MerkleRootMultisigMetadataBuilder::new(base))
+                }
+                ModuleType::MessageIdMultisig => {
+                    Box::new(MessageIdMultisigMetadataBuilder::new(base))
+                }
+                ModuleType::Routing => Box::new(RoutingIsmMetadataBuilder::new(base)),
+                ModuleType::Aggregation => Box::new(AggregationIsmMetadataBuilder::new(base)),
+                ModuleType::Null => Box::new(NullMetadataBuilder::new()),
+                ModuleType::CcipRead => Box::new(CcipReadIsmMetadataBuilder::new(base)),
+                ModuleType::Optimistic => Box::new(OptimisticIsmMetadataBuilder::new(base)),
+                _ => return Err(MetadataBuilderError::UnsupportedModuleType(module_type).into()),
             }
-            ModuleType::MessageIdMultisig => Box::new(MessageIdMultisigMetadataBuilder::new(base)),
-            ModuleType::Routing => Box::new(RoutingIsmMetadataBuilder::new(base)),
-            ModuleType::Aggregation => Box::new(AggregationIsmMetadataBuilder::new(base)),
-            ModuleType::Null => Box::new(NullMetadataBuilder::new()),
-            ModuleType::CcipRead => Box::new(CcipReadIsmMetadataBuilder::new(base)),
-            _ => return Err(MetadataBuilderError::UnsupportedModuleType(module_type).into()),
         };
-        metadata_builder
+        let metadata = metadata_builder
             .build(ism_address, message)
             .await
-            .context(CTX)
+            .context(CTX)?;
+        if let Some(metadata) = &metadata {
+            self.cache
+                .set_metadata(ism_address, message.id(), module_type, metadata.clone());
+        }
+        Ok(metadata)
     }
 }
 
@@ -103,6 +144,34 @@ impl BaseMetadataBuilder {
         &self.destination_chain_setup.domain
     }
 
+    pub fn origin_domain(&self) -> &HyperlaneDomain {
+        self.origin_validator_announce.domain()
+    }
+
+    pub fn metrics(&self) -> &CoreMetrics {
+        &self.metrics
+    }
+
+    pub fn cache(&self) -> &MetadataCache {
+        &self.cache
+    }
+
+    pub fn ccip_read_gateway(&self) -> &CcipReadGateway {
+        &self.ccip_read_gateway
+    }
+
+    /// The external metadata provider configured to build metadata for an
+    /// ISM, if any. `raw_module_type` is the type reported by the ISM, which
+    /// may not be known to the relayer.
+    pub fn external_metadata_provider(
+        &self,
+        ism_address: H256,
+        raw_module_type: u8,
+    ) -> Option<&Arc<ExternalMetadataProvider>> {
+        self.external_metadata_providers
+            .find(ism_address, raw_module_type)
+    }
+
     pub fn clone_with_incremented_depth(&self) -> Result<BaseMetadataBuilder> {
         let mut cloned = self.clone();
         cloned.depth += 1;
@@ -140,6 +209,55 @@ impl BaseMetadataBuilder {
         self.origin_prover_sync.read().await.count() - 1
     }
 
+    /// The module type of an ISM, `Unused` if it is not known to the relayer.
+    pub async fn module_type(&self, ism_address: H256) -> Result<ModuleType> {
+        let module_type = self.raw_module_type(ism_address).await?;
+        Ok(ModuleType::from_u8(module_type).unwrap_or(ModuleType::Unused))
+    }
+
+    /// The module type reported by an ISM, memoized across messages.
+    pub async fn raw_module_type(&self, ism_address: H256) -> Result<u8> {
+        if let Some(module_type) = self.cache.raw_module_type(ism_address) {
+            return Ok(module_type);
+        }
+        let module_type = self.build_ism(ism_address).await?.raw_module_type().await?;
+        self.cache.set_raw_module_type(ism_address, module_type);
+        Ok(module_type)
+    }
+
+    /// The validators and threshold of a multisig ISM for a message, memoized
+    /// across messages from the same origin for legacy multisig ISMs, and for
+    /// retries of the message otherwise.
+    pub async fn validators_and_threshold(
+        &self,
+        ism: &dyn MultisigIsm,
+        message: &HyperlaneMessage,
+    ) -> Result<(Vec<H256>, u8)> {
+        let ism_address = ism.address();
+        let module_type = self.module_type(ism_address).await?;
+        if let Some(cached) = self
+            .cache
+            .validators_and_threshold(ism_address, module_type, message)
+        {
+            return Ok(cached);
+        }
+        let validators_and_threshold = ism.validators_and_threshold(message).await?;
+        if !validators_and_threshold.0.is_empty() {
+            self.cache.set_validators_and_threshold(
+                ism_address,
+                module_type,
+                message,
+                validators_and_threshold.clone(),
+            );
+        }
+        Ok(validators_and_threshold)
+    }
+
+    /// Forget any metadata cached for a message so it is built again.
+    pub fn invalidate_cached_metadata(&self, message_id: H256) {
+        self.cache.invalidate_metadata(message_id);
+    }
+
     pub async fn build_ism(&self, address: H256) -> Result<Box<dyn InterchainSecurityModule>> {
         self.destination_chain_setup
             .build_ism(address, &self.metrics)
@@ -170,6 +288,54 @@ impl BaseMetadataBuilder {
             .await
     }
 
+    pub async fn build_optimistic_ism(&self, address: H256) -> Result<Box<dyn OptimisticIsm>> {
+        self.destination_chain_setup
+            .build_optimistic_ism(address, &self.metrics)
+            .await
+    }
+
+    /// The optimistic ISM verifying a message, if the ISM at `ism_address` is
+    /// one or routes the message to one. Optimistic ISMs within aggregation
+    /// ISMs are not pre-verified.
+    pub async fn optimistic_ism(
+        &self,
+        ism_address: H256,
+        message: &HyperlaneMessage,
+    ) -> Result<Option<Box<dyn OptimisticIsm>>> {
+        let mut ism_address = ism_address;
+        for _ in 0..=self.max_depth {
+            let raw_module_type = self.raw_module_type(ism_address).await?;
+            if self
+                .external_metadata_provider(ism_address, raw_module_type)
+                .is_some()
+            {
+                // The provider builds the metadata for the whole subtree
+                return Ok(None);
+            }
+            match ModuleType::from_u8(raw_module_type) {
+                Some(ModuleType::Optimistic) => {
+                    return Ok(Some(self.build_optimistic_ism(ism_address).await?))
+                }
+                Some(ModuleType::Routing) => {
+                    let ism = self.build_routing_ism(ism_address).await?;
+                    ism_address = ism.route(message).await?;
+                }
+                _ => return Ok(None),
+            }
+        }
+        Err(MetadataBuilderError::MaxDepthExceeded(self.max_depth).into())
+    }
+
+    pub async fn announced_storage_locations(
+        &self,
+        validators: &[H256],
+    ) -> Result<Vec<Vec<String>>> {
+        Ok(self
+            .origin_validator_announce
+            .get_announced_storage_locations(validators)
+            .await?)
+    }
+
     pub async fn build_checkpoint_syncer(
         &self,
         validators: &[H256],
@@ -228,6 +394,11 @@ impl BaseMetadataBuilder {
                 }
             }
         }
-        Ok(MultisigCheckpointSyncer::new(checkpoint_syncers))
+        Ok(MultisigCheckpointSyncer::new(
+            checkpoint_syncers,
+            self.origin_validator_health.clone(),
+            self.origin_equivocations.clone(),
+            self.checkpoint_fetch.clone(),
+        ))
     }
 }
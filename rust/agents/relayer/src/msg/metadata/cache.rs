@@ -0,0 +1,266 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hyperlane_core::{HyperlaneMessage, ModuleType, H160, H256};
+
+/// Once a cache has this many entries, the expired ones are removed.
+const MAX_ENTRIES_BEFORE_PRUNING: usize = 10_000;
+
+/// ISMs are rarely upgraded to a different type, so their types are kept for a
+/// long time.
+const MODULE_TYPE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Validator sets may be changed by the owner of an ISM.
+const VALIDATORS_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How long metadata built for an ISM of the given type is used for, or `None`
+/// if it is not cached.
+fn metadata_ttl(module_type: ModuleType) -> Option<Duration> {
+    match module_type {
+        ModuleType::LegacyMultisig
+        | ModuleType::MerkleRootMultisig
+        | ModuleType::MessageIdMultisig => Some(Duration::from_secs(10 * 60)),
+        // Off-chain responses may only be valid for a short time
+        ModuleType::CcipRead => Some(Duration::from_secs(60)),
+        // Routing and aggregation metadata is made up of the metadata of other
+        // ISMs, which is cached itself, and null metadata is free to build.
+        _ => None,
+    }
+}
+
+/// What the validators and threshold of a multisig ISM are cached by.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ValidatorsKey {
+    /// The ISM address and the origin domain of the messages
+    Origin(H256, u32),
+    /// The ISM address and the id of the message
+    Message(H256, H256),
+}
+
+impl ValidatorsKey {
+    /// Legacy multisig ISMs are configured with a validator set per origin
+    /// domain, so their validators are shared by the messages from an origin.
+    /// Other multisig ISMs may pick their validators by any part of a message.
+    fn new(ism: H256, module_type: ModuleType, message: &HyperlaneMessage) -> Self {
+        match module_type {
+            ModuleType::LegacyMultisig => Self::Origin(ism, message.origin),
+            _ => Self::Message(ism, message.id()),
+        }
+    }
+}
+
+/// Results of building metadata and of the lookups made when building it,
+/// shared by the metadata builders of every message going to a destination so
+/// retries and other messages verified by the same ISMs don't repeat them.
+#[derive(Debug, Default)]
+pub struct MetadataCache {
+    /// Module types as reported by the ISMs, by ISM address.
+    module_types: TtlMap<H256, u8>,
+    /// Validators and threshold of multisig ISMs.
+    validators: TtlMap<ValidatorsKey, (Vec<H256>, u8)>,
+    /// Metadata by ISM address and message id.
+    metadata: TtlMap<(H256, H256), Vec<u8>>,
+    /// Data returned by CCIP-read gateways by the sender and call data of the
+    /// lookup.
+    ccip_read_responses: TtlMap<(H160, Vec<u8>), Vec<u8>>,
+}
+
+impl MetadataCache {
+    pub fn raw_module_type(&self, ism: H256) -> Option<u8> {
+        self.module_types.get(&ism, Instant::now())
+    }
+
+    pub fn set_raw_module_type(&self, ism: H256, module_type: u8) {
+        self.module_types
+            .insert(ism, module_type, MODULE_TYPE_TTL, Instant::now());
+    }
+
+    /// The validators and threshold of a multisig ISM of type `module_type`
+    /// for a message.
+    pub fn validators_and_threshold(
+        &self,
+        ism: H256,
+        module_type: ModuleType,
+        message: &HyperlaneMessage,
+    ) -> Option<(Vec<H256>, u8)> {
+        self.validators.get(
+            &ValidatorsKey::new(ism, module_type, message),
+            Instant::now(),
+        )
+    }
+
+    pub fn set_validators_and_threshold(
+        &self,
+        ism: H256,
+        module_type: ModuleType,
+        message: &HyperlaneMessage,
+        validators_and_threshold: (Vec<H256>, u8),
+    ) {
+        self.validators.insert(
+            ValidatorsKey::new(ism, module_type, message),
+            validators_and_threshold,
+            VALIDATORS_TTL,
+            Instant::now(),
+        );
+    }
+
+    pub fn metadata(&self, ism: H256, message_id: H256) -> Option<Vec<u8>> {
+        self.metadata.get(&(ism, message_id), Instant::now())
+    }
+
+    /// Cache metadata built for a message if metadata for ISMs of its type is
+    /// cached.
+    pub fn set_metadata(
+        &self,
+        ism: H256,
+        message_id: H256,
+        module_type: ModuleType,
+        metadata: Vec<u8>,
+    ) {
+        if let Some(ttl) = metadata_ttl(module_type) {
+            self.metadata
+                .insert((ism, message_id), metadata, ttl, Instant::now());
+        }
+    }
+
+    pub fn ccip_read_response(&self, sender: H160, call_data: &[u8]) -> Option<Vec<u8>> {
+        self.ccip_read_responses
+            .get(&(sender, call_data.to_vec()), Instant::now())
+    }
+
+    pub fn set_ccip_read_response(
+        &self,
+        sender: H160,
+        call_data: Vec<u8>,
+        response: Vec<u8>,
+        ttl: Duration,
+    ) {
+        self.ccip_read_responses
+            .insert((sender, call_data), response, ttl, Instant::now());
+    }
+
+    /// Forget the metadata built for a message by every ISM, e.g. because the
+    /// message could not be processed with it.
+    pub fn invalidate_metadata(&self, message_id: H256) {
+        self.metadata.retain(|(_, id)| *id != message_id);
+    }
+}
+
+/// A map whose entries expire.
+#[derive(Debug)]
+struct TtlMap<K, V>(Mutex<HashMap<K, (Instant, V)>>);
+
+impl<K, V> Default for TtlMap<K, V> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> TtlMap<K, V> {
+    fn get(&self, key: &K, now: Instant) -> Option<V> {
+        let entries = self.0.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|(expires_at, _)| *expires_at > now)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn insert(&self, key: K, value: V, ttl: Duration, now: Instant) {
+        let mut entries = self.0.lock().unwrap();
+        if entries.len() >= MAX_ENTRIES_BEFORE_PRUNING {
+            entries.retain(|_, (expires_at, _)| *expires_at > now);
+        }
+        entries.insert(key, (now + ttl, value));
+    }
+
+    fn retain(&self, mut f: impl FnMut(&K) -> bool) {
+        self.0.lock().unwrap().retain(|key, _| f(key));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_entries_expire() {
+        let map = TtlMap::default();
+        let now = Instant::now();
+        map.insert(1, "a", Duration::from_secs(10), now);
+        assert_eq!(map.get(&1, now + Duration::from_secs(9)), Some("a"));
+        assert_eq!(map.get(&1, now + Duration::from_secs(10)), None);
+        assert_eq!(map.get(&2, now), None);
+    }
+
+    #[test]
+    fn test_expired_entries_are_pruned() {
+        let map = TtlMap::default();
+        let now = Instant::now();
+        for i in 0..MAX_ENTRIES_BEFORE_PRUNING {
+            map.insert(i, (), Duration::from_secs(1), now);
+        }
+        map.insert(
+            MAX_ENTRIES_BEFORE_PRUNING,
+            (),
+            Duration::from_secs(1),
+            now + Duration::from_secs(1),
+        );
+        assert_eq!(map.0.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_metadata_is_cached_by_module_type() {
+        let cache = MetadataCache::default();
+        let ism = H256::from_low_u64_be(1);
+        let message_id = H256::from_low_u64_be(2);
+        cache.set_metadata(ism, message_id, ModuleType::Routing, vec![1]);
+        assert_eq!(cache.metadata(ism, message_id), None);
+
+        cache.set_metadata(ism, message_id, ModuleType::MessageIdMultisig, vec![2]);
+        assert_eq!(cache.metadata(ism, message_id), Some(vec![2]));
+
+        cache.invalidate_metadata(message_id);
+        assert_eq!(cache.metadata(ism, message_id), None);
+    }
+
+    #[test]
+    fn test_validators_are_shared_by_origin_for_legacy_multisig_isms() {
+        let cache = MetadataCache::default();
+        let ism = H256::from_low_u64_be(1);
+        let message = |origin, nonce| HyperlaneMessage {
+            origin,
+            nonce,
+            ..Default::default()
+        };
+        let validators = (vec![H256::from_low_u64_be(2)], 1);
+        for module_type in [ModuleType::LegacyMultisig, ModuleType::MessageIdMultisig] {
+            cache.set_validators_and_threshold(
+                ism,
+                module_type,
+                &message(1, 0),
+                validators.clone(),
+            );
+            assert_eq!(
+                cache.validators_and_threshold(ism, module_type, &message(1, 0)),
+                Some(validators.clone())
+            );
+            assert_eq!(
+                cache.validators_and_threshold(ism, module_type, &message(2, 0)),
+                None
+            );
+        }
+        assert_eq!(
+            cache.validators_and_threshold(ism, ModuleType::LegacyMultisig, &message(1, 1)),
+            Some(validators)
+        );
+        // The ISM may pick different validators for another message
+        assert_eq!(
+            cache.validators_and_threshold(ism, ModuleType::MessageIdMultisig, &message(1, 1)),
+            None
+        );
+    }
+}
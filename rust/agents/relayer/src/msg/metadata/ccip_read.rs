@@ -1,25 +1,225 @@
 use async_trait::async_trait;
 use hyperlane_ethereum::OffchainLookup;
-use reqwest::Client;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Client, Url};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::ops::Deref;
+use std::{
+    error::Error,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    ops::Deref,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time::sleep;
 
 use derive_new::new;
-use eyre::Context;
-use tracing::{info, instrument};
+use eyre::{bail, Context, Result};
+use tracing::{debug, info, instrument, warn};
 
 use super::{BaseMetadataBuilder, MetadataBuilder};
+use crate::settings::CcipReadPolicy;
 use ethers::abi::AbiDecode;
 use ethers::core::utils::hex::decode as hex_decode;
-use hyperlane_core::{HyperlaneMessage, RawHyperlaneMessage, H256};
+use hyperlane_core::{HyperlaneMessage, RawHyperlaneMessage, H160, H256};
 use regex::Regex;
 
+/// Max number of redirects followed for a gateway request.
+const MAX_REDIRECTS: usize = 10;
+/// Longest time waited between going through the gateway URLs again.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
 #[derive(Serialize, Deserialize)]
 struct OffchainResponse {
     data: String,
 }
 
+/// HTTP client for the gateways CCIP-read ISMs direct the relayer to, which
+/// only fetches from gateways allowed by a `CcipReadPolicy`.
+#[derive(Debug)]
+pub struct CcipReadGateway {
+    policy: Arc<CcipReadPolicy>,
+    client: Client,
+}
+
+impl Default for CcipReadGateway {
+    fn default() -> Self {
+        Self::new(Default::default()).expect("Failed to build CCIP-read HTTP client")
+    }
+}
+
+impl CcipReadGateway {
+    pub fn new(policy: CcipReadPolicy) -> Result<Self> {
+        let policy = Arc::new(policy);
+        let redirect_policy = policy.clone();
+        let mut builder = Client::builder();
+        if policy.allowed_hosts.is_empty() {
+            // Any host is allowed, as long as it is not in our own network
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = builder
+            .timeout(policy.timeout)
+            // Redirects must not lead away from the allowed hosts
+            .redirect(redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if is_allowed_url(&redirect_policy, attempt.url()) {
+                    attempt.follow()
+                } else {
+                    attempt.stop()
+                }
+            }))
+            .build()
+            .context("When building CCIP-read HTTP client")?;
+        Ok(Self { policy, client })
+    }
+
+    pub fn policy(&self) -> &CcipReadPolicy {
+        &self.policy
+    }
+
+    /// How long to wait before the given retry, counting from 1. The wait
+    /// doubles with each retry, up to `MAX_RETRY_BACKOFF`.
+    fn retry_backoff(&self, retry: u32) -> Duration {
+        self.policy
+            .retry_backoff
+            .checked_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .map_or(MAX_RETRY_BACKOFF, |backoff| backoff.min(MAX_RETRY_BACKOFF))
+    }
+
+    /// Fetch the data for an offchain lookup from the gateway at `url`, which
+    /// may contain `{sender}` and `{data}` placeholders as in EIP-3668.
+    async fn fetch(&self, url: &str, info: &OffchainLookup) -> Result<Vec<u8>> {
+        // The full lowercase hex address, `Display` abbreviates it
+        let sender = format!("{:?}", info.sender);
+        let data = info.call_data.to_string();
+        let interpolated_url = url.replace("{sender}", &sender).replace("{data}", &data);
+        let parsed_url = Url::parse(&interpolated_url).context("Invalid gateway URL")?;
+        if !is_allowed_url(&self.policy, &parsed_url) {
+            bail!("Gateway host is not allowed");
+        }
+
+        let request = if url.contains("{data}") {
+            self.client.get(parsed_url)
+        } else {
+            self.client.post(parsed_url).json(&json!({
+                "data": data,
+                "sender": sender,
+            }))
+        };
+        let mut response = request.send().await?.error_for_status()?;
+
+        let max_bytes = self.policy.max_response_bytes;
+        if matches!(response.content_length(), Some(len) if len > max_bytes as u64) {
+            bail!("Gateway response is larger than {max_bytes} bytes");
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > max_bytes {
+                bail!("Gateway response is larger than {max_bytes} bytes");
+            }
+            body.extend_from_slice(&chunk);
+        }
+        parse_response(&body)
+    }
+}
+
+/// Resolves host names to their public addresses only, so gateways cannot
+/// direct requests to the relayer's own network.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(resolve_public(name))
+    }
+}
+
+async fn resolve_public(name: Name) -> Result<Addrs, Box<dyn Error + Send + Sync>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+        .await?
+        .filter(|addr| is_public_ip(addr.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{} does not resolve to a public address", name.as_str()).into());
+    }
+    Ok(Box::new(addrs.into_iter()))
+}
+
+/// Whether a gateway URL uses HTTP(S) and is on one of the hosts allowed by
+/// the policy. If the policy does not list hosts, the host must be a domain
+/// name; the addresses it resolves to are checked by `PublicResolver`.
+fn is_allowed_url(policy: &CcipReadPolicy, url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str().map(str::to_ascii_lowercase) else {
+        return false;
+    };
+    if policy.allowed_hosts.is_empty() {
+        // IPv6 hosts are enclosed in brackets
+        let ip = host.trim_start_matches('[').trim_end_matches(']');
+        return ip.parse::<IpAddr>().is_err();
+    }
+    policy
+        .allowed_hosts
+        .iter()
+        .any(|pattern| match pattern.strip_prefix("*.") {
+            Some(domain) => host
+                .strip_suffix(domain)
+                .map_or(false, |subdomain| subdomain.ends_with('.')),
+            None => host == *pattern,
+        })
+}
+
+/// Whether an address is publicly routable, i.e. not loopback, private,
+/// link-local or otherwise reserved.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // shared address space, RFC 6598
+        || (a == 100 && (64..128).contains(&b))
+        // reserved, RFC 1112
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // unique local, RFC 4193
+        || (first & 0xfe00) == 0xfc00
+        // link-local
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// Decode the metadata from the JSON body of a gateway response.
+fn parse_response(body: &[u8]) -> Result<Vec<u8>> {
+    let response: OffchainResponse =
+        serde_json::from_slice(body).context("Invalid gateway response")?;
+    let Some(data) = response.data.strip_prefix("0x") else {
+        bail!("Gateway response data is not 0x-prefixed hex");
+    };
+    hex_decode(data).context("Gateway response data is not 0x-prefixed hex")
+}
+
 #[derive(Clone, Debug, new)]
 pub struct CcipReadIsmMetadataBuilder {
     base: BaseMetadataBuilder,
@@ -63,40 +263,145 @@ impl MetadataBuilder for CcipReadIsmMetadataBuilder {
             }
         };
 
-        for url in info.urls.iter() {
-            let interpolated_url = url
-                .replace("{sender}", &info.sender.to_string())
-                .replace("{data}", &info.call_data.to_string());
-            let res = if !url.contains("{data}") {
-                let body = json!({
-                    "data": info.call_data.to_string(),
-                    "sender": info.sender.to_string(),
-                });
-                Client::new()
-                    .post(interpolated_url)
-                    .header("Content-Type", "application/json")
-                    .json(&body)
-                    .send()
-                    .await?
-            } else {
-                reqwest::get(interpolated_url).await?
-            };
-
-            let json: Result<OffchainResponse, reqwest::Error> = res.json().await;
-
-            match json {
-                Ok(result) => {
-                    // remove leading 0x which hex_decode doesn't like
-                    let metadata = hex_decode(&result.data[2..])?;
-                    return Ok(Some(metadata));
-                }
-                Err(_err) => {
-                    // try the next URL
+        let sender = H160::from(info.sender);
+        if let Some(metadata) = self.cache().ccip_read_response(sender, &info.call_data) {
+            debug!("Using cached CCIP-read response");
+            return Ok(Some(metadata));
+        }
+
+        let verifier = self.build_ism(ism_address).await.context(CTX)?;
+        let gateway = self.ccip_read_gateway();
+        for attempt in 0..=gateway.policy().retries {
+            if attempt > 0 {
+                sleep(gateway.retry_backoff(attempt)).await;
+            }
+            for url in info.urls.iter() {
+                let metadata = match gateway.fetch(url, &info).await {
+                    Ok(metadata) => metadata,
+                    Err(err) => {
+                        warn!(url, attempt, ?err, "Failed to fetch CCIP-read metadata");
+                        continue;
+                    }
+                };
+                // Only accept metadata the ISM verifies, so a misbehaving
+                // gateway cannot make us submit transactions which revert
+                match verifier.dry_run_verify(message, &metadata).await {
+                    Ok(Some(_)) => {
+                        self.cache().set_ccip_read_response(
+                            sender,
+                            info.call_data.to_vec(),
+                            metadata.clone(),
+                            gateway.policy().cache_ttl,
+                        );
+                        return Ok(Some(metadata));
+                    }
+                    Ok(None) => {
+                        warn!(url, attempt, "ISM does not verify CCIP-read metadata");
+                    }
+                    Err(err) => {
+                        warn!(url, attempt, ?err, "Failed to verify CCIP-read metadata");
+                    }
                 }
             }
         }
 
-        // No metadata endpoints or endpoints down
+        // No metadata endpoints, endpoints down or no valid metadata
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn policy(allowed_hosts: &[&str]) -> CcipReadPolicy {
+        CcipReadPolicy {
+            allowed_hosts: allowed_hosts.iter().map(|h| h.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn allowed(policy: &CcipReadPolicy, url: &str) -> bool {
+        is_allowed_url(policy, &Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_retry_backoff_is_bounded() {
+        let gateway = |retry_backoff: Duration| {
+            CcipReadGateway::new(CcipReadPolicy {
+                retry_backoff,
+                ..Default::default()
+            })
+            .unwrap()
+        };
+
+        let gateway_1s = gateway(Duration::from_secs(1));
+        assert_eq!(gateway_1s.retry_backoff(1), Duration::from_secs(1));
+        assert_eq!(gateway_1s.retry_backoff(3), Duration::from_secs(4));
+        assert_eq!(gateway_1s.retry_backoff(u32::MAX), MAX_RETRY_BACKOFF);
+        assert_eq!(gateway(Duration::MAX).retry_backoff(2), MAX_RETRY_BACKOFF);
+    }
+
+    #[test]
+    fn test_allowed_hosts() {
+        let any = policy(&[]);
+        assert!(allowed(&any, "https://gateway.example.com/{sender}"));
+        assert!(!allowed(&any, "file:///etc/passwd"));
+        assert!(!allowed(&any, "http://127.0.0.1:8080/lookup"));
+        assert!(!allowed(&any, "http://169.254.169.254/latest/meta-data"));
+        assert!(!allowed(&any, "http://[::1]/lookup"));
+        assert!(!allowed(&any, "http://8.8.8.8/lookup"));
+
+        let list = policy(&["gateway.example.com", "*.hyperlane.xyz"]);
+        assert!(allowed(&list, "https://gateway.example.com/lookup"));
+        assert!(allowed(&list, "http://GATEWAY.example.com:8080/lookup"));
+        assert!(!allowed(&list, "https://other.example.com/lookup"));
+        assert!(allowed(&list, "https://ccip.hyperlane.xyz/lookup"));
+        assert!(allowed(&list, "https://a.b.hyperlane.xyz/lookup"));
+        assert!(!allowed(&list, "https://hyperlane.xyz/lookup"));
+        assert!(!allowed(&list, "https://evilhyperlane.xyz/lookup"));
+        assert!(!allowed(&list, "https://127.0.0.1/lookup"));
+    }
+
+    #[test]
+    fn test_public_ip() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "8.8.8.8",
+            "1.1.1.1",
+            "2606:4700:4700::1111",
+            "::ffff:8.8.8.8",
+        ] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(
+            parse_response(br#"{"data": "0x0102"}"#).unwrap(),
+            vec![1, 2]
+        );
+        assert!(parse_response(br#"{"data": "0102"}"#).is_err());
+        assert!(parse_response(br#"{"data": "0x"}"#).unwrap().is_empty());
+        assert!(parse_response(br#"{"data": "0xzz"}"#).is_err());
+        assert!(parse_response(b"<html></html>").is_err());
+    }
+}
@@ -0,0 +1,345 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use derive_new::new;
+use ethers::core::utils::hex::{decode as hex_decode, encode as hex_encode};
+use eyre::{bail, Context, Result};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument, warn};
+
+use hyperlane_core::{HyperlaneMessage, InterchainSecurityModule, RawHyperlaneMessage, H256};
+
+use super::{BaseMetadataBuilder, MetadataBuilder};
+use crate::settings::ExternalMetadataProviderConf;
+
+/// An external service which builds metadata for the ISMs it is configured
+/// for.
+#[derive(Debug)]
+pub struct ExternalMetadataProvider {
+    conf: ExternalMetadataProviderConf,
+    client: Client,
+}
+
+/// The external metadata providers, the first one configured for an ISM
+/// builds its metadata.
+#[derive(Debug, Default)]
+pub struct ExternalMetadataProviders(Vec<Arc<ExternalMetadataProvider>>);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MetadataRequest {
+    ism_address: H256,
+    /// The module type reported by the ISM.
+    module_type: u8,
+    origin: u32,
+    destination: u32,
+    nonce: u32,
+    message_id: H256,
+    /// The encoded message as 0x-prefixed hex.
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct MetadataResponse {
+    metadata: Option<String>,
+}
+
+impl ExternalMetadataProviders {
+    pub fn new(confs: Vec<ExternalMetadataProviderConf>) -> Result<Self> {
+        confs
+            .into_iter()
+            .map(|conf| {
+                let client = Client::builder()
+                    .timeout(conf.timeout)
+                    .build()
+                    .context("When building external metadata provider HTTP client")?;
+                Ok(Arc::new(ExternalMetadataProvider { conf, client }))
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+
+    /// The provider which builds metadata for an ISM. Providers configured
+    /// for the address of the ISM take precedence over those configured for
+    /// its type, which is the type reported by the ISM so providers can build
+    /// metadata for ISM types unknown to the relayer.
+    pub fn find(
+        &self,
+        ism_address: H256,
+        module_type: u8,
+    ) -> Option<&Arc<ExternalMetadataProvider>> {
+        self.0
+            .iter()
+            .find(|p| p.conf.ism_addresses.contains(&ism_address))
+            .or_else(|| {
+                self.0
+                    .iter()
+                    .find(|p| p.conf.module_types.contains(&module_type))
+            })
+    }
+}
+
+impl ExternalMetadataProvider {
+    async fn fetch(
+        &self,
+        ism_address: H256,
+        module_type: u8,
+        message: &HyperlaneMessage,
+    ) -> Result<Option<Vec<u8>>> {
+        let request = MetadataRequest {
+            ism_address,
+            module_type,
+            origin: message.origin,
+            destination: message.destination,
+            nonce: message.nonce,
+            message_id: message.id(),
+            message: format!(
+                "0x{}",
+                hex_encode(RawHyperlaneMessage::from(message).to_vec())
+            ),
+        };
+        let response = self
+            .client
+            .post(self.conf.url.clone())
+            .json(&request)
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response: MetadataResponse = response
+            .error_for_status()?
+            .json()
+            .await
+            .context("Invalid external metadata provider response")?;
+        response.metadata.as_deref().map(parse_metadata).transpose()
+    }
+}
+
+fn parse_metadata(metadata: &str) -> Result<Vec<u8>> {
+    let Some(hex) = metadata.strip_prefix("0x") else {
+        bail!("External metadata provider returned metadata which is not 0x-prefixed hex");
+    };
+    hex_decode(hex)
+        .context("External metadata provider returned metadata which is not 0x-prefixed hex")
+}
+
+/// Only accept metadata the ISM verifies, so a misbehaving provider cannot
+/// make us submit transactions which revert.
+async fn verified_metadata(
+    ism: &dyn InterchainSecurityModule,
+    message: &HyperlaneMessage,
+    metadata: Vec<u8>,
+) -> Option<Vec<u8>> {
+    match ism.dry_run_verify(message, &metadata).await {
+        Ok(Some(_)) => Some(metadata),
+        Ok(None) => {
+            warn!("ISM does not verify metadata from external metadata provider");
+            None
+        }
+        Err(err) => {
+            warn!(
+                ?err,
+                "Failed to verify metadata from external metadata provider"
+            );
+            None
+        }
+    }
+}
+
+/// Builds metadata by requesting it from an external metadata provider.
+#[derive(Clone, Debug, new)]
+pub struct ExternalMetadataBuilder {
+    base: BaseMetadataBuilder,
+    provider: Arc<ExternalMetadataProvider>,
+    /// The module type reported by the ISM.
+    module_type: u8,
+}
+
+#[async_trait]
+impl MetadataBuilder for ExternalMetadataBuilder {
+    #[instrument(err, skip(self), fields(url = %self.provider.conf.url))]
+    async fn build(
+        &self,
+        ism_address: H256,
+        message: &HyperlaneMessage,
+    ) -> Result<Option<Vec<u8>>> {
+        const CTX: &str = "When fetching metadata from external metadata provider";
+        let metadata = self
+            .provider
+            .fetch(ism_address, self.module_type, message)
+            .await
+            .context(CTX)?;
+        let Some(metadata) = metadata else {
+            info!("External metadata provider has no metadata for message");
+            return Ok(None);
+        };
+        let ism = self.base.build_ism(ism_address).await.context(CTX)?;
+        Ok(verified_metadata(&ism, message, metadata).await)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashSet, sync::Mutex, time::Duration};
+
+    use hyperlane_core::{
+        ChainResult, HyperlaneChain, HyperlaneContract, HyperlaneDomain, HyperlaneProvider,
+        ModuleType, U256,
+    };
+    use reqwest::Url;
+    use serde_json::{json, Value};
+    use warp::Filter;
+
+    use super::*;
+
+    fn conf(ism_addresses: &[u64], module_types: &[u8]) -> ExternalMetadataProviderConf {
+        ExternalMetadataProviderConf {
+            url: "http://localhost:9090".parse().unwrap(),
+            ism_addresses: ism_addresses
+                .iter()
+                .map(|a| H256::from_low_u64_be(*a))
+                .collect::<HashSet<_>>(),
+            module_types: module_types.to_vec(),
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn test_providers_for_ism_address_take_precedence() {
+        let providers =
+            ExternalMetadataProviders::new(vec![conf(&[], &[12]), conf(&[1], &[])]).unwrap();
+        let ism = H256::from_low_u64_be;
+
+        let found = providers.find(ism(1), 12).unwrap();
+        assert!(Arc::ptr_eq(found, &providers.0[1]));
+        let found = providers.find(ism(2), 12).unwrap();
+        assert!(Arc::ptr_eq(found, &providers.0[0]));
+        assert!(providers.find(ism(2), ModuleType::Routing as u8).is_none());
+        // Unknown types are not conflated with each other
+        assert!(providers.find(ism(2), 13).is_none());
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        assert_eq!(parse_metadata("0x0a0b").unwrap(), vec![10, 11]);
+        assert!(parse_metadata("0a0b").is_err());
+        assert!(parse_metadata("0xno").is_err());
+    }
+
+    /// Serves `response` to metadata requests, recording the last request.
+    fn serve(response: Value) -> (Url, Arc<Mutex<Option<Value>>>) {
+        let requests = Arc::new(Mutex::new(None));
+        let recorded = requests.clone();
+        let route = warp::post()
+            .and(warp::body::json())
+            .map(move |request: Value| {
+                *recorded.lock().unwrap() = Some(request);
+                warp::reply::json(&response)
+            });
+        let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let url = format!("http://{address}/metadata").parse().unwrap();
+        (url, requests)
+    }
+
+    fn provider(url: Url) -> Arc<ExternalMetadataProvider> {
+        let conf = ExternalMetadataProviderConf {
+            url,
+            ..conf(&[1], &[])
+        };
+        let providers = ExternalMetadataProviders::new(vec![conf]).unwrap();
+        providers.0[0].clone()
+    }
+
+    #[derive(Debug)]
+    struct MockIsm {
+        verifies: Vec<u8>,
+    }
+
+    impl HyperlaneChain for MockIsm {
+        fn domain(&self) -> &HyperlaneDomain {
+            unimplemented!()
+        }
+
+        fn provider(&self) -> Box<dyn HyperlaneProvider> {
+            unimplemented!()
+        }
+    }
+
+    impl HyperlaneContract for MockIsm {
+        fn address(&self) -> H256 {
+            H256::from_low_u64_be(1)
+        }
+    }
+
+    #[async_trait]
+    impl InterchainSecurityModule for MockIsm {
+        async fn module_type(&self) -> ChainResult<ModuleType> {
+            Ok(ModuleType::Unused)
+        }
+
+        async fn dry_run_verify(
+            &self,
+            _message: &HyperlaneMessage,
+            metadata: &[u8],
+        ) -> ChainResult<Option<U256>> {
+            Ok((metadata == self.verifies).then(U256::zero))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetches_metadata_over_http() {
+        let (url, requests) = serve(json!({ "metadata": "0x0a0b" }));
+        let provider = provider(url);
+        let message = HyperlaneMessage {
+            nonce: 3,
+            origin: 1,
+            destination: 2,
+            ..Default::default()
+        };
+        let ism = H256::from_low_u64_be(1);
+
+        let metadata = provider.fetch(ism, 12, &message).await.unwrap().unwrap();
+        assert_eq!(metadata, vec![10, 11]);
+
+        let request = requests.lock().unwrap().take().unwrap();
+        assert_eq!(request["ismAddress"], json!(ism));
+        assert_eq!(request["moduleType"], json!(12));
+        assert_eq!(request["origin"], json!(1));
+        assert_eq!(request["destination"], json!(2));
+        assert_eq!(request["nonce"], json!(3));
+        assert_eq!(request["messageId"], json!(message.id()));
+        assert_eq!(
+            request["message"],
+            json!(format!(
+                "0x{}",
+                hex_encode(RawHyperlaneMessage::from(&message).to_vec())
+            ))
+        );
+
+        // The metadata is only used if the ISM verifies it
+        let verifies = MockIsm {
+            verifies: vec![10, 11],
+        };
+        let rejects = MockIsm { verifies: vec![] };
+        assert_eq!(
+            verified_metadata(&verifies, &message, metadata.clone()).await,
+            Some(vec![10, 11])
+        );
+        assert_eq!(verified_metadata(&rejects, &message, metadata).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_provider_without_metadata() {
+        let (url, _) = serve(json!({ "metadata": null }));
+        let message = HyperlaneMessage::default();
+
+        let metadata = provider(url)
+            .fetch(H256::from_low_u64_be(1), 12, &message)
+            .await
+            .unwrap();
+        assert_eq!(metadata, None);
+    }
+}
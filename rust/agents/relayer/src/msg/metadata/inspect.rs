@@ -0,0 +1,309 @@
+//! Describes the tree of ISMs verifying a message and what stops them from
+//! verifying it, to debug messages whose metadata cannot be built.
+
+use std::fmt::{Display, Formatter};
+
+use eyre::{Context, Result};
+use futures_util::future::{join_all, BoxFuture, FutureExt};
+use serde::Serialize;
+
+use hyperlane_core::{HyperlaneMessage, ModuleType, H160, H256};
+use num_traits::FromPrimitive;
+
+use super::{base::MetadataBuilderError, deliverable_at, unix_now, BaseMetadataBuilder};
+
+/// An ISM in the tree verifying a message.
+#[derive(Debug, Default, Serialize)]
+pub struct IsmNode {
+    pub address: H256,
+    /// `None` if it could not be fetched.
+    pub module_type: Option<String>,
+    /// Why this ISM would not verify the message, `None` if no reason was
+    /// found.
+    pub problem: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<u8>,
+    /// The ISM a routing ISM routes the message to, or the submodule of an
+    /// optimistic ISM which has yet to pre-verify it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<Box<IsmNode>>,
+    /// The modules of an aggregation ISM.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<IsmNode>,
+    /// The validators of a multisig ISM.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub validators: Vec<ValidatorStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidatorStatus {
+    pub address: H256,
+    pub storage_locations: Vec<String>,
+    /// Index of the latest checkpoint signed by the validator, `None` if it
+    /// could not be fetched.
+    pub latest_checkpoint_index: Option<u32>,
+}
+
+/// Walk the tree of ISMs from `ism_address`, noting at each one what would
+/// stop it from verifying `message`.
+pub fn inspect_ism<'a>(
+    builder: &'a BaseMetadataBuilder,
+    ism_address: H256,
+    message: &'a HyperlaneMessage,
+) -> BoxFuture<'a, IsmNode> {
+    async move {
+        let mut node = IsmNode {
+            address: ism_address,
+            ..Default::default()
+        };
+        if let Err(e) = inspect_node(builder, &mut node, message).await {
+            node.problem = Some(format!("{e:#}"));
+        }
+        node
+    }
+    .boxed()
+}
+
+async fn inspect_node(
+    builder: &BaseMetadataBuilder,
+    node: &mut IsmNode,
+    message: &HyperlaneMessage,
+) -> Result<()> {
+    let raw_module_type = builder
+        .build_ism(node.address)
+        .await?
+        .raw_module_type()
+        .await
+        .context("When fetching module type")?;
+    let module_type = ModuleType::from_u8(raw_module_type);
+    node.module_type = Some(match module_type {
+        Some(module_type) => format!("{module_type:?}"),
+        None => raw_module_type.to_string(),
+    });
+    let module_type = module_type.unwrap_or(ModuleType::Unused);
+
+    if builder
+        .external_metadata_provider(node.address, raw_module_type)
+        .is_some()
+    {
+        // Whether the provider can build metadata is up to it
+        return Ok(());
+    }
+
+    match module_type {
+        ModuleType::Routing => {
+            let ism = builder.build_routing_ism(node.address).await?;
+            let route = ism.route(message).await.context("When fetching route")?;
+            let base = builder.clone_with_incremented_depth()?;
+            let route = inspect_ism(&base, route, message).await;
+            if route.problem.is_some() {
+                node.problem = Some("The ISM the message is routed to would not verify it".into());
+            }
+            node.route = Some(Box::new(route));
+        }
+        ModuleType::Aggregation => {
+            let ism = builder.build_aggregation_ism(node.address).await?;
+            let (modules, threshold) = ism
+                .modules_and_threshold(message)
+                .await
+                .context("When fetching modules and threshold")?;
+            let base = builder.clone_with_incremented_depth()?;
+            node.threshold = Some(threshold);
+            node.modules = join_all(
+                modules
+                    .into_iter()
+                    .map(|module| inspect_ism(&base, module, message)),
+            )
+            .await;
+            let verifying = node.modules.iter().filter(|m| m.problem.is_none()).count();
+            if verifying < threshold as usize {
+                node.problem = Some(format!(
+                    "Only {verifying} of the {threshold} modules needed would verify the message"
+                ));
+            }
+        }
+        ModuleType::LegacyMultisig
+        | ModuleType::MerkleRootMultisig
+        | ModuleType::MessageIdMultisig => {
+            let ism = builder.build_multisig_ism(node.address).await?;
+            let (validators, threshold) = ism
+                .validators_and_threshold(message)
+                .await
+                .context("When fetching validators and threshold")?;
+            node.threshold = Some(threshold);
+            node.validators = validator_statuses(builder, &validators).await?;
+            let signed = node
+                .validators
+                .iter()
+                .filter(|v| matches!(v.latest_checkpoint_index, Some(i) if i >= message.nonce))
+                .count();
+            if validators.is_empty() {
+                node.problem = Some("No validator set found for ISM".into());
+            } else if signed < threshold as usize {
+                node.problem = Some(format!(
+                    "Only {signed} of the {threshold} validators needed have signed a checkpoint including nonce {}",
+                    message.nonce
+                ));
+            }
+        }
+        ModuleType::Optimistic => {
+            let ism = builder.build_optimistic_ism(node.address).await?;
+            let pre_verified_at = ism
+                .pre_verified_at(message)
+                .await
+                .context("When fetching pre-verification time")?;
+            match pre_verified_at {
+                Some(pre_verified_at) => {
+                    let fraud_window = ism
+                        .fraud_window()
+                        .await
+                        .context("When fetching fraud window")?;
+                    let deliverable_at = deliverable_at(pre_verified_at, fraud_window);
+                    if unix_now() < deliverable_at {
+                        node.problem = Some(format!(
+                            "The message was pre-verified, its fraud window elapses at {deliverable_at}"
+                        ));
+                    }
+                }
+                None => {
+                    let submodule = ism
+                        .submodule(message)
+                        .await
+                        .context("When fetching submodule")?;
+                    let base = builder.clone_with_incremented_depth()?;
+                    let route = inspect_ism(&base, submodule, message).await;
+                    node.problem = Some(if route.problem.is_some() {
+                        "The submodule would not pre-verify the message".into()
+                    } else {
+                        "The message has not been pre-verified".into()
+                    });
+                    node.route = Some(Box::new(route));
+                }
+            }
+        }
+        ModuleType::Null | ModuleType::CcipRead => {}
+        _ => {
+            node.problem =
+                Some(MetadataBuilderError::UnsupportedModuleType(module_type).to_string())
+        }
+    }
+    Ok(())
+}
+
+async fn validator_statuses(
+    builder: &BaseMetadataBuilder,
+    validators: &[H256],
+) -> Result<Vec<ValidatorStatus>> {
+    let storage_locations = builder
+        .announced_storage_locations(validators)
+        .await
+        .context("When fetching announced storage locations")?;
+    let checkpoint_syncer = builder.build_checkpoint_syncer(validators).await?;
+    let latest_indices = join_all(validators.iter().map(|&validator| {
+        let checkpoint_syncer = checkpoint_syncer.checkpoint_syncer(&H160::from(validator));
+        async move {
+            match checkpoint_syncer {
+                Some(syncer) => syncer.latest_index().await.ok().flatten(),
+                None => None,
+            }
+        }
+    }))
+    .await;
+
+    Ok(validators
+        .iter()
+        .zip(storage_locations)
+        .zip(latest_indices)
+        .map(
+            |((&address, storage_locations), latest_checkpoint_index)| ValidatorStatus {
+                address,
+                storage_locations,
+                latest_checkpoint_index,
+            },
+        )
+        .collect())
+}
+
+impl IsmNode {
+    fn fmt_indented(&self, f: &mut Formatter<'_>, indent: usize) -> std::fmt::Result {
+        let pad = "  ".repeat(indent);
+        write!(
+            f,
+            "{pad}{:?} {}",
+            self.address,
+            self.module_type.as_deref().unwrap_or("Unknown")
+        )?;
+        if let Some(threshold) = self.threshold {
+            write!(f, " (threshold {threshold})")?;
+        }
+        match &self.problem {
+            Some(problem) => writeln!(f, ": {problem}")?,
+            None => writeln!(f)?,
+        }
+        for validator in &self.validators {
+            let latest = validator
+                .latest_checkpoint_index
+                .map(|i| i.to_string())
+                .unwrap_or_else(|| "unknown".into());
+            writeln!(
+                f,
+                "{pad}  validator {:?}: latest checkpoint {latest}, storage locations [{}]",
+                validator.address,
+                validator.storage_locations.join(", ")
+            )?;
+        }
+        if let Some(route) = &self.route {
+            route.fmt_indented(f, indent + 1)?;
+        }
+        for module in &self.modules {
+            module.fmt_indented(f, indent + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints the tree with a line for each ISM and validator.
+impl Display for IsmNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display_tree() {
+        let node = IsmNode {
+            address: H256::from_low_u64_be(1),
+            module_type: Some("Routing".into()),
+            problem: Some("The ISM the message is routed to would not verify it".into()),
+            route: Some(Box::new(IsmNode {
+                address: H256::from_low_u64_be(2),
+                module_type: Some("MessageIdMultisig".into()),
+                problem: Some(
+                    "Only 1 of the 2 validators needed have signed a checkpoint including nonce 5"
+                        .into(),
+                ),
+                threshold: Some(2),
+                validators: vec![ValidatorStatus {
+                    address: H256::from_low_u64_be(3),
+                    storage_locations: vec!["s3://bucket/region".into()],
+                    latest_checkpoint_index: Some(5),
+                }],
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let lines: Vec<String> = node.to_string().lines().map(String::from).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines[0].ends_with(" Routing: The ISM the message is routed to would not verify it")
+        );
+        assert!(lines[1].starts_with("  0x"));
+        assert!(lines[1].contains("MessageIdMultisig (threshold 2): Only 1 of the 2"));
+        assert!(lines[2].starts_with("    validator 0x"));
+        assert!(lines[2].ends_with("latest checkpoint 5, storage locations [s3://bucket/region]"));
+    }
+}
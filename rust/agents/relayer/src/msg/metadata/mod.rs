@@ -1,13 +1,24 @@
 mod aggregation;
 mod base;
+mod cache;
 mod ccip_read;
+mod external;
+mod inspect;
 mod multisig;
 mod null_metadata;
+mod optimistic;
 mod routing;
 
 use aggregation::AggregationIsmMetadataBuilder;
 pub(crate) use base::BaseMetadataBuilder;
 pub(crate) use base::MetadataBuilder;
+pub(crate) use cache::MetadataCache;
+pub(crate) use ccip_read::CcipReadGateway;
 use ccip_read::CcipReadIsmMetadataBuilder;
+pub(crate) use external::ExternalMetadataProviders;
+use external::{ExternalMetadataBuilder, ExternalMetadataProvider};
+pub(crate) use inspect::inspect_ism;
 use null_metadata::NullMetadataBuilder;
+pub(crate) use optimistic::{deliverable_at, unix_now};
+use optimistic::OptimisticIsmMetadataBuilder;
 use routing::RoutingIsmMetadataBuilder;
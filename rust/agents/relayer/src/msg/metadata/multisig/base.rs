@@ -104,8 +104,9 @@ impl<T: MultisigIsmMetadataBuilder> MetadataBuilder for T {
             .await
             .context(CTX)?;
 
-        let (validators, threshold) = multisig_ism
-            .validators_and_threshold(message)
+        let (validators, threshold) = self
+            .as_ref()
+            .validators_and_threshold(&*multisig_ism, message)
             .await
             .context(CTX)?;
 
@@ -7,6 +7,7 @@ use derive_new::new;
 use eyre::{Context, Result};
 use hyperlane_base::MultisigCheckpointSyncer;
 use hyperlane_core::{HyperlaneMessage, H256};
+use tracing::debug;
 
 use crate::msg::metadata::BaseMetadataBuilder;
 
@@ -35,12 +36,42 @@ impl MultisigIsmMetadataBuilder for MerkleRootMultisigMetadataBuilder {
     ) -> Result<Option<MultisigMetadata>> {
         const CTX: &str = "When fetching MerkleRootMultisig metadata";
         let highest_nonce = self.highest_known_nonce().await;
-        let Some(quorum_checkpoint) = checkpoint_syncer
-            .fetch_checkpoint_in_range(validators, threshold as usize, message.nonce, highest_nonce)
+        if message.nonce > highest_nonce {
+            debug!(
+                highest_nonce,
+                "Message has not been inserted into the merkle tree yet"
+            );
+            return Ok(None);
+        }
+
+        // Prefer the checkpoint at the index of the message, but validators may
+        // not have signed it, so fall back to proving the message against the
+        // lowest later checkpoint with a quorum.
+        let (quorum_checkpoint, checkpoint_kind) = match checkpoint_syncer
+            .fetch_checkpoint(validators, threshold as usize, message.nonce)
             .await
             .context(CTX)?
-        else {
-            return Ok(None);
+        {
+            Some(quorum_checkpoint) => (quorum_checkpoint, "exact"),
+            None => {
+                let Some(quorum_checkpoint) = checkpoint_syncer
+                    .fetch_lowest_checkpoint_in_range(
+                        validators,
+                        threshold as usize,
+                        message.nonce.saturating_add(1),
+                        highest_nonce,
+                    )
+                    .await
+                    .context(CTX)?
+                else {
+                    return Ok(None);
+                };
+                debug!(
+                    index = quorum_checkpoint.checkpoint.checkpoint.index,
+                    "No quorum checkpoint at the index of the message, proving it against a later one"
+                );
+                (quorum_checkpoint, "fallback")
+            }
         };
 
         let Some(proof) = self
@@ -51,6 +82,15 @@ impl MultisigIsmMetadataBuilder for MerkleRootMultisigMetadataBuilder {
             return Ok(None);
         };
 
+        self.metrics()
+            .merkle_root_multisig_proofs()
+            .with_label_values(&[
+                self.origin_domain().name(),
+                self.domain().name(),
+                checkpoint_kind,
+            ])
+            .inc();
+
         Ok(Some(MultisigMetadata::new(
             quorum_checkpoint.checkpoint.checkpoint,
             quorum_checkpoint.signatures,
@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use std::{
+    ops::Deref,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use derive_new::new;
+use eyre::Context;
+use tracing::{info, instrument};
+
+use hyperlane_core::{HyperlaneMessage, H256};
+
+use super::{BaseMetadataBuilder, MetadataBuilder};
+
+/// The unix timestamp from which a message pre-verified at `pre_verified_at`
+/// can be delivered.
+pub fn deliverable_at(pre_verified_at: u64, fraud_window: u64) -> u64 {
+    pre_verified_at.saturating_add(fraud_window)
+}
+
+/// The current unix timestamp.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Debug, new)]
+pub struct OptimisticIsmMetadataBuilder {
+    base: BaseMetadataBuilder,
+}
+
+impl Deref for OptimisticIsmMetadataBuilder {
+    type Target = BaseMetadataBuilder;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+#[async_trait]
+impl MetadataBuilder for OptimisticIsmMetadataBuilder {
+    /// Optimistic ISMs need no metadata once the message was pre-verified and
+    /// its fraud window has elapsed. Pre-verifying is left to the
+    /// `PendingMessage`.
+    #[instrument(err, skip(self))]
+    async fn build(
+        &self,
+        ism_address: H256,
+        message: &HyperlaneMessage,
+    ) -> eyre::Result<Option<Vec<u8>>> {
+        const CTX: &str = "When fetching OptimisticIsm metadata";
+        let ism = self.build_optimistic_ism(ism_address).await.context(CTX)?;
+        let Some(pre_verified_at) = ism.pre_verified_at(message).await.context(CTX)? else {
+            info!("Message has not been pre-verified");
+            return Ok(None);
+        };
+        let fraud_window = ism.fraud_window().await.context(CTX)?;
+        let deliverable_at = deliverable_at(pre_verified_at, fraud_window);
+        if unix_now() < deliverable_at {
+            info!(deliverable_at, "Fraud window has not elapsed");
+            return Ok(None);
+        }
+        Ok(Some(vec![]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_deliverable_at() {
+        assert_eq!(deliverable_at(1_000, 3_600), 4_600);
+        assert_eq!(deliverable_at(u64::MAX - 1, 3_600), u64::MAX);
+    }
+}
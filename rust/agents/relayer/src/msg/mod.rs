@@ -15,11 +15,11 @@
 //! allows us to re-run the operation if it succeeded but did not actually end
 //! up getting included.
 //!
-//! Right now there is one strategy: serial.
+//! Right now there is one strategy: serial, which can optionally batch several
+//! prepared operations into a single transaction. A destination can be served
+//! by several serial submitters running concurrently, one per submission lane.
 //!
 //! In the future it could make sense for there to be more, some ideas are:
-//!   - BatchingMessagesSubmitter
-//!   - ShardedWalletSubmitter (to get parallelism / nonce)
 //!   - SpeculativeSerializedSubmitter (batches with higher optimistic nonces,
 //!     recovery behavior)
 //!   - FallbackProviderSubmitter (Serialized, but if some RPC provider sucks,
@@ -30,4 +30,5 @@ pub(crate) mod metadata;
 pub(crate) mod pending_message;
 pub(crate) mod pending_operation;
 pub(crate) mod processor;
+pub(crate) mod rate_limiter;
 pub(crate) mod serial_submitter;
@@ -1,22 +1,26 @@
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
 use derive_new::new;
 use eyre::{Context, Result};
-use hyperlane_base::db::HyperlaneRocksDB;
+use hyperlane_base::db::{DeadLetter, HyperlaneRocksDB};
 use prometheus::{IntCounter, IntGauge};
 use tracing::{debug, error, info, instrument, trace, warn};
 
 use hyperlane_base::CoreMetrics;
-use hyperlane_core::{HyperlaneChain, HyperlaneDomain, HyperlaneMessage, Mailbox, U256};
+use hyperlane_core::{
+    ChainCommunicationError, HyperlaneChain, HyperlaneDomain, HyperlaneMessage, Mailbox,
+    OptimisticIsm, ProcessRevert, ProcessRevertClass, TxOutcome, H256, U256,
+};
 
 use super::{
     gas_payment::GasPaymentEnforcer,
-    metadata::{BaseMetadataBuilder, MetadataBuilder},
+    metadata::{deliverable_at, unix_now, BaseMetadataBuilder, MetadataBuilder},
     pending_operation::*,
 };
+use crate::settings::{PriorityClassConf, RetryPolicy, RetryPolicyConf};
 
 const CONFIRM_DELAY: Duration = if cfg!(any(test, feature = "test-utils")) {
     // Wait 5 seconds after submitting the message before confirming in test mode
@@ -42,6 +46,12 @@ pub struct MessageContext {
     /// Hard limit on transaction gas when submitting a transaction to the
     /// destination.
     pub transaction_gas_limit: Option<U256>,
+    /// Policies for retrying failed delivery attempts, the first one matching
+    /// a message applies to it.
+    pub retry_policies: Arc<Vec<RetryPolicyConf>>,
+    /// Classes of messages to attempt before others, the first one matching a
+    /// message sets its priority.
+    pub priority_classes: Arc<Vec<PriorityClassConf>>,
     pub metrics: MessageSubmissionMetrics,
 }
 
@@ -60,12 +70,43 @@ pub struct PendingMessage {
     last_attempted_at: Instant,
     #[new(default)]
     next_attempt_after: Option<Instant>,
+    #[new(default)]
+    priority: u32,
+    /// Why the last attempt to deliver the message failed.
+    #[new(default)]
+    last_error: Option<String>,
+    /// The kind of revert the last attempt failed with, if it failed because
+    /// the simulated process call reverted.
+    #[new(default)]
+    last_revert_class: Option<ProcessRevertClass>,
 }
 
 /// State for the next submission attempt generated by a prepare call.
-struct SubmissionData {
-    metadata: Vec<u8>,
-    gas_limit: U256,
+enum SubmissionData {
+    /// Process the message on the destination mailbox.
+    Process { metadata: Vec<u8>, gas_limit: U256 },
+    /// Pre-verify the message on the optimistic ISM verifying it, which starts
+    /// its fraud window. The message is processed once the window elapsed.
+    PreVerify {
+        ism: Box<dyn OptimisticIsm>,
+        metadata: Vec<u8>,
+        gas_limit: U256,
+    },
+}
+
+/// Where a message verified by an optimistic ISM is in its two-phase
+/// delivery.
+#[derive(Debug, PartialEq)]
+enum PreVerifyPhase {
+    /// The message has to be pre-verified.
+    Unverified,
+    /// A transaction pre-verifying the message was submitted, possibly before
+    /// a restart, and may still be included within `wait`.
+    Submitted { wait: Duration },
+    /// The message was pre-verified and its fraud window elapses in `wait`.
+    FraudWindow { wait: Duration },
+    /// The fraud window elapsed, so the message can be processed.
+    Deliverable,
 }
 
 impl Debug for PendingMessage {
@@ -83,8 +124,8 @@ impl Debug for PendingMessage {
                 }
             })
             .unwrap_or(0);
-        write!(f, "PendingMessage {{ num_retries: {}, since_last_attempt_s: {last_attempt}, next_attempt_after_s: {next_attempt}, message: {:?} }}",
-               self.num_retries, self.message)
+        write!(f, "PendingMessage {{ num_retries: {}, since_last_attempt_s: {last_attempt}, next_attempt_after_s: {next_attempt}, priority: {}, message: {:?} }}",
+               self.num_retries, self.priority, self.message)
     }
 }
 
@@ -100,13 +141,17 @@ impl Eq for PendingMessage {}
 
 #[async_trait]
 impl PendingOperation for PendingMessage {
+    fn id(&self) -> H256 {
+        self.message.id()
+    }
+
     fn domain(&self) -> &HyperlaneDomain {
         self.ctx.destination_mailbox.domain()
     }
 
     #[instrument]
     async fn prepare(&mut self) -> PendingOperationResult {
-        make_op_try!(|| self.on_reprepare());
+        make_op_try!(|reason| self.on_reprepare(reason));
 
         if !self.is_ready() {
             trace!("Message is not ready to be submitted yet");
@@ -142,7 +187,7 @@ impl PendingOperation for PendingMessage {
                 recipient=?self.message.recipient,
                 "Dropping message because recipient is not a contract"
             );
-            return PendingOperationResult::Drop;
+            return self.on_drop("Recipient is not a contract");
         }
 
         let ism_address = op_try!(
@@ -153,6 +198,22 @@ impl PendingOperation for PendingMessage {
             "fetching ISM address. Potentially malformed recipient ISM address."
         );
 
+        let optimistic_ism = op_try!(
+            self.ctx
+                .metadata_builder
+                .optimistic_ism(ism_address, &self.message)
+                .await,
+            "fetching optimistic ISM"
+        );
+        if let Some(ism) = optimistic_ism {
+            if let Some(result) = op_try!(
+                self.prepare_pre_verify(ism).await,
+                "preparing pre-verification by optimistic ISM"
+            ) {
+                return result;
+            }
+        }
+
         let Some(metadata) = op_try!(
             self.ctx
                 .metadata_builder
@@ -161,20 +222,33 @@ impl PendingOperation for PendingMessage {
             "building metadata"
         ) else {
             info!("Could not fetch metadata");
-            return self.on_reprepare();
+            return self.on_reprepare("Could not fetch metadata");
         };
 
-        // Estimate transaction costs for the process call. If there are issues, it's
-        // likely that gas estimation has failed because the message is
-        // reverting. This is defined behavior, so we just log the error and
+        // Estimate transaction costs for the process call. If the process call
+        // would revert, the revert is handled according to its cause rather
+        // than retried like any other failure. Other issues are logged and we
         // move onto the next tick.
-        let tx_cost_estimate = op_try!(
+        let tx_cost_estimate = match self
+            .ctx
+            .destination_mailbox
+            .process_estimate_costs(&self.message, &metadata)
+            .await
+        {
+            Err(ChainCommunicationError::ProcessReverted(revert)) => {
+                return self.on_process_revert(ism_address, &metadata, revert).await;
+            }
+            tx_cost_estimate => tx_cost_estimate,
+        };
+        self.last_revert_class = None;
+        if tx_cost_estimate.is_err() {
+            // The metadata may be what the call reverts on, so build it again
+            // next time.
             self.ctx
-                .destination_mailbox
-                .process_estimate_costs(&self.message, &metadata)
-                .await,
-            "estimating costs for process call"
-        );
+                .metadata_builder
+                .invalidate_cached_metadata(self.message.id());
+        }
+        let tx_cost_estimate = op_try!(tx_cost_estimate, "estimating costs for process call");
 
         // If the gas payment requirement hasn't been met, move to the next tick.
         let Some(gas_limit) = op_try!(
@@ -186,7 +260,7 @@ impl PendingOperation for PendingMessage {
         )
             else {
                 info!(?tx_cost_estimate, "Gas payment requirement not met yet");
-                return self.on_reprepare();
+                return self.on_reprepare("Gas payment requirement not met");
             };
 
         // Go ahead and attempt processing of message to destination chain.
@@ -200,11 +274,11 @@ impl PendingOperation for PendingMessage {
         if let Some(max_limit) = self.ctx.transaction_gas_limit {
             if gas_limit > max_limit {
                 info!("Message delivery estimated gas exceeds max gas limit");
-                return self.on_reprepare();
+                return self.on_reprepare("Message delivery estimated gas exceeds max gas limit");
             }
         }
 
-        self.submission_data = Some(Box::new(SubmissionData {
+        self.submission_data = Some(Box::new(SubmissionData::Process {
             metadata,
             gas_limit,
         }));
@@ -213,7 +287,7 @@ impl PendingOperation for PendingMessage {
 
     #[instrument]
     async fn submit(&mut self) -> PendingOperationResult {
-        make_op_try!(|| self.on_reprepare());
+        make_op_try!(|reason| self.on_reprepare(reason));
 
         if self.submitted {
             // this message has already been submitted, possibly not by us
@@ -229,39 +303,46 @@ impl PendingOperation for PendingMessage {
             .take()
             .expect("Pending message must be prepared before it can be submitted");
 
-        // We use the estimated gas limit from the prior call to
-        // `process_estimate_costs` to avoid a second gas estimation.
-        let tx_outcome = op_try!(
-            self.ctx
-                .destination_mailbox
-                .process(&self.message, &state.metadata, Some(state.gas_limit))
-                .await,
-            "processing message"
-        );
-
-        op_try!(critical: self.ctx.origin_gas_payment_enforcer.record_tx_outcome(&self.message, tx_outcome), "recording tx outcome");
-        if tx_outcome.executed {
-            info!(
-                txid=?tx_outcome.transaction_id,
-                "Message successfully processed by transaction"
-            );
-            self.submitted = true;
-            self.reset_attempts();
-            self.next_attempt_after = Some(Instant::now() + CONFIRM_DELAY);
-            PendingOperationResult::Success
-        } else {
-            info!(
-                txid=?tx_outcome.transaction_id,
-                "Transaction attempting to process message reverted"
-            );
-            self.on_reprepare()
+        match *state {
+            SubmissionData::Process {
+                metadata,
+                gas_limit,
+            } => {
+                // We use the estimated gas limit from the prior call to
+                // `process_estimate_costs` to avoid a second gas estimation.
+                let tx_outcome = op_try!(
+                    self.ctx
+                        .destination_mailbox
+                        .process(&self.message, &metadata, Some(gas_limit))
+                        .await,
+                    "processing message"
+                );
+                self.on_tx_outcome(tx_outcome)
+            }
+            SubmissionData::PreVerify {
+                ism,
+                metadata,
+                gas_limit,
+            } => {
+                // Recorded before submitting, so a pre-verification is not
+                // submitted again after a restart while it may still be
+                // included.
+                self.record_pre_verify_submission();
+                let tx_outcome = op_try!(
+                    ism.pre_verify(&self.message, &metadata, Some(gas_limit))
+                        .await,
+                    "pre-verifying message"
+                );
+                self.on_pre_verify_tx_outcome(tx_outcome)
+            }
         }
     }
 
     async fn confirm(&mut self) -> PendingOperationResult {
-        make_op_try!(|| {
+        make_op_try!(|reason| {
             // Provider error; just try again later
             // Note: this means that we are using `NotReady` for a retryable error case
+            self.last_error = Some(reason);
             self.inc_attempts();
             PendingOperationResult::NotReady
         });
@@ -290,14 +371,90 @@ impl PendingOperation for PendingMessage {
             PendingOperationResult::Success
         } else {
             self.reset_attempts();
-            self.on_reprepare()
+            self.on_reprepare(
+                "Message was not delivered after it was submitted, e.g. due to a reorg",
+            )
         }
     }
 
+    fn batch_item(&self) -> Option<BatchItem> {
+        if self.submitted {
+            return None;
+        }
+        let SubmissionData::Process {
+            metadata,
+            gas_limit,
+        } = self.submission_data.as_deref()?
+        else {
+            // Pre-verifications are not batched
+            return None;
+        };
+        Some(BatchItem {
+            mailbox: self.ctx.destination_mailbox.clone(),
+            message: self.message.clone(),
+            metadata: metadata.clone(),
+            gas_limit: *gas_limit,
+            tx_gas_limit: self.ctx.transaction_gas_limit,
+        })
+    }
+
+    #[instrument]
+    fn on_batch_submitted(&mut self, tx_outcome: TxOutcome) -> PendingOperationResult {
+        self.submission_data = None;
+        self.on_tx_outcome(tx_outcome)
+    }
+
+    fn on_batch_reverted(&mut self, tx_outcome: TxOutcome) -> Result<()> {
+        self.ctx
+            .origin_gas_payment_enforcer
+            .record_tx_outcome(&self.message, tx_outcome)
+    }
+
     fn _next_attempt_after(&self) -> Option<Instant> {
         self.next_attempt_after
     }
 
+    fn num_retries(&self) -> u32 {
+        self.num_retries
+    }
+
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn set_priority(&mut self, priority: u32) {
+        self.priority = priority;
+    }
+
+    fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    fn reset_attempts(&mut self) {
+        self.reset_attempts();
+    }
+
+    fn on_drop(&mut self, reason: &str) -> PendingOperationResult {
+        let dead_letter = DeadLetter {
+            message_id: self.message.id(),
+            nonce: self.message.nonce,
+            reason: reason.to_owned(),
+            last_error: self.last_error.clone(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        };
+        if let Err(e) = self
+            .ctx
+            .origin_db
+            .store_dead_letter_by_nonce(&self.message.nonce, &dead_letter)
+        {
+            warn!(message_id = ?self.message.id(), err = %e, "Persisting the dead letter failed for message");
+        }
+        PendingOperationResult::Drop
+    }
+
     #[cfg(test)]
     fn set_retries(&mut self, retries: u32) {
         self.set_retries(retries);
@@ -309,14 +466,14 @@ impl PendingMessage {
     /// In case of failure, behaves like `Self::new(...)`.
     pub fn from_persisted_retries(message: HyperlaneMessage, ctx: Arc<MessageContext>) -> Self {
         let mut pm = Self::new(message, ctx);
+        pm.priority = pm.class_priority();
         match pm
             .ctx
             .origin_db
             .retrieve_pending_message_retry_count_by_message_id(&pm.message.id())
         {
             Ok(Some(num_retries)) => {
-                let next_attempt_after = PendingMessage::calculate_msg_backoff(num_retries)
-                    .map(|dur| Instant::now() + dur);
+                let next_attempt_after = pm.backoff(num_retries).map(|dur| Instant::now() + dur);
                 pm.num_retries = num_retries;
                 pm.next_attempt_after = next_attempt_after;
             }
@@ -324,12 +481,299 @@ impl PendingMessage {
                 trace!(message_id = ?pm.message.id(), result = ?r, "Failed to read retry count from HyperlaneDB for message.")
             }
         }
+        // A message pre-verified before a restart still has to wait out its
+        // fraud window
+        if let Ok(Some(deliverable_at)) = pm
+            .ctx
+            .origin_db
+            .retrieve_optimistic_deliverable_at_by_message_id(&pm.message.id())
+        {
+            let now = unix_now();
+            if deliverable_at > now {
+                let deliverable_after = Instant::now() + Duration::from_secs(deliverable_at - now);
+                pm.next_attempt_after = pm.next_attempt_after.max(Some(deliverable_after));
+            }
+        }
         pm
     }
 
-    fn on_reprepare(&mut self) -> PendingOperationResult {
+    /// Prepare to pre-verify the message on the optimistic ISM verifying it,
+    /// or wait for the fraud window to elapse if it was pre-verified. Returns
+    /// `None` once the message can be processed.
+    async fn prepare_pre_verify(
+        &mut self,
+        ism: Box<dyn OptimisticIsm>,
+    ) -> Result<Option<PendingOperationResult>> {
+        let wait = match self.pre_verify_phase(&*ism).await? {
+            PreVerifyPhase::Unverified => None,
+            PreVerifyPhase::Submitted { wait } => {
+                debug!(
+                    ?wait,
+                    "Waiting for the submitted pre-verification to be included"
+                );
+                Some(wait)
+            }
+            PreVerifyPhase::FraudWindow { wait } => {
+                debug!(?wait, "Waiting for the fraud window to elapse");
+                Some(wait)
+            }
+            PreVerifyPhase::Deliverable => return Ok(None),
+        };
+        if let Some(wait) = wait {
+            self.next_attempt_after = Some(Instant::now() + wait);
+            return Ok(Some(PendingOperationResult::NotReady));
+        }
+
+        let submodule = ism
+            .submodule(&self.message)
+            .await
+            .context("When fetching optimistic ISM submodule")?;
+        let Some(metadata) = self
+            .ctx
+            .metadata_builder
+            .build(submodule, &self.message)
+            .await
+            .context("When building metadata")?
+        else {
+            info!("Could not fetch metadata for pre-verification");
+            return Ok(Some(self.on_reprepare("Could not fetch metadata")));
+        };
+
+        let tx_cost_estimate = ism
+            .pre_verify_estimate_costs(&self.message, &metadata)
+            .await;
+        if tx_cost_estimate.is_err() {
+            self.ctx
+                .metadata_builder
+                .invalidate_cached_metadata(self.message.id());
+        }
+        let tx_cost_estimate =
+            tx_cost_estimate.context("When estimating costs for preVerify call")?;
+
+        // Pre-verifying is paid for from the same gas payment as processing
+        if self
+            .ctx
+            .origin_gas_payment_enforcer
+            .message_meets_gas_payment_requirement(&self.message, &tx_cost_estimate)
+            .await
+            .context("When checking if message meets gas payment requirement")?
+            .is_none()
+        {
+            info!(?tx_cost_estimate, "Gas payment requirement not met yet");
+            return Ok(Some(self.on_reprepare("Gas payment requirement not met")));
+        }
+
+        let gas_limit = tx_cost_estimate.gas_limit;
+        if let Some(max_limit) = self.ctx.transaction_gas_limit {
+            if gas_limit > max_limit {
+                info!("Message pre-verification estimated gas exceeds max gas limit");
+                return Ok(Some(self.on_reprepare(
+                    "Message pre-verification estimated gas exceeds max gas limit",
+                )));
+            }
+        }
+
+        self.submission_data = Some(Box::new(SubmissionData::PreVerify {
+            ism,
+            metadata,
+            gas_limit,
+        }));
+        Ok(Some(PendingOperationResult::Success))
+    }
+
+    /// Where the message is in its two-phase delivery, going by whether the
+    /// optimistic ISM verifying it pre-verified it and whether a
+    /// pre-verification was submitted.
+    async fn pre_verify_phase(&self, ism: &dyn OptimisticIsm) -> Result<PreVerifyPhase> {
+        let now = unix_now();
+        if let Some(pre_verified_at) = ism.pre_verified_at(&self.message).await? {
+            let deliverable_at = deliverable_at(pre_verified_at, ism.fraud_window().await?);
+            if let Err(e) = self
+                .ctx
+                .origin_db
+                .store_optimistic_deliverable_at_by_message_id(&self.message.id(), &deliverable_at)
+            {
+                warn!(message_id = ?self.message.id(), err = %e, "Persisting the time the message can be delivered failed");
+            }
+            return Ok(if now >= deliverable_at {
+                PreVerifyPhase::Deliverable
+            } else {
+                PreVerifyPhase::FraudWindow {
+                    wait: Duration::from_secs(deliverable_at - now),
+                }
+            });
+        }
+
+        let Some(submitted_at) = self
+            .ctx
+            .origin_db
+            .retrieve_optimistic_pre_verify_submitted_at_by_message_id(&self.message.id())?
+        else {
+            return Ok(PreVerifyPhase::Unverified);
+        };
+        // Give a submitted pre-verification as long to be included as a
+        // processed message is given to be confirmed
+        let included_by = submitted_at.saturating_add(CONFIRM_DELAY.as_secs());
+        if now < included_by {
+            return Ok(PreVerifyPhase::Submitted {
+                wait: Duration::from_secs(included_by - now),
+            });
+        }
+        info!(
+            submitted_at,
+            "Submitted pre-verification was not included, submitting it again"
+        );
+        self.clear_pre_verify_submission();
+        Ok(PreVerifyPhase::Unverified)
+    }
+
+    fn record_pre_verify_submission(&self) {
+        if let Err(e) = self
+            .ctx
+            .origin_db
+            .store_optimistic_pre_verify_submitted_at_by_message_id(&self.message.id(), &unix_now())
+        {
+            warn!(message_id = ?self.message.id(), err = %e, "Persisting the pre-verification submission failed for message");
+        }
+    }
+
+    fn clear_pre_verify_submission(&self) {
+        if let Err(e) = self
+            .ctx
+            .origin_db
+            .remove_optimistic_pre_verify_submitted_at_by_message_id(&self.message.id())
+        {
+            warn!(message_id = ?self.message.id(), err = %e, "Removing the pre-verification submission failed for message");
+        }
+    }
+
+    /// Record the outcome of a transaction that attempted to pre-verify this
+    /// message. Once it is pre-verified, the message is prepared again to
+    /// wait out the fraud window.
+    fn on_pre_verify_tx_outcome(&mut self, tx_outcome: TxOutcome) -> PendingOperationResult {
+        make_op_try!(|reason| self.on_reprepare(reason));
+
+        // The outcome is known, so whether the message was pre-verified can
+        // be read from the ISM from now on
+        self.clear_pre_verify_submission();
+
+        op_try!(critical: self.ctx.origin_gas_payment_enforcer.record_tx_outcome(&self.message, tx_outcome), "recording tx outcome");
+        if tx_outcome.executed {
+            info!(
+                txid=?tx_outcome.transaction_id,
+                "Message successfully pre-verified by transaction"
+            );
+            self.reset_attempts();
+            PendingOperationResult::Reprepare
+        } else {
+            info!(
+                txid=?tx_outcome.transaction_id,
+                "Transaction attempting to pre-verify message reverted"
+            );
+            self.on_reprepare("Transaction attempting to pre-verify message reverted")
+        }
+    }
+
+    /// Handle a revert of the simulated process call according to its class.
+    async fn on_process_revert(
+        &mut self,
+        ism_address: H256,
+        metadata: &[u8],
+        mut revert: ProcessRevert,
+    ) -> PendingOperationResult {
+        // The revert of an ISM which reverts rather than returning false is
+        // indistinguishable from that of the recipient, so ask the ISM.
+        if revert.class == ProcessRevertClass::RecipientReverted
+            && !self.ism_verifies(ism_address, metadata).await
+        {
+            revert.class = ProcessRevertClass::IsmRejected;
+        }
+        info!(class = ?revert.class, reason = %revert.reason, "Simulated process call reverted");
+        self.last_revert_class = Some(revert.class);
+        let reason = format!("Simulated process call reverted with {revert}");
+        match revert.class {
+            ProcessRevertClass::AlreadyDelivered => {
+                debug!("Message has already been delivered, marking as submitted.");
+                self.submitted = true;
+                self.next_attempt_after = Some(Instant::now() + CONFIRM_DELAY);
+                PendingOperationResult::Success
+            }
+            ProcessRevertClass::IsmRejected => {
+                // The metadata may be what the ISM rejects, so build it again
+                // next time.
+                self.ctx
+                    .metadata_builder
+                    .invalidate_cached_metadata(self.message.id());
+                self.on_reprepare(reason)
+            }
+            ProcessRevertClass::RecipientReverted => self.on_reprepare(reason),
+            ProcessRevertClass::InvalidMessage => {
+                self.last_error = Some(reason);
+                self.on_drop("Message can never be processed by the destination mailbox")
+            }
+        }
+    }
+
+    /// Whether the ISM at `ism_address` verifies the message with `metadata`,
+    /// assuming it does if that cannot be checked.
+    async fn ism_verifies(&self, ism_address: H256, metadata: &[u8]) -> bool {
+        let ism = match self.ctx.metadata_builder.build_ism(ism_address).await {
+            Ok(ism) => ism,
+            Err(err) => {
+                debug!(
+                    ?err,
+                    "Failed to build ISM to check whether it verifies message"
+                );
+                return true;
+            }
+        };
+        match ism.dry_run_verify(&self.message, metadata).await {
+            Ok(verifies) => verifies.is_some(),
+            // Most likely the ISM reverted
+            Err(err) => {
+                debug!(?err, "ISM failed to verify message");
+                false
+            }
+        }
+    }
+
+    /// Record the outcome of a transaction that attempted to process this
+    /// message, either on its own or as part of a batch.
+    fn on_tx_outcome(&mut self, tx_outcome: TxOutcome) -> PendingOperationResult {
+        make_op_try!(|reason| self.on_reprepare(reason));
+
+        op_try!(critical: self.ctx.origin_gas_payment_enforcer.record_tx_outcome(&self.message, tx_outcome), "recording tx outcome");
+        if tx_outcome.executed {
+            info!(
+                txid=?tx_outcome.transaction_id,
+                "Message successfully processed by transaction"
+            );
+            self.submitted = true;
+            self.reset_attempts();
+            self.next_attempt_after = Some(Instant::now() + CONFIRM_DELAY);
+            PendingOperationResult::Success
+        } else {
+            info!(
+                txid=?tx_outcome.transaction_id,
+                "Transaction attempting to process message reverted"
+            );
+            self.on_reprepare("Transaction attempting to process message reverted")
+        }
+    }
+
+    fn on_reprepare(&mut self, reason: impl Into<String>) -> PendingOperationResult {
+        self.last_error = Some(reason.into());
         self.inc_attempts();
         self.submitted = false;
+        if let Some(max_retries) = self.retry_policy().and_then(|p| p.max_retries) {
+            if self.num_retries >= max_retries {
+                info!(
+                    num_retries = self.num_retries,
+                    "Giving up on message after reaching the max number of retries"
+                );
+                return self.on_drop("Reached the max number of retries");
+            }
+        }
         PendingOperationResult::Reprepare
     }
 
@@ -364,7 +808,8 @@ impl PendingMessage {
     fn inc_attempts(&mut self) {
         self.set_retries(self.num_retries + 1);
         self.last_attempted_at = Instant::now();
-        self.next_attempt_after = PendingMessage::calculate_msg_backoff(self.num_retries)
+        self.next_attempt_after = self
+            .backoff(self.num_retries)
             .map(|dur| self.last_attempted_at + dur);
     }
 
@@ -383,6 +828,68 @@ impl PendingMessage {
         }
     }
 
+    /// The priority of the first priority class matching this message, or 0 if
+    /// it is in none.
+    fn class_priority(&self) -> u32 {
+        self.ctx
+            .priority_classes
+            .iter()
+            .find(|class| class.matching_list.msg_matches(&self.message, true))
+            .map(|class| class.priority)
+            .unwrap_or_default()
+    }
+
+    /// The policy of the first retry policy config matching this message and
+    /// the class of revert its last attempt failed with, if any.
+    fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.ctx
+            .retry_policies
+            .iter()
+            .find(|conf| {
+                conf.matching_list.msg_matches(&self.message, true)
+                    && conf.matches_revert_class(self.last_revert_class)
+            })
+            .map(|conf| &conf.policy)
+    }
+
+    /// Get duration we should wait before re-attempting to deliver this
+    /// message given the number of retries, using its retry policy if it has
+    /// one.
+    fn backoff(&self, num_retries: u32) -> Option<Duration> {
+        match self.retry_policy() {
+            Some(policy) => {
+                PendingMessage::calculate_policy_backoff(policy, self.message.id(), num_retries)
+            }
+            None => PendingMessage::calculate_msg_backoff(num_retries),
+        }
+    }
+
+    /// Get duration we should wait before re-attempting to deliver a message
+    /// given the number of retries and a retry policy.
+    ///
+    /// The jitter is derived from the message id and the number of retries
+    /// rather than a random number generator, so it is reproducible while
+    /// still spreading out messages that failed at the same time.
+    /// `pub(crate)` for testing purposes
+    pub(crate) fn calculate_policy_backoff(
+        policy: &RetryPolicy,
+        message_id: H256,
+        num_retries: u32,
+    ) -> Option<Duration> {
+        if num_retries < 1 {
+            return None;
+        }
+        let exponent = i32::try_from(num_retries - 1).unwrap_or(i32::MAX);
+        let delay = (policy.base_delay.as_secs_f64() * policy.multiplier.powi(exponent))
+            .min(policy.max_delay.as_secs_f64());
+        let sample = (message_id.to_low_u64_be()
+            ^ u64::from(num_retries).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            as f64
+            / u64::MAX as f64;
+        let delay = delay * (1. + policy.jitter * (2. * sample - 1.));
+        Some(Duration::try_from_secs_f64(delay.max(0.)).unwrap_or(policy.max_delay))
+    }
+
     /// Get duration we should wait before re-attempting to deliver a message
     /// given the number of retries.
     /// `pub(crate)` for testing purposes
@@ -435,3 +942,210 @@ impl MessageSubmissionMetrics {
             .set(std::cmp::max(self.last_known_nonce.get(), msg.nonce as i64));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    };
+
+    use hyperlane_base::db::{test_utils, GasLedgerEntryKind};
+    use hyperlane_core::{ChainResult, HyperlaneContract, HyperlaneProvider, TxCostEstimate, H512};
+
+    use super::*;
+    use crate::msg::processor::test::{
+        dummy_domain, dummy_hyperlane_message, dummy_message_context,
+    };
+
+    const FRAUD_WINDOW: u64 = 3600;
+
+    /// An optimistic ISM whose pre-verification transactions are included
+    /// when the test says so.
+    #[derive(Debug, Default)]
+    struct MockOptimisticIsm {
+        pre_verified_at: Mutex<Option<u64>>,
+        pre_verify_executes: Mutex<bool>,
+        pre_verify_calls: AtomicU32,
+    }
+
+    impl HyperlaneChain for MockOptimisticIsm {
+        fn domain(&self) -> &HyperlaneDomain {
+            unimplemented!()
+        }
+
+        fn provider(&self) -> Box<dyn HyperlaneProvider> {
+            unimplemented!()
+        }
+    }
+
+    impl HyperlaneContract for MockOptimisticIsm {
+        fn address(&self) -> H256 {
+            H256::zero()
+        }
+    }
+
+    #[async_trait]
+    impl OptimisticIsm for MockOptimisticIsm {
+        async fn submodule(&self, _message: &HyperlaneMessage) -> ChainResult<H256> {
+            unimplemented!()
+        }
+
+        async fn fraud_window(&self) -> ChainResult<u64> {
+            Ok(FRAUD_WINDOW)
+        }
+
+        async fn pre_verified_at(&self, _message: &HyperlaneMessage) -> ChainResult<Option<u64>> {
+            Ok(*self.pre_verified_at.lock().unwrap())
+        }
+
+        async fn pre_verify(
+            &self,
+            _message: &HyperlaneMessage,
+            _metadata: &[u8],
+            _tx_gas_limit: Option<U256>,
+        ) -> ChainResult<TxOutcome> {
+            self.pre_verify_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(TxOutcome {
+                transaction_id: H512::zero(),
+                executed: *self.pre_verify_executes.lock().unwrap(),
+                gas_used: U256::zero(),
+                gas_price: U256::zero(),
+            })
+        }
+
+        async fn pre_verify_estimate_costs(
+            &self,
+            _message: &HyperlaneMessage,
+            _metadata: &[u8],
+        ) -> ChainResult<TxCostEstimate> {
+            unimplemented!()
+        }
+    }
+
+    async fn submit_pre_verify(pm: &mut PendingMessage, ism: &Arc<MockOptimisticIsm>) {
+        pm.submission_data = Some(Box::new(SubmissionData::PreVerify {
+            ism: Box::new(ism.clone()),
+            metadata: vec![],
+            gas_limit: U256::zero(),
+        }));
+        assert!(matches!(
+            pm.submit().await,
+            PendingOperationResult::Reprepare
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pre_verify_lifecycle() {
+        test_utils::run_test_db(|db| async move {
+            let origin = dummy_domain(0, "dummy_origin_domain");
+            let destination = dummy_domain(1, "dummy_destination_domain");
+            let db = HyperlaneRocksDB::new(&origin, db);
+            let ctx = Arc::new(dummy_message_context(&origin, &db));
+            let message = dummy_hyperlane_message(&destination, 0);
+            let id = message.id();
+            let ism = Arc::new(MockOptimisticIsm::default());
+
+            let mut pm = PendingMessage::from_persisted_retries(message.clone(), ctx.clone());
+            assert_eq!(
+                pm.pre_verify_phase(&*ism).await.unwrap(),
+                PreVerifyPhase::Unverified
+            );
+
+            // A reverted pre-verification is submitted again right away
+            submit_pre_verify(&mut pm, &ism).await;
+            assert_eq!(
+                db.retrieve_optimistic_pre_verify_submitted_at_by_message_id(&id)
+                    .unwrap(),
+                None
+            );
+            assert_eq!(
+                pm.pre_verify_phase(&*ism).await.unwrap(),
+                PreVerifyPhase::Unverified
+            );
+
+            // A pre-verification submitted before a restart is not submitted
+            // again while it may still be included
+            pm.record_pre_verify_submission();
+            let mut pm = PendingMessage::from_persisted_retries(message.clone(), ctx.clone());
+            assert!(matches!(
+                pm.pre_verify_phase(&*ism).await.unwrap(),
+                PreVerifyPhase::Submitted { wait } if wait <= CONFIRM_DELAY
+            ));
+            assert!(matches!(
+                pm.prepare_pre_verify(Box::new(ism.clone())).await.unwrap(),
+                Some(PendingOperationResult::NotReady)
+            ));
+            assert!(!pm.is_ready());
+            assert_eq!(ism.pre_verify_calls.load(Ordering::SeqCst), 1);
+
+            // Unless it was submitted too long ago
+            db.store_optimistic_pre_verify_submitted_at_by_message_id(
+                &id,
+                &(unix_now() - CONFIRM_DELAY.as_secs() - 1),
+            )
+            .unwrap();
+            assert_eq!(
+                pm.pre_verify_phase(&*ism).await.unwrap(),
+                PreVerifyPhase::Unverified
+            );
+
+            // Once included, the fraud window is waited out, also across
+            // restarts
+            *ism.pre_verify_executes.lock().unwrap() = true;
+            submit_pre_verify(&mut pm, &ism).await;
+            assert_eq!(ism.pre_verify_calls.load(Ordering::SeqCst), 2);
+            let now = unix_now();
+            *ism.pre_verified_at.lock().unwrap() = Some(now);
+            assert!(matches!(
+                pm.pre_verify_phase(&*ism).await.unwrap(),
+                PreVerifyPhase::FraudWindow { wait } if wait.as_secs() <= FRAUD_WINDOW
+            ));
+            assert_eq!(
+                db.retrieve_optimistic_deliverable_at_by_message_id(&id)
+                    .unwrap(),
+                Some(now + FRAUD_WINDOW)
+            );
+            let mut pm = PendingMessage::from_persisted_retries(message.clone(), ctx.clone());
+            assert!(!pm.is_ready());
+
+            // After which the message is processed
+            *ism.pre_verified_at.lock().unwrap() = Some(now - FRAUD_WINDOW);
+            assert!(pm
+                .prepare_pre_verify(Box::new(ism.clone()))
+                .await
+                .unwrap()
+                .is_none());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_reverted_batch_gas_is_ledgered() {
+        test_utils::run_test_db(|db| async move {
+            let origin = dummy_domain(0, "dummy_origin_domain");
+            let destination = dummy_domain(1, "dummy_destination_domain");
+            let db = HyperlaneRocksDB::new(&origin, db);
+            let ctx = Arc::new(dummy_message_context(&origin, &db));
+            let message = dummy_hyperlane_message(&destination, 0);
+            let mut pm = PendingMessage::from_persisted_retries(message.clone(), ctx);
+
+            let tx_outcome = TxOutcome {
+                transaction_id: H512::repeat_byte(1),
+                executed: false,
+                gas_used: U256::from(1000),
+                gas_price: U256::from(2),
+            };
+            pm.on_batch_reverted(tx_outcome).unwrap();
+
+            let ledger = db.retrieve_gas_ledger();
+            assert_eq!(ledger.len(), 1);
+            assert_eq!(ledger[0].kind, GasLedgerEntryKind::Expenditure);
+            assert_eq!(ledger[0].message_id, message.id());
+            assert_eq!(ledger[0].transaction_id, tx_outcome.transaction_id);
+            assert_eq!(ledger[0].gas, U256::from(1000));
+            assert_eq!(ledger[0].tokens, U256::from(2000));
+        })
+        .await;
+    }
+}
@@ -1,11 +1,13 @@
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::sync::Arc;
 use std::time::Instant;
 
 use async_trait::async_trait;
 use enum_dispatch::enum_dispatch;
 use eyre::Report;
 
-use hyperlane_core::HyperlaneDomain;
+use hyperlane_core::{HyperlaneDomain, HyperlaneMessage, Mailbox, TxOutcome, H256, U256};
 
 #[allow(unused_imports)] // required for enum_dispatch
 use super::pending_message::PendingMessage;
@@ -32,6 +34,9 @@ use super::pending_message::PendingMessage;
 #[async_trait]
 #[enum_dispatch]
 pub trait PendingOperation {
+    /// Unique id of this operation, e.g. the id of the message it delivers.
+    fn id(&self) -> H256;
+
     /// The domain this operation will take place on.
     fn domain(&self) -> &HyperlaneDomain;
 
@@ -49,12 +54,48 @@ pub trait PendingOperation {
     /// which we consider it safe from reorgs.
     async fn confirm(&mut self) -> PendingOperationResult;
 
+    /// The call this operation would make if it were submitted, or `None` if
+    /// it cannot be submitted as part of a batch. Only meaningful after a
+    /// successful `prepare`.
+    fn batch_item(&self) -> Option<BatchItem>;
+
+    /// Record the outcome of a batch transaction this operation was included
+    /// in. This is called instead of `submit` when the batch was executed.
+    fn on_batch_submitted(&mut self, tx_outcome: TxOutcome) -> PendingOperationResult;
+
+    /// Record the gas spent on a batch transaction this operation was
+    /// included in which reverted. The operation is submitted on its own
+    /// afterwards.
+    fn on_batch_reverted(&mut self, tx_outcome: TxOutcome) -> eyre::Result<()>;
+
     /// Get the earliest instant at which this should next be attempted.
     ///
-    /// This is only used for sorting, the functions are responsible for
-    /// returning `NotReady` if it is too early and matters.
+    /// This is only used for sorting and to pass over operations which are not
+    /// ready in the queues, the functions are responsible for returning
+    /// `NotReady` if it is too early and matters.
     fn _next_attempt_after(&self) -> Option<Instant>;
 
+    /// The number of times this operation has been attempted and failed.
+    fn num_retries(&self) -> u32;
+
+    /// Operations with a higher priority are attempted before others that are
+    /// equally ready to be attempted.
+    fn priority(&self) -> u32;
+
+    /// Set the priority of this operation.
+    fn set_priority(&mut self, priority: u32);
+
+    /// Why the last attempt at this operation failed, if it did.
+    fn last_error(&self) -> Option<&str>;
+
+    /// Clear the retry count and backoff so this operation will be attempted
+    /// again as soon as possible.
+    fn reset_attempts(&mut self);
+
+    /// Give up on this operation, recording why in the dead-letter queue so it
+    /// can be re-enqueued later.
+    fn on_drop(&mut self, reason: &str) -> PendingOperationResult;
+
     #[cfg(test)]
     /// Set the number of times this operation has been retried.
     fn set_retries(&mut self, retries: u32);
@@ -75,34 +116,85 @@ impl PartialOrd for DynPendingOperation {
     }
 }
 
-/// Sort by their next allowed attempt time and if no allowed time is set,
-/// then put it in front of those with a time (they have been tried
-/// before) and break ties between ones that have not been tried with
-/// the nonce.
+/// Sort operations by priority, higher first. Operations of the same priority
+/// are sorted by their next allowed attempt time with those never tried before
+/// first, and finally by nonce. The order does not depend on the current time,
+/// so it stays valid while operations sit in a queue. Whether an operation is
+/// ready to be attempted is checked when it is taken off the queue instead,
+/// see `pop_ready`.
 impl Ord for DynPendingOperation {
     fn cmp(&self, other: &Self) -> Ordering {
         use DynPendingOperation::*;
         use Ordering::*;
-        match (self._next_attempt_after(), other._next_attempt_after()) {
-            (Some(a), Some(b)) => a.cmp(&b),
-            // No time means it should come before
-            (None, Some(_)) => Less,
-            (Some(_), None) => Greater,
-            (None, None) => match (self, other) {
+        // Higher priority should come before
+        other
+            .priority()
+            .cmp(&self.priority())
+            .then_with(
+                || match (self._next_attempt_after(), other._next_attempt_after()) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    // No time means it should come before
+                    (None, Some(_)) => Less,
+                    (Some(_), None) => Greater,
+                    (None, None) => Equal,
+                },
+            )
+            .then_with(|| match (self, other) {
                 (PendingMessage(a), PendingMessage(b)) => {
                     if a.message.origin == b.message.origin {
                         // Should execute in order of nonce for the same origin
                         a.message.nonce.cmp(&b.message.nonce)
                     } else {
-                        // There is no priority between these messages, so arbitrarily use the id
+                        // There is no priority between these messages, so arbitrarily use
+                        // the id
                         a.message.id().cmp(&b.message.id())
                     }
                 }
-            },
-        }
+            })
     }
 }
 
+/// Take the first operation in the queue's order which is ready to be
+/// attempted, i.e. has no next allowed attempt time or one which has passed.
+/// Operations of a higher priority which are not ready yet stay in the queue.
+/// Returns `None` if no operation is ready.
+///
+/// Within a priority, operations which are ready come before those which are
+/// not, so only those of higher priorities are passed over.
+pub fn pop_ready(
+    queue: &mut BinaryHeap<Reverse<Box<DynPendingOperation>>>,
+) -> Option<Box<DynPendingOperation>> {
+    let now = Instant::now();
+    let mut not_ready = Vec::new();
+    let ready = loop {
+        match queue.pop() {
+            Some(Reverse(op)) if op._next_attempt_after().map_or(true, |t| t <= now) => {
+                break Some(op)
+            }
+            Some(op) => not_ready.push(op),
+            None => break None,
+        }
+    };
+    queue.extend(not_ready);
+    ready
+}
+
+/// A prepared mailbox call which can be combined with others into a single
+/// batch transaction.
+pub struct BatchItem {
+    /// Mailbox the call is made to.
+    pub mailbox: Arc<dyn Mailbox>,
+    /// Message to process.
+    pub message: HyperlaneMessage,
+    /// Metadata to process the message with.
+    pub metadata: Vec<u8>,
+    /// Estimated gas limit for processing the message on its own.
+    pub gas_limit: U256,
+    /// The highest gas limit a transaction processing the message may have,
+    /// `None` if there is no limit.
+    pub tx_gas_limit: Option<U256>,
+}
+
 pub enum PendingOperationResult {
     /// Promote to the next step
     Success,
@@ -117,7 +209,8 @@ pub enum PendingOperationResult {
     CriticalFailure(Report),
 }
 
-/// create a `op_try!` macro for the `on_retry` handler.
+/// create a `op_try!` macro for the `on_retry` handler, which is passed a
+/// description of the error.
 macro_rules! make_op_try {
     ($on_retry:expr) => {
         /// Handle a result and either return early with retry or a critical failure on
@@ -141,7 +234,7 @@ macro_rules! make_op_try {
                                             Ok(v) => v,
                                             Err(e) => {
                                                 warn!(error=?e, concat!("Error when ", $ctx));
-                                                return $on_retry();
+                                                return $on_retry(format!(concat!("Error when ", $ctx, ": {}"), e));
                                             }
                                         }
                                     };
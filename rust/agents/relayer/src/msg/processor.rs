@@ -1,27 +1,45 @@
 use std::{
-    collections::HashMap,
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
     fmt::{Debug, Formatter},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use derive_new::new;
 use eyre::Result;
 use hyperlane_base::{db::HyperlaneRocksDB, CoreMetrics};
-use hyperlane_core::{HyperlaneDomain, HyperlaneMessage};
-use prometheus::IntGauge;
+use hyperlane_core::{HyperlaneDomain, HyperlaneMessage, H256};
+use prometheus::{IntCounter, IntGauge};
 use tokio::{
-    sync::{mpsc::UnboundedSender, RwLock},
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        RwLock,
+    },
     task::JoinHandle,
 };
-use tracing::{debug, info_span, instrument, instrument::Instrumented, trace, Instrument};
+use tracing::{
+    debug, info, info_span, instrument, instrument::Instrumented, trace, warn, Instrument,
+};
 
 use super::pending_message::*;
 use crate::{
-    merkle_tree_builder::MerkleTreeBuilder, msg::pending_operation::DynPendingOperation,
+    merkle_tree_builder::MerkleTreeBuilder,
+    msg::{pending_operation::DynPendingOperation, rate_limiter::RateLimiter},
     settings::matching_list::MatchingList,
 };
 
+/// Max number of messages held back by each rate limit bucket. Once reached,
+/// further messages of the bucket are skipped and picked up again once some of
+/// its deferred messages have been sent.
+const MAX_DEFERRED_MESSAGES_PER_BUCKET: usize = 1_000;
+/// Max number of nonces looked at again for the skipped messages of a bucket
+/// in one round of processing.
+const MAX_CATCH_UP_NONCES: u32 = 1_000;
+
+/// A rate limit bucket, by the index of its rate limiter and its key.
+type BucketId = (usize, H256);
+
 /// Finds unprocessed messages from an origin and submits then through a channel
 /// for to the appropriate destination.
 #[derive(new)]
@@ -31,24 +49,84 @@ pub struct MessageProcessor {
     blacklist: Arc<MatchingList>,
     metrics: MessageProcessorMetrics,
     prover_sync: Arc<RwLock<MerkleTreeBuilder>>,
-    /// channel for each destination chain to send operations (i.e. message
-    /// submissions) to
-    send_channels: HashMap<u32, UnboundedSender<Box<DynPendingOperation>>>,
-    /// Needed context to send a message for each destination chain
-    destination_ctxs: HashMap<u32, Arc<MessageContext>>,
+    /// channels for each destination chain to send operations (i.e. message
+    /// submissions) to, one per submission lane
+    send_channels: HashMap<u32, Vec<UnboundedSender<Box<DynPendingOperation>>>>,
+    /// Needed context to send a message for each destination chain, one per
+    /// submission lane
+    destination_ctxs: HashMap<u32, Vec<Arc<MessageContext>>>,
+    /// Nonces of dead-lettered messages an operator has asked to be delivered
+    /// again
+    requeue_receiver: UnboundedReceiver<u32>,
+    /// Limits on the rate messages are sent to submitters at
+    rate_limiters: Arc<Vec<RateLimiter>>,
     #[new(default)]
     message_nonce: u32,
+    /// Messages held back by a rate limit, the first one to become ready on
+    /// top
+    #[new(default)]
+    deferred: BinaryHeap<Reverse<DeferredMessage>>,
+    /// Number of deferred messages by the bucket they are held back by
+    #[new(default)]
+    deferred_per_bucket: HashMap<BucketId, usize>,
+    /// Buckets whose messages are skipped because they have too many deferred
+    /// messages
+    #[new(default)]
+    overflowed: HashMap<BucketId, Overflow>,
+    #[new(value = "MAX_DEFERRED_MESSAGES_PER_BUCKET")]
+    max_deferred_per_bucket: usize,
+}
+
+/// The messages of a rate limit bucket skipped since it had too many deferred
+/// messages.
+#[derive(Debug, Clone, Copy)]
+struct Overflow {
+    /// Nonce of the first message skipped
+    from_nonce: u32,
+    /// Nonce to look for skipped messages from once the bucket has room again
+    next_nonce: u32,
+}
+
+/// A message held back because sending it would exceed a rate limit.
+struct DeferredMessage {
+    message: HyperlaneMessage,
+    /// Index of the rate limiter the message was last held back by
+    limiter: usize,
+    /// When the rate limiter is expected to let the message through
+    ready_at: Instant,
+}
+
+impl PartialEq for DeferredMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DeferredMessage {}
+
+impl PartialOrd for DeferredMessage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DeferredMessage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.ready_at, self.message.nonce).cmp(&(other.ready_at, other.message.nonce))
+    }
 }
 
 impl Debug for MessageProcessor {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "MessageProcessor {{ whitelist: {:?}, blacklist: {:?}, prover_sync: {:?}, message_nonce: {:?} }}",
+            "MessageProcessor {{ whitelist: {:?}, blacklist: {:?}, prover_sync: {:?}, message_nonce: {:?}, deferred: {:?}, overflowed: {:?} }}",
             self.whitelist,
             self.blacklist,
             self.prover_sync,
-            self.message_nonce
+            self.message_nonce,
+            self.deferred.len(),
+            self.overflowed.len()
         )
     }
 }
@@ -79,8 +157,8 @@ impl MessageProcessor {
     ///
     /// If no message with self.message_nonce is found, returns None.
     /// If the message with self.message_nonce is found and has previously
-    /// been marked as processed, increments self.message_nonce and returns
-    /// None.
+    /// been marked as processed or dead-lettered, increments
+    /// self.message_nonce and tries the next one.
     fn try_get_unprocessed_message(&mut self) -> Result<Option<HyperlaneMessage>> {
         loop {
             // First, see if we can find the message so we can update the gauge.
@@ -93,16 +171,24 @@ impl MessageProcessor {
                     metrics.set(message.nonce as i64);
                 }
 
-                // If this message has already been processed, on to the next one.
-                if !self
+                // If this message has already been processed or was given up on,
+                // on to the next one.
+                if self
                     .db
                     .retrieve_processed_by_nonce(&self.message_nonce)?
                     .unwrap_or(false)
                 {
-                    return Ok(Some(message));
-                } else {
                     debug!(nonce=?self.message_nonce, "Message already marked as processed in DB");
                     self.message_nonce += 1;
+                } else if self
+                    .db
+                    .retrieve_dead_letter_by_nonce(&self.message_nonce)?
+                    .is_some()
+                {
+                    debug!(nonce=?self.message_nonce, "Message is in the dead-letter queue, skipping");
+                    self.message_nonce += 1;
+                } else {
+                    return Ok(Some(message));
                 }
             } else {
                 trace!(nonce=?self.message_nonce, "No message found in DB for nonce");
@@ -114,66 +200,346 @@ impl MessageProcessor {
     /// One round of processing, extracted from infinite work loop for
     /// testing purposes.
     async fn tick(&mut self) -> Result<()> {
+        self.requeue_dead_letters()?;
+        self.send_deferred_messages()?;
+        self.catch_up_overflowed()?;
+
         // Scan until we find next nonce without delivery confirmation.
         if let Some(msg) = self.try_get_unprocessed_message()? {
             debug!(?msg, "Processor working on message");
-            let destination = msg.destination;
 
-            // Skip if not whitelisted.
-            if !self.whitelist.msg_matches(&msg, true) {
-                debug!(?msg, whitelist=?self.whitelist, "Message not whitelisted, skipping");
+            if !self.is_relayable(&msg) {
                 self.message_nonce += 1;
                 return Ok(());
             }
 
-            // Skip if the message is blacklisted
-            if self.blacklist.msg_matches(&msg, false) {
-                debug!(?msg, blacklist=?self.blacklist, "Message blacklisted, skipping");
+            // Feed the message to the prover sync
+            self.prover_sync
+                .write()
+                .await
+                .update_to_index(msg.nonce)
+                .await?;
+
+            // Skip if the message's bucket has too many deferred messages, it
+            // is picked up again once the bucket has room
+            if self.overflow_bucket(&msg).is_some() {
+                debug!(%msg, "Rate limit bucket has too many deferred messages, skipping");
                 self.message_nonce += 1;
                 return Ok(());
             }
 
-            // Skip if the message is intended for this origin
-            if destination == self.domain().id() {
-                debug!(?msg, "Message destined for self, skipping");
+            // Defer if sending the message now would exceed a rate limit
+            let now = now();
+            if let Some((limiter, ready_at)) = self.deferral(&msg, now)? {
+                let bucket = (limiter, self.rate_limiters[limiter].bucket_key(&msg));
+                if self.bucket_is_full(&bucket) {
+                    debug!(%msg, limiter=self.rate_limiters[limiter].name(), "Rate limit bucket has too many deferred messages, skipping");
+                    self.overflowed.insert(
+                        bucket,
+                        Overflow {
+                            from_nonce: msg.nonce,
+                            next_nonce: msg.nonce,
+                        },
+                    );
+                } else {
+                    debug!(%msg, limiter=self.rate_limiters[limiter].name(), "Message exceeds rate limit, deferring");
+                    self.metrics.rate_limited[limiter].inc();
+                    self.defer(msg, limiter, ready_at, now)?;
+                }
                 self.message_nonce += 1;
                 return Ok(());
             }
 
-            // Skip if the message is intended for a destination we do not service
-            if !self.send_channels.contains_key(&destination) {
-                debug!(?msg, "Message destined for unknown domain, skipping");
-                self.message_nonce += 1;
-                return Ok(());
+            self.take_rate_limits(&msg, now);
+            self.send_to_submitter(msg)?;
+            self.message_nonce += 1;
+        } else {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        Ok(())
+    }
+
+    /// Whether the message should be relayed by this processor at all.
+    fn is_relayable(&self, msg: &HyperlaneMessage) -> bool {
+        let destination = msg.destination;
+
+        // Skip if not whitelisted.
+        if !self.whitelist.msg_matches(msg, true) {
+            debug!(?msg, whitelist=?self.whitelist, "Message not whitelisted, skipping");
+            return false;
+        }
+
+        // Skip if the message is blacklisted
+        if self.blacklist.msg_matches(msg, false) {
+            debug!(?msg, blacklist=?self.blacklist, "Message blacklisted, skipping");
+            return false;
+        }
+
+        // Skip if the message is intended for this origin
+        if destination == self.domain().id() {
+            debug!(?msg, "Message destined for self, skipping");
+            return false;
+        }
+
+        // Skip if the message is intended for a destination we do not service
+        if !self.destination_ctxs.contains_key(&destination) {
+            debug!(?msg, "Message destined for unknown domain, skipping");
+            return false;
+        }
+        true
+    }
+
+    /// Whether a bucket holds back as many messages as it may.
+    fn bucket_is_full(&self, bucket: &BucketId) -> bool {
+        self.deferred_per_bucket
+            .get(bucket)
+            .copied()
+            .unwrap_or_default()
+            >= self.max_deferred_per_bucket
+    }
+
+    /// The overflowed bucket the message was skipped for, if any. If it is in
+    /// several, it is the one which overflowed first, so every skipped message
+    /// is picked up again by exactly one of them.
+    fn overflow_bucket(&self, msg: &HyperlaneMessage) -> Option<BucketId> {
+        self.overflowed
+            .iter()
+            .filter(|((limiter, key), overflow)| {
+                let limiter = &self.rate_limiters[*limiter];
+                overflow.from_nonce <= msg.nonce
+                    && limiter.applies_to(msg)
+                    && limiter.bucket_key(msg) == *key
+            })
+            .min_by_key(|(bucket, overflow)| (overflow.from_nonce, **bucket))
+            .map(|(bucket, _)| *bucket)
+    }
+
+    /// Pick up the messages skipped for overflowed buckets which have room
+    /// again, in order of nonce. A bucket is no longer overflowed once all of
+    /// its skipped messages have been picked up.
+    fn catch_up_overflowed(&mut self) -> Result<()> {
+        let buckets: Vec<_> = self.overflowed.keys().copied().collect();
+        for bucket in buckets {
+            let mut next_nonce = self.overflowed[&bucket].next_nonce;
+            let end = self
+                .message_nonce
+                .min(next_nonce.saturating_add(MAX_CATCH_UP_NONCES));
+            while next_nonce < end && !self.bucket_is_full(&bucket) {
+                if !self.catch_up_message(next_nonce, bucket)? {
+                    break;
+                }
+                next_nonce += 1;
             }
+            if next_nonce >= self.message_nonce {
+                debug!(limiter=self.rate_limiters[bucket.0].name(), key=?bucket.1, "Picked up all messages skipped for rate limit bucket");
+                self.overflowed.remove(&bucket);
+            } else if let Some(overflow) = self.overflowed.get_mut(&bucket) {
+                overflow.next_nonce = next_nonce;
+            }
+        }
+        Ok(())
+    }
 
-            // Feed the message to the prover sync
-            self.prover_sync
-                .write()
-                .await
-                .update_to_index(msg.nonce)
-                .await?;
+    /// Pick up the message with the nonce if it was skipped for the bucket.
+    /// Returns false if it has to be skipped for now again because the bucket
+    /// which would hold it back is full.
+    fn catch_up_message(&mut self, nonce: u32, bucket: BucketId) -> Result<bool> {
+        let Some(msg) = self.db.retrieve_message_by_nonce(nonce)? else {
+            return Ok(true);
+        };
+        if self
+            .db
+            .retrieve_processed_by_nonce(&nonce)?
+            .unwrap_or(false)
+            || self.db.retrieve_dead_letter_by_nonce(&nonce)?.is_some()
+            || !self.is_relayable(&msg)
+            || self.overflow_bucket(&msg) != Some(bucket)
+        {
+            return Ok(true);
+        }
+
+        let now = now();
+        if let Some((limiter, ready_at)) = self.deferral(&msg, now)? {
+            if self.bucket_is_full(&(limiter, self.rate_limiters[limiter].bucket_key(&msg))) {
+                return Ok(false);
+            }
+            self.metrics.rate_limited[limiter].inc();
+            self.defer(msg, limiter, ready_at, now)?;
+        } else {
+            self.take_rate_limits(&msg, now);
+            self.send_to_submitter(msg)?;
+        }
+        Ok(true)
+    }
 
-            debug!(%msg, "Sending message to submitter");
+    /// Send messages that were re-enqueued from the dead-letter queue to their
+    /// submitters again, starting with a clean retry count.
+    fn requeue_dead_letters(&mut self) -> Result<()> {
+        while let Ok(nonce) = self.requeue_receiver.try_recv() {
+            let Some(msg) = self.db.retrieve_message_by_nonce(nonce)? else {
+                warn!(nonce, "Requeued message not found in DB, skipping");
+                continue;
+            };
+            if !self.destination_ctxs.contains_key(&msg.destination) {
+                warn!(%msg, "Requeued message destined for unknown domain, skipping");
+                continue;
+            }
+            info!(%msg, "Requeueing message from the dead-letter queue");
+            self.db
+                .store_pending_message_retry_count_by_message_id(&msg.id(), &0)?;
+            self.send_to_submitter(msg)?;
+        }
+        Ok(())
+    }
 
-            // Finally, build the submit arg and dispatch it to the submitter.
-            let pending_msg = PendingMessage::from_persisted_retries(
-                msg,
-                self.destination_ctxs[&destination].clone(),
+    /// Send the deferred messages that have become ready and no longer exceed
+    /// a rate limit, in the order they became ready.
+    fn send_deferred_messages(&mut self) -> Result<()> {
+        let now = now();
+        while matches!(self.deferred.peek(), Some(Reverse(deferred)) if deferred.ready_at <= now) {
+            let Reverse(deferred) = self.deferred.pop().unwrap();
+            self.metrics.rate_limit_deferred[deferred.limiter].dec();
+            let bucket = (
+                deferred.limiter,
+                self.rate_limiters[deferred.limiter].bucket_key(&deferred.message),
             );
-            self.send_channels[&destination].send(Box::new(pending_msg.into()))?;
-            self.message_nonce += 1;
-        } else {
-            tokio::time::sleep(Duration::from_secs(1)).await;
+            if let Some(count) = self.deferred_per_bucket.get_mut(&bucket) {
+                *count -= 1;
+                if *count == 0 {
+                    self.deferred_per_bucket.remove(&bucket);
+                }
+            }
+            match self.exceeded_rate_limit(&deferred.message, now) {
+                // Other messages used up the capacity in the meantime
+                Some(limiter) => {
+                    let ready_at = self.rate_limiters[limiter].ready_at(&deferred.message, now);
+                    self.defer(deferred.message, limiter, ready_at, now)?;
+                }
+                None => {
+                    self.take_rate_limits(&deferred.message, now);
+                    self.send_to_submitter(deferred.message)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The index of the rate limiter holding back the message if it were sent
+    /// at `now`, and when it is expected to let it through. Deferrals
+    /// persisted before a restart are honored, since the rate limits start
+    /// out with their full capacity.
+    fn deferral(&self, msg: &HyperlaneMessage, now: Instant) -> Result<Option<(usize, Instant)>> {
+        if let Some(limiter) = self.exceeded_rate_limit(msg, now) {
+            return Ok(Some((
+                limiter,
+                self.rate_limiters[limiter].ready_at(msg, now),
+            )));
         }
+        let Some(deferred_until) = self
+            .db
+            .retrieve_rate_limit_deferred_until_by_message_id(&msg.id())?
+        else {
+            return Ok(None);
+        };
+        let wait = deferred_until.saturating_sub(unix_timestamp());
+        let limiter = self
+            .rate_limiters
+            .iter()
+            .position(|limiter| limiter.applies_to(msg));
+        Ok(limiter
+            .filter(|_| wait > 0)
+            .map(|limiter| (limiter, now + Duration::from_secs(wait))))
+    }
+
+    /// Hold back a message until `ready_at`, persisting the deferral so it
+    /// survives restarts.
+    fn defer(
+        &mut self,
+        message: HyperlaneMessage,
+        limiter: usize,
+        ready_at: Instant,
+        now: Instant,
+    ) -> Result<()> {
+        let wait = ready_at.saturating_duration_since(now).as_secs_f64().ceil() as u64;
+        self.db.store_rate_limit_deferred_until_by_message_id(
+            &message.id(),
+            &(unix_timestamp() + wait),
+        )?;
+        self.metrics.rate_limit_deferred[limiter].inc();
+        *self
+            .deferred_per_bucket
+            .entry((limiter, self.rate_limiters[limiter].bucket_key(&message)))
+            .or_default() += 1;
+        self.deferred.push(Reverse(DeferredMessage {
+            message,
+            limiter,
+            ready_at,
+        }));
         Ok(())
     }
+
+    /// Index of the first rate limiter the message would exceed if it were
+    /// sent at `now`, if any.
+    fn exceeded_rate_limit(&self, msg: &HyperlaneMessage, now: Instant) -> Option<usize> {
+        self.rate_limiters
+            .iter()
+            .position(|limiter| limiter.applies_to(msg) && !limiter.has_capacity(msg, now))
+    }
+
+    /// Use up the capacity of every rate limiter that applies to the message.
+    fn take_rate_limits(&self, msg: &HyperlaneMessage, now: Instant) {
+        self.rate_limiters
+            .iter()
+            .filter(|limiter| limiter.applies_to(msg))
+            .for_each(|limiter| limiter.take(msg, now));
+    }
+
+    /// Build the pending operation for a message and dispatch it to the
+    /// submitter of its destination and lane.
+    fn send_to_submitter(&self, msg: HyperlaneMessage) -> Result<()> {
+        let destination = msg.destination;
+        let lane = submission_lane(&msg, self.send_channels[&destination].len());
+        debug!(%msg, lane, "Sending message to submitter");
+
+        let pending_msg = PendingMessage::from_persisted_retries(
+            msg,
+            self.destination_ctxs[&destination][lane].clone(),
+        );
+        self.send_channels[&destination][lane].send(Box::new(pending_msg.into()))?;
+        Ok(())
+    }
+}
+
+/// The current time, from tokio's clock so it can be paused in tests.
+fn now() -> Instant {
+    tokio::time::Instant::now().into_std()
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Pick the submission lane for a message. Messages from the same sender always
+/// use the same lane so they keep being delivered in order relative to each
+/// other.
+fn submission_lane(msg: &HyperlaneMessage, lane_count: usize) -> usize {
+    if lane_count <= 1 {
+        return 0;
+    }
+    (msg.sender.to_low_u64_be() % lane_count as u64) as usize
 }
 
 #[derive(Debug)]
 pub struct MessageProcessorMetrics {
     max_last_known_message_nonce_gauge: IntGauge,
     last_known_message_nonce_gauges: HashMap<u32, IntGauge>,
+    /// Messages deferred by each rate limiter, by limiter index
+    rate_limited: Vec<IntCounter>,
+    /// Messages currently deferred by each rate limiter, by limiter index
+    rate_limit_deferred: Vec<IntGauge>,
 }
 
 impl MessageProcessorMetrics {
@@ -181,6 +547,7 @@ impl MessageProcessorMetrics {
         metrics: &CoreMetrics,
         origin: &HyperlaneDomain,
         destinations: impl Iterator<Item = &'a HyperlaneDomain>,
+        rate_limiters: &[RateLimiter],
     ) -> Self {
         let mut gauges: HashMap<u32, IntGauge> = HashMap::new();
         for destination in destinations {
@@ -198,6 +565,22 @@ impl MessageProcessorMetrics {
                 .last_known_message_nonce()
                 .with_label_values(&["processor_loop", origin.name(), "any"]),
             last_known_message_nonce_gauges: gauges,
+            rate_limited: rate_limiters
+                .iter()
+                .map(|limiter| {
+                    metrics
+                        .rate_limited_messages()
+                        .with_label_values(&[limiter.name(), origin.name()])
+                })
+                .collect(),
+            rate_limit_deferred: rate_limiters
+                .iter()
+                .map(|limiter| {
+                    metrics
+                        .rate_limit_deferred_messages()
+                        .with_label_values(&[limiter.name(), origin.name()])
+                })
+                .collect(),
         }
     }
 
@@ -207,11 +590,11 @@ impl MessageProcessorMetrics {
 }
 
 #[cfg(test)]
-mod test {
-    use std::time::Instant;
+pub(crate) mod test {
+    use std::{cmp::Reverse, collections::BinaryHeap, time::Instant};
 
     use hyperlane_base::{
-        db::{test_utils, HyperlaneRocksDB},
+        db::{test_utils, DeadLetter, HyperlaneRocksDB},
         settings::{ChainConf, ChainConnectionConf, Settings},
     };
     use hyperlane_test::mocks::{MockMailboxContract, MockValidatorAnnounceContract};
@@ -222,9 +605,13 @@ mod test {
     };
 
     use super::*;
-    use crate::msg::{
-        gas_payment::GasPaymentEnforcer, metadata::BaseMetadataBuilder,
-        pending_operation::PendingOperation,
+    use crate::{
+        msg::{
+            gas_payment::GasPaymentEnforcer,
+            metadata::BaseMetadataBuilder,
+            pending_operation::{pop_ready, PendingOperation},
+        },
+        settings::{PriorityClassConf, RateLimitConf, RateLimitKey, RetryPolicy, RetryPolicyConf},
     };
 
     fn dummy_processor_metrics(domain_id: u32) -> MessageProcessorMetrics {
@@ -238,6 +625,8 @@ mod test {
                 domain_id,
                 IntGauge::new("dummy_last_known_message_nonce_gauge", "help string").unwrap(),
             )]),
+            rate_limited: vec![],
+            rate_limit_deferred: vec![],
         }
     }
 
@@ -254,11 +643,14 @@ mod test {
             signer: Default::default(),
             finality_blocks: Default::default(),
             addresses: Default::default(),
+            deployments: Default::default(),
             connection: ChainConnectionConf::Ethereum(hyperlane_ethereum::ConnectionConf::Http {
                 url: "http://example.com".parse().unwrap(),
             }),
             metrics_conf: Default::default(),
             index: Default::default(),
+            tx_escalation: None,
+            multicall_address: None,
         }
     }
 
@@ -276,12 +668,34 @@ mod test {
             destination_chain_conf.clone(),
             Arc::new(RwLock::new(MerkleTreeBuilder::new(db.clone()))),
             Arc::new(MockValidatorAnnounceContract::default()),
+            Default::default(),
+            Default::default(),
+            Default::default(),
             false,
             Arc::new(core_metrics),
+            Default::default(),
+            Default::default(),
+            Default::default(),
             5,
         )
     }
 
+    pub(crate) fn dummy_message_context(
+        origin_domain: &HyperlaneDomain,
+        db: &HyperlaneRocksDB,
+    ) -> MessageContext {
+        MessageContext {
+            destination_mailbox: Arc::new(MockMailboxContract::default()),
+            origin_db: db.clone(),
+            metadata_builder: dummy_metadata_builder(origin_domain, db),
+            origin_gas_payment_enforcer: Arc::new(GasPaymentEnforcer::new([], db.clone())),
+            transaction_gas_limit: Default::default(),
+            retry_policies: Default::default(),
+            priority_classes: Default::default(),
+            metrics: dummy_submission_metrics(),
+        }
+    }
+
     fn dummy_message_processor(
         origin_domain: &HyperlaneDomain,
         destination_domain: &HyperlaneDomain,
@@ -289,18 +703,12 @@ mod test {
     ) -> (
         MessageProcessor,
         UnboundedReceiver<Box<DynPendingOperation>>,
+        UnboundedSender<u32>,
     ) {
-        let base_metadata_builder = dummy_metadata_builder(origin_domain, db);
-        let message_context = Arc::new(MessageContext {
-            destination_mailbox: Arc::new(MockMailboxContract::default()),
-            origin_db: db.clone(),
-            metadata_builder: base_metadata_builder,
-            origin_gas_payment_enforcer: Arc::new(GasPaymentEnforcer::new([], db.clone())),
-            transaction_gas_limit: Default::default(),
-            metrics: dummy_submission_metrics(),
-        });
+        let message_context = Arc::new(dummy_message_context(origin_domain, db));
 
         let (send_channel, receive_channel) = mpsc::unbounded_channel::<Box<DynPendingOperation>>();
+        let (requeue_sender, requeue_receiver) = mpsc::unbounded_channel();
         (
             MessageProcessor::new(
                 db.clone(),
@@ -308,14 +716,20 @@ mod test {
                 Default::default(),
                 dummy_processor_metrics(origin_domain.id()),
                 Arc::new(RwLock::new(MerkleTreeBuilder::new(db.clone()))),
-                HashMap::from([(destination_domain.id(), send_channel)]),
-                HashMap::from([(destination_domain.id(), message_context)]),
+                HashMap::from([(destination_domain.id(), vec![send_channel])]),
+                HashMap::from([(destination_domain.id(), vec![message_context])]),
+                requeue_receiver,
+                Default::default(),
             ),
             receive_channel,
+            requeue_sender,
         )
     }
 
-    fn dummy_hyperlane_message(destination: &HyperlaneDomain, nonce: u32) -> HyperlaneMessage {
+    pub(crate) fn dummy_hyperlane_message(
+        destination: &HyperlaneDomain,
+        nonce: u32,
+    ) -> HyperlaneMessage {
         HyperlaneMessage {
             version: Default::default(),
             nonce,
@@ -336,7 +750,7 @@ mod test {
         }
     }
 
-    fn dummy_domain(domain_id: u32, name: &str) -> HyperlaneDomain {
+    pub(crate) fn dummy_domain(domain_id: u32, name: &str) -> HyperlaneDomain {
         let test_domain = HyperlaneDomain::new_test_domain(name);
         HyperlaneDomain::Unknown {
             domain_id,
@@ -370,7 +784,7 @@ mod test {
         db: &HyperlaneRocksDB,
         num_operations: usize,
     ) -> Vec<Box<DynPendingOperation>> {
-        let (message_processor, mut receive_channel) =
+        let (message_processor, mut receive_channel, _requeue_sender) =
             dummy_message_processor(origin_domain, destination_domain, db);
 
         let process_fut = message_processor.spawn();
@@ -391,6 +805,408 @@ mod test {
         pending_messages
     }
 
+    #[test]
+    fn test_submission_lane_is_stable_per_sender() {
+        let destination = dummy_domain(1, "dummy_destination_domain");
+        let mut msg = dummy_hyperlane_message(&destination, 0);
+        msg.sender = H256::from_low_u64_be(7);
+        assert_eq!(submission_lane(&msg, 1), 0);
+        assert_eq!(submission_lane(&msg, 4), 3);
+
+        // Other messages from the same sender use the same lane
+        msg.nonce = 1;
+        msg.recipient = H256::from_low_u64_be(1);
+        assert_eq!(submission_lane(&msg, 4), 3);
+    }
+
+    #[tokio::test]
+    async fn test_priority_classes_order_ready_messages() {
+        test_utils::run_test_db(|db| async move {
+            let origin_domain = dummy_domain(0, "dummy_origin_domain");
+            let destination_domain = dummy_domain(1, "dummy_destination_domain");
+            let db = HyperlaneRocksDB::new(&origin_domain, db);
+            let mut ctx = dummy_message_context(&origin_domain, &db);
+            ctx.priority_classes = Arc::new(vec![PriorityClassConf {
+                priority: 10,
+                matching_list: serde_json::from_str(
+                    r#"[{"senderAddress": "0x0000000000000000000000000000000000000000000000000000000000000007"}]"#,
+                )
+                .unwrap(),
+            }]);
+            let ctx = Arc::new(ctx);
+
+            let spam = dummy_hyperlane_message(&destination_domain, 0);
+            let mut premium = dummy_hyperlane_message(&destination_domain, 1);
+            premium.sender = H256::from_low_u64_be(7);
+
+            let spam: DynPendingOperation =
+                PendingMessage::from_persisted_retries(spam, ctx.clone()).into();
+            let premium: DynPendingOperation =
+                PendingMessage::from_persisted_retries(premium, ctx).into();
+            assert_eq!(spam.priority(), 0);
+            assert_eq!(premium.priority(), 10);
+
+            // The premium message is attempted first despite its higher nonce
+            let mut queue = BinaryHeap::from([Reverse(spam), Reverse(premium)]);
+            assert_eq!(queue.pop().unwrap().0.priority(), 10);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_retried_high_priority_message_is_ordered_by_priority() {
+        test_utils::run_test_db(|db| async move {
+            let origin_domain = dummy_domain(0, "dummy_origin_domain");
+            let destination_domain = dummy_domain(1, "dummy_destination_domain");
+            let db = HyperlaneRocksDB::new(&origin_domain, db);
+            let ctx = |base_delay: Duration| {
+                let mut ctx = dummy_message_context(&origin_domain, &db);
+                ctx.priority_classes = Arc::new(vec![PriorityClassConf {
+                    priority: 10,
+                    matching_list: serde_json::from_str(
+                        r#"[{"senderAddress": "0x0000000000000000000000000000000000000000000000000000000000000007"}]"#,
+                    )
+                    .unwrap(),
+                }]);
+                ctx.retry_policies = Arc::new(vec![RetryPolicyConf {
+                    policy: RetryPolicy {
+                        base_delay,
+                        multiplier: 1.,
+                        max_delay: base_delay,
+                        jitter: 0.,
+                        max_retries: None,
+                    },
+                    matching_list: Default::default(),
+                    revert_classes: vec![],
+                }]);
+                Arc::new(ctx)
+            };
+
+            let spam = dummy_hyperlane_message(&destination_domain, 0);
+            let mut premium = dummy_hyperlane_message(&destination_domain, 1);
+            premium.sender = H256::from_low_u64_be(7);
+            // The premium message failed once before
+            db.store_pending_message_retry_count_by_message_id(&premium.id(), &1)
+                .unwrap();
+            let spam: DynPendingOperation =
+                PendingMessage::from_persisted_retries(spam, ctx(Duration::ZERO)).into();
+
+            // Once its backoff has passed it is attempted before the untried
+            // message of lower priority
+            let ready: DynPendingOperation =
+                PendingMessage::from_persisted_retries(premium.clone(), ctx(Duration::ZERO))
+                    .into();
+            assert_eq!(ready.priority(), 10);
+            assert_eq!(ready.num_retries(), 1);
+            let mut queue = BinaryHeap::from([Reverse(Box::new(spam)), Reverse(Box::new(ready))]);
+            assert_eq!(pop_ready(&mut queue).unwrap().priority(), 10);
+
+            // Until then it is not
+            let spam = pop_ready(&mut queue).unwrap();
+            let backing_off: DynPendingOperation =
+                PendingMessage::from_persisted_retries(premium, ctx(Duration::from_secs(60)))
+                    .into();
+            let mut queue = BinaryHeap::from([Reverse(spam), Reverse(Box::new(backing_off))]);
+            assert_eq!(pop_ready(&mut queue).unwrap().priority(), 0);
+            // and it is left in the queue
+            assert!(pop_ready(&mut queue).is_none());
+            assert_eq!(queue.len(), 1);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_queued_message_is_ordered_by_priority_once_ready() {
+        test_utils::run_test_db(|db| async move {
+            let origin_domain = dummy_domain(0, "dummy_origin_domain");
+            let destination_domain = dummy_domain(1, "dummy_destination_domain");
+            let db = HyperlaneRocksDB::new(&origin_domain, db);
+            let backoff = Duration::from_millis(200);
+            let mut ctx = dummy_message_context(&origin_domain, &db);
+            ctx.priority_classes = Arc::new(vec![PriorityClassConf {
+                priority: 10,
+                matching_list: serde_json::from_str(
+                    r#"[{"senderAddress": "0x0000000000000000000000000000000000000000000000000000000000000007"}]"#,
+                )
+                .unwrap(),
+            }]);
+            ctx.retry_policies = Arc::new(vec![RetryPolicyConf {
+                policy: RetryPolicy {
+                    base_delay: backoff,
+                    multiplier: 1.,
+                    max_delay: backoff,
+                    jitter: 0.,
+                    max_retries: None,
+                },
+                matching_list: Default::default(),
+                revert_classes: vec![],
+            }]);
+            let ctx = Arc::new(ctx);
+
+            let mut premium = dummy_hyperlane_message(&destination_domain, 0);
+            premium.sender = H256::from_low_u64_be(7);
+            db.store_pending_message_retry_count_by_message_id(&premium.id(), &1)
+                .unwrap();
+            let premium: DynPendingOperation =
+                PendingMessage::from_persisted_retries(premium, ctx.clone()).into();
+            let mut queue = BinaryHeap::from([Reverse(Box::new(premium))]);
+            for nonce in 1..4 {
+                let spam: DynPendingOperation = PendingMessage::from_persisted_retries(
+                    dummy_hyperlane_message(&destination_domain, nonce),
+                    ctx.clone(),
+                )
+                .into();
+                queue.push(Reverse(Box::new(spam)));
+            }
+
+            // While the premium message backs off the others are attempted in
+            // order of nonce
+            let spam = pop_ready(&mut queue).unwrap();
+            assert_eq!((spam.priority(), spam.num_retries()), (0, 0));
+            queue.push(Reverse(spam));
+
+            // Once its backoff passes while it is queued, it is attempted first
+            sleep(backoff).await;
+            let ready = pop_ready(&mut queue).unwrap();
+            assert_eq!(ready.priority(), 10);
+            assert_eq!(queue.len(), 3);
+            assert!(std::iter::from_fn(|| pop_ready(&mut queue)).all(|op| op.priority() == 0));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_messages_are_deferred() {
+        tokio::time::pause();
+        test_utils::run_test_db(|db| async move {
+            let origin_domain = dummy_domain(0, "dummy_origin_domain");
+            let destination_domain = dummy_domain(1, "dummy_destination_domain");
+            let db = HyperlaneRocksDB::new(&origin_domain, db);
+            persist_retried_messages(&[0, 0], &db, &destination_domain);
+
+            let (mut processor, mut receive_channel, _requeue_sender) =
+                dummy_message_processor(&origin_domain, &destination_domain, &db);
+            processor.rate_limiters = Arc::new(vec![RateLimiter::new(RateLimitConf {
+                name: "per-sender".to_owned(),
+                key: RateLimitKey::Sender,
+                capacity: 1,
+                refill_per_second: 10.,
+                matching_list: Default::default(),
+            })]);
+            processor.metrics.rate_limited =
+                vec![IntCounter::new("dummy_rate_limited", "help string").unwrap()];
+            processor.metrics.rate_limit_deferred =
+                vec![IntGauge::new("dummy_rate_limit_deferred", "help string").unwrap()];
+
+            // The first message uses up the bucket and the second is deferred
+            processor.tick().await.unwrap();
+            processor.tick().await.unwrap();
+            assert_eq!(
+                receive_channel.try_recv().unwrap().id(),
+                db.retrieve_message_by_nonce(0).unwrap().unwrap().id()
+            );
+            assert!(receive_channel.try_recv().is_err());
+            assert_eq!(processor.metrics.rate_limited[0].get(), 1);
+            assert_eq!(processor.metrics.rate_limit_deferred[0].get(), 1);
+
+            // Once the bucket has refilled the deferred message is sent
+            tokio::time::advance(Duration::from_millis(150)).await;
+            processor.tick().await.unwrap();
+            assert_eq!(
+                receive_channel.try_recv().unwrap().id(),
+                db.retrieve_message_by_nonce(1).unwrap().unwrap().id()
+            );
+            assert_eq!(processor.metrics.rate_limit_deferred[0].get(), 0);
+        })
+        .await;
+    }
+
+    fn set_rate_limit(processor: &mut MessageProcessor, capacity: u32, refill_per_second: f64) {
+        processor.rate_limiters = Arc::new(vec![RateLimiter::new(RateLimitConf {
+            name: "per-sender".to_owned(),
+            key: RateLimitKey::Sender,
+            capacity,
+            refill_per_second,
+            matching_list: Default::default(),
+        })]);
+        processor.metrics.rate_limited =
+            vec![IntCounter::new("dummy_rate_limited", "help string").unwrap()];
+        processor.metrics.rate_limit_deferred =
+            vec![IntGauge::new("dummy_rate_limit_deferred", "help string").unwrap()];
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_deferrals_survive_restarts() {
+        tokio::time::pause();
+        test_utils::run_test_db(|db| async move {
+            let origin_domain = dummy_domain(0, "dummy_origin_domain");
+            let destination_domain = dummy_domain(1, "dummy_destination_domain");
+            let db = HyperlaneRocksDB::new(&origin_domain, db);
+            persist_retried_messages(&[0, 0], &db, &destination_domain);
+
+            let (mut processor, _receive_channel, _requeue_sender) =
+                dummy_message_processor(&origin_domain, &destination_domain, &db);
+            set_rate_limit(&mut processor, 1, 0.1);
+            processor.tick().await.unwrap();
+            processor.tick().await.unwrap();
+            assert_eq!(processor.deferred.len(), 1);
+
+            // A restarted processor starts out with full buckets, but keeps
+            // holding back the message until its deferral ends
+            let (mut processor, mut receive_channel, _requeue_sender) =
+                dummy_message_processor(&origin_domain, &destination_domain, &db);
+            set_rate_limit(&mut processor, 10, 0.1);
+            processor.tick().await.unwrap();
+            processor.tick().await.unwrap();
+            let first_message = db.retrieve_message_by_nonce(0).unwrap().unwrap();
+            assert_eq!(receive_channel.try_recv().unwrap().id(), first_message.id());
+            assert!(receive_channel.try_recv().is_err());
+            assert_eq!(processor.deferred.len(), 1);
+
+            tokio::time::advance(Duration::from_secs(11)).await;
+            processor.tick().await.unwrap();
+            let second_message = db.retrieve_message_by_nonce(1).unwrap().unwrap();
+            assert_eq!(
+                receive_channel.try_recv().unwrap().id(),
+                second_message.id()
+            );
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_deferred_messages_are_bounded_per_bucket() {
+        tokio::time::pause();
+        test_utils::run_test_db(|db| async move {
+            let origin_domain = dummy_domain(0, "dummy_origin_domain");
+            let destination_domain = dummy_domain(1, "dummy_destination_domain");
+            let db = HyperlaneRocksDB::new(&origin_domain, db);
+            // Four messages of one sender followed by one of another
+            let messages: Vec<_> = (0..5)
+                .map(|nonce| {
+                    let mut msg = dummy_hyperlane_message(&destination_domain, nonce);
+                    if nonce == 4 {
+                        msg.sender = H256::from_low_u64_be(9);
+                    }
+                    add_db_entry(&db, &msg, 0);
+                    msg
+                })
+                .collect();
+
+            let (mut processor, mut receive_channel, _requeue_sender) =
+                dummy_message_processor(&origin_domain, &destination_domain, &db);
+            set_rate_limit(&mut processor, 1, 1.);
+            processor.max_deferred_per_bucket = 2;
+            let mut received = || {
+                std::iter::from_fn(|| receive_channel.try_recv().ok())
+                    .map(|op| op.id())
+                    .collect::<Vec<_>>()
+            };
+
+            // The first message of the sender uses up its bucket, the next two
+            // are deferred and the last is skipped since the bucket is full.
+            // The message of the other sender is not held up by them.
+            for _ in 0..5 {
+                processor.tick().await.unwrap();
+            }
+            assert_eq!(received(), vec![messages[0].id(), messages[4].id()]);
+            assert_eq!(processor.message_nonce, 5);
+            assert_eq!(processor.deferred.len(), 2);
+            assert_eq!(processor.overflowed.len(), 1);
+
+            // Once a deferred message is sent the skipped one is picked up
+            tokio::time::advance(Duration::from_secs(1)).await;
+            processor.tick().await.unwrap();
+            assert_eq!(received(), vec![messages[1].id()]);
+            assert_eq!(processor.deferred.len(), 2);
+            assert!(processor.overflowed.is_empty());
+
+            tokio::time::advance(Duration::from_secs(5)).await;
+            processor.tick().await.unwrap();
+            processor.tick().await.unwrap();
+            assert_eq!(received(), vec![messages[2].id(), messages[3].id()]);
+            assert!(processor.deferred.is_empty());
+            assert!(processor.deferred_per_bucket.is_empty());
+        })
+        .await;
+    }
+
+    #[test]
+    fn test_policy_backoff_grows_up_to_max_delay() {
+        let mut policy = RetryPolicy {
+            base_delay: Duration::from_secs(5),
+            multiplier: 2.,
+            max_delay: Duration::from_secs(60),
+            jitter: 0.,
+            max_retries: None,
+        };
+        let id = H256::from_low_u64_be(42);
+        let backoff = |policy: &RetryPolicy, retries| {
+            PendingMessage::calculate_policy_backoff(policy, id, retries).map(|d| d.as_secs())
+        };
+        assert_eq!(backoff(&policy, 0), None);
+        assert_eq!(backoff(&policy, 1), Some(5));
+        assert_eq!(backoff(&policy, 2), Some(10));
+        assert_eq!(backoff(&policy, 4), Some(40));
+        assert_eq!(backoff(&policy, 5), Some(60));
+        assert_eq!(backoff(&policy, u32::MAX), Some(60));
+
+        policy.jitter = 0.5;
+        for retries in 1..10 {
+            let delay = backoff(&policy, retries).unwrap();
+            assert!(
+                (2..=90).contains(&delay),
+                "{delay}s is not within the jitter"
+            );
+        }
+
+        // Delays which a `Duration` cannot hold fall back to the max delay
+        policy.base_delay = Duration::MAX;
+        policy.max_delay = Duration::MAX;
+        policy.jitter = 1.;
+        for retries in 1..10 {
+            assert!(PendingMessage::calculate_policy_backoff(&policy, id, retries).is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dead_lettered_messages_are_skipped_until_requeued() {
+        test_utils::run_test_db(|db| async move {
+            let origin_domain = dummy_domain(0, "dummy_origin_domain");
+            let destination_domain = dummy_domain(1, "dummy_destination_domain");
+            let db = HyperlaneRocksDB::new(&origin_domain, db);
+            persist_retried_messages(&[0, 3], &db, &destination_domain);
+            let dead_message = db.retrieve_message_by_nonce(1).unwrap().unwrap();
+            db.store_dead_letter_by_nonce(
+                &1,
+                &DeadLetter {
+                    message_id: dead_message.id(),
+                    nonce: 1,
+                    reason: "Recipient is not a contract".to_owned(),
+                    last_error: None,
+                    timestamp: 0,
+                },
+            )
+            .unwrap();
+
+            let (mut processor, mut receive_channel, requeue_sender) =
+                dummy_message_processor(&origin_domain, &destination_domain, &db);
+            processor.tick().await.unwrap();
+            processor.tick().await.unwrap();
+            let first_message = db.retrieve_message_by_nonce(0).unwrap().unwrap();
+            assert_eq!(receive_channel.try_recv().unwrap().id(), first_message.id());
+            assert!(receive_channel.try_recv().is_err());
+
+            db.remove_dead_letter_by_nonce(1).unwrap();
+            requeue_sender.send(1).unwrap();
+            processor.tick().await.unwrap();
+            let requeued = receive_channel.try_recv().unwrap();
+            assert_eq!(requeued.id(), dead_message.id());
+            assert_eq!(requeued.num_retries(), 0);
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn test_full_pending_message_persistence_flow() {
         test_utils::run_test_db(|db| async move {
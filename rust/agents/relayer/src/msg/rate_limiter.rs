@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hyperlane_core::{HyperlaneMessage, H256};
+
+use crate::settings::{RateLimitConf, RateLimitKey};
+
+/// Once a rate limit has this many buckets, those which have refilled
+/// completely are forgotten.
+const MAX_BUCKETS_BEFORE_PRUNING: usize = 10_000;
+/// Messages are never held back by a rate limit for longer than this at a
+/// time.
+const MAX_WAIT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A token-bucket rate limit on the messages sent to submitters. Shared by the
+/// message processors of all origins.
+pub struct RateLimiter {
+    conf: RateLimitConf,
+    /// Buckets by sender, recipient or origin depending on the key of the
+    /// limit. A missing bucket is full.
+    buckets: Mutex<HashMap<H256, TokenBucket>>,
+}
+
+impl Debug for RateLimiter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RateLimiter {{ conf: {:?} }}", self.conf)
+    }
+}
+
+impl RateLimiter {
+    pub fn new(conf: RateLimitConf) -> Self {
+        Self {
+            conf,
+            buckets: Default::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.conf.name
+    }
+
+    /// Whether this limit applies to the message.
+    pub fn applies_to(&self, message: &HyperlaneMessage) -> bool {
+        self.conf.matching_list.msg_matches(message, true)
+    }
+
+    /// Whether the message can be sent at `now` without exceeding this limit.
+    pub fn has_capacity(&self, message: &HyperlaneMessage, now: Instant) -> bool {
+        let buckets = self.buckets.lock().unwrap();
+        buckets
+            .get(&self.bucket_key(message))
+            .map(|bucket| bucket.available(&self.conf, now) >= 1.)
+            .unwrap_or(true)
+    }
+
+    /// When the message can be sent without exceeding this limit, assuming no
+    /// other message uses up its capacity in the meantime.
+    pub fn ready_at(&self, message: &HyperlaneMessage, now: Instant) -> Instant {
+        let buckets = self.buckets.lock().unwrap();
+        let available = buckets
+            .get(&self.bucket_key(message))
+            .map(|bucket| bucket.available(&self.conf, now))
+            .unwrap_or(f64::INFINITY);
+        if available >= 1. {
+            return now;
+        }
+        let wait = ((1. - available) / self.conf.refill_per_second).min(MAX_WAIT.as_secs_f64());
+        now + Duration::from_secs_f64(wait).max(Duration::from_millis(1))
+    }
+
+    /// Use up the capacity for sending the message at `now`.
+    pub fn take(&self, message: &HyperlaneMessage, now: Instant) {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS_BEFORE_PRUNING {
+            let capacity = self.conf.capacity as f64;
+            buckets.retain(|_, bucket| bucket.available(&self.conf, now) < capacity);
+        }
+        let bucket = buckets
+            .entry(self.bucket_key(message))
+            .or_insert_with(|| TokenBucket {
+                tokens: self.conf.capacity as f64,
+                updated_at: now,
+            });
+        bucket.tokens = bucket.available(&self.conf, now) - 1.;
+        bucket.updated_at = now;
+    }
+
+    /// The key of the bucket the message takes its capacity from.
+    pub fn bucket_key(&self, message: &HyperlaneMessage) -> H256 {
+        match self.conf.key {
+            RateLimitKey::Sender => message.sender,
+            RateLimitKey::Recipient => message.recipient,
+            RateLimitKey::Origin => H256::from_low_u64_be(message.origin as u64),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    /// Number of tokens at `updated_at`. May be negative if several message
+    /// processors took a token at the same time.
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    /// Number of tokens in the bucket at `now`.
+    fn available(&self, conf: &RateLimitConf, now: Instant) -> f64 {
+        let refilled =
+            now.saturating_duration_since(self.updated_at).as_secs_f64() * conf.refill_per_second;
+        (self.tokens + refilled).min(conf.capacity as f64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn limiter(key: RateLimitKey) -> RateLimiter {
+        RateLimiter::new(RateLimitConf {
+            name: "test".to_owned(),
+            key,
+            capacity: 2,
+            refill_per_second: 0.5,
+            matching_list: Default::default(),
+        })
+    }
+
+    fn message(sender: u64) -> HyperlaneMessage {
+        HyperlaneMessage {
+            sender: H256::from_low_u64_be(sender),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bucket_empties_and_refills() {
+        let limiter = limiter(RateLimitKey::Sender);
+        let start = Instant::now();
+        let msg = message(1);
+
+        for _ in 0..2 {
+            assert!(limiter.has_capacity(&msg, start));
+            limiter.take(&msg, start);
+        }
+        assert!(!limiter.has_capacity(&msg, start));
+        assert!(!limiter.has_capacity(&msg, start + Duration::from_secs(1)));
+        assert!(limiter.has_capacity(&msg, start + Duration::from_secs(2)));
+        assert_eq!(
+            limiter.ready_at(&msg, start),
+            start + Duration::from_secs(2)
+        );
+        assert_eq!(
+            limiter.ready_at(&msg, start + Duration::from_secs(3)),
+            start + Duration::from_secs(3)
+        );
+    }
+
+    #[test]
+    fn test_buckets_are_separate_per_key() {
+        let now = Instant::now();
+        let by_sender = limiter(RateLimitKey::Sender);
+        let by_origin = limiter(RateLimitKey::Origin);
+        for limiter in [&by_sender, &by_origin] {
+            limiter.take(&message(1), now);
+            limiter.take(&message(1), now);
+        }
+
+        // Another sender has a bucket of its own, but shares the origin
+        assert!(by_sender.has_capacity(&message(2), now));
+        assert!(!by_origin.has_capacity(&message(2), now));
+    }
+}
@@ -14,14 +14,18 @@ use tokio::sync::{
 };
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
-use tracing::{debug, info_span, instrument, instrument::Instrumented, trace, Instrument};
+use tracing::{
+    debug, info, info_span, instrument, instrument::Instrumented, trace, warn, Instrument,
+};
 
 use hyperlane_base::CoreMetrics;
-use hyperlane_core::HyperlaneDomain;
+use hyperlane_core::{
+    ChainCommunicationError, HyperlaneContract, HyperlaneDomain, TxOutcome, H256, U256,
+};
 
 use super::pending_operation::*;
 
-type OpQueue = Arc<Mutex<BinaryHeap<Reverse<Box<DynPendingOperation>>>>>;
+pub type OpQueue = Arc<Mutex<BinaryHeap<Reverse<Box<DynPendingOperation>>>>>;
 
 /// SerialSubmitter accepts operations over a channel. It is responsible for
 /// executing the right strategy to deliver those messages to the destination
@@ -46,6 +50,11 @@ type OpQueue = Arc<Mutex<BinaryHeap<Reverse<Box<DynPendingOperation>>>>>;
 /// destination chain reorgs prior to committing delivery status to
 /// HyperlaneRocksDB.
 ///
+/// A destination may be served by several SerialSubmitters, one per
+/// submission lane, so that a stuck transaction in one lane does not stall the
+/// others. Each of several lanes has a signer of its own, so the lanes never
+/// race for nonces.
+///
 ///
 /// Objectives
 /// ----------
@@ -70,36 +79,85 @@ type OpQueue = Arc<Mutex<BinaryHeap<Reverse<Box<DynPendingOperation>>>>>;
 /// eligible for submission, we should be working on it within reason. This
 /// must be balanced with the cost of making RPCs that will almost certainly
 /// fail and potentially block new messages from being sent immediately.
+///
+///
+/// Batching
+/// --------
+///
+/// When configured with a max batch size greater than one, the submitter
+/// takes as many prepared operations as are ready (up to the max) and submits
+/// them in a single transaction. A batch is atomic, so if it fails to execute
+/// every operation in it is submitted individually instead. This keeps the
+/// single execution slot but lets it deliver several messages at a time.
 #[derive(Debug, new)]
 pub struct SerialSubmitter {
-    /// Domain this submitter delivers to.
+    /// Domain this submitter delivers to. It also delivers to the mailboxes
+    /// of the additional deployments on the chain.
     domain: HyperlaneDomain,
+    /// Submission lane to the domain this submitter is for. Each lane has its
+    /// own queues and execution slot.
+    lane: usize,
     /// Receiver for new messages to submit.
     rx: mpsc::UnboundedReceiver<Box<DynPendingOperation>>,
+    /// Max number of operations to submit in a single transaction.
+    max_batch_size: usize,
     /// Metrics for serial submitter.
     metrics: SerialSubmitterMetrics,
+    /// Operations waiting to be prepared and submitted.
+    #[new(default)]
+    prepare_queue: OpQueue,
+    /// Operations which have been submitted and are waiting to be confirmed.
+    #[new(default)]
+    confirm_queue: OpQueue,
+}
+
+/// Handles to the queues of a SerialSubmitter so they can be inspected and
+/// modified while it runs. Operations that are in the middle of being
+/// prepared, submitted or confirmed are not in either queue.
+#[derive(Debug, Clone)]
+pub struct SubmitterQueues {
+    /// Domain the submitter delivers to.
+    pub domain: HyperlaneDomain,
+    /// Submission lane of the submitter.
+    pub lane: usize,
+    /// Operations waiting to be prepared and submitted.
+    pub prepare_queue: OpQueue,
+    /// Operations which have been submitted and are waiting to be confirmed.
+    pub confirm_queue: OpQueue,
 }
 
 impl SerialSubmitter {
+    pub fn queues(&self) -> SubmitterQueues {
+        SubmitterQueues {
+            domain: self.domain.clone(),
+            lane: self.lane,
+            prepare_queue: self.prepare_queue.clone(),
+            confirm_queue: self.confirm_queue.clone(),
+        }
+    }
+
     pub fn spawn(self) -> Instrumented<JoinHandle<Result<()>>> {
-        let span = info_span!("SerialSubmitter", destination=%self.domain);
+        let span = info_span!("SerialSubmitter", destination=%self.domain, lane=self.lane);
         spawn(async move { self.run().await }).instrument(span)
     }
 
     async fn run(self) -> Result<()> {
         let Self {
             domain,
+            lane: _,
             metrics,
             rx: rx_prepare,
+            max_batch_size,
+            prepare_queue,
+            confirm_queue,
         } = self;
-        let prepare_queue: OpQueue = Default::default();
-        let confirm_queue: OpQueue = Default::default();
+        let max_batch_size = max_batch_size.max(1);
 
         // This is a channel because we want to only have a small number of messages
         // sitting ready to go at a time and this acts as a synchronization tool
         // to slow down the preparation of messages when the submitter gets
-        // behind.
-        let (tx_submit, rx_submit) = mpsc::channel(1);
+        // behind. It holds up to one batch worth of prepared messages.
+        let (tx_submit, rx_submit) = mpsc::channel(max_batch_size);
 
         let tasks = [
             spawn(receive_task(
@@ -118,6 +176,7 @@ impl SerialSubmitter {
                 rx_submit,
                 prepare_queue.clone(),
                 confirm_queue.clone(),
+                max_batch_size,
                 metrics.clone(),
             )),
             spawn(confirm_task(
@@ -164,10 +223,11 @@ async fn prepare_task(
         let next = {
             let mut queue = prepare_queue.lock().await;
             metrics.prepare_queue_length.set(queue.len() as i64);
-            queue.pop()
+            pop_ready(&mut queue)
         };
-        let Some(Reverse(mut op)) = next else {
-            // queue is empty so give some time before checking again to prevent burning CPU
+        let Some(mut op) = next else {
+            // no operation is ready so give some time before checking again to prevent
+            // burning CPU
             sleep(Duration::from_millis(200)).await;
             continue;
         };
@@ -206,32 +266,259 @@ async fn submit_task(
     mut rx_submit: mpsc::Receiver<Box<DynPendingOperation>>,
     prepare_queue: OpQueue,
     confirm_queue: OpQueue,
+    max_batch_size: usize,
     metrics: SerialSubmitterMetrics,
 ) -> Result<()> {
-    while let Some(mut op) = rx_submit.recv().await {
-        trace!(?op, "Submitting operation");
-        debug_assert_eq!(*op.domain(), domain);
+    while let Some(op) = rx_submit.recv().await {
+        // Take any other operations that have already been prepared, up to
+        // the max batch size.
+        let mut batch = vec![op];
+        while batch.len() < max_batch_size {
+            match rx_submit.try_recv() {
+                Ok(op) => batch.push(op),
+                Err(_) => break,
+            }
+        }
+        for op in &batch {
+            debug_assert_eq!(*op.domain(), domain);
+        }
 
-        match op.submit().await {
-            PendingOperationResult::Success => {
-                debug!(?op, "Operation submitted");
-                metrics.ops_submitted.inc();
-                confirm_queue.lock().await.push(Reverse(op));
+        if batch.len() > 1 {
+            submit_batch(batch, &prepare_queue, &confirm_queue, &metrics).await?;
+        } else {
+            for op in batch {
+                submit_single(op, &prepare_queue, &confirm_queue, &metrics).await?;
             }
-            PendingOperationResult::NotReady => {
-                panic!("Pending operation was prepared and therefore must be ready")
+        }
+    }
+    bail!("Internal submitter channel was closed");
+}
+
+async fn submit_single(
+    mut op: Box<DynPendingOperation>,
+    prepare_queue: &OpQueue,
+    confirm_queue: &OpQueue,
+    metrics: &SerialSubmitterMetrics,
+) -> Result<()> {
+    trace!(?op, "Submitting operation");
+    let result = op.submit().await;
+    handle_submit_result(op, result, prepare_queue, confirm_queue, metrics).await
+}
+
+async fn submit_batch(
+    batch: Vec<Box<DynPendingOperation>>,
+    prepare_queue: &OpQueue,
+    confirm_queue: &OpQueue,
+    metrics: &SerialSubmitterMetrics,
+) -> Result<()> {
+    let Some(items) = batch
+        .iter()
+        .map(|op| op.batch_item())
+        .collect::<Option<Vec<_>>>()
+    else {
+        // Not every operation can be batched (e.g. one was already delivered),
+        // so submit them one at a time.
+        for op in batch {
+            submit_single(op, prepare_queue, confirm_queue, metrics).await?;
+        }
+        return Ok(());
+    };
+
+    // A batch is a single call to one mailbox, so the operations for the
+    // mailboxes of different deployments on the chain are batched separately.
+    // Batches are split further so their gas limit stays within the
+    // transaction gas limit.
+    let batches: Vec<_> = group_by_mailbox(batch, items)
+        .into_iter()
+        .flat_map(|(batch, items)| split_by_gas_limit(batch, items))
+        .collect();
+    for (batch, items) in batches {
+        if batch.len() > 1 {
+            submit_mailbox_batch(batch, items, prepare_queue, confirm_queue, metrics).await?;
+        } else {
+            for op in batch {
+                submit_single(op, prepare_queue, confirm_queue, metrics).await?;
             }
-            PendingOperationResult::Reprepare => {
-                metrics.ops_failed.inc();
-                prepare_queue.lock().await.push(Reverse(op));
+        }
+    }
+    Ok(())
+}
+
+/// Group operations by the address of the mailbox they are delivered to,
+/// keeping their order within each group.
+fn group_by_mailbox<T>(ops: Vec<T>, items: Vec<BatchItem>) -> Vec<(Vec<T>, Vec<BatchItem>)> {
+    let mut groups: Vec<(H256, Vec<T>, Vec<BatchItem>)> = vec![];
+    for (op, item) in ops.into_iter().zip(items) {
+        let address = item.mailbox.address();
+        match groups.iter_mut().find(|(mailbox, ..)| *mailbox == address) {
+            Some((_, ops, items)) => {
+                ops.push(op);
+                items.push(item);
             }
-            PendingOperationResult::Drop => {
-                metrics.ops_dropped.inc();
+            None => groups.push((address, vec![op], vec![item])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, ops, items)| (ops, items))
+        .collect()
+}
+
+/// Split operations into batches whose summed gas limit stays within the
+/// transaction gas limit of each of their operations, keeping their order.
+fn split_by_gas_limit<T>(ops: Vec<T>, items: Vec<BatchItem>) -> Vec<(Vec<T>, Vec<BatchItem>)> {
+    let mut batches: Vec<(Vec<T>, Vec<BatchItem>)> = vec![];
+    let mut total_gas_limit = U256::zero();
+    let mut tx_gas_limit: Option<U256> = None;
+    for (op, item) in ops.into_iter().zip(items) {
+        let total_with_item = total_gas_limit.saturating_add(item.gas_limit);
+        let tx_limit_with_item = match (tx_gas_limit, item.tx_gas_limit) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let fits = tx_limit_with_item.map_or(true, |limit| total_with_item <= limit);
+        match batches.last_mut() {
+            Some((ops, items)) if fits => {
+                ops.push(op);
+                items.push(item);
+                total_gas_limit = total_with_item;
+                tx_gas_limit = tx_limit_with_item;
+            }
+            _ => {
+                total_gas_limit = item.gas_limit;
+                tx_gas_limit = item.tx_gas_limit;
+                batches.push((vec![op], vec![item]));
             }
-            PendingOperationResult::CriticalFailure(e) => return Err(e),
         }
     }
-    bail!("Internal submitter channel was closed");
+    batches
+}
+
+/// Submit operations for the same mailbox in a single transaction.
+async fn submit_mailbox_batch(
+    mut batch: Vec<Box<DynPendingOperation>>,
+    items: Vec<BatchItem>,
+    prepare_queue: &OpQueue,
+    confirm_queue: &OpQueue,
+    metrics: &SerialSubmitterMetrics,
+) -> Result<()> {
+    trace!(?batch, "Submitting batch of operations");
+    let gas_limits = items.iter().map(|item| item.gas_limit).collect::<Vec<_>>();
+    let total_gas_limit = gas_limits.iter().fold(U256::zero(), |acc, gas_limit| {
+        acc.saturating_add(*gas_limit)
+    });
+    let messages = items
+        .iter()
+        .map(|item| (item.message.clone(), item.metadata.clone()))
+        .collect::<Vec<_>>();
+
+    match items[0]
+        .mailbox
+        .process_batch(&messages, Some(total_gas_limit))
+        .await
+    {
+        Ok(tx_outcome) if tx_outcome.executed => {
+            info!(
+                txid=?tx_outcome.transaction_id,
+                batch_size=batch.len(),
+                "Batch of messages successfully processed by transaction"
+            );
+            metrics.ops_batched.inc_by(batch.len() as u64);
+            let outcomes = split_batch_outcome(&tx_outcome, &gas_limits);
+            for (mut op, tx_outcome) in batch.into_iter().zip(outcomes) {
+                let result = op.on_batch_submitted(tx_outcome);
+                handle_submit_result(op, result, prepare_queue, confirm_queue, metrics).await?;
+            }
+            return Ok(());
+        }
+        Ok(tx_outcome) => {
+            warn!(
+                txid=?tx_outcome.transaction_id,
+                batch_size=batch.len(),
+                "Batch transaction reverted, submitting operations individually"
+            );
+            // the gas of the reverted transaction was spent all the same
+            let outcomes = split_batch_outcome(&tx_outcome, &gas_limits);
+            for (op, tx_outcome) in batch.iter_mut().zip(outcomes) {
+                op.on_batch_reverted(tx_outcome)?;
+            }
+        }
+        Err(ChainCommunicationError::BatchingUnsupported) => {
+            debug!(
+                batch_size = batch.len(),
+                "Batching is not supported, submitting operations individually"
+            );
+        }
+        Err(err) => {
+            warn!(
+                ?err,
+                batch_size = batch.len(),
+                "Batch submission failed, submitting operations individually"
+            );
+        }
+    }
+    metrics.ops_batch_failed.inc_by(batch.len() as u64);
+    for op in batch {
+        submit_single(op, prepare_queue, confirm_queue, metrics).await?;
+    }
+    Ok(())
+}
+
+async fn handle_submit_result(
+    op: Box<DynPendingOperation>,
+    result: PendingOperationResult,
+    prepare_queue: &OpQueue,
+    confirm_queue: &OpQueue,
+    metrics: &SerialSubmitterMetrics,
+) -> Result<()> {
+    match result {
+        PendingOperationResult::Success => {
+            debug!(?op, "Operation submitted");
+            metrics.ops_submitted.inc();
+            confirm_queue.lock().await.push(Reverse(op));
+        }
+        PendingOperationResult::NotReady => {
+            panic!("Pending operation was prepared and therefore must be ready")
+        }
+        PendingOperationResult::Reprepare => {
+            metrics.ops_failed.inc();
+            prepare_queue.lock().await.push(Reverse(op));
+        }
+        PendingOperationResult::Drop => {
+            metrics.ops_dropped.inc();
+        }
+        PendingOperationResult::CriticalFailure(e) => return Err(e),
+    }
+    Ok(())
+}
+
+/// Split the gas used by a batch transaction between the operations in it, in
+/// proportion to their individually estimated gas limits. The last operation
+/// gets what is left after rounding, so the gas used by the operations adds
+/// up to that of the transaction.
+fn split_batch_outcome(tx_outcome: &TxOutcome, gas_limits: &[U256]) -> Vec<TxOutcome> {
+    let total_gas_limit = gas_limits.iter().fold(U256::zero(), |acc, gas_limit| {
+        acc.saturating_add(*gas_limit)
+    });
+    let mut unassigned = tx_outcome.gas_used;
+    gas_limits
+        .iter()
+        .enumerate()
+        .map(|(i, gas_limit)| {
+            let gas_used = if i + 1 == gas_limits.len() {
+                unassigned
+            } else if total_gas_limit.is_zero() {
+                tx_outcome.gas_used / U256::from(gas_limits.len())
+            } else {
+                tx_outcome.gas_used * *gas_limit / total_gas_limit
+            };
+            unassigned = unassigned.saturating_sub(gas_used);
+            TxOutcome {
+                gas_used,
+                ..*tx_outcome
+            }
+        })
+        .collect()
 }
 
 #[instrument(skip_all, fields(%domain))]
@@ -246,9 +533,9 @@ async fn confirm_task(
         let next = {
             let mut queue = confirm_queue.lock().await;
             metrics.confirm_queue_length.set(queue.len() as i64);
-            queue.pop()
+            pop_ready(&mut queue)
         };
-        let Some(Reverse(mut op)) = next else {
+        let Some(mut op) = next else {
             sleep(Duration::from_secs(5)).await;
             continue;
         };
@@ -279,8 +566,8 @@ async fn confirm_task(
 
 #[derive(Debug, Clone)]
 pub struct SerialSubmitterMetrics {
-    prepare_queue_length: IntGauge,
-    confirm_queue_length: IntGauge,
+    prepare_queue_length: QueueLengthGauge,
+    confirm_queue_length: QueueLengthGauge,
 
     ops_prepared: IntCounter,
     ops_submitted: IntCounter,
@@ -288,18 +575,27 @@ pub struct SerialSubmitterMetrics {
     ops_reorged: IntCounter,
     ops_failed: IntCounter,
     ops_dropped: IntCounter,
+    ops_batched: IntCounter,
+    ops_batch_failed: IntCounter,
 }
 
 impl SerialSubmitterMetrics {
-    pub fn new(metrics: &CoreMetrics, destination: &HyperlaneDomain) -> Self {
+    pub fn new(metrics: &CoreMetrics, destination: &HyperlaneDomain, lane: usize) -> Self {
         let destination = destination.name();
+        let lane = lane.to_string();
         Self {
-            prepare_queue_length: metrics
-                .submitter_queue_length()
-                .with_label_values(&[destination, "prepare_queue"]),
-            confirm_queue_length: metrics
-                .submitter_queue_length()
-                .with_label_values(&[destination, "confirm_queue"]),
+            prepare_queue_length: QueueLengthGauge::new(
+                metrics,
+                destination,
+                "prepare_queue",
+                &lane,
+            ),
+            confirm_queue_length: QueueLengthGauge::new(
+                metrics,
+                destination,
+                "confirm_queue",
+                &lane,
+            ),
             ops_prepared: metrics
                 .operations_processed_count()
                 .with_label_values(&["prepared", destination]),
@@ -318,6 +614,144 @@ impl SerialSubmitterMetrics {
             ops_dropped: metrics
                 .operations_processed_count()
                 .with_label_values(&["dropped", destination]),
+            ops_batched: metrics
+                .batched_operations_count()
+                .with_label_values(&["executed", destination]),
+            ops_batch_failed: metrics
+                .batched_operations_count()
+                .with_label_values(&["failed", destination]),
+        }
+    }
+}
+
+/// Length of a queue of one lane, which is also added to the length of the
+/// queue across the lanes to the destination.
+#[derive(Debug, Clone)]
+struct QueueLengthGauge {
+    total: IntGauge,
+    lane: IntGauge,
+}
+
+impl QueueLengthGauge {
+    fn new(metrics: &CoreMetrics, destination: &str, queue_name: &str, lane: &str) -> Self {
+        Self {
+            total: metrics
+                .submitter_queue_length()
+                .with_label_values(&[destination, queue_name]),
+            lane: metrics.submitter_lane_queue_length().with_label_values(&[
+                destination,
+                queue_name,
+                lane,
+            ]),
+        }
+    }
+
+    fn set(&self, len: i64) {
+        let previous = self.lane.get();
+        self.lane.set(len);
+        self.total.add(len - previous);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyperlane_core::{HyperlaneMessage, Mailbox, H512};
+    use hyperlane_test::mocks::MockMailboxContract;
+
+    use super::*;
+
+    fn batch_item(mailbox: &Arc<dyn Mailbox>, nonce: u32) -> BatchItem {
+        BatchItem {
+            mailbox: mailbox.clone(),
+            message: HyperlaneMessage {
+                nonce,
+                ..Default::default()
+            },
+            metadata: vec![],
+            gas_limit: U256::from(100_000),
+            tx_gas_limit: Some(U256::from(250_000)),
+        }
+    }
+
+    fn mailbox(address: H256) -> Arc<dyn Mailbox> {
+        let mut mailbox = MockMailboxContract::default();
+        mailbox.expect__address().return_const(address);
+        Arc::new(mailbox)
+    }
+
+    #[test]
+    fn test_group_by_mailbox() {
+        // the mailboxes of two deployments on the same chain
+        let default = mailbox(H256::repeat_byte(1));
+        let private = mailbox(H256::repeat_byte(2));
+        let items = vec![
+            batch_item(&default, 0),
+            batch_item(&private, 1),
+            batch_item(&default, 2),
+        ];
+
+        let groups = group_by_mailbox(vec![0, 1, 2], items);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, vec![0, 2]);
+        assert_eq!(groups[1].0, vec![1]);
+        for (ops, items) in &groups {
+            for (op, item) in ops.iter().zip(items) {
+                assert_eq!(item.message.nonce, *op);
+            }
+        }
+        assert_eq!(groups[1].1[0].mailbox.address(), H256::repeat_byte(2));
+    }
+
+    #[test]
+    fn test_split_by_gas_limit() {
+        let mailbox = mailbox(H256::repeat_byte(1));
+        let mut items: Vec<_> = (0..5).map(|nonce| batch_item(&mailbox, nonce)).collect();
+        // a message whose destination has no transaction gas limit
+        items[4].tx_gas_limit = None;
+
+        // two messages fit within the limit of 250k gas
+        let batches = split_by_gas_limit(vec![0, 1, 2, 3, 4], items);
+        let ops: Vec<_> = batches.iter().map(|(ops, _)| ops.clone()).collect();
+        assert_eq!(ops, vec![vec![0, 1], vec![2, 3], vec![4]]);
+        for (ops, items) in &batches {
+            let nonces: Vec<_> = items.iter().map(|item| item.message.nonce).collect();
+            assert_eq!(&nonces, ops);
         }
     }
+
+    #[test]
+    fn test_split_batch_outcome_by_gas_limit() {
+        let tx_outcome = TxOutcome {
+            transaction_id: H512::zero(),
+            executed: true,
+            gas_used: U256::from(300_000),
+            gas_price: U256::from(10),
+        };
+        let outcomes =
+            split_batch_outcome(&tx_outcome, &[U256::from(100_000), U256::from(200_000)]);
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].gas_used, U256::from(100_000));
+        assert_eq!(outcomes[1].gas_used, U256::from(200_000));
+        assert!(outcomes
+            .iter()
+            .all(|o| o.executed && o.gas_price == U256::from(10)));
+    }
+
+    #[test]
+    fn test_split_batch_outcome_accounts_for_all_gas() {
+        let tx_outcome = TxOutcome {
+            transaction_id: H512::zero(),
+            executed: false,
+            gas_used: U256::from(100),
+            gas_price: U256::from(10),
+        };
+        let outcomes = split_batch_outcome(&tx_outcome, &[U256::from(1); 3]);
+        let gas_used: Vec<_> = outcomes.iter().map(|o| o.gas_used).collect();
+        assert_eq!(gas_used, [33u64, 33, 34].map(U256::from));
+
+        // without gas limits the gas is split evenly
+        let outcomes = split_batch_outcome(&tx_outcome, &[U256::zero(); 2]);
+        let gas_used: Vec<_> = outcomes.iter().map(|o| o.gas_used).collect();
+        assert_eq!(gas_used, [50u64, 50].map(U256::from));
+    }
 }
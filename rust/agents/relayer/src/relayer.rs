@@ -16,65 +16,95 @@ use tokio::{
     task::JoinHandle,
 };
 use tracing::{info, info_span, instrument::Instrumented, Instrument};
+use warp::{filters::BoxedFilter, Reply};
 
 use hyperlane_base::{
     db::{HyperlaneRocksDB, DB},
-    run_all, BaseAgent, ContractSyncMetrics, CoreMetrics, HyperlaneAgentCore,
+    run_all,
+    settings::ChainConf,
+    with_bearer_token, BaseAgent, ContractSyncMetrics, CoreMetrics, EquivocationDetector,
+    HyperlaneAgentCore, ValidatorHealth,
+};
+use hyperlane_core::{
+    DeploymentKey, HyperlaneChain, HyperlaneDomain, InterchainGasPayment, Mailbox, U256,
 };
-use hyperlane_core::{HyperlaneDomain, InterchainGasPayment, U256};
 
 use crate::msg::pending_message::MessageSubmissionMetrics;
 use crate::{
+    admin::{self, DeadLetterQueue, DeadLetterQueues, QueueRegistry},
     merkle_tree_builder::MerkleTreeBuilder,
     msg::{
         gas_payment::GasPaymentEnforcer,
-        metadata::BaseMetadataBuilder,
+        metadata::{
+            BaseMetadataBuilder, CcipReadGateway, ExternalMetadataProviders, MetadataCache,
+        },
         pending_message::MessageContext,
         pending_operation::DynPendingOperation,
         processor::{MessageProcessor, MessageProcessorMetrics},
+        rate_limiter::RateLimiter,
         serial_submitter::{SerialSubmitter, SerialSubmitterMetrics},
     },
     settings::{matching_list::MatchingList, RelayerSettings},
 };
 
-#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+/// A route messages are relayed on. Messages are only relayed between the
+/// mailboxes of the same deployment, which the origin key names.
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
 struct ContextKey {
-    origin: u32,
+    origin: DeploymentKey,
     destination: u32,
 }
 
 /// A relayer agent
 pub struct Relayer {
-    origin_chains: HashSet<HyperlaneDomain>,
-    destination_chains: HashSet<HyperlaneDomain>,
+    origin_chains: Vec<HyperlaneDomain>,
+    destination_chains: Vec<HyperlaneDomain>,
     core: HyperlaneAgentCore,
-    message_syncs: HashMap<HyperlaneDomain, Arc<MessageContractSync>>,
+    message_syncs: HashMap<DeploymentKey, Arc<MessageContractSync>>,
     interchain_gas_payment_syncs:
-        HashMap<HyperlaneDomain, Arc<WatermarkContractSync<InterchainGasPayment>>>,
+        HashMap<DeploymentKey, Arc<WatermarkContractSync<InterchainGasPayment>>>,
     /// Context data for each (origin, destination) chain pair a message can be
-    /// sent between
-    msg_ctxs: HashMap<ContextKey, Arc<MessageContext>>,
-    prover_syncs: HashMap<HyperlaneDomain, Arc<RwLock<MerkleTreeBuilder>>>,
-    dbs: HashMap<HyperlaneDomain, HyperlaneRocksDB>,
+    /// sent between, one per submission lane to the destination
+    msg_ctxs: HashMap<ContextKey, Vec<Arc<MessageContext>>>,
+    prover_syncs: HashMap<DeploymentKey, Arc<RwLock<MerkleTreeBuilder>>>,
+    dbs: HashMap<DeploymentKey, HyperlaneRocksDB>,
     whitelist: Arc<MatchingList>,
     blacklist: Arc<MatchingList>,
     transaction_gas_limit: Option<U256>,
     skip_transaction_gas_limit_for: HashSet<u32>,
     allow_local_checkpoint_syncers: bool,
+    max_batch_sizes: HashMap<u32, usize>,
+    submission_lane_counts: HashMap<u32, usize>,
+    /// The bearer token of the admin API, which is only served if set
+    admin_api_token: Option<String>,
+    /// Queues of the running submitters, for the admin API
+    queue_registry: QueueRegistry,
+    /// Dead-letter queues of the origin chains, for the admin API
+    dead_letter_queues: DeadLetterQueues,
+    /// Receiving ends of the channels dead-lettered messages are re-enqueued
+    /// through, by origin deployment. Taken by the message processors when
+    /// they are started.
+    requeue_receivers: HashMap<DeploymentKey, UnboundedReceiver<u32>>,
+    /// Limits on the rate messages are sent to submitters at, shared by the
+    /// message processors of all origins
+    rate_limiters: Arc<Vec<RateLimiter>>,
 }
 
 impl Debug for Relayer {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Relayer {{ origin_chains: {:?}, destination_chains: {:?}, whitelist: {:?}, blacklist: {:?}, transaction_gas_limit: {:?}, skip_transaction_gas_limit_for: {:?}, allow_local_checkpoint_syncers: {:?} }}",
+            "Relayer {{ origin_chains: {:?}, destination_chains: {:?}, whitelist: {:?}, blacklist: {:?}, transaction_gas_limit: {:?}, skip_transaction_gas_limit_for: {:?}, allow_local_checkpoint_syncers: {:?}, max_batch_sizes: {:?}, submission_lane_counts: {:?}, admin_api: {:?} }}",
             self.origin_chains,
             self.destination_chains,
             self.whitelist,
             self.blacklist,
             self.transaction_gas_limit,
             self.skip_transaction_gas_limit_for,
-            self.allow_local_checkpoint_syncers
+            self.allow_local_checkpoint_syncers,
+            self.max_batch_sizes,
+            self.submission_lane_counts,
+            self.admin_api_token.is_some()
         )
     }
 }
@@ -101,15 +131,42 @@ impl BaseAgent for Relayer {
         let dbs = settings
             .origin_chains
             .iter()
-            .map(|origin| (origin.clone(), HyperlaneRocksDB::new(origin, db.clone())))
+            .map(|origin| {
+                let db = HyperlaneRocksDB::new(origin, db.clone());
+                (origin.deployment_key(), db)
+            })
             .collect::<HashMap<_, _>>();
 
-        let mailboxes = settings
-            .build_mailboxes(settings.destination_chains.iter(), &metrics)
-            .await?;
+        let mut dead_letter_queues = HashMap::with_capacity(dbs.len());
+        let mut requeue_receivers = HashMap::with_capacity(dbs.len());
+        for (origin, db) in &dbs {
+            let (requeue_sender, requeue_receiver) = mpsc::unbounded_channel();
+            dead_letter_queues.insert(
+                origin.clone(),
+                DeadLetterQueue {
+                    db: db.clone(),
+                    requeue_sender,
+                },
+            );
+            requeue_receivers.insert(origin.clone(), requeue_receiver);
+        }
+
         let validator_announces = settings
             .build_validator_announces(settings.origin_chains.iter(), &metrics)
             .await?;
+        let validator_healths: HashMap<_, Arc<ValidatorHealth>> = settings
+            .origin_chains
+            .iter()
+            .map(|origin| (origin.deployment_key(), Default::default()))
+            .collect();
+        let equivocation_detectors: HashMap<_, _> = dbs
+            .iter()
+            .map(|(origin, db)| {
+                let detector =
+                    EquivocationDetector::new(db.clone(), metrics.validator_equivocations());
+                (origin.clone(), Arc::new(detector))
+            })
+            .collect();
 
         let contract_sync_metrics = Arc::new(ContractSyncMetrics::new(&metrics));
 
@@ -119,7 +176,7 @@ impl BaseAgent for Relayer {
                 &metrics,
                 &contract_sync_metrics,
                 dbs.iter()
-                    .map(|(d, db)| (d.clone(), Arc::new(db.clone()) as _))
+                    .map(|(key, db)| (key.clone(), Arc::new(db.clone()) as _))
                     .collect(),
             )
             .await?;
@@ -129,7 +186,7 @@ impl BaseAgent for Relayer {
                 &metrics,
                 &contract_sync_metrics,
                 dbs.iter()
-                    .map(|(d, db)| (d.clone(), Arc::new(db.clone()) as _))
+                    .map(|(key, db)| (key.clone(), Arc::new(db.clone()) as _))
                     .collect(),
             )
             .await?;
@@ -152,11 +209,9 @@ impl BaseAgent for Relayer {
             .origin_chains
             .iter()
             .map(|origin| {
-                let db = dbs.get(origin).unwrap().clone();
-                (
-                    origin.clone(),
-                    Arc::new(RwLock::new(MerkleTreeBuilder::new(db))),
-                )
+                let key = origin.deployment_key();
+                let db = dbs.get(&key).unwrap().clone();
+                (key, Arc::new(RwLock::new(MerkleTreeBuilder::new(db))))
             })
             .collect::<HashMap<_, _>>();
 
@@ -168,51 +223,116 @@ impl BaseAgent for Relayer {
             .origin_chains
             .iter()
             .map(|domain| {
-                (
-                    domain.clone(),
-                    Arc::new(GasPaymentEnforcer::new(
-                        settings.gas_payment_enforcement.clone(),
-                        dbs.get(domain).unwrap().clone(),
-                    )),
-                )
+                let key = domain.deployment_key();
+                let enforcer = GasPaymentEnforcer::new(
+                    settings.gas_payment_enforcement.clone(),
+                    dbs.get(&key).unwrap().clone(),
+                );
+                (key, Arc::new(enforcer))
             })
             .collect();
 
+        let retry_policies = Arc::new(settings.retry_policies.clone());
+        let priority_classes = Arc::new(settings.priority_classes.clone());
+        let checkpoint_fetch = Arc::new(settings.checkpoint_fetch.clone());
+        let ccip_read_gateway = Arc::new(CcipReadGateway::new(settings.ccip_read.clone())?);
+        let external_metadata_providers = Arc::new(ExternalMetadataProviders::new(
+            settings.external_metadata_providers.clone(),
+        )?);
+
         let mut msg_ctxs = HashMap::new();
-        for destination in &settings.destination_chains {
-            let destination_chain_setup = core.settings.chain_setup(destination).unwrap().clone();
+        for chain in settings
+            .destination_chains
+            .iter()
+            .filter(|destination| destination.deployment().is_none())
+        {
+            let chain_setup = core.settings.chain_setup(chain)?;
+
+            // The mailboxes of the deployments on the chain for each lane. Each lane
+            // has a signer of its own, unless there is a single lane using the chain
+            // signer. The mailboxes of a lane share one signing provider across the
+            // deployments, so their transactions do not race for nonces.
+            let lanes = settings
+                .submission_lanes
+                .get(&chain.id())
+                .cloned()
+                .unwrap_or_default();
+            let tx_store = HyperlaneRocksDB::new(chain, db.clone());
+            let mut lane_mailboxes = Vec::with_capacity(lanes.count);
+            if lanes.signers.is_empty() {
+                lane_mailboxes
+                    .push(build_deployment_mailboxes(chain_setup, &metrics, &tx_store).await?);
+            }
+            for signer in lanes.signers {
+                let mut lane_chain_setup = chain_setup.clone();
+                lane_chain_setup.signer = Some(signer);
+                lane_mailboxes.push(
+                    build_deployment_mailboxes(&lane_chain_setup, &metrics, &tx_store).await?,
+                );
+            }
 
             let transaction_gas_limit: Option<U256> =
-                if skip_transaction_gas_limit_for.contains(&destination.id()) {
+                if skip_transaction_gas_limit_for.contains(&chain.id()) {
                     None
                 } else {
                     transaction_gas_limit
                 };
 
-            for origin in &settings.origin_chains {
-                let metadata_builder = BaseMetadataBuilder::new(
-                    destination_chain_setup.clone(),
-                    prover_syncs[origin].clone(),
-                    validator_announces[origin].clone(),
-                    settings.allow_local_checkpoint_syncers,
-                    core.metrics.clone(),
-                    5,
-                );
-
-                msg_ctxs.insert(
-                    ContextKey {
-                        origin: origin.id(),
-                        destination: destination.id(),
-                    },
-                    Arc::new(MessageContext {
-                        destination_mailbox: mailboxes[destination].clone(),
-                        origin_db: dbs.get(origin).unwrap().clone(),
-                        metadata_builder,
-                        origin_gas_payment_enforcer: gas_payment_enforcers[origin].clone(),
-                        transaction_gas_limit,
-                        metrics: MessageSubmissionMetrics::new(&metrics, origin, destination),
-                    }),
-                );
+            for destination in settings
+                .destination_chains
+                .iter()
+                .filter(|destination| destination.id() == chain.id())
+            {
+                let destination_chain_setup =
+                    core.settings.chain_setup(destination).unwrap().clone();
+                let destination_key = destination.deployment_key();
+                let metadata_cache = Arc::new(MetadataCache::default());
+                for origin in deployment_peers(destination, &settings.origin_chains) {
+                    let origin_key = origin.deployment_key();
+                    let lane_ctxs = lane_mailboxes
+                        .iter()
+                        .map(|mailboxes| {
+                            let metadata_builder = BaseMetadataBuilder::new(
+                                destination_chain_setup.clone(),
+                                prover_syncs[&origin_key].clone(),
+                                validator_announces[&origin_key].clone(),
+                                validator_healths[&origin_key].clone(),
+                                equivocation_detectors[&origin_key].clone(),
+                                checkpoint_fetch.clone(),
+                                settings.allow_local_checkpoint_syncers,
+                                core.metrics.clone(),
+                                metadata_cache.clone(),
+                                ccip_read_gateway.clone(),
+                                external_metadata_providers.clone(),
+                                5,
+                            );
+
+                            Arc::new(MessageContext {
+                                destination_mailbox: mailboxes[&destination_key].clone(),
+                                origin_db: dbs.get(&origin_key).unwrap().clone(),
+                                metadata_builder,
+                                origin_gas_payment_enforcer: gas_payment_enforcers[&origin_key]
+                                    .clone(),
+                                transaction_gas_limit,
+                                retry_policies: retry_policies.clone(),
+                                priority_classes: priority_classes.clone(),
+                                metrics: MessageSubmissionMetrics::new(
+                                    &metrics,
+                                    origin,
+                                    destination,
+                                ),
+                            })
+                        })
+                        .collect();
+
+                    msg_ctxs.insert(
+                        ContextKey {
+                            origin: origin_key,
+                            destination: destination.id(),
+                        },
+                        lane_ctxs,
+                    );
+                }
             }
         }
 
@@ -230,21 +350,53 @@ impl BaseAgent for Relayer {
             transaction_gas_limit,
             skip_transaction_gas_limit_for,
             allow_local_checkpoint_syncers: settings.allow_local_checkpoint_syncers,
+            max_batch_sizes: settings.max_batch_sizes,
+            submission_lane_counts: settings
+                .submission_lanes
+                .iter()
+                .map(|(domain, lanes)| (*domain, lanes.count))
+                .collect(),
+            admin_api_token: settings.admin_api_token,
+            queue_registry: QueueRegistry::default(),
+            dead_letter_queues: Arc::new(dead_letter_queues),
+            requeue_receivers,
+            rate_limiters: Arc::new(
+                settings
+                    .rate_limits
+                    .into_iter()
+                    .map(RateLimiter::new)
+                    .collect(),
+            ),
         })
     }
 
     #[allow(clippy::async_yields_async)]
-    async fn run(self) -> Instrumented<JoinHandle<Result<()>>> {
+    async fn run(mut self) -> Instrumented<JoinHandle<Result<()>>> {
         let mut tasks = vec![];
 
-        // send channels by destination chain
+        // send channels by destination chain, one per submission lane. The
+        // additional mailbox deployments on a chain share its submitters, as their
+        // transactions are sent by the same signers.
         let mut send_channels = HashMap::with_capacity(self.destination_chains.len());
-        for destination in &self.destination_chains {
-            let (send_channel, receive_channel) =
-                mpsc::unbounded_channel::<Box<DynPendingOperation>>();
-            send_channels.insert(destination.id(), send_channel);
-
-            tasks.push(self.run_destination_submitter(destination, receive_channel));
+        for destination in self
+            .destination_chains
+            .iter()
+            .filter(|destination| destination.deployment().is_none())
+        {
+            let lane_count = self
+                .submission_lane_counts
+                .get(&destination.id())
+                .copied()
+                .unwrap_or(1);
+            let mut lane_channels = Vec::with_capacity(lane_count);
+            for lane in 0..lane_count {
+                let (send_channel, receive_channel) =
+                    mpsc::unbounded_channel::<Box<DynPendingOperation>>();
+                lane_channels.push(send_channel);
+
+                tasks.push(self.run_destination_submitter(destination, lane, receive_channel));
+            }
+            send_channels.insert(destination.id(), lane_channels);
         }
 
         for origin in &self.origin_chains {
@@ -254,11 +406,35 @@ impl BaseAgent for Relayer {
 
         // each message process attempts to send messages from a chain
         for origin in &self.origin_chains {
-            tasks.push(self.run_message_processor(origin, send_channels.clone()));
+            let requeue_receiver = self
+                .requeue_receivers
+                .remove(&origin.deployment_key())
+                .unwrap();
+            tasks.push(self.run_message_processor(origin, send_channels.clone(), requeue_receiver));
         }
 
         run_all(tasks)
     }
+
+    fn http_routes(&self) -> Option<BoxedFilter<(Box<dyn Reply>,)>> {
+        let token = self.admin_api_token.as_deref()?;
+        Some(with_bearer_token(
+            token,
+            admin::routes(
+                self.queue_registry.clone(),
+                self.dead_letter_queues.clone(),
+                Arc::new(self.dbs.values().cloned().collect()),
+                Arc::new(
+                    self.msg_ctxs
+                        .iter()
+                        .filter_map(|(key, ctxs)| {
+                            Some(((key.origin.clone(), key.destination), ctxs.first()?.clone()))
+                        })
+                        .collect(),
+                ),
+            ),
+        ))
+    }
 }
 
 impl Relayer {
@@ -267,7 +443,11 @@ impl Relayer {
         origin: &HyperlaneDomain,
     ) -> Instrumented<JoinHandle<eyre::Result<()>>> {
         let index_settings = self.as_ref().settings.chains[origin.name()].index.clone();
-        let contract_sync = self.message_syncs.get(origin).unwrap().clone();
+        let contract_sync = self
+            .message_syncs
+            .get(&origin.deployment_key())
+            .unwrap()
+            .clone();
         let cursor = contract_sync
             .forward_backward_message_sync_cursor(index_settings)
             .await;
@@ -287,7 +467,7 @@ impl Relayer {
         let index_settings = self.as_ref().settings.chains[origin.name()].index.clone();
         let contract_sync = self
             .interchain_gas_payment_syncs
-            .get(origin)
+            .get(&origin.deployment_key())
             .unwrap()
             .clone();
         let cursor = contract_sync.rate_limited_cursor(index_settings).await;
@@ -298,22 +478,23 @@ impl Relayer {
     fn run_message_processor(
         &self,
         origin: &HyperlaneDomain,
-        send_channels: HashMap<u32, UnboundedSender<Box<DynPendingOperation>>>,
+        send_channels: HashMap<u32, Vec<UnboundedSender<Box<DynPendingOperation>>>>,
+        requeue_receiver: UnboundedReceiver<u32>,
     ) -> Instrumented<JoinHandle<Result<()>>> {
+        let destinations = deployment_peers(origin, &self.destination_chains);
         let metrics = MessageProcessorMetrics::new(
             &self.core.metrics,
             origin,
-            self.destination_chains.iter(),
+            destinations.clone(),
+            &self.rate_limiters,
         );
-        let destination_ctxs = self
-            .destination_chains
-            .iter()
+        let destination_ctxs = destinations
             .filter(|&destination| destination != origin)
             .map(|destination| {
                 (
                     destination.id(),
                     self.msg_ctxs[&ContextKey {
-                        origin: origin.id(),
+                        origin: origin.deployment_key(),
                         destination: destination.id(),
                     }]
                         .clone(),
@@ -321,13 +502,15 @@ impl Relayer {
             })
             .collect();
         let message_processor = MessageProcessor::new(
-            self.dbs.get(origin).unwrap().clone(),
+            self.dbs.get(&origin.deployment_key()).unwrap().clone(),
             self.whitelist.clone(),
             self.blacklist.clone(),
             metrics,
-            self.prover_syncs[origin].clone(),
+            self.prover_syncs[&origin.deployment_key()].clone(),
             send_channels,
             destination_ctxs,
+            requeue_receiver,
+            self.rate_limiters.clone(),
         );
 
         let span = info_span!("MessageProcessor", origin=%message_processor.domain());
@@ -345,14 +528,23 @@ impl Relayer {
     fn run_destination_submitter(
         &self,
         destination: &HyperlaneDomain,
+        lane: usize,
         receiver: UnboundedReceiver<Box<DynPendingOperation>>,
     ) -> Instrumented<JoinHandle<Result<()>>> {
+        let max_batch_size = self
+            .max_batch_sizes
+            .get(&destination.id())
+            .copied()
+            .unwrap_or(1);
         let serial_submitter = SerialSubmitter::new(
             destination.clone(),
+            lane,
             receiver,
-            SerialSubmitterMetrics::new(&self.core.metrics, destination),
+            max_batch_size,
+            SerialSubmitterMetrics::new(&self.core.metrics, destination, lane),
         );
-        let span = info_span!("SerialSubmitter", destination=%destination);
+        self.queue_registry.register(serial_submitter.queues());
+        let span = info_span!("SerialSubmitter", destination=%destination, lane);
         let submit_fut = serial_submitter.spawn();
 
         tokio::spawn(async move {
@@ -364,5 +556,63 @@ impl Relayer {
     }
 }
 
+/// The domains of `domains` that belong to the same mailbox deployment as
+/// `domain`. Messages are only relayed between the mailboxes of a deployment.
+fn deployment_peers<'a>(
+    domain: &'a HyperlaneDomain,
+    domains: &'a [HyperlaneDomain],
+) -> impl Iterator<Item = &'a HyperlaneDomain> + Clone {
+    domains
+        .iter()
+        .filter(|peer| peer.deployment() == domain.deployment())
+}
+
+/// Build the mailboxes of the deployments on the chain of `chain_setup` by
+/// deployment. Their transactions in flight are persisted to `tx_store`.
+async fn build_deployment_mailboxes(
+    chain_setup: &ChainConf,
+    metrics: &CoreMetrics,
+    tx_store: &HyperlaneRocksDB,
+) -> Result<HashMap<DeploymentKey, Arc<dyn Mailbox>>> {
+    Ok(chain_setup
+        .build_deployment_mailboxes(metrics, Some(Arc::new(tx_store.clone())))
+        .await?
+        .into_iter()
+        .map(|mailbox| (mailbox.domain().deployment_key(), Arc::from(mailbox)))
+        .collect())
+}
+
 #[cfg(test)]
-mod test {}
+mod test {
+    use hyperlane_core::KnownHyperlaneDomain;
+
+    use super::*;
+
+    #[test]
+    fn test_routes_two_deployments_on_one_chain() {
+        let ethereum = HyperlaneDomain::Known(KnownHyperlaneDomain::Ethereum);
+        let polygon = HyperlaneDomain::Known(KnownHyperlaneDomain::Polygon);
+        let chains = vec![
+            ethereum.clone(),
+            ethereum.with_deployment("private"),
+            polygon.clone(),
+            polygon.with_deployment("private"),
+        ];
+        let routes = |origin: &HyperlaneDomain| {
+            deployment_peers(origin, &chains)
+                .filter(|destination| destination.id() != origin.id())
+                .map(HyperlaneDomain::deployment_key)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(routes(&ethereum), vec![polygon.deployment_key()]);
+        assert_eq!(
+            routes(&ethereum.with_deployment("private")),
+            vec![polygon.with_deployment("private").deployment_key()]
+        );
+        assert_eq!(
+            routes(&polygon.with_deployment("private")),
+            vec![ethereum.with_deployment("private").deployment_key()]
+        );
+    }
+}
@@ -1,10 +1,22 @@
 //! Configuration
 
-use std::{collections::HashSet, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
 
 use eyre::{eyre, Context};
-use hyperlane_base::{decl_settings, settings::Settings};
-use hyperlane_core::{cfg_unwrap_all, config::*, HyperlaneDomain, U256};
+use hyperlane_base::{
+    decl_settings,
+    settings::{parser::RawSignerConf, Settings, SignerConf},
+    CheckpointFetchConf,
+};
+use hyperlane_core::{
+    cfg_unwrap_all, config::*, utils::hex_or_base58_to_h256, HyperlaneDomain, ModuleType,
+    ProcessRevertClass, H160, H256, U256,
+};
+use reqwest::Url;
 use serde::Deserialize;
 use tracing::warn;
 
@@ -26,6 +38,65 @@ pub enum GasPaymentEnforcementPolicy {
         gas_fraction_numerator: u64,
         gas_fraction_denominator: u64,
     },
+    /// The USD value of the payment covers the estimated USD cost of
+    /// delivering the message plus a margin.
+    Profitability {
+        /// Where to get the USD prices of the native tokens from.
+        price_source: TokenPriceSourceConf,
+        /// Fraction of the delivery cost that must be paid on top of it, e.g.
+        /// `0.1` requires a payment worth 110% of the cost.
+        margin: f64,
+    },
+}
+
+/// Where to get the USD prices of the native tokens of chains from. Prices are
+/// expected as a JSON object keyed by domain id, e.g.
+/// `{"1": {"priceUsd": 1850.5, "decimals": 18}}`.
+#[derive(Debug, Clone)]
+pub enum TokenPriceSourceConf {
+    /// A file which is read again whenever the cached prices expire
+    File { path: PathBuf },
+    /// An HTTP endpoint, e.g. a price service running next to the relayer
+    Http { url: Url },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum RawTokenPriceSourceConf {
+    File {
+        path: Option<String>,
+    },
+    Http {
+        url: Option<String>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+impl FromRawConf<RawTokenPriceSourceConf> for TokenPriceSourceConf {
+    fn from_config_filtered(
+        raw: RawTokenPriceSourceConf,
+        cwp: &ConfigPath,
+        _filter: (),
+    ) -> ConfigResult<Self> {
+        use RawTokenPriceSourceConf::*;
+        match raw {
+            File { path } => Ok(Self::File {
+                path: path
+                    .ok_or_else(|| eyre!("Missing `path` for file token price source"))
+                    .into_config_result(|| cwp + "path")?
+                    .into(),
+            }),
+            Http { url } => Ok(Self::Http {
+                url: url
+                    .ok_or_else(|| eyre!("Missing `url` for HTTP token price source"))
+                    .into_config_result(|| cwp + "url")?
+                    .parse()
+                    .into_config_result(|| cwp + "url")?,
+            }),
+            Unknown => Err(eyre!("Unknown token price source")).into_config_result(|| cwp.clone()),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -42,6 +113,13 @@ enum RawGasPaymentEnforcementPolicy {
         #[serde(default = "default_gasfraction")]
         gasfraction: String,
     },
+    Profitability {
+        pricesource: Option<RawTokenPriceSourceConf>,
+        /// Optional fraction of the delivery cost which must be paid on top of
+        /// it. Defaults to 0.
+        #[serde(default)]
+        margin: Option<f64>,
+    },
     #[serde(other)]
     Unknown,
 }
@@ -82,6 +160,26 @@ impl FromRawConf<RawGasPaymentEnforcementPolicy> for GasPaymentEnforcementPolicy
                         .into_config_result(|| cwp + "gasfraction")?,
                 })
             }
+            Profitability {
+                pricesource,
+                margin,
+            } => {
+                let price_source = pricesource
+                    .ok_or_else(|| {
+                        eyre!("Missing `pricesource` for Profitability gas payment enforcement policy")
+                    })
+                    .into_config_result(|| cwp + "pricesource")?
+                    .parse_config(&(cwp + "pricesource"))?;
+                let margin = margin.unwrap_or_default();
+                if margin.is_nan() || margin < 0. {
+                    return Err(eyre!("`margin` must not be negative"))
+                        .into_config_result(|| cwp + "margin");
+                }
+                Ok(Self::Profitability {
+                    price_source,
+                    margin,
+                })
+            }
             Unknown => Err(eyre!("Unknown gas payment enforcement policy"))
                 .into_config_result(|| cwp.clone()),
         }
@@ -128,14 +226,681 @@ impl FromRawConf<RawGasPaymentEnforcementConf> for GasPaymentEnforcementConf {
     }
 }
 
+/// Config for submitting messages to a destination over several concurrent
+/// lanes
+#[derive(Debug, Clone)]
+pub struct SubmissionLanesConf {
+    /// Number of lanes submitting concurrently.
+    pub count: usize,
+    /// Signers for the lanes, by lane index. Each of several lanes needs a
+    /// signer of its own, so the lanes do not race for nonces. A single lane
+    /// without one uses the destination chain signer.
+    pub signers: Vec<SignerConf>,
+}
+
+impl Default for SubmissionLanesConf {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            signers: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSubmissionLanesConf {
+    count: Option<StrOrInt>,
+    #[serde(default)]
+    signers: Vec<RawSignerConf>,
+}
+
+impl FromRawConf<RawSubmissionLanesConf> for SubmissionLanesConf {
+    fn from_config_filtered(
+        raw: RawSubmissionLanesConf,
+        cwp: &ConfigPath,
+        _filter: (),
+    ) -> ConfigResult<Self> {
+        let mut err = ConfigParsingError::default();
+
+        let signers: Vec<SignerConf> = raw
+            .signers
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, r)| {
+                r.parse_config(&(cwp + "signers").join(i.to_string()))
+                    .take_config_err(&mut err)
+            })
+            .collect();
+
+        let count = raw
+            .count
+            .and_then(|r| {
+                u32::try_from(r)
+                    .map(|c| c as usize)
+                    .take_err(&mut err, || cwp + "count")
+            })
+            .unwrap_or_else(|| signers.len().max(1));
+
+        if count == 0 {
+            err.push(cwp + "count", eyre!("There must be at least one lane"));
+        }
+        if signers.len() > count {
+            err.push(
+                cwp + "signers",
+                eyre!("More lane signers were given than there are lanes"),
+            );
+        } else if count > 1 && signers.len() < count {
+            err.push(
+                cwp + "signers",
+                eyre!("Each of several lanes needs a signer of its own"),
+            );
+        }
+        for (i, signer) in signers.iter().enumerate() {
+            if signers[..i].contains(signer) {
+                err.push(
+                    (cwp + "signers").join(i.to_string()),
+                    eyre!("Lane signer is already used by another lane"),
+                );
+            }
+        }
+
+        err.into_result(Self { count, signers })
+    }
+}
+
+/// Longest delay between delivery attempts a retry policy may configure.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Config for how a message is retried after a failed delivery attempt
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// How long to wait after the first failed attempt.
+    pub base_delay: Duration,
+    /// Factor the delay grows by after each further failed attempt.
+    pub multiplier: f64,
+    /// Upper bound on the delay between attempts.
+    pub max_delay: Duration,
+    /// Fraction by which each delay is randomly shortened or lengthened, so
+    /// messages which failed together are not all retried together.
+    pub jitter: f64,
+    /// Number of failed attempts after which the message is moved to the
+    /// dead-letter queue. Retries forever if `None`.
+    pub max_retries: Option<u32>,
+}
+
+/// Config for a retry policy and the messages it applies to
+#[derive(Debug, Clone)]
+pub struct RetryPolicyConf {
+    /// The retry policy
+    pub policy: RetryPolicy,
+    /// An optional matching list, any message that matches will use this
+    /// policy. By default all messages will match.
+    pub matching_list: MatchingList,
+    /// Only messages whose last attempt failed because simulating the process
+    /// call reverted with one of these classes of revert use this policy. By
+    /// default messages use it however their last attempt failed.
+    pub revert_classes: Vec<ProcessRevertClass>,
+}
+
+impl RetryPolicyConf {
+    /// Whether the policy applies to a message whose last attempt failed with
+    /// `revert_class`, `None` if it did not fail because of a revert.
+    pub fn matches_revert_class(&self, revert_class: Option<ProcessRevertClass>) -> bool {
+        self.revert_classes.is_empty()
+            || revert_class.map_or(false, |class| self.revert_classes.contains(&class))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRetryPolicyConf {
+    /// Seconds to wait after the first failed attempt. Defaults to 10.
+    base_delay: Option<StrOrInt>,
+    /// Defaults to 2.
+    multiplier: Option<f64>,
+    /// Max seconds to wait between attempts. Defaults to 3600.
+    max_delay: Option<StrOrInt>,
+    /// Defaults to 0.
+    jitter: Option<f64>,
+    max_retries: Option<StrOrInt>,
+    #[serde(default)]
+    matching_list: Option<MatchingList>,
+    /// Names of `ProcessRevertClass`es, e.g. `["recipientReverted"]`.
+    #[serde(default)]
+    revert_classes: Vec<String>,
+}
+
+impl FromRawConf<RawRetryPolicyConf> for RetryPolicyConf {
+    fn from_config_filtered(
+        raw: RawRetryPolicyConf,
+        cwp: &ConfigPath,
+        _filter: (),
+    ) -> ConfigResult<Self> {
+        let mut err = ConfigParsingError::default();
+
+        let base_delay = raw
+            .base_delay
+            .and_then(|r| u64::try_from(r).take_err(&mut err, || cwp + "baseDelay"))
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(10));
+
+        let max_delay = raw
+            .max_delay
+            .and_then(|r| u64::try_from(r).take_err(&mut err, || cwp + "maxDelay"))
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(60 * 60));
+        if max_delay < base_delay {
+            err.push(
+                cwp + "maxDelay",
+                eyre!("`maxDelay` must not be less than `baseDelay`"),
+            );
+        }
+        if max_delay > MAX_RETRY_DELAY {
+            err.push(
+                cwp + "maxDelay",
+                eyre!(
+                    "`maxDelay` must not be more than {} seconds",
+                    MAX_RETRY_DELAY.as_secs()
+                ),
+            );
+        }
+
+        let multiplier = raw.multiplier.unwrap_or(2.);
+        if multiplier.is_nan() || multiplier < 1. {
+            err.push(cwp + "multiplier", eyre!("`multiplier` must be at least 1"));
+        }
+
+        let jitter = raw.jitter.unwrap_or_default();
+        if !(0. ..=1.).contains(&jitter) {
+            err.push(cwp + "jitter", eyre!("`jitter` must be between 0 and 1"));
+        }
+
+        let max_retries = raw
+            .max_retries
+            .and_then(|r| u32::try_from(r).take_err(&mut err, || cwp + "maxRetries"));
+
+        let revert_classes = raw
+            .revert_classes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, name)| {
+                parse_revert_class(name)
+                    .take_err(&mut err, || (cwp + "revertClasses").join(i.to_string()))
+            })
+            .collect();
+
+        err.into_result(Self {
+            policy: RetryPolicy {
+                base_delay,
+                multiplier,
+                max_delay,
+                jitter,
+                max_retries,
+            },
+            matching_list: raw.matching_list.unwrap_or_default(),
+            revert_classes,
+        })
+    }
+}
+
+fn parse_revert_class(name: &str) -> eyre::Result<ProcessRevertClass> {
+    Ok(match name.to_ascii_lowercase().as_str() {
+        "alreadydelivered" => ProcessRevertClass::AlreadyDelivered,
+        "ismrejected" => ProcessRevertClass::IsmRejected,
+        "recipientreverted" => ProcessRevertClass::RecipientReverted,
+        "invalidmessage" => ProcessRevertClass::InvalidMessage,
+        _ => return Err(eyre!("Unknown revert class `{name}`")),
+    })
+}
+
+/// Config for a class of messages that are attempted before others
+#[derive(Debug, Clone)]
+pub struct PriorityClassConf {
+    /// Priority of the messages in this class. Among messages that are ready
+    /// to be attempted, those with a higher priority are attempted first.
+    /// Messages in no class have a priority of 0.
+    pub priority: u32,
+    /// An optional matching list, any message that matches is in this class.
+    /// By default all messages will match.
+    pub matching_list: MatchingList,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawPriorityClassConf {
+    priority: Option<StrOrInt>,
+    #[serde(default)]
+    matching_list: Option<MatchingList>,
+}
+
+impl FromRawConf<RawPriorityClassConf> for PriorityClassConf {
+    fn from_config_filtered(
+        raw: RawPriorityClassConf,
+        cwp: &ConfigPath,
+        _filter: (),
+    ) -> ConfigResult<Self> {
+        let priority = raw
+            .priority
+            .ok_or_else(|| eyre!("Missing `priority` for priority class"))
+            .into_config_result(|| cwp + "priority")?
+            .try_into()
+            .into_config_result(|| cwp + "priority")?;
+
+        Ok(Self {
+            priority,
+            matching_list: raw.matching_list.unwrap_or_default(),
+        })
+    }
+}
+
+/// What a rate limit keeps a separate token bucket for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// Each sender address
+    Sender,
+    /// Each recipient address
+    Recipient,
+    /// Each origin domain
+    Origin,
+}
+
+/// Config for a token-bucket rate limit on the messages sent to submitters
+#[derive(Debug, Clone)]
+pub struct RateLimitConf {
+    /// Name of the rate limit, used in metrics and logs.
+    pub name: String,
+    /// What to keep a separate token bucket for.
+    pub key: RateLimitKey,
+    /// Max number of messages that can be sent at once when the bucket is
+    /// full.
+    pub capacity: u32,
+    /// Number of messages per second the bucket refills by.
+    pub refill_per_second: f64,
+    /// An optional matching list, only messages that match are limited. By
+    /// default all messages will match.
+    pub matching_list: MatchingList,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRateLimitConf {
+    name: Option<String>,
+    /// One of `sender`, `recipient` or `origin`.
+    key: Option<String>,
+    capacity: Option<StrOrInt>,
+    refill_per_second: Option<f64>,
+    #[serde(default)]
+    matching_list: Option<MatchingList>,
+}
+
+impl FromRawConf<RawRateLimitConf> for RateLimitConf {
+    fn from_config_filtered(
+        raw: RawRateLimitConf,
+        cwp: &ConfigPath,
+        _filter: (),
+    ) -> ConfigResult<Self> {
+        let mut err = ConfigParsingError::default();
+
+        let name = raw
+            .name
+            .ok_or_else(|| eyre!("Missing `name` for rate limit"))
+            .take_err(&mut err, || cwp + "name");
+
+        let key = raw
+            .key
+            .ok_or_else(|| eyre!("Missing `key` for rate limit"))
+            .and_then(|key| match key.to_ascii_lowercase().as_str() {
+                "sender" => Ok(RateLimitKey::Sender),
+                "recipient" => Ok(RateLimitKey::Recipient),
+                "origin" => Ok(RateLimitKey::Origin),
+                _ => Err(eyre!(
+                    "Unknown rate limit key; expected `sender`, `recipient` or `origin`"
+                )),
+            })
+            .take_err(&mut err, || cwp + "key");
+
+        let capacity = raw
+            .capacity
+            .ok_or_else(|| eyre!("Missing `capacity` for rate limit"))
+            .and_then(|c| Ok(u32::try_from(c)?))
+            .take_err(&mut err, || cwp + "capacity");
+        if capacity == Some(0) {
+            err.push(
+                cwp + "capacity",
+                eyre!("Rate limit `capacity` must be at least 1"),
+            );
+        }
+
+        let refill_per_second = raw
+            .refill_per_second
+            .ok_or_else(|| eyre!("Missing `refillPerSecond` for rate limit"))
+            .take_err(&mut err, || cwp + "refillPerSecond");
+        if matches!(refill_per_second, Some(r) if r.is_nan() || r <= 0.) {
+            err.push(
+                cwp + "refillPerSecond",
+                eyre!("Rate limit `refillPerSecond` must be positive"),
+            );
+        }
+
+        cfg_unwrap_all!(cwp, err: [name, key, capacity, refill_per_second]);
+        err.into_result(Self {
+            name,
+            key,
+            capacity,
+            refill_per_second,
+            matching_list: raw.matching_list.unwrap_or_default(),
+        })
+    }
+}
+
+/// Policy for the gateways CCIP-read ISMs direct the relayer to fetch
+/// metadata from
+#[derive(Debug, Clone, PartialEq)]
+pub struct CcipReadPolicy {
+    /// Hosts gateways may be on. `*.example.com` matches any subdomain of
+    /// `example.com`. If empty, any public host is allowed: IP addresses and
+    /// hosts resolving to loopback, private or link-local addresses are not.
+    pub allowed_hosts: Vec<String>,
+    /// Responses larger than this many bytes are rejected.
+    pub max_response_bytes: usize,
+    /// How long to wait for each gateway to respond.
+    pub timeout: Duration,
+    /// Number of times to go through the gateway URLs again after none of
+    /// them returned valid metadata.
+    pub retries: u32,
+    /// How long to wait before the first retry, doubling for each one after
+    /// up to five minutes.
+    pub retry_backoff: Duration,
+    /// How long successful responses are reused for.
+    pub cache_ttl: Duration,
+}
+
+impl Default for CcipReadPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: vec![],
+            max_response_bytes: 1024 * 1024,
+            timeout: Duration::from_secs(10),
+            retries: 0,
+            retry_backoff: Duration::from_secs(1),
+            cache_ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawCcipReadPolicy {
+    #[serde(default)]
+    allowed_hosts: Vec<String>,
+    max_response_bytes: Option<StrOrInt>,
+    /// Seconds to wait for each gateway. Defaults to 10.
+    timeout: Option<StrOrInt>,
+    /// Defaults to 0.
+    retries: Option<StrOrInt>,
+    /// Seconds to wait before the first retry. Defaults to 1.
+    retry_backoff: Option<StrOrInt>,
+    /// Seconds to reuse successful responses for. Defaults to 60.
+    cache_ttl: Option<StrOrInt>,
+}
+
+impl FromRawConf<RawCcipReadPolicy> for CcipReadPolicy {
+    fn from_config_filtered(
+        raw: RawCcipReadPolicy,
+        cwp: &ConfigPath,
+        _filter: (),
+    ) -> ConfigResult<Self> {
+        let mut err = ConfigParsingError::default();
+        let default = Self::default();
+
+        for (i, host) in raw.allowed_hosts.iter().enumerate() {
+            let name = host.strip_prefix("*.").unwrap_or(host);
+            if name.is_empty() || name.contains(['*', '/', ':']) {
+                err.push(
+                    (cwp + "allowedHosts").join(i.to_string()),
+                    eyre!("Expected a host name, optionally starting with `*.`"),
+                );
+            }
+        }
+
+        let max_response_bytes = raw
+            .max_response_bytes
+            .and_then(|r| u64::try_from(r).take_err(&mut err, || cwp + "maxResponseBytes"))
+            .map(|b| b as usize)
+            .unwrap_or(default.max_response_bytes);
+        if max_response_bytes == 0 {
+            err.push(
+                cwp + "maxResponseBytes",
+                eyre!("`maxResponseBytes` must be at least 1"),
+            );
+        }
+
+        let timeout = raw
+            .timeout
+            .and_then(|r| u64::try_from(r).take_err(&mut err, || cwp + "timeout"))
+            .map(Duration::from_secs)
+            .unwrap_or(default.timeout);
+        if timeout.is_zero() {
+            err.push(cwp + "timeout", eyre!("`timeout` must be at least 1"));
+        }
+
+        let retries = raw
+            .retries
+            .and_then(|r| u32::try_from(r).take_err(&mut err, || cwp + "retries"))
+            .unwrap_or(default.retries);
+
+        let retry_backoff = raw
+            .retry_backoff
+            .and_then(|r| u64::try_from(r).take_err(&mut err, || cwp + "retryBackoff"))
+            .map(Duration::from_secs)
+            .unwrap_or(default.retry_backoff);
+
+        let cache_ttl = raw
+            .cache_ttl
+            .and_then(|r| u64::try_from(r).take_err(&mut err, || cwp + "cacheTtl"))
+            .map(Duration::from_secs)
+            .unwrap_or(default.cache_ttl);
+
+        err.into_result(Self {
+            allowed_hosts: raw
+                .allowed_hosts
+                .into_iter()
+                .map(|h| h.to_ascii_lowercase())
+                .collect(),
+            max_response_bytes,
+            timeout,
+            retries,
+            retry_backoff,
+            cache_ttl,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawCheckpointFetchConf {
+    /// Milliseconds to wait for each validator's checkpoint syncer. Defaults
+    /// to 5000.
+    timeout_ms: Option<StrOrInt>,
+    /// Milliseconds to wait for specific validators' checkpoint syncers, by
+    /// validator address.
+    #[serde(default)]
+    validator_timeouts_ms: HashMap<String, StrOrInt>,
+    /// Milliseconds to wait for responses before also querying another
+    /// validator. Defaults to 500.
+    hedge_delay_ms: Option<StrOrInt>,
+}
+
+impl FromRawConf<RawCheckpointFetchConf> for CheckpointFetchConf {
+    fn from_config_filtered(
+        raw: RawCheckpointFetchConf,
+        cwp: &ConfigPath,
+        _filter: (),
+    ) -> ConfigResult<Self> {
+        let mut err = ConfigParsingError::default();
+        let default = Self::default();
+
+        let timeout = raw
+            .timeout_ms
+            .and_then(|r| parse_millis(r, &mut err, cwp + "timeoutMs"))
+            .unwrap_or(default.timeout);
+
+        let validator_timeouts = raw
+            .validator_timeouts_ms
+            .into_iter()
+            .filter_map(|(address, r)| {
+                let cwp = &(cwp + "validatorTimeoutsMs") + &address;
+                let timeout = parse_millis(r, &mut err, cwp.clone());
+                let address = address
+                    .parse::<H160>()
+                    .map_err(|e| eyre!("Invalid validator address: {e}"));
+                Some((address.take_err(&mut err, || cwp)?, timeout?))
+            })
+            .collect();
+
+        let hedge_delay = raw
+            .hedge_delay_ms
+            .and_then(|r| parse_millis(r, &mut err, cwp + "hedgeDelayMs"))
+            .unwrap_or(default.hedge_delay);
+
+        err.into_result(Self {
+            timeout,
+            validator_timeouts,
+            hedge_delay,
+        })
+    }
+}
+
+/// Parse a positive number of milliseconds.
+fn parse_millis(r: StrOrInt, err: &mut ConfigParsingError, cwp: ConfigPath) -> Option<Duration> {
+    let millis = u64::try_from(r).take_err(err, || cwp.clone())?;
+    if millis == 0 {
+        err.push(cwp, eyre!("Expected at least 1 millisecond"));
+        return None;
+    }
+    Some(Duration::from_millis(millis))
+}
+
+/// Config for an external service, e.g. a sidecar next to the relayer, which
+/// builds metadata for ISMs the relayer cannot build metadata for itself.
+///
+/// Metadata requests are POSTed to the service as JSON with the ISM address,
+/// its module type and the message. The service responds with
+/// `{"metadata": "0x..."}`, or `{"metadata": null}` if it cannot build it yet.
+#[derive(Debug, Clone)]
+pub struct ExternalMetadataProviderConf {
+    /// Where metadata requests are sent.
+    pub url: Url,
+    /// ISMs the service builds metadata for.
+    pub ism_addresses: HashSet<H256>,
+    /// Types of ISMs the service builds metadata for, as reported by the ISMs,
+    /// so types unknown to the relayer can be configured too.
+    pub module_types: Vec<u8>,
+    /// How long to wait for the service to respond.
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawExternalMetadataProviderConf {
+    url: Option<String>,
+    #[serde(default)]
+    ism_addresses: Vec<String>,
+    /// Module type names, e.g. `ccipRead`, or numbers, e.g. `"12"` for a type
+    /// unknown to the relayer.
+    #[serde(default)]
+    module_types: Vec<String>,
+    /// Seconds to wait for a response. Defaults to 30.
+    timeout: Option<StrOrInt>,
+}
+
+impl FromRawConf<RawExternalMetadataProviderConf> for ExternalMetadataProviderConf {
+    fn from_config_filtered(
+        raw: RawExternalMetadataProviderConf,
+        cwp: &ConfigPath,
+        _filter: (),
+    ) -> ConfigResult<Self> {
+        let mut err = ConfigParsingError::default();
+
+        let url = raw
+            .url
+            .ok_or_else(|| eyre!("Missing `url` for external metadata provider"))
+            .and_then(|url| Ok(url.parse::<Url>()?))
+            .take_err(&mut err, || cwp + "url");
+
+        let ism_addresses = raw
+            .ism_addresses
+            .iter()
+            .enumerate()
+            .filter_map(|(i, address)| {
+                hex_or_base58_to_h256(address)
+                    .take_err(&mut err, || (cwp + "ismAddresses").join(i.to_string()))
+            })
+            .collect::<HashSet<_>>();
+
+        let module_types = raw
+            .module_types
+            .iter()
+            .enumerate()
+            .filter_map(|(i, name)| {
+                parse_module_type(name)
+                    .take_err(&mut err, || (cwp + "moduleTypes").join(i.to_string()))
+            })
+            .collect::<Vec<_>>();
+
+        if raw.ism_addresses.is_empty() && raw.module_types.is_empty() {
+            err.push(
+                cwp.clone(),
+                eyre!("External metadata provider must have `ismAddresses` or `moduleTypes`"),
+            );
+        }
+
+        let timeout = raw
+            .timeout
+            .and_then(|r| u64::try_from(r).take_err(&mut err, || cwp + "timeout"))
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(30));
+
+        cfg_unwrap_all!(cwp, err: [url]);
+        err.into_result(Self {
+            url,
+            ism_addresses,
+            module_types,
+            timeout,
+        })
+    }
+}
+
+fn parse_module_type(name: &str) -> eyre::Result<u8> {
+    if let Ok(module_type) = name.parse::<u8>() {
+        return Ok(module_type);
+    }
+    let module_type = match name.to_ascii_lowercase().as_str() {
+        "unused" => ModuleType::Unused,
+        "routing" => ModuleType::Routing,
+        "aggregation" => ModuleType::Aggregation,
+        "legacymultisig" => ModuleType::LegacyMultisig,
+        "merklerootmultisig" => ModuleType::MerkleRootMultisig,
+        "messageidmultisig" => ModuleType::MessageIdMultisig,
+        "null" => ModuleType::Null,
+        "ccipread" => ModuleType::CcipRead,
+        "optimistic" => ModuleType::Optimistic,
+        _ => return Err(eyre!("Unknown module type `{name}`")),
+    };
+    Ok(module_type as u8)
+}
+
 decl_settings!(Relayer,
     Parsed {
         /// Database path
         db: PathBuf,
-        /// The chain to relay messages from
-        origin_chains: HashSet<HyperlaneDomain>,
-        /// Chains to relay messages to
-        destination_chains: HashSet<HyperlaneDomain>,
+        /// The chain to relay messages from, with the additional mailbox
+        /// deployments on them
+        origin_chains: Vec<HyperlaneDomain>,
+        /// Chains to relay messages to, with the additional mailbox deployments
+        /// on them
+        destination_chains: Vec<HyperlaneDomain>,
         /// The gas payment enforcement policies
         gas_payment_enforcement: Vec<GasPaymentEnforcementConf>,
         /// Filter for what messages to relay.
@@ -150,6 +915,34 @@ decl_settings!(Relayer,
         /// If true, allows local storage based checkpoint syncers.
         /// Not intended for production use.
         allow_local_checkpoint_syncers: bool,
+        /// Max number of messages to submit in a single transaction, keyed by
+        /// destination domain id. Destinations that are not listed submit one
+        /// message per transaction.
+        max_batch_sizes: HashMap<u32, usize>,
+        /// Concurrent submission lanes, keyed by destination domain id.
+        /// Destinations that are not listed use a single lane.
+        submission_lanes: HashMap<u32, SubmissionLanesConf>,
+        /// If set, serves an API for inspecting and manipulating the message
+        /// queues on the metrics port to requests bearing this token.
+        admin_api_token: Option<String>,
+        /// The retry policies, the first one matching a message applies to it.
+        /// Messages matching none of them use the default backoff and are
+        /// retried forever.
+        retry_policies: Vec<RetryPolicyConf>,
+        /// The priority classes, the first one matching a message sets its
+        /// priority.
+        priority_classes: Vec<PriorityClassConf>,
+        /// Rate limits on the messages sent to submitters. A message is only
+        /// sent once none of the limits matching it are exceeded.
+        rate_limits: Vec<RateLimitConf>,
+        /// Policy for the gateways metadata for CCIP-read ISMs is fetched
+        /// from.
+        ccip_read: CcipReadPolicy,
+        /// External services which build metadata for the ISMs they are
+        /// configured for, in place of the relayer.
+        external_metadata_providers: Vec<ExternalMetadataProviderConf>,
+        /// How long to wait for the checkpoint syncers of validators.
+        checkpoint_fetch: CheckpointFetchConf,
     },
     Raw {
         /// Database path (path on the fs)
@@ -179,6 +972,43 @@ decl_settings!(Relayer,
         /// Not intended for production use. Defaults to false.
         #[serde(default)]
         allowlocalcheckpointsyncers: bool,
+        /// This is optional. JSON object mapping destination domain ids to the max number of
+        /// messages to submit in a single transaction, e.g. `{"1": 10}`.
+        batchsubmission: Option<String>,
+        /// This is optional. JSON object mapping destination domain ids to the number of
+        /// concurrent submission lanes and optionally a signer per lane, e.g.
+        /// `{"1": {"count": 2, "signers": [{"type": "hexKey", "key": "0x..."}]}}`.
+        submissionlanes: Option<String>,
+        /// If true, serves an API for inspecting and manipulating the message queues on the
+        /// metrics port. Defaults to false.
+        #[serde(default)]
+        adminapi: bool,
+        /// The bearer token requests to the admin API must carry in their `Authorization`
+        /// header. Required if `adminapi` is true.
+        adminapitoken: Option<String>,
+        /// This is optional. The retry policy configuration as JSON. Expects an ordered array
+        /// of `RetryPolicyConf`, e.g.
+        /// `[{"baseDelay": 5, "multiplier": 1.5, "maxDelay": 600, "jitter": 0.1, "maxRetries": 50}]`.
+        /// A policy with `revertClasses`, e.g. `["recipientReverted", "ismRejected"]`, only
+        /// applies when simulating the delivery of a message reverted for one of those reasons.
+        retrypolicies: Option<String>,
+        /// This is optional. The priority class configuration as JSON. Expects an ordered array
+        /// of `PriorityClassConf`, e.g. `[{"priority": 10, "matchingList": [{"senderAddress": "0x..."}]}]`.
+        priorityclasses: Option<String>,
+        /// This is optional. The rate limit configuration as JSON. Expects an array of
+        /// `RateLimitConf`, e.g.
+        /// `[{"name": "per-sender", "key": "sender", "capacity": 20, "refillPerSecond": 0.5}]`.
+        ratelimits: Option<String>,
+        /// This is optional. The CCIP-read gateway policy as JSON, e.g.
+        /// `{"allowedHosts": ["*.example.com"], "maxResponseBytes": 65536, "timeout": 5, "retries": 1, "retryBackoff": 2, "cacheTtl": 60}`.
+        ccipread: Option<String>,
+        /// This is optional. The external metadata providers as JSON. Expects an array of
+        /// `ExternalMetadataProviderConf`, e.g.
+        /// `[{"url": "http://localhost:9090/metadata", "moduleTypes": ["ccipRead", "12"], "ismAddresses": ["0x..."]}]`.
+        externalmetadataproviders: Option<String>,
+        /// This is optional. How long to wait for the checkpoint syncers of validators as JSON,
+        /// e.g. `{"timeoutMs": 5000, "hedgeDelayMs": 500, "validatorTimeoutsMs": {"0x...": 10000}}`.
+        checkpointfetch: Option<String>,
     }
 );
 
@@ -238,6 +1068,128 @@ impl FromRawConf<RawRelayerSettings> for RelayerSettings {
             })
             .unwrap_or_default();
 
+        let max_batch_sizes = raw
+            .batchsubmission
+            .and_then(|j| {
+                serde_json::from_str::<HashMap<u32, usize>>(&j)
+                    .take_err(&mut err, || cwp + "batchsubmission")
+            })
+            .unwrap_or_default();
+
+        let submission_lanes = raw
+            .submissionlanes
+            .and_then(|j| {
+                serde_json::from_str::<HashMap<u32, RawSubmissionLanesConf>>(&j)
+                    .take_err(&mut err, || cwp + "submissionlanes")
+            })
+            .map(|rv| {
+                let cwp = cwp + "submissionlanes";
+                rv.into_iter()
+                    .filter_map(|(domain, r)| {
+                        r.parse_config(&cwp.join(domain.to_string()))
+                            .take_config_err(&mut err)
+                            .map(|conf| (domain, conf))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let retry_policies = raw
+            .retrypolicies
+            .and_then(|j| {
+                serde_json::from_str::<Vec<RawRetryPolicyConf>>(&j)
+                    .take_err(&mut err, || cwp + "retrypolicies")
+            })
+            .map(|rv| {
+                let cwp = cwp + "retrypolicies";
+                rv.into_iter()
+                    .enumerate()
+                    .filter_map(|(i, r)| {
+                        r.parse_config(&cwp.join(i.to_string()))
+                            .take_config_err(&mut err)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let priority_classes = raw
+            .priorityclasses
+            .and_then(|j| {
+                serde_json::from_str::<Vec<RawPriorityClassConf>>(&j)
+                    .take_err(&mut err, || cwp + "priorityclasses")
+            })
+            .map(|rv| {
+                let cwp = cwp + "priorityclasses";
+                rv.into_iter()
+                    .enumerate()
+                    .filter_map(|(i, r)| {
+                        r.parse_config(&cwp.join(i.to_string()))
+                            .take_config_err(&mut err)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let rate_limits = raw
+            .ratelimits
+            .and_then(|j| {
+                serde_json::from_str::<Vec<RawRateLimitConf>>(&j)
+                    .take_err(&mut err, || cwp + "ratelimits")
+            })
+            .map(|rv| {
+                let cwp = cwp + "ratelimits";
+                rv.into_iter()
+                    .enumerate()
+                    .filter_map(|(i, r)| {
+                        r.parse_config(&cwp.join(i.to_string()))
+                            .take_config_err(&mut err)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let ccip_read = raw
+            .ccipread
+            .and_then(|j| {
+                serde_json::from_str::<RawCcipReadPolicy>(&j)
+                    .take_err(&mut err, || cwp + "ccipread")
+            })
+            .and_then(|r| {
+                r.parse_config(&(cwp + "ccipread"))
+                    .take_config_err(&mut err)
+            })
+            .unwrap_or_default();
+
+        let external_metadata_providers = raw
+            .externalmetadataproviders
+            .and_then(|j| {
+                serde_json::from_str::<Vec<RawExternalMetadataProviderConf>>(&j)
+                    .take_err(&mut err, || cwp + "externalmetadataproviders")
+            })
+            .map(|rv| {
+                let cwp = cwp + "externalmetadataproviders";
+                rv.into_iter()
+                    .enumerate()
+                    .filter_map(|(i, r)| {
+                        r.parse_config(&cwp.join(i.to_string()))
+                            .take_config_err(&mut err)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let checkpoint_fetch = raw
+            .checkpointfetch
+            .and_then(|j| {
+                serde_json::from_str::<RawCheckpointFetchConf>(&j)
+                    .take_err(&mut err, || cwp + "checkpointfetch")
+            })
+            .and_then(|r| {
+                r.parse_config(&(cwp + "checkpointfetch"))
+                    .take_config_err(&mut err)
+            })
+            .unwrap_or_default();
+
         let mut origin_chain_names = {
             #[allow(deprecated)]
             raw.originchainname
@@ -335,12 +1287,14 @@ impl FromRawConf<RawRelayerSettings> for RelayerSettings {
                             .context("Missing configuration for an origin chain")
                             .take_err(&mut err, || cwp + "chains" + origin)
                     })
+                    .flat_map(|domain| with_deployments(base, domain))
                     .collect()
             })
+            .map(unique_deployments)
             .unwrap_or_default();
 
         // validate all destination chains are present and get their HyperlaneDomain.
-        let destination_chains: HashSet<_> = base
+        let destination_chains = base
             .as_ref()
             .map(|base| {
                 destination_chain_names
@@ -350,10 +1304,20 @@ impl FromRawConf<RawRelayerSettings> for RelayerSettings {
                             .context("Missing configuration for a destination chain")
                             .take_err(&mut err, || cwp + "chains" + destination)
                     })
+                    .flat_map(|domain| with_deployments(base, domain))
                     .collect()
             })
+            .map(unique_deployments)
             .unwrap_or_default();
 
+        let admin_api_token = raw.adminapitoken.filter(|token| !token.is_empty());
+        if raw.adminapi && admin_api_token.is_none() {
+            err.push(
+                cwp + "adminapitoken",
+                eyre!("A bearer token is required to serve the admin API"),
+            );
+        }
+
         if let Some(base) = &base {
             for domain in &destination_chains {
                 base.chain_setup(domain)
@@ -377,6 +1341,15 @@ impl FromRawConf<RawRelayerSettings> for RelayerSettings {
             transaction_gas_limit,
             skip_transaction_gas_limit_for,
             allow_local_checkpoint_syncers: raw.allowlocalcheckpointsyncers,
+            max_batch_sizes,
+            submission_lanes,
+            admin_api_token: admin_api_token.filter(|_| raw.adminapi),
+            retry_policies,
+            priority_classes,
+            rate_limits,
+            ccip_read,
+            external_metadata_providers,
+            checkpoint_fetch,
         })
     }
 }
@@ -388,3 +1361,159 @@ fn default_gasfraction() -> String {
 fn parse_chains(chains_str: String) -> Vec<String> {
     chains_str.split(',').map(str::to_ascii_lowercase).collect()
 }
+
+/// The domain of a chain followed by the domains of the additional mailbox
+/// deployments on it, which are relayed for alongside the chain.
+fn with_deployments(base: &Settings, domain: HyperlaneDomain) -> Vec<HyperlaneDomain> {
+    let deployments = base
+        .chain_setup(&domain)
+        .map(|chain| {
+            chain
+                .deployments
+                .keys()
+                .map(|name| chain.domain.with_deployment(name))
+                .collect()
+        })
+        .unwrap_or_default();
+    [vec![domain], deployments].concat()
+}
+
+/// Drop chains listed more than once. The domains of the deployments on a
+/// chain are equal, so they are told apart by their deployment keys.
+fn unique_deployments(mut domains: Vec<HyperlaneDomain>) -> Vec<HyperlaneDomain> {
+    domains.sort_by_key(HyperlaneDomain::deployment_key);
+    domains.dedup_by_key(|domain| domain.deployment_key());
+    domains
+}
+
+#[cfg(test)]
+mod test {
+    use hyperlane_core::KnownHyperlaneDomain;
+    use serde_json::json;
+
+    use super::*;
+
+    fn chain(name: &str, domain: u32) -> serde_json::Value {
+        let addresses = |byte: u8| {
+            let address = format!("{:?}", H256::repeat_byte(byte));
+            json!({
+                "mailbox": address,
+                "interchainGasPaymaster": address,
+                "validatorAnnounce": address,
+            })
+        };
+        json!({
+            "name": name,
+            "domain": domain,
+            "protocol": "ethereum",
+            "connection": { "type": "http", "url": "http://localhost:8545" },
+            "signer": { "type": "hexKey", "key": format!("0x{}", "11".repeat(32)) },
+            "addresses": addresses(1),
+            "deployments": { "Private": addresses(2) },
+        })
+    }
+
+    #[test]
+    fn test_relays_for_two_deployments_on_one_chain() {
+        let raw: RawRelayerSettings = serde_json::from_value(json!({
+            "chains": {
+                "ethereum": chain("ethereum", 1),
+                "polygon": chain("polygon", 137),
+            },
+            "relaychains": "ethereum,polygon",
+            "db": "/tmp/hyperlane_db",
+        }))
+        .unwrap();
+        let settings = RelayerSettings::from_config(raw, &ConfigPath::default()).unwrap();
+
+        let ethereum = HyperlaneDomain::Known(KnownHyperlaneDomain::Ethereum);
+        let polygon = HyperlaneDomain::Known(KnownHyperlaneDomain::Polygon);
+        let expected = vec![
+            ethereum.deployment_key(),
+            ethereum.with_deployment("private").deployment_key(),
+            polygon.deployment_key(),
+            polygon.with_deployment("private").deployment_key(),
+        ];
+        for chains in [&settings.origin_chains, &settings.destination_chains] {
+            let keys: Vec<_> = chains.iter().map(HyperlaneDomain::deployment_key).collect();
+            assert_eq!(keys, expected);
+        }
+
+        let private = settings
+            .chain_setup(&ethereum.with_deployment("private"))
+            .unwrap();
+        assert_eq!(private.addresses.mailbox, H256::repeat_byte(2));
+        assert_eq!(
+            settings.chain_setup(&ethereum).unwrap().addresses.mailbox,
+            H256::repeat_byte(1)
+        );
+    }
+
+    #[test]
+    fn test_lanes_need_distinct_signers() {
+        let parse = |lanes: serde_json::Value| {
+            let raw: RawSubmissionLanesConf = serde_json::from_value(lanes).unwrap();
+            SubmissionLanesConf::from_config(raw, &ConfigPath::default())
+        };
+        let signer =
+            |byte: &str| json!({ "type": "hexKey", "key": format!("0x{}", byte.repeat(32)) });
+
+        let single = parse(json!({ "count": 1 })).unwrap();
+        assert_eq!((single.count, single.signers.len()), (1, 0));
+        let lanes = parse(json!({ "signers": [signer("11"), signer("22")] })).unwrap();
+        assert_eq!((lanes.count, lanes.signers.len()), (2, 2));
+
+        // lanes without a signer would share the chain signer
+        assert!(parse(json!({ "count": 2 })).is_err());
+        assert!(parse(json!({ "count": 3, "signers": [signer("11"), signer("22")] })).is_err());
+        assert!(parse(json!({ "signers": [signer("11"), signer("11")] })).is_err());
+    }
+
+    #[test]
+    fn test_parses_checkpoint_fetch_timeouts() {
+        let parse = |conf: serde_json::Value| {
+            let raw: RawCheckpointFetchConf = serde_json::from_value(conf).unwrap();
+            CheckpointFetchConf::from_config(raw, &ConfigPath::default())
+        };
+        let validator = H160::repeat_byte(1);
+
+        assert_eq!(parse(json!({})).unwrap(), CheckpointFetchConf::default());
+        let conf = parse(json!({
+            "timeoutMs": 2000,
+            "hedgeDelayMs": "250",
+            "validatorTimeoutsMs": { format!("{validator:?}"): 10000 },
+        }))
+        .unwrap();
+        assert_eq!(conf.timeout(&validator), Duration::from_secs(10));
+        assert_eq!(conf.timeout(&H160::zero()), Duration::from_secs(2));
+        assert_eq!(conf.hedge_delay, Duration::from_millis(250));
+
+        assert!(parse(json!({ "timeoutMs": 0 })).is_err());
+        assert!(parse(json!({ "validatorTimeoutsMs": { "validator": 1000 } })).is_err());
+    }
+
+    #[test]
+    fn test_retry_delays_are_bounded() {
+        let parse = |conf: serde_json::Value| {
+            let raw: RawRetryPolicyConf = serde_json::from_value(conf).unwrap();
+            RetryPolicyConf::from_config(raw, &ConfigPath::default())
+        };
+
+        let year = MAX_RETRY_DELAY.as_secs();
+        let conf = parse(json!({ "baseDelay": 60, "maxDelay": year })).unwrap();
+        assert_eq!(conf.policy.max_delay, MAX_RETRY_DELAY);
+        assert!(parse(json!({ "maxDelay": year + 1 })).is_err());
+        assert!(parse(json!({ "baseDelay": u64::MAX, "maxDelay": u64::MAX })).is_err());
+    }
+
+    #[test]
+    fn test_parses_module_types_by_name_or_number() {
+        assert_eq!(
+            parse_module_type("ccipRead").unwrap(),
+            ModuleType::CcipRead as u8
+        );
+        assert_eq!(parse_module_type("12").unwrap(), 12);
+        assert!(parse_module_type("bespoke").is_err());
+        assert!(parse_module_type("256").is_err());
+    }
+}
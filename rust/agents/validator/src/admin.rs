@@ -0,0 +1,146 @@
+//! HTTP API for operators to inspect and acknowledge the reorgs detected on
+//! the origin chains. It is served alongside `/metrics` by the metrics server,
+//! and only answers requests carrying the configured token in an
+//! `Authorization: Bearer <token>` header.
+//!
+//! - `GET /reorgs`: list the reorgs detected on every origin chain.
+//! - `POST /reorgs/<origin domain id>/acknowledge`: acknowledge the reorgs
+//!   detected on an origin chain, replacing the stored messages with the
+//!   freshly indexed ones. The validator resumes signing checkpoints of the
+//!   origin once it notices, and responds with the acknowledged reorgs.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tracing::info;
+use warp::{
+    filters::BoxedFilter,
+    http::StatusCode,
+    reply::{json, with_status},
+    Filter, Reply,
+};
+
+use hyperlane_base::db::{HyperlaneRocksDB, ReorgEvent};
+
+#[derive(Debug, Serialize)]
+struct ReorgStatus {
+    origin: String,
+    #[serde(flatten)]
+    event: ReorgEvent,
+}
+
+/// Build the routes of the admin API.
+pub fn routes(origin_dbs: Arc<Vec<HyperlaneRocksDB>>) -> BoxedFilter<(Box<dyn Reply>,)> {
+    let with_origin_dbs = warp::any().map(move || origin_dbs.clone());
+
+    let list = warp::get()
+        .and(warp::path!("reorgs"))
+        .and(with_origin_dbs.clone())
+        .and_then(list_reorgs);
+    let acknowledge = warp::post()
+        .and(warp::path!("reorgs" / u32 / "acknowledge"))
+        .and(with_origin_dbs)
+        .and_then(acknowledge_reorgs);
+
+    list.or(acknowledge).unify().boxed()
+}
+
+async fn list_reorgs(origin_dbs: Arc<Vec<HyperlaneRocksDB>>) -> Result<Box<dyn Reply>, Infallible> {
+    let statuses: Vec<_> = origin_dbs
+        .iter()
+        .flat_map(|db| {
+            db.retrieve_reorg_events()
+                .into_iter()
+                .map(|event| ReorgStatus {
+                    origin: db.domain().name().to_owned(),
+                    event,
+                })
+        })
+        .collect();
+    Ok(Box::new(json(&statuses)))
+}
+
+/// Acknowledge the reorgs detected on an origin chain so the validator
+/// resumes signing its checkpoints.
+async fn acknowledge_reorgs(
+    origin: u32,
+    origin_dbs: Arc<Vec<HyperlaneRocksDB>>,
+) -> Result<Box<dyn Reply>, Infallible> {
+    let Some(db) = origin_dbs.iter().find(|db| db.domain().id() == origin) else {
+        return Ok(Box::new(with_status(
+            "Unknown origin domain",
+            StatusCode::NOT_FOUND,
+        )));
+    };
+    match db.acknowledge_reorgs() {
+        Ok(acknowledged) => {
+            info!(
+                ?acknowledged,
+                origin = %db.domain(),
+                "Reorgs acknowledged at the request of an operator"
+            );
+            Ok(Box::new(json(&acknowledged)))
+        }
+        Err(e) => Ok(Box::new(with_status(
+            e.to_string(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyperlane_base::{db::test_utils, with_bearer_token};
+    use hyperlane_core::{HyperlaneDomain, HyperlaneMessage};
+
+    use super::*;
+
+    const TOKEN: &str = "secret";
+
+    #[tokio::test]
+    async fn test_acknowledge_reorgs() {
+        test_utils::run_test_db(|db| async move {
+            let origin = HyperlaneDomain::new_test_domain("test1");
+            let db = HyperlaneRocksDB::new(&origin, db).with_reorg_detection();
+            let message = |body: u8| HyperlaneMessage {
+                nonce: 0,
+                body: vec![body],
+                ..Default::default()
+            };
+            db.store_message(&message(1), 10).unwrap();
+            db.store_message(&message(2), 11).unwrap();
+            let routes = with_bearer_token(TOKEN, routes(Arc::new(vec![db.clone()])));
+            let path = format!("/reorgs/{}/acknowledge", origin.id());
+
+            let unauthorized = warp::test::request()
+                .method("POST")
+                .path(&path)
+                .reply(&routes)
+                .await;
+            assert_eq!(unauthorized.status(), StatusCode::UNAUTHORIZED);
+            assert!(db.has_unacknowledged_reorgs());
+
+            let unknown = warp::test::request()
+                .method("POST")
+                .path("/reorgs/0/acknowledge")
+                .header("authorization", format!("Bearer {TOKEN}"))
+                .reply(&routes)
+                .await;
+            assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+
+            let acknowledged = warp::test::request()
+                .method("POST")
+                .path(&path)
+                .header("authorization", format!("Bearer {TOKEN}"))
+                .reply(&routes)
+                .await;
+            assert_eq!(acknowledged.status(), StatusCode::OK);
+            let events: Vec<serde_json::Value> =
+                serde_json::from_slice(acknowledged.body()).unwrap();
+            assert_eq!(events.len(), 1);
+            assert!(!db.has_unacknowledged_reorgs());
+        })
+        .await;
+    }
+}
@@ -1,6 +1,6 @@
 //! Configuration
 
-use std::{path::PathBuf, time::Duration};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
 use eyre::{eyre, Context};
 use hyperlane_base::{
@@ -11,39 +11,73 @@ use hyperlane_base::{
     },
 };
 use hyperlane_core::{cfg_unwrap_all, config::*, HyperlaneDomain, HyperlaneDomainProtocol};
+use serde::Deserialize;
 
 decl_settings!(Validator,
     Parsed {
         /// Database path
         db: PathBuf,
-        /// Chain to validate messages on
-        origin_chain: HyperlaneDomain,
+        /// Chains to validate messages on
+        origins: Vec<ValidatorOriginConf>,
         /// The validator attestation signer
         validator: SignerConf,
-        /// The checkpoint syncer configuration
-        checkpoint_syncer: CheckpointSyncerConf,
-        /// The reorg_period in blocks
-        reorg_period: u64,
-        /// How frequently to check for new checkpoints
-        interval: Duration,
+        /// If set, serves an API for inspecting and acknowledging reorgs on
+        /// the metrics port to requests bearing this token.
+        admin_api_token: Option<String>,
     },
     Raw {
         /// Database path (path on the fs)
         db: Option<String>,
         // Name of the chain to validate message on
         originchainname: Option<String>,
+        /// Chains to validate messages on by name, each with its own checkpoint
+        /// syncer. Replaces `originchainname` and `checkpointsyncer`.
+        originchains: Option<HashMap<String, RawValidatorOriginConf>>,
         /// The validator attestation signer
         #[serde(default)]
         validator: RawSignerConf,
         /// The checkpoint syncer configuration
         checkpointsyncer: Option<RawCheckpointSyncerConf>,
-        /// The reorg_period in blocks
+        /// The reorg_period in blocks, the default for every origin chain
         reorgperiod: Option<StrOrInt>,
-        /// How frequently to check for new checkpoints
+        /// How frequently to check for new checkpoints, the default for every
+        /// origin chain
         interval: Option<StrOrInt>,
+        /// If true, serves an API for inspecting and acknowledging reorgs on the metrics
+        /// port. Defaults to false.
+        #[serde(default)]
+        adminapi: bool,
+        /// The bearer token requests to the admin API must carry in their `Authorization`
+        /// header. Required if `adminapi` is true.
+        adminapitoken: Option<String>,
     },
 );
 
+/// A chain the validator signs checkpoints of.
+#[derive(Debug)]
+pub struct ValidatorOriginConf {
+    /// Chain to validate messages on
+    pub origin_chain: HyperlaneDomain,
+    /// The checkpoint syncer configuration
+    pub checkpoint_syncer: CheckpointSyncerConf,
+    /// The reorg_period in blocks
+    pub reorg_period: u64,
+    /// How frequently to check for new checkpoints
+    pub interval: Duration,
+}
+
+/// Raw configuration of a chain the validator signs checkpoints of.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawValidatorOriginConf {
+    /// The checkpoint syncer configuration
+    checkpointsyncer: Option<RawCheckpointSyncerConf>,
+    /// The reorg_period in blocks, overrides the default
+    reorgperiod: Option<StrOrInt>,
+    /// How frequently to check for new checkpoints, overrides the default
+    interval: Option<StrOrInt>,
+}
+
 impl FromRawConf<RawValidatorSettings> for ValidatorSettings {
     fn from_config_filtered(
         raw: RawValidatorSettings,
@@ -57,19 +91,8 @@ impl FromRawConf<RawValidatorSettings> for ValidatorSettings {
             .parse_config::<SignerConf>(&cwp.join("validator"))
             .take_config_err(&mut err);
 
-        let checkpoint_syncer = raw
-            .checkpointsyncer
-            .ok_or_else(|| eyre!("Missing `checkpointsyncer`"))
-            .take_err(&mut err, || cwp + "checkpointsyncer")
-            .and_then(|r| {
-                r.parse_config(&cwp.join("checkpointsyncer"))
-                    .take_config_err(&mut err)
-            });
-
-        let reorg_period = raw
+        let reorg_period: Option<u64> = raw
             .reorgperiod
-            .ok_or_else(|| eyre!("Missing `reorgperiod`"))
-            .take_err(&mut err, || cwp + "reorgperiod")
             .and_then(|r| r.try_into().take_err(&mut err, || cwp + "reorgperiod"));
 
         let interval = raw
@@ -81,12 +104,53 @@ impl FromRawConf<RawValidatorSettings> for ValidatorSettings {
             })
             .unwrap_or(Duration::from_secs(5));
 
-        let Some(origin_chain_name) = raw
-            .originchainname
-            .ok_or_else(|| eyre!("Missing `originchainname`"))
-            .take_err(&mut err, || cwp + "originchainname")
-            .map(|s| s.to_ascii_lowercase())
-        else { return Err(err) };
+        // the raw configuration of each origin chain and the path it is at
+        let mut raw_origins: Vec<(String, ConfigPath, RawValidatorOriginConf)> =
+            if let Some(origin_chains) = raw.originchains {
+                if raw.originchainname.is_some() {
+                    err.push(
+                        cwp + "originchainname",
+                        eyre!("Cannot use `originchains` and `originchainname` at the same time"),
+                    );
+                }
+                if raw.checkpointsyncer.is_some() {
+                    err.push(
+                        cwp + "checkpointsyncer",
+                        eyre!("Cannot use `originchains` and `checkpointsyncer` at the same time"),
+                    );
+                }
+                let origins_path = cwp + "originchains";
+                origin_chains
+                    .into_iter()
+                    .map(|(name, origin)| {
+                        let origin_cwp = &origins_path + &name;
+                        (name.to_ascii_lowercase(), origin_cwp, origin)
+                    })
+                    .collect()
+            } else {
+                let Some(origin_chain_name) = raw
+                    .originchainname
+                    .ok_or_else(|| eyre!("Missing `originchainname`"))
+                    .take_err(&mut err, || cwp + "originchainname")
+                    .map(|s| s.to_ascii_lowercase())
+                else { return Err(err) };
+                let origin = RawValidatorOriginConf {
+                    checkpointsyncer: raw.checkpointsyncer,
+                    ..Default::default()
+                };
+                vec![(origin_chain_name, cwp.clone(), origin)]
+            };
+        raw_origins.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+        if raw_origins.is_empty() {
+            err.push(
+                cwp + "originchains",
+                eyre!("The validator must be configured with at least one origin chain"),
+            );
+        }
+        let origin_chain_names: Vec<_> = raw_origins
+            .iter()
+            .map(|(name, _, _)| name.as_str())
+            .collect();
 
         let db = raw
             .db
@@ -94,42 +158,257 @@ impl FromRawConf<RawValidatorSettings> for ValidatorSettings {
             .unwrap_or_else(|| {
                 std::env::current_dir()
                     .unwrap()
-                    .join(format!("validator_db_{origin_chain_name}"))
+                    .join(format!("validator_db_{}", origin_chain_names.join("_")))
             });
 
         let base = raw
             .base
             .parse_config_with_filter::<Settings>(
                 cwp,
-                Some(&[origin_chain_name.as_ref()].into_iter().collect()),
+                Some(&origin_chain_names.iter().copied().collect()),
             )
             .take_config_err(&mut err);
 
-        let origin_chain = base.as_ref().and_then(|base| {
-            base.lookup_domain(&origin_chain_name)
-                .context("Missing configuration for the origin chain")
-                .take_err(&mut err, || cwp + "chains" + &origin_chain_name)
-        });
+        let origins = raw_origins
+            .into_iter()
+            .filter_map(|(name, origin_cwp, origin)| {
+                let origin_chain = base.as_ref().and_then(|base| {
+                    base.lookup_domain(&name)
+                        .context("Missing configuration for the origin chain")
+                        .take_err(&mut err, || cwp + "chains" + &name)
+                });
 
-        cfg_unwrap_all!(cwp, err: [base, origin_chain, validator, checkpoint_syncer, reorg_period]);
+                let checkpoint_syncer = origin
+                    .checkpointsyncer
+                    .ok_or_else(|| eyre!("Missing `checkpointsyncer`"))
+                    .take_err(&mut err, || &origin_cwp + "checkpointsyncer")
+                    .and_then(|r| {
+                        r.parse_config(&origin_cwp.join("checkpointsyncer"))
+                            .take_config_err(&mut err)
+                    });
+
+                let reorg_period = origin
+                    .reorgperiod
+                    .and_then(|r| {
+                        r.try_into()
+                            .take_err(&mut err, || &origin_cwp + "reorgperiod")
+                    })
+                    .or(reorg_period)
+                    .ok_or_else(|| eyre!("Missing `reorgperiod`"))
+                    .take_err(&mut err, || &origin_cwp + "reorgperiod");
+
+                let interval = origin
+                    .interval
+                    .and_then(|r| {
+                        r.try_into()
+                            .map(Duration::from_secs)
+                            .take_err(&mut err, || &origin_cwp + "interval")
+                    })
+                    .unwrap_or(interval);
+
+                Some(ValidatorOriginConf {
+                    origin_chain: origin_chain?,
+                    checkpoint_syncer: checkpoint_syncer?,
+                    reorg_period: reorg_period?,
+                    interval,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let admin_api_token = raw.adminapitoken.filter(|token| !token.is_empty());
+        if raw.adminapi && admin_api_token.is_none() {
+            err.push(
+                cwp + "adminapitoken",
+                eyre!("A bearer token is required to serve the admin API"),
+            );
+        }
+
+        cfg_unwrap_all!(cwp, err: [base, validator]);
         let mut base = base;
 
-        if origin_chain.domain_protocol() == HyperlaneDomainProtocol::Ethereum {
-            // if an EVM chain we can assume the chain signer is the validator signer when not
-            // specified
-            if let Some(chain) = base.chains.get_mut(origin_chain.name()) {
-                chain.signer.get_or_insert_with(|| validator.clone());
+        for origin in &origins {
+            if origin.origin_chain.domain_protocol() == HyperlaneDomainProtocol::Ethereum {
+                // if an EVM chain we can assume the chain signer is the validator signer when not
+                // specified
+                if let Some(chain) = base.chains.get_mut(origin.origin_chain.name()) {
+                    chain.signer.get_or_insert_with(|| validator.clone());
+                }
             }
         }
 
         err.into_result(Self {
             base,
             db,
-            origin_chain,
+            origins,
             validator,
-            checkpoint_syncer,
-            reorg_period,
-            interval,
+            admin_api_token: admin_api_token.filter(|_| raw.adminapi),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyperlane_core::KnownHyperlaneDomain;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn chain(name: &str, domain: u32) -> Value {
+        let address = format!("{:?}", hyperlane_core::H256::repeat_byte(1));
+        json!({
+            "name": name,
+            "domain": domain,
+            "protocol": "ethereum",
+            "connection": { "type": "http", "url": "http://localhost:8545" },
+            "addresses": {
+                "mailbox": address,
+                "interchainGasPaymaster": address,
+                "validatorAnnounce": address,
+            },
         })
     }
+
+    fn checkpoint_syncer(path: &str) -> Value {
+        json!({ "type": "localStorage", "path": path })
+    }
+
+    fn parse(settings: Value) -> ConfigResult<ValidatorSettings> {
+        let mut raw = json!({
+            "chains": {
+                "ethereum": chain("ethereum", 1),
+                "polygon": chain("polygon", 137),
+            },
+            "validator": { "type": "hexKey", "key": format!("0x{}", "11".repeat(32)) },
+            "db": "/tmp/validator_db",
+            "reorgperiod": 10,
+        });
+        raw.as_object_mut()
+            .unwrap()
+            .extend(settings.as_object().unwrap().clone());
+        let raw: RawValidatorSettings = serde_json::from_value(raw).unwrap();
+        ValidatorSettings::from_config(raw, &ConfigPath::default())
+    }
+
+    #[test]
+    fn test_parses_several_origin_chains() {
+        let settings = parse(json!({
+            "interval": 3,
+            "originchains": {
+                "ethereum": { "checkpointsyncer": checkpoint_syncer("/tmp/ethereum") },
+                "polygon": {
+                    "checkpointsyncer": checkpoint_syncer("/tmp/polygon"),
+                    "reorgperiod": 256,
+                    "interval": 1,
+                },
+            },
+        }))
+        .unwrap();
+
+        let origins: Vec<_> = settings
+            .origins
+            .iter()
+            .map(|origin| {
+                let CheckpointSyncerConf::LocalStorage { path } = &origin.checkpoint_syncer else {
+                    panic!("Unexpected checkpoint syncer {:?}", origin.checkpoint_syncer);
+                };
+                (
+                    origin.origin_chain.clone(),
+                    path.to_str().unwrap().to_owned(),
+                    origin.reorg_period,
+                    origin.interval,
+                )
+            })
+            .collect();
+        assert_eq!(
+            origins,
+            [
+                (
+                    HyperlaneDomain::Known(KnownHyperlaneDomain::Ethereum),
+                    "/tmp/ethereum".to_owned(),
+                    10,
+                    Duration::from_secs(3)
+                ),
+                (
+                    HyperlaneDomain::Known(KnownHyperlaneDomain::Polygon),
+                    "/tmp/polygon".to_owned(),
+                    256,
+                    Duration::from_secs(1)
+                ),
+            ]
+        );
+        // the validator signer is used to announce on EVM origins
+        for origin in &settings.origins {
+            assert!(settings
+                .chain_setup(&origin.origin_chain)
+                .unwrap()
+                .signer
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn test_parses_single_origin_chain() {
+        let settings = parse(json!({
+            "originchainname": "Ethereum",
+            "checkpointsyncer": checkpoint_syncer("/tmp/ethereum"),
+        }))
+        .unwrap();
+        assert_eq!(settings.origins.len(), 1);
+        assert_eq!(
+            settings.origins[0].origin_chain,
+            HyperlaneDomain::Known(KnownHyperlaneDomain::Ethereum)
+        );
+        assert_eq!(settings.origins[0].interval, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_rejects_invalid_origin_chains() {
+        // origins are either listed or named with a single checkpoint syncer
+        assert!(parse(json!({
+            "originchainname": "ethereum",
+            "originchains": {
+                "polygon": { "checkpointsyncer": checkpoint_syncer("/tmp/polygon") },
+            },
+        }))
+        .is_err());
+        // each origin needs its own checkpoint syncer
+        assert!(parse(json!({
+            "originchains": {
+                "ethereum": { "checkpointsyncer": checkpoint_syncer("/tmp/ethereum") },
+                "polygon": {},
+            },
+        }))
+        .is_err());
+        assert!(parse(json!({ "originchains": {} })).is_err());
+        // origins need a chain configuration
+        assert!(parse(json!({
+            "originchains": {
+                "arbitrum": { "checkpointsyncer": checkpoint_syncer("/tmp/arbitrum") },
+            },
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn test_admin_api_requires_a_token() {
+        let origin = json!({
+            "originchainname": "ethereum",
+            "checkpointsyncer": checkpoint_syncer("/tmp/ethereum"),
+        });
+        let with = |extra: Value| {
+            let mut settings = origin.clone();
+            settings
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            parse(settings)
+        };
+
+        assert!(with(json!({ "adminapi": true })).is_err());
+        assert!(with(json!({ "adminapi": true, "adminapitoken": "" })).is_err());
+        let settings = with(json!({ "adminapi": true, "adminapitoken": "secret" })).unwrap();
+        assert_eq!(settings.admin_api_token.as_deref(), Some("secret"));
+        let settings = with(json!({ "adminapitoken": "secret" })).unwrap();
+        assert_eq!(settings.admin_api_token, None);
+    }
 }
@@ -3,11 +3,11 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::vec;
 
-use eyre::Result;
-use prometheus::IntGauge;
+use eyre::{bail, Result};
+use prometheus::{IntCounter, IntGauge};
 use tokio::time::sleep;
 use tracing::instrument;
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 
 use hyperlane_base::{db::HyperlaneRocksDB, CheckpointSyncer, CoreMetrics};
 use hyperlane_core::{
@@ -57,6 +57,37 @@ impl ValidatorSubmitter {
         }
     }
 
+    /// Record a checkpoint about to be signed in the signing ledger. Fails if
+    /// a different checkpoint was already signed for its index, since signing
+    /// both would be an equivocation.
+    fn record_in_signing_ledger(&self, checkpoint: &Checkpoint) -> Result<()> {
+        let Some(signed) = self.message_db.record_signed_checkpoint(checkpoint)? else {
+            return Ok(());
+        };
+        self.metrics.refused_conflicting_signatures.inc();
+        error!(
+            ?checkpoint,
+            signed_checkpoint = ?signed.checkpoint,
+            signed_at = signed.timestamp,
+            "Refusing to sign a checkpoint conflicting with one already signed for the same index. \
+            The checkpoint storage or message DB may have been wiped or replaced, or the origin \
+            chain may have reorged deeper than the reorg period."
+        );
+        bail!(
+            "Refusing to sign checkpoint for index {} conflicting with the one already signed",
+            checkpoint.index
+        )
+    }
+
+    /// Fail if the origin reorged without an operator acknowledging it, so
+    /// that no checkpoint is signed for messages the origin may have dropped.
+    fn halt_on_reorg(&self) -> Result<()> {
+        if self.message_db.has_unacknowledged_reorgs() {
+            bail!("The origin reorged, not signing checkpoints until the reorg is acknowledged");
+        }
+        Ok(())
+    }
+
     #[instrument(err, skip(self, tree), fields(domain=%self.mailbox.domain()))]
     pub(crate) async fn checkpoint_submitter(
         self,
@@ -99,6 +130,8 @@ impl ValidatorSubmitter {
                 if checkpoint == correctness_checkpoint {
                     debug!(index = checkpoint.index, "Reached tree consistency");
 
+                    self.halt_on_reorg()?;
+
                     // drain and sign all checkpoints in the queue
                     for queued_checkpoint in checkpoint_queue.drain(..) {
                         let existing = self
@@ -113,6 +146,7 @@ impl ValidatorSubmitter {
                             continue;
                         }
 
+                        self.record_in_signing_ledger(&queued_checkpoint)?;
                         let signed_checkpoint = self.signer.sign(queued_checkpoint).await?;
                         self.checkpoint_syncer
                             .write_checkpoint(&signed_checkpoint)
@@ -207,6 +241,8 @@ impl ValidatorSubmitter {
                 .map(|i| i < latest_checkpoint.index)
                 .unwrap_or(true)
             {
+                self.halt_on_reorg()?;
+                self.record_in_signing_ledger(&latest_checkpoint)?;
                 let signed_checkpoint = self.signer.sign(latest_checkpoint).await?;
 
                 info!(signed_checkpoint = ?signed_checkpoint, signer=?self.signer, "Signed new latest checkpoint");
@@ -231,6 +267,7 @@ pub(crate) struct ValidatorSubmitterMetrics {
     latest_checkpoint_processed: IntGauge,
     legacy_latest_checkpoint_observed: IntGauge,
     legacy_latest_checkpoint_processed: IntGauge,
+    refused_conflicting_signatures: IntCounter,
 }
 
 impl ValidatorSubmitterMetrics {
@@ -249,6 +286,9 @@ impl ValidatorSubmitterMetrics {
             latest_checkpoint_processed: metrics
                 .latest_checkpoint()
                 .with_label_values(&["validator_processed", chain_name]),
+            refused_conflicting_signatures: metrics
+                .refused_conflicting_signatures()
+                .with_label_values(&[chain_name]),
         }
     }
 }
@@ -3,14 +3,17 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
-use eyre::Result;
+use eyre::{Context, Result};
 use tokio::{task::JoinHandle, time::sleep};
 use tracing::{error, info, info_span, instrument::Instrumented, warn, Instrument};
+use warp::{filters::BoxedFilter, Reply};
 
 use hyperlane_base::{
     db::{HyperlaneRocksDB, DB},
-    run_all, BaseAgent, CheckpointSyncer, ContractSyncMetrics, CoreMetrics, HyperlaneAgentCore,
-    MessageContractSync,
+    run_all,
+    settings::IndexSettings,
+    with_bearer_token, BaseAgent, CheckpointSyncer, ContractSyncMetrics, CoreMetrics,
+    HyperlaneAgentCore, MessageContractSync,
 };
 use hyperlane_core::{
     accumulator::incremental::IncrementalMerkle, Announcement, ChainResult, HyperlaneChain,
@@ -20,24 +23,56 @@ use hyperlane_core::{
 use hyperlane_ethereum::{SingletonSigner, SingletonSignerHandle};
 
 use crate::{
-    settings::ValidatorSettings, submit::ValidatorSubmitter, submit::ValidatorSubmitterMetrics,
+    admin,
+    settings::{ValidatorOriginConf, ValidatorSettings},
+    submit::ValidatorSubmitter,
+    submit::ValidatorSubmitterMetrics,
 };
 
 /// A validator agent
 #[derive(Debug)]
 pub struct Validator {
-    origin_chain: HyperlaneDomain,
     core: HyperlaneAgentCore,
+    /// The chains checkpoints are signed of, which share the signer
+    origins: Vec<OriginValidatorBuilder>,
+    /// The bearer token of the admin API, which is only served if set
+    admin_api_token: Option<String>,
+    // temporary holder until `run` is called
+    signer_instance: Option<Box<SingletonSigner>>,
+}
+
+/// Builds the validator of one origin chain inside its own task, so an origin
+/// whose RPC is unavailable when the validator starts does not stop the
+/// others.
+#[derive(Debug, Clone)]
+struct OriginValidatorBuilder {
+    settings: Arc<ValidatorSettings>,
+    /// Index of the origin in the settings
+    index: usize,
+    db: HyperlaneRocksDB,
+    signer: SingletonSignerHandle,
+    metrics: Arc<CoreMetrics>,
+    contract_sync_metrics: Arc<ContractSyncMetrics>,
+}
+
+/// Signs the checkpoints of one origin chain. The tasks of each origin run
+/// independently, so an origin whose RPC is unavailable does not hold up the
+/// others.
+#[derive(Debug, Clone)]
+struct OriginValidator {
+    origin_chain: HyperlaneDomain,
     db: HyperlaneRocksDB,
     message_sync: Arc<MessageContractSync>,
     mailbox: Arc<dyn Mailbox>,
     validator_announce: Arc<dyn ValidatorAnnounce>,
     signer: SingletonSignerHandle,
-    // temporary holder until `run` is called
-    signer_instance: Option<Box<SingletonSigner>>,
+    /// Whether a signer is configured for the origin chain to announce with
+    has_chain_signer: bool,
+    index_settings: IndexSettings,
     reorg_period: u64,
     interval: Duration,
     checkpoint_syncer: Arc<dyn CheckpointSyncer>,
+    metrics: Arc<CoreMetrics>,
 }
 
 impl AsRef<HyperlaneAgentCore> for Validator {
@@ -57,46 +92,34 @@ impl BaseAgent for Validator {
         Self: Sized,
     {
         let db = DB::from_path(&settings.db)?;
-        let msg_db = HyperlaneRocksDB::new(&settings.origin_chain, db);
 
         // Intentionally using hyperlane_ethereum for the validator's signer
         let (signer_instance, signer) = SingletonSigner::new(settings.validator.build().await?);
 
         let core = settings.build_hyperlane_core(metrics.clone());
-        let checkpoint_syncer = settings.checkpoint_syncer.build(None)?.into();
-
-        let mailbox = settings
-            .build_mailbox(&settings.origin_chain, &metrics)
-            .await?;
-
-        let validator_announce = settings
-            .build_validator_announce(&settings.origin_chain, &metrics)
-            .await?;
-
         let contract_sync_metrics = Arc::new(ContractSyncMetrics::new(&metrics));
-
-        let message_sync = settings
-            .build_message_indexer(
-                &settings.origin_chain,
-                &metrics,
-                &contract_sync_metrics,
-                Arc::new(msg_db.clone()),
-            )
-            .await?
-            .into();
+        let admin_api_token = settings.admin_api_token.clone();
+        let settings = Arc::new(settings);
+
+        let origins = (0..settings.origins.len())
+            .map(|index| {
+                let origin_chain = &settings.origins[index].origin_chain;
+                OriginValidatorBuilder {
+                    settings: settings.clone(),
+                    index,
+                    db: HyperlaneRocksDB::new(origin_chain, db.clone()).with_reorg_detection(),
+                    signer: signer.clone(),
+                    metrics: metrics.clone(),
+                    contract_sync_metrics: contract_sync_metrics.clone(),
+                }
+            })
+            .collect();
 
         Ok(Self {
-            origin_chain: settings.origin_chain,
             core,
-            db: msg_db,
-            mailbox: mailbox.into(),
-            message_sync,
-            validator_announce: validator_announce.into(),
-            signer,
+            origins,
+            admin_api_token,
             signer_instance: Some(Box::new(signer_instance)),
-            reorg_period: settings.reorg_period,
-            interval: settings.interval,
-            checkpoint_syncer,
         })
     }
 
@@ -114,38 +137,150 @@ impl BaseAgent for Validator {
             );
         }
 
-        // announce the validator after spawning the signer task
-        self.announce().await.expect("Failed to announce validator");
+        // each origin announces the validator after the signer task is spawned
+        for origin in &self.origins {
+            let origin = origin.clone();
+            let span = info_span!("OriginValidator", origin_chain=%origin.conf().origin_chain);
+            tasks.push(tokio::spawn(origin.run()).instrument(span));
+        }
+
+        run_all(tasks)
+    }
+
+    fn http_routes(&self) -> Option<BoxedFilter<(Box<dyn Reply>,)>> {
+        let token = self.admin_api_token.as_deref()?;
+        Some(with_bearer_token(
+            token,
+            admin::routes(Arc::new(
+                self.origins
+                    .iter()
+                    .map(|origin| origin.db.clone())
+                    .collect(),
+            )),
+        ))
+    }
+}
+
+impl OriginValidatorBuilder {
+    fn conf(&self) -> &ValidatorOriginConf {
+        &self.settings.origins[self.index]
+    }
+
+    /// Build the validator of the origin, trying again until its contracts
+    /// can be reached, then validate the origin.
+    async fn run(self) -> Result<()> {
+        let validator = loop {
+            match self.build().await {
+                Ok(validator) => break validator,
+                Err(err) => error!(?err, "Failed to build origin validator, retrying"),
+            }
+            sleep(self.conf().interval).await;
+        };
+        validator.supervise().await
+    }
+
+    async fn build(&self) -> Result<OriginValidator> {
+        let settings = &self.settings;
+        let origin = self.conf();
+        let origin_chain = &origin.origin_chain;
+        let checkpoint_syncer = origin.checkpoint_syncer.build(None)?.into();
+
+        let mailbox = settings.build_mailbox(origin_chain, &self.metrics).await?;
+
+        let validator_announce = settings
+            .build_validator_announce(origin_chain, &self.metrics)
+            .await?;
+
+        let message_sync = settings
+            .build_message_indexer(
+                origin_chain,
+                &self.metrics,
+                &self.contract_sync_metrics,
+                Arc::new(self.db.clone()),
+            )
+            .await?
+            .into();
+
+        let chain_setup = settings.chain_setup(origin_chain)?;
+        Ok(OriginValidator {
+            origin_chain: origin_chain.clone(),
+            db: self.db.clone(),
+            mailbox: mailbox.into(),
+            message_sync,
+            validator_announce: validator_announce.into(),
+            signer: self.signer.clone(),
+            has_chain_signer: chain_setup.signer.is_some(),
+            index_settings: chain_setup.index.clone(),
+            reorg_period: origin.reorg_period,
+            interval: origin.interval,
+            checkpoint_syncer,
+            metrics: self.metrics.clone(),
+        })
+    }
+}
+
+impl OriginValidator {
+    /// Validate the origin chain, starting over whenever one of its tasks
+    /// fails rather than stopping the validators of the other origins.
+    async fn supervise(self) -> Result<()> {
+        loop {
+            match self.validate().await {
+                Ok(()) => warn!("Origin validator tasks stopped, restarting"),
+                Err(err) => error!(?err, "Origin validator failed, restarting"),
+            }
+            sleep(self.interval).await;
+        }
+    }
+
+    /// Announce the validator, then index messages and sign checkpoints until
+    /// one of the tasks doing so stops.
+    async fn validate(&self) -> Result<()> {
+        self.wait_for_reorg_acknowledgement().await;
+        self.announce().await?;
 
         let reorg_period = NonZeroU64::new(self.reorg_period);
 
         // Ensure that the mailbox has count > 0 before we begin indexing
         // messages or submitting checkpoints.
-        while self
-            .mailbox
-            .count(reorg_period)
-            .await
-            .expect("Failed to get count of mailbox")
-            == 0
-        {
+        while self.mailbox.count(reorg_period).await? == 0 {
             info!("Waiting for first message to mailbox");
             sleep(self.interval).await;
         }
 
-        tasks.push(self.run_message_sync().await);
-        for checkpoint_sync_task in self.run_checkpoint_submitters().await {
-            tasks.push(checkpoint_sync_task);
-        }
+        let mut tasks = vec![self.run_message_sync().await];
+        tasks.extend(self.run_checkpoint_submitters().await?);
 
-        run_all(tasks)
+        run_all(tasks).await?
+    }
+
+    /// Hold off signing checkpoints of the origin until an operator has
+    /// acknowledged every reorg detected on it.
+    async fn wait_for_reorg_acknowledgement(&self) {
+        let gauge = self
+            .metrics
+            .unacknowledged_reorgs()
+            .with_label_values(&[self.origin_chain.name()]);
+        loop {
+            let unacknowledged: Vec<_> = self
+                .db
+                .retrieve_reorg_events()
+                .into_iter()
+                .filter(|event| !event.acknowledged)
+                .collect();
+            gauge.set(unacknowledged.len() as i64);
+            if unacknowledged.is_empty() {
+                return;
+            }
+            error!(
+                reorgs=?unacknowledged,
+                "The origin reorged, not signing checkpoints until an operator acknowledges the reorg"
+            );
+            sleep(self.interval).await;
+        }
     }
-}
 
-impl Validator {
     async fn run_message_sync(&self) -> Instrumented<JoinHandle<Result<()>>> {
-        let index_settings = self.as_ref().settings.chains[self.origin_chain.name()]
-            .index
-            .clone();
+        let index_settings = self.index_settings.clone();
         let contract_sync = self.message_sync.clone();
         let cursor = contract_sync
             .forward_backward_message_sync_cursor(index_settings)
@@ -159,7 +294,7 @@ impl Validator {
         .instrument(info_span!("MailboxMessageSyncer"))
     }
 
-    async fn run_checkpoint_submitters(&self) -> Vec<Instrumented<JoinHandle<Result<()>>>> {
+    async fn run_checkpoint_submitters(&self) -> Result<Vec<Instrumented<JoinHandle<Result<()>>>>> {
         let submitter = ValidatorSubmitter::new(
             self.interval,
             self.reorg_period,
@@ -167,7 +302,7 @@ impl Validator {
             self.signer.clone(),
             self.checkpoint_syncer.clone(),
             self.db.clone(),
-            ValidatorSubmitterMetrics::new(&self.core.metrics, &self.origin_chain),
+            ValidatorSubmitterMetrics::new(&self.metrics, &self.origin_chain),
         );
 
         let empty_tree = IncrementalMerkle::default();
@@ -176,7 +311,7 @@ impl Validator {
             .mailbox
             .tree(reorg_period)
             .await
-            .expect("failed to get mailbox tree");
+            .context("failed to get mailbox tree")?;
         assert!(tip_tree.count() > 0, "mailbox tree is empty");
         let backfill_target = submitter.checkpoint(&tip_tree);
 
@@ -202,7 +337,7 @@ impl Validator {
                 .instrument(info_span!("LegacyCheckpointSubmitter")),
         );
 
-        tasks
+        Ok(tasks)
     }
 
     fn log_on_announce_failure(result: ChainResult<TxOutcome>) {
@@ -227,10 +362,7 @@ impl Validator {
     }
 
     async fn announce(&self) -> Result<()> {
-        if self.core.settings.chains[self.origin_chain.name()]
-            .signer
-            .is_none()
-        {
+        if !self.has_chain_signer {
             warn!(origin_chain=%self.origin_chain, "Cannot announce validator without a signer; make sure a signer is set for the origin chain");
             return Ok(());
         }
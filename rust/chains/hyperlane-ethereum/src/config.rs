@@ -1,4 +1,6 @@
-use hyperlane_core::config::*;
+use std::time::Duration;
+
+use hyperlane_core::{cfg_unwrap_all, config::*, U256};
 use serde::Deserialize;
 use url::Url;
 
@@ -119,3 +121,86 @@ impl FromRawConf<RawConnectionConf> for ConnectionConf {
         }
     }
 }
+
+/// Configuration for replacing transactions which have not been included
+/// after a timeout with ones paying a higher gas price.
+#[derive(Debug, Clone)]
+pub struct TxEscalationConf {
+    /// How long to wait for a transaction to be included before replacing it.
+    pub timeout: Duration,
+    /// Percentage the gas price (or max fee and max priority fee) is increased
+    /// by with each replacement. Most nodes require at least 10.
+    pub bump_percent: u64,
+    /// The highest max fee per gas a replacement may pay.
+    pub max_fee_per_gas: U256,
+    /// Max number of times a transaction will be replaced.
+    pub max_escalations: u32,
+}
+
+/// Raw transaction escalation configuration
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawTxEscalationConf {
+    /// Seconds to wait for inclusion before replacing a transaction
+    timeout: Option<StrOrInt>,
+    bump_percent: Option<StrOrInt>,
+    max_fee_per_gas: Option<StrOrInt>,
+    max_escalations: Option<StrOrInt>,
+}
+
+/// Error type when parsing a transaction escalation configuration.
+#[derive(Debug, thiserror::Error)]
+pub enum TxEscalationConfError {
+    /// The max fee per gas was not specified
+    #[error("Missing `maxFeePerGas` for transaction escalation configuration")]
+    MissingMaxFeePerGas,
+    /// Replacements must increase the gas price
+    #[error("`bumpPercent` must be greater than 0")]
+    ZeroBumpPercent,
+}
+
+impl FromRawConf<RawTxEscalationConf> for TxEscalationConf {
+    fn from_config_filtered(
+        raw: RawTxEscalationConf,
+        cwp: &ConfigPath,
+        _filter: (),
+    ) -> ConfigResult<Self> {
+        use TxEscalationConfError::*;
+
+        let mut err = ConfigParsingError::default();
+
+        let timeout = raw
+            .timeout
+            .and_then(|v| v.try_into().take_err(&mut err, || cwp + "timeout"))
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(180));
+
+        let bump_percent = raw
+            .bump_percent
+            .and_then(|v| v.try_into().take_err(&mut err, || cwp + "bump_percent"))
+            .unwrap_or(12);
+        if bump_percent == 0 {
+            err.push(cwp + "bump_percent", ZeroBumpPercent.into());
+        }
+
+        let max_fee_per_gas = raw
+            .max_fee_per_gas
+            .ok_or(MissingMaxFeePerGas)
+            .take_err(&mut err, || cwp + "max_fee_per_gas")
+            .and_then(|v| v.try_into().take_err(&mut err, || cwp + "max_fee_per_gas"));
+
+        let max_escalations = raw
+            .max_escalations
+            .and_then(|v| v.try_into().take_err(&mut err, || cwp + "max_escalations"))
+            .unwrap_or(5);
+
+        cfg_unwrap_all!(cwp, err: [max_fee_per_gas]);
+
+        err.into_result(Self {
+            timeout,
+            bump_percent,
+            max_fee_per_gas,
+            max_escalations,
+        })
+    }
+}
@@ -100,7 +100,7 @@ where
 {
     #[instrument]
     async fn module_type(&self) -> ChainResult<ModuleType> {
-        let module = self.contract.module_type().call().await?;
+        let module = self.raw_module_type().await?;
         if let Some(module_type) = ModuleType::from_u8(module) {
             Ok(module_type)
         } else {
@@ -109,6 +109,11 @@ where
         }
     }
 
+    #[instrument]
+    async fn raw_module_type(&self) -> ChainResult<u8> {
+        Ok(self.contract.module_type().call().await?)
+    }
+
     #[instrument]
     async fn dry_run_verify(
         &self,
@@ -12,8 +12,8 @@ use ethers::prelude::{abi, Lazy, Middleware};
 pub use self::{
     aggregation_ism::*, ccip_read_ism::*, config::*, config::*, interchain_gas::*,
     interchain_gas::*, interchain_security_module::*, interchain_security_module::*, mailbox::*,
-    mailbox::*, multisig_ism::*, provider::*, routing_ism::*, rpc_clients::*, signers::*,
-    singleton_signer::*, trait_builder::*, validator_announce::*,
+    mailbox::*, multisig_ism::*, optimistic_ism::*, provider::*, remote_signer::*, routing_ism::*,
+    rpc_clients::*, signers::*, singleton_signer::*, trait_builder::*, tx::*, validator_announce::*,
 };
 
 #[cfg(not(doctest))]
@@ -46,6 +46,10 @@ mod multisig_ism;
 #[cfg(not(doctest))]
 mod routing_ism;
 
+/// OptimisticIsm abi
+#[cfg(not(doctest))]
+mod optimistic_ism;
+
 /// CcipReadIsm abi
 #[cfg(not(doctest))]
 mod ccip_read_ism;
@@ -67,6 +71,8 @@ mod rpc_clients;
 
 mod signers;
 
+mod remote_signer;
+
 #[cfg(not(doctest))]
 mod singleton_signer;
 
@@ -3,12 +3,16 @@
 
 use std::collections::HashMap;
 use std::num::NonZeroU64;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use async_trait::async_trait;
-use ethers::abi::AbiEncode;
-use ethers::prelude::Middleware;
+use ethers::abi::{self, AbiEncode, Detokenize, ParamType, Token};
+use ethers::prelude::{Middleware, TransactionReceipt};
+use ethers::providers::{JsonRpcError, MiddlewareError, RpcError};
+use ethers::utils::keccak256;
 use ethers_contract::builders::ContractCall;
+use ethers_contract::ContractError;
+use serde_json::Value;
 use tracing::instrument;
 
 use hyperlane_core::accumulator::incremental::IncrementalMerkle;
@@ -17,18 +21,32 @@ use hyperlane_core::{
     utils::fmt_bytes, BlockRange, ChainCommunicationError, ChainResult, Checkpoint,
     ContractLocator, HyperlaneAbi, HyperlaneChain, HyperlaneContract, HyperlaneDomain,
     HyperlaneMessage, HyperlaneProtocolError, HyperlaneProvider, IndexRange, Indexer, LogMeta,
-    Mailbox, MessageIndexer, RawHyperlaneMessage, TxCostEstimate, TxOutcome, H160, H256, U256,
+    Mailbox, MessageIndexer, ProcessRevert, RawHyperlaneMessage, TxCostEstimate, TxOutcome, H160,
+    H256, U256,
 };
 
 use crate::contracts::arbitrum_node_interface::ArbitrumNodeInterface;
 use crate::contracts::i_mailbox::{IMailbox as EthereumMailboxInternal, ProcessCall, IMAILBOX_ABI};
+use crate::contracts::i_multicall_3::{Call3, IMulticall3};
 use crate::trait_builder::BuildableWithProvider;
-use crate::tx::{fill_tx_gas_params, report_tx};
+use crate::tx::{estimate_gas_limit, fill_tx_gas_params, report_tx, TxEscalator};
 use crate::EthereumProvider;
 
 /// derived from `forge inspect Mailbox storage --pretty`
 const MERKLE_TREE_CONTRACT_SLOT: u32 = 152;
 
+/// Address Multicall3 is deployed to on most EVM chains, see
+/// https://github.com/mds1/multicall#deployments
+const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+/// Selector of the `Error(string)` revert solidity uses for `require` and
+/// `revert` with a reason.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Selector of the `Panic(uint256)` revert solidity uses for failed asserts,
+/// arithmetic overflows and the like.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
 impl<M> std::fmt::Display for EthereumMailboxInternal<M>
 where
     M: Middleware,
@@ -197,7 +215,14 @@ where
             .collect())
     }
 }
-pub struct MailboxBuilder {}
+pub struct MailboxBuilder {
+    /// Replace stuck `process` transactions with ones paying a higher gas
+    /// price. Disabled if `None`.
+    pub tx_escalator: Option<TxEscalator>,
+    /// Address of the Multicall3 contract batches are submitted through,
+    /// the canonical deployment if `None`.
+    pub multicall_address: Option<H160>,
+}
 
 #[async_trait]
 impl BuildableWithProvider for MailboxBuilder {
@@ -208,7 +233,61 @@ impl BuildableWithProvider for MailboxBuilder {
         provider: M,
         locator: &ContractLocator,
     ) -> Self::Output {
-        Box::new(EthereumMailbox::new(Arc::new(provider), locator))
+        let mut mailbox = EthereumMailbox::new(Arc::new(provider), locator);
+        if let Some(address) = self.multicall_address {
+            mailbox = mailbox.with_multicall_address(address);
+        }
+        Box::new(match &self.tx_escalator {
+            Some(escalator) => mailbox.with_tx_escalator(escalator.clone()),
+            None => mailbox,
+        })
+    }
+}
+
+/// Builds the mailbox of the locator followed by the mailboxes of the
+/// additional deployments on its chain. The mailboxes share one provider, so
+/// their transactions go through the same signer and nonce manager.
+pub struct DeploymentMailboxesBuilder {
+    /// Replace stuck `process` transactions with ones paying a higher gas
+    /// price. Disabled if `None`.
+    pub tx_escalator: Option<TxEscalator>,
+    /// Address of the Multicall3 contract batches are submitted through,
+    /// the canonical deployment if `None`.
+    pub multicall_address: Option<H160>,
+    /// Domains and mailbox addresses of the additional deployments
+    pub deployments: Vec<(HyperlaneDomain, H256)>,
+}
+
+#[async_trait]
+impl BuildableWithProvider for DeploymentMailboxesBuilder {
+    type Output = Vec<Box<dyn Mailbox>>;
+
+    async fn build_with_provider<M: Middleware + 'static>(
+        &self,
+        provider: M,
+        locator: &ContractLocator,
+    ) -> Self::Output {
+        let provider = Arc::new(provider);
+        let deployments = self
+            .deployments
+            .iter()
+            .map(|(domain, address)| ContractLocator {
+                domain,
+                address: *address,
+            });
+        std::iter::once(locator.clone())
+            .chain(deployments)
+            .map(|locator| {
+                let mut mailbox = EthereumMailbox::new(provider.clone(), &locator);
+                if let Some(address) = self.multicall_address {
+                    mailbox = mailbox.with_multicall_address(address);
+                }
+                Box::new(match &self.tx_escalator {
+                    Some(escalator) => mailbox.with_tx_escalator(escalator.clone()),
+                    None => mailbox,
+                }) as Box<dyn Mailbox>
+            })
+            .collect()
     }
 }
 
@@ -222,6 +301,10 @@ where
     domain: HyperlaneDomain,
     provider: Arc<M>,
     arbitrum_node_interface: Option<Arc<ArbitrumNodeInterface<M>>>,
+    multicall: Arc<IMulticall3<M>>,
+    /// Whether the multicall contract has code, checked on the first batch.
+    multicall_deployed: OnceLock<bool>,
+    tx_escalator: Option<TxEscalator>,
 }
 
 impl<M> EthereumMailbox<M>
@@ -242,6 +325,13 @@ where
             ))
         });
 
+        let multicall = Arc::new(IMulticall3::new(
+            MULTICALL3_ADDRESS
+                .parse::<H160>()
+                .expect("valid multicall address"),
+            provider.clone(),
+        ));
+
         Self {
             contract: Arc::new(EthereumMailboxInternal::new(
                 locator.address,
@@ -250,6 +340,56 @@ where
             domain: locator.domain.clone(),
             provider,
             arbitrum_node_interface,
+            multicall,
+            multicall_deployed: OnceLock::new(),
+            tx_escalator: None,
+        }
+    }
+
+    /// Submit batches through the Multicall3 contract at `address` instead of
+    /// the canonical deployment.
+    pub fn with_multicall_address(mut self, address: H160) -> Self {
+        self.multicall = Arc::new(IMulticall3::new(address, self.provider.clone()));
+        self.multicall_deployed = OnceLock::new();
+        self
+    }
+
+    /// Whether there is a contract at the multicall address. Not every chain
+    /// has Multicall3 deployed at the canonical address.
+    async fn multicall_deployed(&self) -> ChainResult<bool> {
+        if let Some(deployed) = self.multicall_deployed.get() {
+            return Ok(*deployed);
+        }
+        let code = self
+            .provider
+            .get_code(self.multicall.address(), None)
+            .await
+            .map_err(ChainCommunicationError::from_other)?;
+        Ok(*self.multicall_deployed.get_or_init(|| !code.is_empty()))
+    }
+
+    /// Replace transactions that are not included in time with ones paying a
+    /// higher gas price.
+    pub fn with_tx_escalator(mut self, tx_escalator: TxEscalator) -> Self {
+        self.tx_escalator = Some(tx_escalator);
+        self
+    }
+
+    /// Dispatches a transaction and waits for its receipt, escalating the gas
+    /// price if configured to do so. `call_id` identifies the call across
+    /// attempts, so one still in flight from an earlier attempt is tracked.
+    async fn send_tx<D: Detokenize>(
+        &self,
+        contract_call: ContractCall<M, D>,
+        call_id: H256,
+    ) -> ChainResult<TransactionReceipt> {
+        match &self.tx_escalator {
+            Some(escalator) => {
+                escalator
+                    .report_tx(contract_call, self.provider.clone(), call_id)
+                    .await
+            }
+            None => report_tx(contract_call).await,
         }
     }
 
@@ -265,7 +405,24 @@ where
             metadata.to_vec().into(),
             RawHyperlaneMessage::from(message).to_vec().into(),
         );
-        fill_tx_gas_params(tx, tx_gas_limit, self.provider.clone(), message.destination).await
+        let gas_limit = match tx_gas_limit {
+            Some(gas_limit) => gas_limit,
+            // A revert of the estimation is why processing the message would
+            // revert
+            None => estimate_gas_limit(&tx)
+                .await
+                .map_err(|err| match process_revert(&err) {
+                    Some(revert) => ChainCommunicationError::ProcessReverted(revert),
+                    None => err.into(),
+                })?,
+        };
+        fill_tx_gas_params(
+            tx,
+            Some(gas_limit),
+            self.provider.clone(),
+            message.destination,
+        )
+        .await
     }
 }
 
@@ -433,7 +590,40 @@ where
         let contract_call = self
             .process_contract_call(message, metadata, tx_gas_limit)
             .await?;
-        let receipt = report_tx(contract_call).await?;
+        let receipt = self.send_tx(contract_call, message.id()).await?;
+        Ok(receipt.into())
+    }
+
+    #[instrument(skip(self, messages), fields(batch_size=messages.len()))]
+    async fn process_batch(
+        &self,
+        messages: &[(HyperlaneMessage, Vec<u8>)],
+        tx_gas_limit: Option<U256>,
+    ) -> ChainResult<TxOutcome> {
+        if !self.multicall_deployed().await? {
+            return Err(ChainCommunicationError::BatchingUnsupported);
+        }
+        // `allow_failure` is false so a single failing message reverts the
+        // whole batch and the caller can fall back to individual submission.
+        let calls = messages
+            .iter()
+            .map(|(message, metadata)| Call3 {
+                target: self.contract.address(),
+                allow_failure: false,
+                call_data: self.process_calldata(message, metadata).into(),
+            })
+            .collect();
+        let tx = self.multicall.aggregate_3(calls);
+        let contract_call =
+            fill_tx_gas_params(tx, tx_gas_limit, self.provider.clone(), self.domain.id()).await?;
+        // A batch is identified by the messages in it
+        let message_ids: Vec<u8> = messages
+            .iter()
+            .flat_map(|(message, _)| message.id().to_fixed_bytes())
+            .collect();
+        let receipt = self
+            .send_tx(contract_call, keccak256(message_ids).into())
+            .await?;
         Ok(receipt.into())
     }
 
@@ -494,6 +684,67 @@ where
     }
 }
 
+/// Why processing a message reverted, from the error of the call simulating
+/// it. Returns `None` if the call did not revert.
+fn process_revert<M: Middleware>(err: &ContractError<M>) -> Option<ProcessRevert> {
+    let reason = match err {
+        // Revert data ethers found in the error response
+        ContractError::Revert(data) => decode_revert_data(data),
+        ContractError::MiddlewareError { e } => {
+            revert_reason(MiddlewareError::as_error_response(e)?)?
+        }
+        ContractError::ProviderError { e } => revert_reason(RpcError::as_error_response(e)?)?,
+        _ => return None,
+    };
+    Some(ProcessRevert::from_mailbox_reason(reason))
+}
+
+/// The reason for the revert of a call, from the JSON-RPC error response of
+/// the node. Returns `None` if the call did not revert.
+fn revert_reason(error: &JsonRpcError) -> Option<String> {
+    if let Some(data) = error.data.as_ref().and_then(revert_data) {
+        return Some(decode_revert_data(&data));
+    }
+    // Reverts without data, e.g. of `revert()`, are only reported by the
+    // message
+    let reason = error.message.strip_prefix("execution reverted")?;
+    Some(reason.trim_start_matches(':').trim().to_owned())
+}
+
+/// The revert data in the `data` of a JSON-RPC error response. Some nodes
+/// return it as hex, others nest it within an object.
+fn revert_data(data: &Value) -> Option<Vec<u8>> {
+    match data {
+        Value::String(data) => data
+            .strip_prefix("0x")
+            .and_then(|hex| hex::decode(hex).ok())
+            .filter(|data| !data.is_empty()),
+        Value::Object(fields) => fields.values().find_map(revert_data),
+        _ => None,
+    }
+}
+
+/// Decode the reason of a solidity revert from its data.
+fn decode_revert_data(data: &[u8]) -> String {
+    if data.is_empty() {
+        return String::new();
+    }
+    let (selector, params) = data.split_at(data.len().min(4));
+    let decoded = if selector == ERROR_SELECTOR {
+        abi::decode(&[ParamType::String], params).ok()
+    } else if selector == PANIC_SELECTOR {
+        abi::decode(&[ParamType::Uint(256)], params).ok()
+    } else {
+        None
+    };
+    match decoded.as_deref() {
+        Some([Token::String(reason)]) => reason.clone(),
+        Some([Token::Uint(code)]) => format!("panic code {code:#x}"),
+        // A custom error
+        _ => format!("0x{}", hex::encode(data)),
+    }
+}
+
 pub struct EthereumMailboxAbi;
 
 impl HyperlaneAbi for EthereumMailboxAbi {
@@ -509,15 +760,17 @@ mod test {
     use std::{str::FromStr, sync::Arc};
 
     use ethers::{
-        providers::{MockProvider, Provider},
+        providers::{JsonRpcError, MockProvider, MockResponse, Provider},
         types::{Block, Transaction, U256 as EthersU256},
     };
+    use serde_json::{json, Value};
 
     use hyperlane_core::{
-        ContractLocator, HyperlaneDomain, HyperlaneMessage, KnownHyperlaneDomain, Mailbox,
-        TxCostEstimate, H160, H256, U256,
+        ChainCommunicationError, ContractLocator, HyperlaneDomain, HyperlaneMessage,
+        KnownHyperlaneDomain, Mailbox, ProcessRevertClass, TxCostEstimate, H160, H256, U256,
     };
 
+    use super::{revert_reason, ERROR_SELECTOR, PANIC_SELECTOR};
     use crate::EthereumMailbox;
 
     /// An amount of gas to add to the estimated gas
@@ -587,4 +840,97 @@ mod test {
             },
         );
     }
+
+    #[tokio::test]
+    async fn test_process_estimate_costs_classifies_reverts() {
+        let mock_provider = Arc::new(MockProvider::new());
+        let provider = Arc::new(Provider::new(mock_provider.clone()));
+        let mailbox = EthereumMailbox::new(
+            provider,
+            &ContractLocator {
+                domain: &HyperlaneDomain::Known(KnownHyperlaneDomain::Goerli),
+                address: H256::default(),
+            },
+        );
+
+        // RPC 1: eth_estimateGas of process, which reverts
+        mock_provider.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted: delivered".into(),
+            data: Some(json!(error_data("delivered"))),
+        }));
+
+        let err = mailbox
+            .process_estimate_costs(&HyperlaneMessage::default(), &[])
+            .await
+            .unwrap_err();
+        let ChainCommunicationError::ProcessReverted(revert) = err else {
+            panic!("Expected a revert, got {err:?}");
+        };
+        assert_eq!(revert.class, ProcessRevertClass::AlreadyDelivered);
+        assert_eq!(revert.reason, "delivered");
+    }
+
+    fn error_data(reason: &str) -> String {
+        let data = [
+            ERROR_SELECTOR.to_vec(),
+            ethers::abi::encode(&[ethers::abi::Token::String(reason.into())]),
+        ]
+        .concat();
+        format!("0x{}", hex::encode(data))
+    }
+
+    fn error(code: i64, message: &str, data: Option<Value>) -> JsonRpcError {
+        JsonRpcError {
+            code,
+            message: message.into(),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_revert_reason() {
+        let reason = |error: JsonRpcError| revert_reason(&error);
+
+        let data = json!(error_data("!module"));
+        assert_eq!(
+            reason(error(3, "execution reverted: !module", Some(data))).as_deref(),
+            Some("!module")
+        );
+
+        let panic_data = [
+            PANIC_SELECTOR.to_vec(),
+            ethers::abi::encode(&[ethers::abi::Token::Uint(EthersU256::from(0x11))]),
+        ]
+        .concat();
+        let data = json!(format!("0x{}", hex::encode(panic_data)));
+        assert_eq!(
+            reason(error(3, "execution reverted", Some(data))).as_deref(),
+            Some("panic code 0x11")
+        );
+
+        // Revert data nested within an object, with an unrelated message
+        let data = json!({ "message": "revert", "data": error_data("!destination") });
+        assert_eq!(
+            reason(error(-32015, "VM execution error.", Some(data))).as_deref(),
+            Some("!destination")
+        );
+
+        // Without revert data
+        assert_eq!(
+            reason(error(-32000, "execution reverted: delivered", None)).as_deref(),
+            Some("delivered")
+        );
+        assert_eq!(
+            reason(error(-32000, "execution reverted", Some(json!("0x")))).as_deref(),
+            Some("")
+        );
+
+        // Not a revert
+        assert_eq!(reason(error(-32005, "rate limited", None)), None);
+        assert_eq!(
+            reason(error(-32000, "header not found", Some(json!("not hex")))),
+            None
+        );
+    }
 }
@@ -0,0 +1,190 @@
+#![allow(clippy::enum_variant_names)]
+#![allow(missing_docs)]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::providers::Middleware;
+use ethers_contract::builders::ContractCall;
+use tracing::instrument;
+
+use hyperlane_core::{
+    ChainCommunicationError, ChainResult, ContractLocator, HyperlaneAbi, HyperlaneChain,
+    HyperlaneContract, HyperlaneDomain, HyperlaneMessage, HyperlaneProtocolError,
+    HyperlaneProvider, OptimisticIsm, RawHyperlaneMessage, TxCostEstimate, TxOutcome, H256, U256,
+};
+
+use crate::contracts::i_optimistic_ism::{
+    IOptimisticIsm as EthereumOptimisticIsmInternal, IOPTIMISTICISM_ABI,
+};
+use crate::trait_builder::BuildableWithProvider;
+use crate::tx::{fill_tx_gas_params, report_tx};
+use crate::EthereumProvider;
+
+pub struct OptimisticIsmBuilder {}
+
+#[async_trait]
+impl BuildableWithProvider for OptimisticIsmBuilder {
+    type Output = Box<dyn OptimisticIsm>;
+
+    async fn build_with_provider<M: Middleware + 'static>(
+        &self,
+        provider: M,
+        locator: &ContractLocator,
+    ) -> Self::Output {
+        Box::new(EthereumOptimisticIsm::new(Arc::new(provider), locator))
+    }
+}
+
+/// A reference to an OptimisticIsm contract on some Ethereum chain
+#[derive(Debug)]
+pub struct EthereumOptimisticIsm<M>
+where
+    M: Middleware,
+{
+    contract: Arc<EthereumOptimisticIsmInternal<M>>,
+    domain: HyperlaneDomain,
+    provider: Arc<M>,
+}
+
+impl<M> EthereumOptimisticIsm<M>
+where
+    M: Middleware + 'static,
+{
+    /// Create a reference to an optimistic ISM at a specific Ethereum address
+    /// on some chain
+    pub fn new(provider: Arc<M>, locator: &ContractLocator) -> Self {
+        Self {
+            contract: Arc::new(EthereumOptimisticIsmInternal::new(
+                locator.address,
+                provider.clone(),
+            )),
+            domain: locator.domain.clone(),
+            provider,
+        }
+    }
+
+    /// Returns a ContractCall that pre-verifies the provided message.
+    /// If the provided tx_gas_limit is None, gas estimation occurs.
+    async fn pre_verify_contract_call(
+        &self,
+        message: &HyperlaneMessage,
+        metadata: &[u8],
+        tx_gas_limit: Option<U256>,
+    ) -> ChainResult<ContractCall<M, bool>> {
+        let tx = self.contract.pre_verify(
+            metadata.to_vec().into(),
+            RawHyperlaneMessage::from(message).to_vec().into(),
+        );
+        fill_tx_gas_params(tx, tx_gas_limit, self.provider.clone(), message.destination).await
+    }
+}
+
+impl<M> HyperlaneChain for EthereumOptimisticIsm<M>
+where
+    M: Middleware + 'static,
+{
+    fn domain(&self) -> &HyperlaneDomain {
+        &self.domain
+    }
+
+    fn provider(&self) -> Box<dyn HyperlaneProvider> {
+        Box::new(EthereumProvider::new(
+            self.provider.clone(),
+            self.domain.clone(),
+        ))
+    }
+}
+
+impl<M> HyperlaneContract for EthereumOptimisticIsm<M>
+where
+    M: Middleware + 'static,
+{
+    fn address(&self) -> H256 {
+        self.contract.address().into()
+    }
+}
+
+#[async_trait]
+impl<M> OptimisticIsm for EthereumOptimisticIsm<M>
+where
+    M: Middleware + 'static,
+{
+    #[instrument(err)]
+    async fn submodule(&self, message: &HyperlaneMessage) -> ChainResult<H256> {
+        let submodule = self
+            .contract
+            .submodule(RawHyperlaneMessage::from(message).to_vec().into())
+            .call()
+            .await?;
+        Ok(submodule.into())
+    }
+
+    #[instrument(err)]
+    async fn fraud_window(&self) -> ChainResult<u64> {
+        let fraud_window = self.contract.fraud_window().call().await?;
+        Ok(fraud_window.try_into().unwrap_or(u64::MAX))
+    }
+
+    #[instrument(err)]
+    async fn pre_verified_at(&self, message: &HyperlaneMessage) -> ChainResult<Option<u64>> {
+        let timestamp = self
+            .contract
+            .pre_verified_at(message.id().into())
+            .call()
+            .await?;
+        Ok((!timestamp.is_zero()).then(|| timestamp.try_into().unwrap_or(u64::MAX)))
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn pre_verify(
+        &self,
+        message: &HyperlaneMessage,
+        metadata: &[u8],
+        tx_gas_limit: Option<U256>,
+    ) -> ChainResult<TxOutcome> {
+        let contract_call = self
+            .pre_verify_contract_call(message, metadata, tx_gas_limit)
+            .await?;
+        let receipt = report_tx(contract_call).await?;
+        Ok(receipt.into())
+    }
+
+    #[instrument(err, ret, skip(self))]
+    async fn pre_verify_estimate_costs(
+        &self,
+        message: &HyperlaneMessage,
+        metadata: &[u8],
+    ) -> ChainResult<TxCostEstimate> {
+        let contract_call = self
+            .pre_verify_contract_call(message, metadata, None)
+            .await?;
+        let gas_limit = contract_call
+            .tx
+            .gas()
+            .copied()
+            .ok_or(HyperlaneProtocolError::ProcessGasLimitRequired)?;
+        let gas_price = self
+            .provider
+            .get_gas_price()
+            .await
+            .map_err(ChainCommunicationError::from_other)?;
+
+        Ok(TxCostEstimate {
+            gas_limit: gas_limit.into(),
+            gas_price: gas_price.into(),
+            l2_gas_limit: None,
+        })
+    }
+}
+
+pub struct EthereumOptimisticIsmAbi;
+
+impl HyperlaneAbi for EthereumOptimisticIsmAbi {
+    const SELECTOR_SIZE_BYTES: usize = 4;
+
+    fn fn_map() -> HashMap<Vec<u8>, &'static str> {
+        super::extract_fn_map(&IOPTIMISTICISM_ABI)
+    }
+}
@@ -0,0 +1,349 @@
+use std::fmt::{Debug, Formatter};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ethers::prelude::{Address, Bytes, Signature, SignatureError, H256};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::transaction::eip712::Eip712;
+use ethers::utils::{hash_message, to_eip155_v};
+use ethers_signers::Signer;
+use reqwest::{Certificate, Client, Identity};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// A signer that has a signing service reached over HTTP sign for it, e.g. a
+/// service in front of an HSM or Vault. The service implements two routes
+/// relative to its base URL:
+///
+/// - `GET address`: responds with `{"address": "0x..."}`, the address of the
+///   key it signs with.
+/// - `POST sign` with `{"hash": "0x..."}`: responds with
+///   `{"signature": "0x..."}`, the 65 byte `r || s || v` signature of the
+///   32 byte hash. `v` may be either 0/1 or 27/28.
+///
+/// Signatures are recovered and checked against the address before they are
+/// used, so a misbehaving service cannot make the signer use a signature of
+/// another key.
+///
+/// The service may also be the coordinator of a threshold ECDSA scheme whose
+/// share holders jointly sign, as long as it returns ordinary ECDSA
+/// signatures of the shared key.
+#[derive(Clone)]
+pub struct RemoteSigner {
+    client: Client,
+    url: Url,
+    bearer_token: Option<String>,
+    address: Address,
+    chain_id: u64,
+}
+
+/// The TLS configuration to reach a signing service with.
+#[derive(Debug, Clone, Default)]
+pub struct RemoteSignerTls {
+    /// PEM encoded client certificate followed by its private key, to
+    /// authenticate to the service with over mutual TLS
+    pub identity_pem: Option<Vec<u8>>,
+    /// PEM encoded CA certificate to trust the service's certificate with, in
+    /// addition to the system roots
+    pub ca_cert_pem: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AddressResponse {
+    address: Address,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SignRequest {
+    hash: H256,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SignResponse {
+    signature: Bytes,
+}
+
+impl Debug for RemoteSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // leave out the bearer token so it does not end up in logs
+        f.debug_struct("RemoteSigner")
+            .field("url", &self.url.as_str())
+            .field("address", &self.address)
+            .field("chain_id", &self.chain_id)
+            .finish()
+    }
+}
+
+impl RemoteSigner {
+    /// Connect to the signing service at `url` and fetch the address it signs
+    /// with. The `bearer_token` is sent with every request if set, and requests
+    /// fail if the service takes longer than `timeout` to respond.
+    pub async fn connect(
+        mut url: Url,
+        bearer_token: Option<String>,
+        tls: RemoteSignerTls,
+        timeout: Duration,
+    ) -> Result<Self, RemoteSignerError> {
+        // routes are relative to the base url, which they would replace the
+        // last segment of without a trailing slash
+        if !url.path().ends_with('/') {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+
+        let mut builder = Client::builder().use_rustls_tls().timeout(timeout);
+        if let Some(pem) = &tls.identity_pem {
+            builder = builder.identity(Identity::from_pem(pem)?);
+        }
+        if let Some(pem) = &tls.ca_cert_pem {
+            builder = builder.add_root_certificate(Certificate::from_pem(pem)?);
+        }
+
+        let mut signer = Self {
+            client: builder.build()?,
+            url,
+            bearer_token,
+            address: Address::zero(),
+            chain_id: 1,
+        };
+        let response: AddressResponse = signer
+            .authorize(signer.client.get(signer.url.join("address")?))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        signer.address = response.address;
+        Ok(signer)
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.bearer_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Have the service sign a 32 byte hash, returning the signature with `v`
+    /// as 27/28.
+    async fn sign_digest(&self, hash: H256) -> Result<Signature, RemoteSignerError> {
+        let response: SignResponse = self
+            .authorize(self.client.post(self.url.join("sign")?))
+            .json(&SignRequest { hash })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let mut signature = Signature::try_from(response.signature.as_ref())?;
+        if signature.v < 27 {
+            signature.v += 27;
+        }
+        signature.verify(hash, self.address)?;
+        Ok(signature)
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    type Error = RemoteSignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        self.sign_digest(hash_message(message)).await
+    }
+
+    async fn sign_transaction(&self, message: &TypedTransaction) -> Result<Signature, Self::Error> {
+        let mut tx = message.clone();
+        let chain_id = tx.chain_id().map(|id| id.as_u64()).unwrap_or(self.chain_id);
+        tx.set_chain_id(chain_id);
+        let mut signature = self.sign_digest(tx.sighash()).await?;
+        // `v` as EIP-155 for every type of transaction, like local wallets.
+        // Typed transactions are encoded with the y-parity derived from it.
+        signature.v = to_eip155_v((signature.v - 27) as u8, chain_id);
+        Ok(signature)
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        let hash = payload
+            .encode_eip712()
+            .map_err(|err| RemoteSignerError::Eip712(err.to_string()))?;
+        self.sign_digest(hash.into()).await
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    fn with_chain_id<T: Into<u64>>(mut self, chain_id: T) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+}
+
+/// Error types for RemoteSigner
+#[derive(Debug, thiserror::Error)]
+pub enum RemoteSignerError {
+    /// The signing service could not be reached or responded with an error
+    #[error("Remote signer request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    /// The signing service url is invalid
+    #[error("Invalid remote signer url: {0}")]
+    Url(#[from] url::ParseError),
+    /// The signing service responded with an invalid signature or one of
+    /// another key
+    #[error("Remote signer returned an invalid signature: {0}")]
+    InvalidSignature(#[from] SignatureError),
+    /// The typed data could not be hashed
+    #[error("Failed to encode typed data: {0}")]
+    Eip712(String),
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use ethers::prelude::{Eip1559TransactionRequest, LocalWallet, TransactionRequest};
+    use ethers::utils::rlp;
+    use hyperlane_core::{Checkpoint, HyperlaneSigner, HyperlaneSignerExt, H256};
+    use warp::Filter;
+
+    use super::*;
+    use crate::signers::Signers;
+
+    const TOKEN: &str = "secret";
+
+    fn wallet() -> LocalWallet {
+        "1111111111111111111111111111111111111111111111111111111111111111"
+            .parse()
+            .unwrap()
+    }
+
+    /// Serve a signing service signing with `wallet` on a local port, which
+    /// only accepts requests bearing `TOKEN`. Signatures have `v` as 0/1 if
+    /// `parity_v` and as 27/28 otherwise.
+    fn mock_signing_service(wallet: LocalWallet, parity_v: bool) -> SocketAddr {
+        let auth = warp::header::exact("authorization", "Bearer secret");
+        let address = wallet.address();
+        let address_route = warp::get()
+            .and(warp::path!("signer" / "address"))
+            .and(auth)
+            .map(move || warp::reply::json(&AddressResponse { address }));
+        let sign_route = warp::post()
+            .and(warp::path!("signer" / "sign"))
+            .and(auth)
+            .and(warp::body::json())
+            .map(move |request: SignRequest| {
+                let mut signature = wallet.sign_hash(request.hash);
+                if parity_v {
+                    signature.v -= 27;
+                }
+                warp::reply::json(&SignResponse {
+                    signature: signature.to_vec().into(),
+                })
+            });
+        let (addr, server) =
+            warp::serve(address_route.or(sign_route)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    fn run(test: impl std::future::Future<Output = ()>) {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(test)
+    }
+
+    async fn connect(addr: SocketAddr, token: &str) -> Result<RemoteSigner, RemoteSignerError> {
+        let url = format!("http://{addr}/signer").parse().unwrap();
+        RemoteSigner::connect(
+            url,
+            Some(token.into()),
+            Default::default(),
+            Duration::from_secs(5),
+        )
+        .await
+    }
+
+    #[test]
+    fn it_signs_like_a_local_wallet() {
+        run(async {
+            let addr = mock_signing_service(wallet(), false);
+            let remote = connect(addr, TOKEN).await.unwrap().with_chain_id(5u64);
+            let local = wallet().with_chain_id(5u64);
+            assert_eq!(Signer::address(&remote), local.address());
+
+            let tx: TypedTransaction = TransactionRequest::new()
+                .to(Address::repeat_byte(1))
+                .value(100u64)
+                .nonce(3u64)
+                .gas(21_000u64)
+                .gas_price(1_000u64)
+                .into();
+            assert_eq!(
+                remote.sign_transaction(&tx).await.unwrap(),
+                local.sign_transaction(&tx).await.unwrap()
+            );
+
+            let signer: Signers = remote.into();
+            let checkpoint = Checkpoint {
+                mailbox_address: H256::repeat_byte(2),
+                mailbox_domain: 5,
+                root: H256::repeat_byte(1),
+                index: 123,
+            };
+            let signed = signer.sign(checkpoint).await.expect("!sign");
+            signed.verify(signer.eth_address()).expect("!verify");
+        })
+    }
+
+    #[test]
+    fn it_signs_eip1559_transactions() {
+        run(async {
+            let addr = mock_signing_service(wallet(), true);
+            let remote = connect(addr, TOKEN).await.unwrap().with_chain_id(5u64);
+            let local = wallet().with_chain_id(5u64);
+
+            let tx: TypedTransaction = Eip1559TransactionRequest::new()
+                .to(Address::repeat_byte(1))
+                .value(100u64)
+                .nonce(3u64)
+                .gas(21_000u64)
+                .max_fee_per_gas(2_000u64)
+                .max_priority_fee_per_gas(1_000u64)
+                .chain_id(5u64)
+                .into();
+            let signature = remote.sign_transaction(&tx).await.unwrap();
+            assert_eq!(signature, local.sign_transaction(&tx).await.unwrap());
+            // a 0/1 `v` from the service is made EIP-155, like local wallets do
+            assert!(signature.v == 45 || signature.v == 46);
+            assert_eq!(signature.recover(tx.sighash()).unwrap(), local.address());
+
+            // the encoded transaction has the y-parity of the signature
+            let signed = tx.rlp_signed(&signature);
+            assert_eq!(signed[0], 2);
+            let y_parity: u64 = rlp::Rlp::new(&signed[1..]).val_at(9).unwrap();
+            assert_eq!(y_parity, signature.v - 45);
+        })
+    }
+
+    #[test]
+    fn it_fails_to_connect_without_the_token() {
+        run(async {
+            let addr = mock_signing_service(wallet(), false);
+            let result = connect(addr, "wrong").await;
+            assert!(matches!(result, Err(RemoteSignerError::Request(_))));
+        })
+    }
+}
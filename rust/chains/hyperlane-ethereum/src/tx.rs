@@ -1,16 +1,23 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use derive_new::new;
 use ethers::abi::Detokenize;
 use ethers::prelude::{NameOrAddress, TransactionReceipt};
-use ethers::types::Eip1559TransactionRequest;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Eip1559TransactionRequest, U256 as EthersU256};
 use ethers_contract::builders::ContractCall;
-use tracing::{error, info};
+use ethers_contract::ContractError;
+use prometheus::{IntCounter, IntGauge};
+use tracing::{error, info, warn};
 
 use hyperlane_core::utils::fmt_bytes;
-use hyperlane_core::{ChainCommunicationError, ChainResult, KnownHyperlaneDomain, H256, U256};
+use hyperlane_core::{
+    ChainCommunicationError, ChainResult, InflightTransaction, InflightTransactionStore,
+    KnownHyperlaneDomain, H256, U256,
+};
 
-use crate::Middleware;
+use crate::{Middleware, TxEscalationConf};
 
 /// An amount of gas to add to the estimated gas
 const GAS_ESTIMATE_BUFFER: u32 = 50000;
@@ -64,6 +71,354 @@ where
     }
 }
 
+/// Metrics for transaction escalation
+#[derive(Debug, Clone)]
+pub struct TxEscalationMetrics {
+    /// Number of times a transaction was replaced with a higher gas price.
+    pub escalations: IntCounter,
+    /// Effective gas price paid by the last escalated transaction that was
+    /// included.
+    pub final_gas_price: IntGauge,
+}
+
+/// Replaces transactions that are stuck in the mempool with ones using the
+/// same nonce and a higher gas price.
+#[derive(Debug, Clone, new)]
+pub struct TxEscalator {
+    conf: TxEscalationConf,
+    metrics: TxEscalationMetrics,
+    /// Where transactions in flight are persisted, if anywhere.
+    #[new(default)]
+    store: Option<Arc<dyn InflightTransactionStore>>,
+}
+
+impl TxEscalator {
+    /// Persist the transactions in flight to `store`, so they are tracked
+    /// again after a restart instead of new ones being sent for their calls.
+    pub fn with_store(mut self, store: Arc<dyn InflightTransactionStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Dispatches a transaction and waits for it to be included, replacing it
+    /// with a higher gas price each time the timeout elapses. `call_id`
+    /// identifies the call across attempts, e.g. by the message it processes,
+    /// so a transaction still in flight from an earlier attempt is tracked
+    /// instead of a new one being sent.
+    pub(crate) async fn report_tx<M, D>(
+        &self,
+        mut tx: ContractCall<M, D>,
+        provider: Arc<M>,
+        call_id: H256,
+    ) -> ChainResult<TransactionReceipt>
+    where
+        M: Middleware + 'static,
+        D: Detokenize,
+    {
+        let mut dispatched_hashes =
+            match self.resume_inflight(&mut tx.tx, &provider, call_id).await? {
+                Resumed::Included(receipt) => return Ok(receipt),
+                Resumed::Pending(tx_hashes) => tx_hashes,
+                Resumed::None => {
+                    // Fill in the nonce now so every replacement reuses it.
+                    provider
+                        .fill_transaction(&mut tx.tx, None)
+                        .await
+                        .map_err(ChainCommunicationError::from_other)?;
+                    vec![]
+                }
+            };
+        if !dispatched_hashes.is_empty() {
+            // The transaction from the earlier attempt already waited, so
+            // replace it right away
+            self.escalate(&mut tx.tx, &dispatched_hashes)?;
+        }
+
+        loop {
+            let dispatched = match tx.send().await {
+                Ok(dispatched) => dispatched,
+                Err(e) if !dispatched_hashes.is_empty() => {
+                    // The replacement was rejected, e.g. because it was already mined or was
+                    // underpriced; see if one of the earlier transactions made it in.
+                    warn!(error=?e, "Failed to dispatch replacement transaction");
+                    return self
+                        .find_receipt(&provider, &dispatched_hashes, call_id)
+                        .await?
+                        .ok_or_else(|| e.into());
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let tx_hash: H256 = (*dispatched).into();
+            info!(
+                ?tx_hash,
+                escalations = dispatched_hashes.len(),
+                "Dispatched tx"
+            );
+            dispatched_hashes.push(tx_hash);
+            self.persist_inflight(&tx.tx, &dispatched_hashes, call_id);
+
+            let result = tokio::time::timeout(self.conf.timeout, dispatched).await;
+            match result {
+                Ok(Ok(Some(receipt))) => {
+                    info!(?tx_hash, "confirmed transaction");
+                    self.forget_inflight(call_id);
+                    return Ok(self.on_included(receipt, dispatched_hashes.len()));
+                }
+                Ok(Ok(None)) => {
+                    // A replaced transaction may have been included instead.
+                    return match self
+                        .find_receipt(&provider, &dispatched_hashes, call_id)
+                        .await?
+                    {
+                        Some(receipt) => Ok(receipt),
+                        None => Err(ChainCommunicationError::TransactionDropped(tx_hash)),
+                    };
+                }
+                Ok(Err(x)) => {
+                    error!(?tx_hash, error = ?x, "encountered error when waiting for receipt");
+                    return Err(x.into());
+                }
+                Err(_) => {
+                    if let Some(receipt) = self
+                        .find_receipt(&provider, &dispatched_hashes, call_id)
+                        .await?
+                    {
+                        return Ok(receipt);
+                    }
+                    self.escalate(&mut tx.tx, &dispatched_hashes)?;
+                }
+            }
+        }
+    }
+
+    /// Raise the gas price of a transaction which was not included in time,
+    /// failing if it cannot be replaced anymore.
+    fn escalate(&self, tx: &mut TypedTransaction, dispatched_hashes: &[H256]) -> ChainResult<()> {
+        let tx_hash = dispatched_hashes.last();
+        let escalations = dispatched_hashes.len() as u32 - 1;
+        if escalations >= self.conf.max_escalations {
+            error!(?tx_hash, escalations, "waiting for receipt timed out");
+            return Err(ChainCommunicationError::TransactionTimeout());
+        }
+        if let Err(err) = bump_gas_price(tx, &self.conf) {
+            error!(?tx_hash, escalations, %err, "waiting for receipt timed out and the transaction cannot be replaced");
+            return Err(ChainCommunicationError::from_other(err));
+        }
+        warn!(
+            replaced_tx_hash=?tx_hash,
+            gas_price=?tx.gas_price(),
+            max_priority_fee_per_gas=?tx.as_eip1559_ref().and_then(|r| r.max_priority_fee_per_gas),
+            escalation = escalations + 1,
+            "Transaction not included before timeout, replacing it with a higher gas price"
+        );
+        self.metrics.escalations.inc();
+        Ok(())
+    }
+
+    /// Pick up where an earlier attempt at the call left off. If one of its
+    /// transactions was included its receipt is returned. If its nonce is
+    /// still unused, `tx` takes its nonce and gas price so it replaces them.
+    async fn resume_inflight<M: Middleware + 'static>(
+        &self,
+        tx: &mut TypedTransaction,
+        provider: &Arc<M>,
+        call_id: H256,
+    ) -> ChainResult<Resumed> {
+        let Some(store) = &self.store else {
+            return Ok(Resumed::None);
+        };
+        let inflight = match store.retrieve_inflight_transaction(call_id) {
+            Ok(Some(inflight)) => inflight,
+            Ok(None) => return Ok(Resumed::None),
+            Err(err) => {
+                warn!(?call_id, ?err, "Failed to read transaction in flight");
+                return Ok(Resumed::None);
+            }
+        };
+        if let Some(receipt) = self
+            .find_receipt(provider, &inflight.tx_hashes, call_id)
+            .await?
+        {
+            return Ok(Resumed::Included(receipt));
+        }
+        let Some(from) = tx.from().copied() else {
+            return Ok(Resumed::None);
+        };
+        let next_nonce = provider
+            .get_transaction_count(from, None)
+            .await
+            .map_err(ChainCommunicationError::from_other)?;
+        if next_nonce > EthersU256::from(inflight.nonce) {
+            // Another transaction used the nonce, so none of these can be
+            // included anymore
+            self.forget_inflight(call_id);
+            return Ok(Resumed::None);
+        }
+        info!(
+            ?call_id,
+            nonce = ?inflight.nonce,
+            tx_hashes = ?inflight.tx_hashes,
+            "Resuming transaction in flight from an earlier attempt"
+        );
+        tx.set_nonce(EthersU256::from(inflight.nonce));
+        match tx {
+            TypedTransaction::Eip1559(request) => {
+                request.max_fee_per_gas = Some(inflight.gas_price.into());
+                request.max_priority_fee_per_gas =
+                    inflight.max_priority_fee_per_gas.map(Into::into);
+            }
+            tx => {
+                tx.set_gas_price(EthersU256::from(inflight.gas_price));
+            }
+        }
+        Ok(Resumed::Pending(inflight.tx_hashes))
+    }
+
+    fn persist_inflight(&self, tx: &TypedTransaction, tx_hashes: &[H256], call_id: H256) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let (Some(nonce), Some(gas_price)) = (tx.nonce(), tx.gas_price()) else {
+            return;
+        };
+        let inflight = InflightTransaction {
+            nonce: (*nonce).into(),
+            gas_price: gas_price.into(),
+            max_priority_fee_per_gas: tx
+                .as_eip1559_ref()
+                .and_then(|r| r.max_priority_fee_per_gas)
+                .map(Into::into),
+            tx_hashes: tx_hashes.to_vec(),
+        };
+        if let Err(err) = store.store_inflight_transaction(call_id, &inflight) {
+            warn!(?call_id, ?err, "Failed to persist transaction in flight");
+        }
+    }
+
+    fn forget_inflight(&self, call_id: H256) {
+        if let Some(store) = &self.store {
+            if let Err(err) = store.remove_inflight_transaction(call_id) {
+                warn!(?call_id, ?err, "Failed to remove transaction in flight");
+            }
+        }
+    }
+
+    /// Look for a receipt of any of the given transactions, which all share a
+    /// nonce so at most one of them can be included.
+    async fn find_receipt<M: Middleware + 'static>(
+        &self,
+        provider: &Arc<M>,
+        tx_hashes: &[H256],
+        call_id: H256,
+    ) -> ChainResult<Option<TransactionReceipt>> {
+        for tx_hash in tx_hashes {
+            let receipt = provider
+                .get_transaction_receipt(*tx_hash)
+                .await
+                .map_err(ChainCommunicationError::from_other)?;
+            if let Some(receipt) = receipt {
+                info!(?tx_hash, "confirmed transaction");
+                self.forget_inflight(call_id);
+                return Ok(Some(self.on_included(receipt, tx_hashes.len())));
+            }
+        }
+        Ok(None)
+    }
+
+    fn on_included(&self, receipt: TransactionReceipt, dispatched: usize) -> TransactionReceipt {
+        if dispatched > 1 {
+            if let Some(gas_price) = receipt.effective_gas_price {
+                self.metrics
+                    .final_gas_price
+                    .set(i64::try_from(gas_price).unwrap_or(i64::MAX));
+            }
+        }
+        receipt
+    }
+}
+
+/// What is left of an earlier attempt at a call.
+enum Resumed {
+    /// There is no transaction in flight for it.
+    None,
+    /// One of its transactions was included.
+    Included(TransactionReceipt),
+    /// Its transactions are still pending, `tx` replaces them.
+    Pending(Vec<H256>),
+}
+
+/// Why a transaction cannot be replaced with a higher gas price
+#[derive(Debug, thiserror::Error)]
+pub enum BumpGasPriceError {
+    /// The transaction has no gas price set
+    #[error("Transaction has no gas price to bump")]
+    NoGasPrice,
+    /// Raising the gas price enough to replace the transaction would exceed
+    /// the configured max fee
+    #[error("Replacing the transaction needs a gas price of {required}, which exceeds the max fee per gas of {max_fee}")]
+    ExceedsMaxFee {
+        /// The gas price the replacement needs at least
+        required: EthersU256,
+        /// The configured max fee per gas
+        max_fee: EthersU256,
+    },
+}
+
+/// Raise the gas price of a transaction by the configured percentage. Fails
+/// if that would exceed the configured max fee, since nodes reject
+/// replacements that do not raise the price by enough.
+fn bump_gas_price(
+    tx: &mut TypedTransaction,
+    conf: &TxEscalationConf,
+) -> Result<(), BumpGasPriceError> {
+    let max_fee: EthersU256 = conf.max_fee_per_gas.into();
+    let bump = |value: EthersU256| {
+        let required = value
+            .saturating_add(value * conf.bump_percent / 100)
+            .saturating_add(1.into());
+        if required > max_fee {
+            Err(BumpGasPriceError::ExceedsMaxFee { required, max_fee })
+        } else {
+            Ok(required)
+        }
+    };
+    match tx {
+        TypedTransaction::Eip1559(request) => {
+            let (Some(max_fee_per_gas), Some(max_priority_fee_per_gas)) =
+                (request.max_fee_per_gas, request.max_priority_fee_per_gas)
+            else {
+                return Err(BumpGasPriceError::NoGasPrice);
+            };
+            let max_fee_per_gas = bump(max_fee_per_gas)?;
+            // The priority fee is part of the max fee, so it is bounded by it
+            let max_priority_fee_per_gas = bump(max_priority_fee_per_gas)?;
+            request.max_fee_per_gas = Some(max_fee_per_gas);
+            request.max_priority_fee_per_gas = Some(max_priority_fee_per_gas.min(max_fee_per_gas));
+            Ok(())
+        }
+        tx => {
+            let gas_price = tx.gas_price().ok_or(BumpGasPriceError::NoGasPrice)?;
+            tx.set_gas_price(bump(gas_price)?);
+            Ok(())
+        }
+    }
+}
+
+/// Estimates the gas of a transaction, with a buffer added
+pub(crate) async fn estimate_gas_limit<M, D>(
+    tx: &ContractCall<M, D>,
+) -> Result<U256, ContractError<M>>
+where
+    M: Middleware + 'static,
+    D: Detokenize,
+{
+    Ok(tx
+        .estimate_gas()
+        .await?
+        .saturating_add(U256::from(GAS_ESTIMATE_BUFFER).into())
+        .into())
+}
+
 /// Populates the gas limit and price for a transaction
 pub(crate) async fn fill_tx_gas_params<M, D>(
     tx: ContractCall<M, D>,
@@ -78,10 +433,7 @@ where
     let gas_limit = if let Some(gas_limit) = tx_gas_limit {
         gas_limit
     } else {
-        tx.estimate_gas()
-            .await?
-            .saturating_add(U256::from(GAS_ESTIMATE_BUFFER).into())
-            .into()
+        estimate_gas_limit(&tx).await?
     };
     let Ok((max_fee, max_priority_fee)) = provider.estimate_eip1559_fees(None).await else {
         // Is not EIP 1559 chain
@@ -117,3 +469,80 @@ where
     eip_1559_tx.tx = ethers::types::transaction::eip2718::TypedTransaction::Eip1559(request);
     Ok(eip_1559_tx.gas(gas_limit))
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use ethers::types::{
+        transaction::eip2718::TypedTransaction, Eip1559TransactionRequest, TransactionRequest,
+        U256 as EthersU256,
+    };
+
+    use hyperlane_core::{Decode, Encode, InflightTransaction, H256, U256};
+
+    use super::{bump_gas_price, BumpGasPriceError, TxEscalationConf};
+
+    fn conf(max_fee_per_gas: u64) -> TxEscalationConf {
+        TxEscalationConf {
+            timeout: Duration::from_secs(60),
+            bump_percent: 10,
+            max_fee_per_gas: U256::from(max_fee_per_gas),
+            max_escalations: 3,
+        }
+    }
+
+    #[test]
+    fn test_bump_eip1559_fees_up_to_max() {
+        let mut tx = TypedTransaction::Eip1559(
+            Eip1559TransactionRequest::new()
+                .max_fee_per_gas(1000)
+                .max_priority_fee_per_gas(100),
+        );
+        assert!(bump_gas_price(&mut tx, &conf(1200)).is_ok());
+        let request = tx.as_eip1559_ref().unwrap();
+        assert_eq!(request.max_fee_per_gas, Some(EthersU256::from(1101)));
+        assert_eq!(
+            request.max_priority_fee_per_gas,
+            Some(EthersU256::from(111))
+        );
+
+        // Bumping by 10% again would exceed the max, and a smaller bump would
+        // not be accepted as a replacement
+        assert!(matches!(
+            bump_gas_price(&mut tx, &conf(1200)),
+            Err(BumpGasPriceError::ExceedsMaxFee { .. })
+        ));
+        let request = tx.as_eip1559_ref().unwrap();
+        assert_eq!(request.max_fee_per_gas, Some(EthersU256::from(1101)));
+    }
+
+    #[test]
+    fn test_bump_legacy_gas_price() {
+        let mut tx = TypedTransaction::Legacy(TransactionRequest::new().gas_price(1000));
+        assert!(bump_gas_price(&mut tx, &conf(10_000)).is_ok());
+        assert_eq!(tx.gas_price(), Some(EthersU256::from(1101)));
+
+        // A capped bump would not replace the transaction
+        assert!(bump_gas_price(&mut tx, &conf(1200)).is_err());
+        assert_eq!(tx.gas_price(), Some(EthersU256::from(1101)));
+
+        let mut tx = TypedTransaction::Legacy(TransactionRequest::new());
+        assert!(matches!(
+            bump_gas_price(&mut tx, &conf(10_000)),
+            Err(BumpGasPriceError::NoGasPrice)
+        ));
+    }
+
+    #[test]
+    fn test_inflight_transaction_round_trip() {
+        let inflight = InflightTransaction {
+            nonce: U256::from(7),
+            gas_price: U256::from(1101),
+            max_priority_fee_per_gas: Some(U256::from(111)),
+            tx_hashes: vec![H256::repeat_byte(1), H256::repeat_byte(2)],
+        };
+        let decoded = InflightTransaction::read_from(&mut inflight.to_vec().as_slice()).unwrap();
+        assert_eq!(decoded, inflight);
+    }
+}
@@ -5,6 +5,7 @@ use eyre::{Report, Result};
 use futures_util::future::select_all;
 use tokio::task::JoinHandle;
 use tracing::{debug_span, instrument::Instrumented, Instrument};
+use warp::{filters::BoxedFilter, Reply};
 
 use crate::{metrics::CoreMetrics, settings::Settings};
 
@@ -42,6 +43,12 @@ pub trait BaseAgent: Send + Sync + Debug {
     /// Start running this agent.
     #[allow(clippy::async_yields_async)]
     async fn run(self) -> Instrumented<JoinHandle<Result<()>>>;
+
+    /// Additional routes for the metrics server to serve alongside
+    /// `/metrics`, e.g. an API for operators.
+    fn http_routes(&self) -> Option<BoxedFilter<(Box<dyn Reply>,)>> {
+        None
+    }
 }
 
 /// Call this from `main` to fully initialize and run the agent for its entire
@@ -68,7 +75,7 @@ pub async fn agent_main<A: BaseAgent>() -> Result<()> {
     let metrics = settings.as_ref().metrics(A::AGENT_NAME)?;
     core_settings.tracing.start_tracing(&metrics)?;
     let agent = A::from_settings(settings, metrics.clone()).await?;
-    metrics.run_http_server();
+    metrics.run_http_server(agent.http_routes());
 
     agent.run().await.await?
 }
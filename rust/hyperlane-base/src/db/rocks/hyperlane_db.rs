@@ -1,20 +1,25 @@
 use std::future::Future;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
 use eyre::Result;
 use paste::paste;
 use tokio::time::sleep;
-use tracing::{debug, instrument, trace};
+use tracing::{debug, instrument, trace, warn};
 
 use hyperlane_core::{
-    HyperlaneDomain, HyperlaneLogStore, HyperlaneMessage, HyperlaneMessageStore,
-    HyperlaneWatermarkedLogStore, InterchainGasExpenditure, InterchainGasPayment,
-    InterchainGasPaymentMeta, LogMeta, H256,
+    Checkpoint, Encode, HyperlaneDomain, HyperlaneLogStore, HyperlaneMessage,
+    HyperlaneMessageStore, HyperlaneWatermarkedLogStore, InflightTransaction,
+    InflightTransactionStore, InterchainGasExpenditure, InterchainGasPayment,
+    InterchainGasPaymentMeta, LogMeta, SignedType, H160, H256, H512,
 };
 
 use super::{
-    storage_types::{InterchainGasExpenditureData, InterchainGasPaymentData},
+    storage_types::{
+        DeadLetter, Equivocation, GasLedgerEntry, GasLedgerEntryKind, InterchainGasExpenditureData,
+        InterchainGasPaymentData, LegacyEquivocation, ReorgEvent, SeenCheckpoint,
+        SignedCheckpointValue, SigningLedgerEntry,
+    },
     DbError, TypedDB, DB,
 };
 
@@ -31,12 +36,26 @@ const GAS_EXPENDITURE_FOR_MESSAGE_ID: &str = "gas_expenditure_for_message_id_v2_
 const PENDING_MESSAGE_RETRY_COUNT_FOR_MESSAGE_ID: &str =
     "pending_message_retry_count_for_message_id_";
 const LATEST_INDEXED_GAS_PAYMENT_BLOCK: &str = "latest_indexed_gas_payment_block";
+const DEAD_LETTER_FOR_NONCE: &str = "dead_letter_for_nonce_";
+const RATE_LIMIT_DEFERRED_UNTIL_FOR_MESSAGE_ID: &str = "rate_limit_deferred_until_for_message_id_";
+const GAS_LEDGER_ENTRY: &str = "gas_ledger_entry_";
+const EQUIVOCATION: &str = "equivocation_";
+const LEGACY_EQUIVOCATION: &str = "legacy_equivocation_";
+const SEEN_CHECKPOINT: &str = "seen_checkpoint_";
+const SEEN_LEGACY_CHECKPOINT: &str = "seen_legacy_checkpoint_";
+const OPTIMISTIC_DELIVERABLE_AT_FOR_MESSAGE_ID: &str = "optimistic_deliverable_at_for_message_id_";
+const OPTIMISTIC_PRE_VERIFY_SUBMITTED_AT_FOR_MESSAGE_ID: &str =
+    "optimistic_pre_verify_submitted_at_for_message_id_";
+const SIGNING_LEDGER_ENTRY_FOR_INDEX: &str = "signing_ledger_entry_for_index_";
+const REORG_EVENT_FOR_NONCE: &str = "reorg_event_for_nonce_";
+const INFLIGHT_TRANSACTION_FOR_CALL_ID: &str = "inflight_transaction_for_call_id_";
 
 type DbResult<T> = std::result::Result<T, DbError>;
 
-/// DB handle for storing data tied to a specific Mailbox.
+/// DB handle for storing data tied to a specific Mailbox. The flag is whether
+/// reorgs are recorded when messages are stored.
 #[derive(Debug, Clone)]
-pub struct HyperlaneRocksDB(HyperlaneDomain, TypedDB);
+pub struct HyperlaneRocksDB(HyperlaneDomain, TypedDB, bool);
 
 impl std::ops::Deref for HyperlaneRocksDB {
     type Target = TypedDB;
@@ -61,7 +80,15 @@ impl AsRef<DB> for HyperlaneRocksDB {
 impl HyperlaneRocksDB {
     /// Instantiated new `HyperlaneRocksDB`
     pub fn new(domain: &HyperlaneDomain, db: DB) -> Self {
-        Self(domain.clone(), TypedDB::new(domain, db))
+        Self(domain.clone(), TypedDB::new(domain, db), false)
+    }
+
+    /// Record a reorg event when a message is indexed for a nonce a different
+    /// message is stored for. Validators use these to stop signing checkpoints
+    /// of a reorged origin until an operator acknowledges the reorg.
+    pub fn with_reorg_detection(mut self) -> Self {
+        self.2 = true;
+        self
     }
 
     /// Get the domain this database is scoped to
@@ -75,12 +102,19 @@ impl HyperlaneRocksDB {
     /// - `nonce` --> `id`
     /// - `id` --> `message`
     /// - `nonce` --> `dispatched block number`
+    ///
+    /// A message for a nonce a different message is stored for does not
+    /// replace it. It is recorded as a reorg event instead if reorg detection
+    /// is enabled.
     pub fn store_message(
         &self,
         message: &HyperlaneMessage,
         dispatched_block_number: u64,
     ) -> DbResult<bool> {
-        if let Ok(Some(_)) = self.retrieve_message_id_by_nonce(&message.nonce) {
+        if let Ok(Some(stored_id)) = self.retrieve_message_id_by_nonce(&message.nonce) {
+            if self.2 && stored_id != message.id() {
+                self.record_reorg(stored_id, message, dispatched_block_number)?;
+            }
             trace!(msg=?message, "Message already stored in db");
             return Ok(false);
         }
@@ -97,6 +131,71 @@ impl HyperlaneRocksDB {
         Ok(true)
     }
 
+    /// Record that `message` was indexed for a nonce the message with
+    /// `stored_id` is stored for. Only the latest reorg is kept for each nonce.
+    fn record_reorg(
+        &self,
+        stored_id: H256,
+        message: &HyperlaneMessage,
+        dispatched_block_number: u64,
+    ) -> DbResult<()> {
+        let id = message.id();
+        if let Some(event) = self.retrieve_reorg_event_by_nonce(&message.nonce)? {
+            if event.message_id == id {
+                return Ok(());
+            }
+        }
+        let event = ReorgEvent {
+            nonce: message.nonce,
+            previous_message_id: stored_id,
+            previous_block_number: self
+                .retrieve_dispatched_block_number_by_nonce(&message.nonce)?
+                .unwrap_or_default(),
+            message_id: id,
+            block_number: dispatched_block_number,
+            timestamp: unix_timestamp(),
+            acknowledged: false,
+        };
+        warn!(
+            ?event,
+            "Indexed a different message for a stored nonce, the origin reorged"
+        );
+        // keep the fresh message so it can replace the stored one once the reorg is acknowledged
+        self.store_message_by_id(&id, message)?;
+        self.store_reorg_event_by_nonce(&message.nonce, &event)
+    }
+
+    /// Retrieve every reorg detected on this domain, ordered by nonce
+    pub fn retrieve_reorg_events(&self) -> Vec<ReorgEvent> {
+        self.iterate_decodable(REORG_EVENT_FOR_NONCE).collect()
+    }
+
+    /// Whether a reorg was detected on this domain that an operator has not
+    /// acknowledged yet
+    pub fn has_unacknowledged_reorgs(&self) -> bool {
+        self.retrieve_reorg_events()
+            .iter()
+            .any(|event| !event.acknowledged)
+    }
+
+    /// Acknowledge every reorg detected on this domain, replacing the stored
+    /// messages with the freshly indexed ones. Returns the acknowledged
+    /// reorgs.
+    pub fn acknowledge_reorgs(&self) -> DbResult<Vec<ReorgEvent>> {
+        let mut acknowledged = vec![];
+        for mut event in self.retrieve_reorg_events() {
+            if event.acknowledged {
+                continue;
+            }
+            self.store_message_id_by_nonce(&event.nonce, &event.message_id)?;
+            self.store_dispatched_block_number_by_nonce(&event.nonce, &event.block_number)?;
+            event.acknowledged = true;
+            self.store_reorg_event_by_nonce(&event.nonce, &event)?;
+            acknowledged.push(event);
+        }
+        Ok(acknowledged)
+    }
+
     /// Retrieve a message by its nonce
     pub fn retrieve_message_by_nonce(&self, nonce: u32) -> DbResult<Option<HyperlaneMessage>> {
         let id = self.retrieve_message_id_by_nonce(&nonce)?;
@@ -106,6 +205,17 @@ impl HyperlaneRocksDB {
         }
     }
 
+    /// Retrieve a message by its id, if it is the message stored for its
+    /// nonce. Messages indexed during a reorg which has not been acknowledged
+    /// yet are not.
+    pub fn retrieve_indexed_message_by_id(&self, id: &H256) -> DbResult<Option<HyperlaneMessage>> {
+        let Some(message) = self.retrieve_message_by_id(id)? else {
+            return Ok(None);
+        };
+        let stored_id = self.retrieve_message_id_by_nonce(&message.nonce)?;
+        Ok((stored_id == Some(*id)).then_some(message))
+    }
+
     // TODO(james): this is a quick-fix for the prover_sync and I don't like it
     /// poll db ever 100 milliseconds waiting for a leaf.
     pub fn wait_for_message_nonce(&self, nonce: u32) -> impl Future<Output = DbResult<H256>> {
@@ -148,15 +258,149 @@ impl HyperlaneRocksDB {
         // Update the total gas payment for the message to include the payment
         self.update_gas_payment_by_message_id(payment)?;
 
+        // Keep the individual payment for accounting
+        self.store_gas_ledger_entry(
+            &GasLedgerEntry {
+                kind: GasLedgerEntryKind::Payment,
+                message_id: payment.message_id,
+                transaction_id: payment_meta.transaction_id,
+                tokens: payment.payment,
+                gas: payment.gas_amount,
+                timestamp: unix_timestamp(),
+            },
+            payment_meta.log_index,
+        )?;
+
         // Return true to indicate the gas payment was processed for the first time
         Ok(true)
     }
 
-    /// Processes the gas expenditure and store the total expenditure for the
-    /// message.
-    pub fn process_gas_expenditure(&self, expenditure: InterchainGasExpenditure) -> DbResult<()> {
+    /// Processes the gas expenditure of the transaction with the given id and
+    /// store the total expenditure for the message.
+    pub fn process_gas_expenditure(
+        &self,
+        expenditure: InterchainGasExpenditure,
+        transaction_id: H512,
+    ) -> DbResult<()> {
         // Update the total gas expenditure for the message to include the payment
-        self.update_gas_expenditure_by_message_id(expenditure)
+        self.update_gas_expenditure_by_message_id(expenditure)?;
+
+        // Keep the individual expenditure for accounting
+        self.store_gas_ledger_entry(
+            &GasLedgerEntry {
+                kind: GasLedgerEntryKind::Expenditure,
+                message_id: expenditure.message_id,
+                transaction_id,
+                tokens: expenditure.tokens_used,
+                gas: expenditure.gas_used,
+                timestamp: unix_timestamp(),
+            },
+            0,
+        )
+    }
+
+    /// Store an entry of the gas ledger. Entries are unique by kind,
+    /// transaction, message and the index of the entry within the transaction.
+    fn store_gas_ledger_entry(&self, entry: &GasLedgerEntry, index: u64) -> DbResult<()> {
+        let mut key = vec![(entry.kind == GasLedgerEntryKind::Payment) as u8];
+        key.extend(entry.transaction_id.to_vec());
+        key.extend(entry.message_id.to_vec());
+        key.extend(index.to_vec());
+        self.store_encodable(GAS_LEDGER_ENTRY, key, entry)
+    }
+
+    /// Retrieve every gas payment and expenditure recorded for the messages
+    /// from this domain
+    pub fn retrieve_gas_ledger(&self) -> Vec<GasLedgerEntry> {
+        self.iterate_decodable(GAS_LEDGER_ENTRY).collect()
+    }
+
+    /// Store evidence of a validator signing conflicting checkpoints. Only
+    /// the latest equivocation is kept for each validator and checkpoint.
+    pub fn store_equivocation<T: SignedCheckpointValue>(
+        &self,
+        equivocation: &Equivocation<T>,
+    ) -> DbResult<()> {
+        let key = checkpoint_slot_key(
+            equivocation.validator,
+            equivocation.first.value.checkpoint(),
+        );
+        self.store_encodable(equivocation_prefix::<T>(), key, equivocation)
+    }
+
+    /// Retrieve every equivocation detected for the validators of this domain
+    pub fn retrieve_equivocations(&self) -> Vec<Equivocation> {
+        self.iterate_decodable(EQUIVOCATION).collect()
+    }
+
+    /// Retrieve every equivocation detected for the validators of this domain
+    /// among their legacy checkpoints
+    pub fn retrieve_legacy_equivocations(&self) -> Vec<LegacyEquivocation> {
+        self.iterate_decodable(LEGACY_EQUIVOCATION).collect()
+    }
+
+    /// Remember a checkpoint fetched from a validator, to detect it signing a
+    /// different checkpoint for the same index later on. Only the first
+    /// checkpoint seen is kept.
+    pub fn store_seen_checkpoint<T: SignedCheckpointValue>(
+        &self,
+        validator: H160,
+        signed: &SignedType<T>,
+    ) -> DbResult<()> {
+        let key = checkpoint_slot_key(validator, signed.value.checkpoint());
+        self.store_encodable(
+            seen_checkpoint_prefix::<T>(),
+            key,
+            &SeenCheckpoint(signed.clone()),
+        )
+    }
+
+    /// Retrieve the checkpoint seen from a validator for the index of
+    /// `checkpoint`, if any
+    pub fn retrieve_seen_checkpoint<T: SignedCheckpointValue>(
+        &self,
+        validator: H160,
+        checkpoint: &Checkpoint,
+    ) -> DbResult<Option<SignedType<T>>> {
+        let key = checkpoint_slot_key(validator, checkpoint);
+        Ok(self
+            .retrieve_decodable::<SeenCheckpoint<T>>(seen_checkpoint_prefix::<T>(), key)?
+            .map(|seen| seen.0))
+    }
+
+    /// Forget the checkpoints seen from validators for indices below
+    /// `min_index`. Returns how many were forgotten.
+    pub fn prune_seen_checkpoints<T: SignedCheckpointValue>(
+        &self,
+        min_index: u32,
+    ) -> DbResult<usize> {
+        self.delete_where(seen_checkpoint_prefix::<T>(), |key| {
+            checkpoint_slot_key_index(key).map_or(false, |index| index < min_index)
+        })
+    }
+
+    /// Record a checkpoint the validator is about to sign in its append-only
+    /// signing ledger. Entries are never replaced: if a different checkpoint
+    /// was already recorded for the index nothing is recorded, and that entry
+    /// is returned so signing can be refused.
+    pub fn record_signed_checkpoint(
+        &self,
+        checkpoint: &Checkpoint,
+    ) -> DbResult<Option<SigningLedgerEntry>> {
+        match self.retrieve_signing_ledger_entry_by_index(&checkpoint.index)? {
+            Some(entry) if entry.checkpoint != *checkpoint => Ok(Some(entry)),
+            Some(_) => Ok(None),
+            None => {
+                self.store_signing_ledger_entry_by_index(
+                    &checkpoint.index,
+                    &SigningLedgerEntry {
+                        checkpoint: *checkpoint,
+                        timestamp: unix_timestamp(),
+                    },
+                )?;
+                Ok(None)
+            }
+        }
     }
 
     /// Update the total gas payment for a message to include gas_payment
@@ -200,6 +444,33 @@ impl HyperlaneRocksDB {
             .complete(message_id))
     }
 
+    /// Retrieve all dead-lettered messages, ordered by nonce
+    pub fn retrieve_dead_letters(&self) -> Vec<DeadLetter> {
+        self.iterate_decodable(DEAD_LETTER_FOR_NONCE).collect()
+    }
+
+    /// Remove a dead-lettered message so it can be processed again. Returns
+    /// the removed entry if there was one.
+    pub fn remove_dead_letter_by_nonce(&self, nonce: u32) -> DbResult<Option<DeadLetter>> {
+        let dead_letter = self.retrieve_dead_letter_by_nonce(&nonce)?;
+        if dead_letter.is_some() {
+            self.delete(DEAD_LETTER_FOR_NONCE, nonce.to_vec())?;
+        }
+        Ok(dead_letter)
+    }
+
+    /// Remove the time a pre-verification of a message was submitted at, once
+    /// it was included or is not expected to be anymore.
+    pub fn remove_optimistic_pre_verify_submitted_at_by_message_id(
+        &self,
+        message_id: &H256,
+    ) -> DbResult<()> {
+        self.delete(
+            OPTIMISTIC_PRE_VERIFY_SUBMITTED_AT_FOR_MESSAGE_ID,
+            message_id.to_vec(),
+        )
+    }
+
     /// Retrieve the total gas payment for a message
     pub fn retrieve_gas_expenditure_by_message_id(
         &self,
@@ -283,6 +554,60 @@ where
     }
 }
 
+impl InflightTransactionStore for HyperlaneRocksDB {
+    fn store_inflight_transaction(&self, call_id: H256, tx: &InflightTransaction) -> Result<()> {
+        Ok(self.store_inflight_transaction_by_call_id(&call_id, tx)?)
+    }
+
+    fn retrieve_inflight_transaction(&self, call_id: H256) -> Result<Option<InflightTransaction>> {
+        Ok(self.retrieve_inflight_transaction_by_call_id(&call_id)?)
+    }
+
+    fn remove_inflight_transaction(&self, call_id: H256) -> Result<()> {
+        Ok(self.delete(INFLIGHT_TRANSACTION_FOR_CALL_ID, call_id.to_vec())?)
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The key of the checkpoint a validator may only sign one value for: the
+/// validator, the mailbox domain and address, and the index.
+fn checkpoint_slot_key(validator: H160, checkpoint: &Checkpoint) -> Vec<u8> {
+    let mut key = validator.to_vec();
+    key.extend(checkpoint.mailbox_domain.to_vec());
+    key.extend(checkpoint.mailbox_address.to_vec());
+    key.extend(checkpoint.index.to_vec());
+    key
+}
+
+/// The checkpoint index of a key made by `checkpoint_slot_key`, which ends in
+/// it.
+fn checkpoint_slot_key_index(key: &[u8]) -> Option<u32> {
+    let index = key.get(key.len().checked_sub(4)?..)?;
+    Some(u32::from_be_bytes(index.try_into().ok()?))
+}
+
+fn equivocation_prefix<T: SignedCheckpointValue>() -> &'static str {
+    if T::LEGACY {
+        LEGACY_EQUIVOCATION
+    } else {
+        EQUIVOCATION
+    }
+}
+
+fn seen_checkpoint_prefix<T: SignedCheckpointValue>() -> &'static str {
+    if T::LEGACY {
+        SEEN_LEGACY_CHECKPOINT
+    } else {
+        SEEN_CHECKPOINT
+    }
+}
+
 /// Generate a call to ChainSetup for the given builder
 macro_rules! make_store_and_retrieve {
     ($vis:vis, $name_suffix:ident, $key_prefix: ident, $key_ty:ty, $val_ty:ty$(,)?) => {
@@ -323,3 +648,52 @@ make_store_and_retrieve!(
     H256,
     u32
 );
+make_store_and_retrieve!(
+    pub,
+    dead_letter_by_nonce,
+    DEAD_LETTER_FOR_NONCE,
+    u32,
+    DeadLetter
+);
+make_store_and_retrieve!(
+    pub,
+    rate_limit_deferred_until_by_message_id,
+    RATE_LIMIT_DEFERRED_UNTIL_FOR_MESSAGE_ID,
+    H256,
+    u64
+);
+make_store_and_retrieve!(
+    pub,
+    optimistic_deliverable_at_by_message_id,
+    OPTIMISTIC_DELIVERABLE_AT_FOR_MESSAGE_ID,
+    H256,
+    u64
+);
+make_store_and_retrieve!(
+    pub,
+    optimistic_pre_verify_submitted_at_by_message_id,
+    OPTIMISTIC_PRE_VERIFY_SUBMITTED_AT_FOR_MESSAGE_ID,
+    H256,
+    u64
+);
+make_store_and_retrieve!(
+    pub(self),
+    reorg_event_by_nonce,
+    REORG_EVENT_FOR_NONCE,
+    u32,
+    ReorgEvent
+);
+make_store_and_retrieve!(
+    pub(self),
+    signing_ledger_entry_by_index,
+    SIGNING_LEDGER_ENTRY_FOR_INDEX,
+    u32,
+    SigningLedgerEntry
+);
+make_store_and_retrieve!(
+    pub(self),
+    inflight_transaction_by_call_id,
+    INFLIGHT_TRANSACTION_FOR_CALL_ID,
+    H256,
+    InflightTransaction
+);
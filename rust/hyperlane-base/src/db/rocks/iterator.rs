@@ -2,30 +2,43 @@ use std::marker::PhantomData;
 
 use rocksdb::DBIterator;
 
-use hyperlane_core::{Decode, Encode};
+use hyperlane_core::Decode;
 
 /// An iterator over a prefix that deserializes values
 pub struct PrefixIterator<'a, V> {
     iter: DBIterator<'a>,
-    prefix: &'a [u8],
+    prefix: Vec<u8>,
     _phantom: PhantomData<*const V>,
 }
 
+impl<'a, V> PrefixIterator<'a, V> {
+    /// Iterate over the values of the keys `iter` yields that start with
+    /// `prefix`. `iter` must start at the first such key.
+    pub fn new(iter: DBIterator<'a>, prefix: Vec<u8>) -> Self {
+        Self {
+            iter,
+            prefix,
+            _phantom: PhantomData,
+        }
+    }
+}
+
 impl<'a, V> Iterator for PrefixIterator<'a, V>
 where
-    V: Encode + Decode,
+    V: Decode,
 {
     type Item = V;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let prefix = self.prefix;
-        self.iter.find_map(|r| {
-            let (k, v) = r.expect("Database error when iterating prefixed keys");
-            if k.strip_prefix(prefix).is_some() {
-                Some(V::read_from(&mut &v[..]).expect("!corrupt"))
-            } else {
-                None
-            }
-        })
+        let (k, v) = self
+            .iter
+            .next()?
+            .expect("Database error when iterating prefixed keys");
+        // Keys are sorted so there are no more matches once past the prefix
+        if k.starts_with(&self.prefix) {
+            Some(V::read_from(&mut &v[..]).expect("!corrupt"))
+        } else {
+            None
+        }
     }
 }
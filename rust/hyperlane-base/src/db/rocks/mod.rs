@@ -1,11 +1,15 @@
 use std::path::PathBuf;
 use std::{io, path::Path, sync::Arc};
 
-use hyperlane_core::HyperlaneProtocolError;
-use rocksdb::{Options, DB as Rocks};
+use hyperlane_core::{Decode, HyperlaneProtocolError};
+use rocksdb::{Options, WriteBatch, DB as Rocks};
 use tracing::info;
 
 pub use hyperlane_db::*;
+pub use storage_types::{
+    DeadLetter, Equivocation, GasLedgerEntry, GasLedgerEntryKind, LegacyEquivocation, ReorgEvent,
+    SeenCheckpoint, SignedCheckpointValue, SigningLedgerEntry,
+};
 pub use typed_db::*;
 
 /// Shared functionality surrounding use of rocksdb
@@ -103,4 +107,37 @@ impl DB {
     pub fn retrieve(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         Ok(self.0.get(key)?)
     }
+
+    /// Remove a value from the DB
+    pub fn delete(&self, key: &[u8]) -> Result<()> {
+        Ok(self.0.delete(key)?)
+    }
+
+    /// Remove every key starting with `prefix` for which `remove` returns true
+    /// given the rest of the key. Returns how many keys were removed.
+    pub fn delete_prefixed_where(
+        &self,
+        prefix: &[u8],
+        mut remove: impl FnMut(&[u8]) -> bool,
+    ) -> Result<usize> {
+        let mut batch = WriteBatch::default();
+        for item in self.0.prefix_iterator(prefix) {
+            let (key, _) = item?;
+            // Keys are sorted so there are no more matches once past the prefix
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if remove(&key[prefix.len()..]) {
+                batch.delete(&key);
+            }
+        }
+        let removed = batch.len();
+        self.0.write(batch)?;
+        Ok(removed)
+    }
+
+    /// Iterate over the values of all keys starting with `prefix`
+    pub fn prefix_iterator<V: Decode>(&self, prefix: &[u8]) -> iterator::PrefixIterator<'_, V> {
+        iterator::PrefixIterator::new(self.0.prefix_iterator(prefix), prefix.to_vec())
+    }
 }
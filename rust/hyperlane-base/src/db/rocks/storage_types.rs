@@ -1,8 +1,12 @@
+use std::fmt::Debug;
 use std::io::{Read, Write};
 
+use serde::Serialize;
+
 use hyperlane_core::{
-    Decode, Encode, HyperlaneProtocolError, InterchainGasExpenditure, InterchainGasPayment, H256,
-    U256,
+    Checkpoint, CheckpointWithMessageId, Decode, Encode, HyperlaneProtocolError,
+    InterchainGasExpenditure, InterchainGasPayment, Signable, Signature, SignedType, H160, H256,
+    H512, U256,
 };
 
 /// Subset of `InterchainGasPayment` excluding the message id which is stored in
@@ -120,3 +124,364 @@ impl Decode for InterchainGasExpenditureData {
         })
     }
 }
+
+/// A message the relayer gave up on delivering.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    /// Id of the message
+    pub message_id: H256,
+    /// Nonce of the message on its origin
+    pub nonce: u32,
+    /// Why delivery was given up on
+    pub reason: String,
+    /// The last error encountered while trying to deliver the message, if any
+    pub last_error: Option<String>,
+    /// Unix timestamp, in seconds, of when delivery was given up on
+    pub timestamp: u64,
+}
+
+impl Encode for DeadLetter {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: Write,
+    {
+        let mut written = self.message_id.write_to(writer)?
+            + self.nonce.write_to(writer)?
+            + self.reason.write_to(writer)?
+            + self.last_error.is_some().write_to(writer)?;
+        if let Some(last_error) = &self.last_error {
+            written += last_error.write_to(writer)?;
+        }
+        Ok(written + self.timestamp.write_to(writer)?)
+    }
+}
+
+impl Decode for DeadLetter {
+    fn read_from<R>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>
+    where
+        R: Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            message_id: H256::read_from(reader)?,
+            nonce: u32::read_from(reader)?,
+            reason: String::read_from(reader)?,
+            last_error: if bool::read_from(reader)? {
+                Some(String::read_from(reader)?)
+            } else {
+                None
+            },
+            timestamp: u64::read_from(reader)?,
+        })
+    }
+}
+
+/// Whether a gas ledger entry records a payment or an expenditure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasLedgerEntryKind {
+    /// Native tokens paid to the IGP on the origin for a message
+    Payment,
+    /// Native tokens spent on the destination attempting to deliver a message
+    Expenditure,
+}
+
+/// A single gas payment or expenditure for a message, kept so the costs of
+/// the relayer can be reconciled with what was paid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasLedgerEntry {
+    /// Whether this is a payment or an expenditure
+    pub kind: GasLedgerEntryKind,
+    /// Id of the message
+    pub message_id: H256,
+    /// The transaction the payment or expenditure was made in
+    pub transaction_id: H512,
+    /// Amount of native tokens paid on the origin or spent on the destination
+    pub tokens: U256,
+    /// Amount of destination gas paid for or used
+    pub gas: U256,
+    /// Unix timestamp, in seconds, of when the entry was recorded
+    pub timestamp: u64,
+}
+
+impl Encode for GasLedgerEntry {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: Write,
+    {
+        Ok((self.kind == GasLedgerEntryKind::Payment).write_to(writer)?
+            + self.message_id.write_to(writer)?
+            + self.transaction_id.write_to(writer)?
+            + self.tokens.write_to(writer)?
+            + self.gas.write_to(writer)?
+            + self.timestamp.write_to(writer)?)
+    }
+}
+
+impl Decode for GasLedgerEntry {
+    fn read_from<R>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>
+    where
+        R: Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            kind: if bool::read_from(reader)? {
+                GasLedgerEntryKind::Payment
+            } else {
+                GasLedgerEntryKind::Expenditure
+            },
+            message_id: H256::read_from(reader)?,
+            transaction_id: H512::read_from(reader)?,
+            tokens: U256::read_from(reader)?,
+            gas: U256::read_from(reader)?,
+            timestamp: u64::read_from(reader)?,
+        })
+    }
+}
+
+/// A value validators sign which contains a checkpoint. Validators may only
+/// sign one such value for an index of a mailbox.
+pub trait SignedCheckpointValue:
+    Signable + Copy + Eq + Debug + Serialize + Send + Sync + 'static
+{
+    /// Whether this is a legacy checkpoint, signed without a message id
+    const LEGACY: bool;
+
+    /// The checkpoint the value contains
+    fn checkpoint(&self) -> &Checkpoint;
+
+    /// Write the value to the writer
+    fn write_value<W: Write>(&self, writer: &mut W) -> std::io::Result<usize>;
+
+    /// Read a value written by `write_value`
+    fn read_value<R: Read>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>;
+}
+
+impl SignedCheckpointValue for Checkpoint {
+    const LEGACY: bool = true;
+
+    fn checkpoint(&self) -> &Checkpoint {
+        self
+    }
+
+    fn write_value<W: Write>(&self, writer: &mut W) -> std::io::Result<usize> {
+        Ok(self.mailbox_address.write_to(writer)?
+            + self.mailbox_domain.write_to(writer)?
+            + self.root.write_to(writer)?
+            + self.index.write_to(writer)?)
+    }
+
+    fn read_value<R: Read>(reader: &mut R) -> Result<Self, HyperlaneProtocolError> {
+        Ok(Checkpoint {
+            mailbox_address: H256::read_from(reader)?,
+            mailbox_domain: u32::read_from(reader)?,
+            root: H256::read_from(reader)?,
+            index: u32::read_from(reader)?,
+        })
+    }
+}
+
+impl SignedCheckpointValue for CheckpointWithMessageId {
+    const LEGACY: bool = false;
+
+    fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    fn write_value<W: Write>(&self, writer: &mut W) -> std::io::Result<usize> {
+        Ok(self.checkpoint.write_value(writer)? + self.message_id.write_to(writer)?)
+    }
+
+    fn read_value<R: Read>(reader: &mut R) -> Result<Self, HyperlaneProtocolError> {
+        Ok(CheckpointWithMessageId {
+            checkpoint: Checkpoint::read_value(reader)?,
+            message_id: H256::read_from(reader)?,
+        })
+    }
+}
+
+/// A checkpoint a validator signed, as remembered to detect equivocations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeenCheckpoint<T: SignedCheckpointValue>(pub SignedType<T>);
+
+impl<T: SignedCheckpointValue> Encode for SeenCheckpoint<T> {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: Write,
+    {
+        write_signed_checkpoint(&self.0, writer)
+    }
+}
+
+impl<T: SignedCheckpointValue> Decode for SeenCheckpoint<T> {
+    fn read_from<R>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>
+    where
+        R: Read,
+        Self: Sized,
+    {
+        Ok(Self(read_signed_checkpoint(reader)?))
+    }
+}
+
+/// Two different checkpoints signed by a validator for the same index of a
+/// mailbox, which is evidence of fraud by the validator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Equivocation<T: SignedCheckpointValue = CheckpointWithMessageId> {
+    /// The validator which signed both checkpoints
+    pub validator: H160,
+    /// The checkpoint which was seen first
+    pub first: SignedType<T>,
+    /// The conflicting checkpoint
+    pub second: SignedType<T>,
+    /// Unix timestamp, in seconds, of when the equivocation was detected
+    pub timestamp: u64,
+}
+
+/// Two different legacy checkpoints signed by a validator for the same index
+/// of a mailbox.
+pub type LegacyEquivocation = Equivocation<Checkpoint>;
+
+impl<T: SignedCheckpointValue> Encode for Equivocation<T> {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: Write,
+    {
+        Ok(self.validator.write_to(writer)?
+            + write_signed_checkpoint(&self.first, writer)?
+            + write_signed_checkpoint(&self.second, writer)?
+            + self.timestamp.write_to(writer)?)
+    }
+}
+
+impl<T: SignedCheckpointValue> Decode for Equivocation<T> {
+    fn read_from<R>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>
+    where
+        R: Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            validator: H160::read_from(reader)?,
+            first: read_signed_checkpoint(reader)?,
+            second: read_signed_checkpoint(reader)?,
+            timestamp: u64::read_from(reader)?,
+        })
+    }
+}
+
+/// A checkpoint recorded in the append-only signing ledger of a validator
+/// before it was signed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SigningLedgerEntry {
+    /// The signed checkpoint
+    pub checkpoint: Checkpoint,
+    /// Unix timestamp, in seconds, of when the checkpoint was recorded
+    pub timestamp: u64,
+}
+
+impl Encode for SigningLedgerEntry {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: Write,
+    {
+        Ok(self.checkpoint.mailbox_address.write_to(writer)?
+            + self.checkpoint.mailbox_domain.write_to(writer)?
+            + self.checkpoint.root.write_to(writer)?
+            + self.checkpoint.index.write_to(writer)?
+            + self.timestamp.write_to(writer)?)
+    }
+}
+
+impl Decode for SigningLedgerEntry {
+    fn read_from<R>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>
+    where
+        R: Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            checkpoint: Checkpoint {
+                mailbox_address: H256::read_from(reader)?,
+                mailbox_domain: u32::read_from(reader)?,
+                root: H256::read_from(reader)?,
+                index: u32::read_from(reader)?,
+            },
+            timestamp: u64::read_from(reader)?,
+        })
+    }
+}
+
+/// A message indexed for a nonce a different message was already stored for,
+/// which is evidence of the origin chain reorging after the stored message
+/// was indexed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReorgEvent {
+    /// Nonce of the reorged message
+    pub nonce: u32,
+    /// Id of the stored message
+    pub previous_message_id: H256,
+    /// Block the stored message was dispatched in
+    pub previous_block_number: u64,
+    /// Id of the freshly indexed message
+    pub message_id: H256,
+    /// Block the freshly indexed message was dispatched in
+    pub block_number: u64,
+    /// Unix timestamp, in seconds, of when the reorg was detected
+    pub timestamp: u64,
+    /// Whether an operator acknowledged the reorg, replacing the stored
+    /// message with the freshly indexed one
+    pub acknowledged: bool,
+}
+
+impl Encode for ReorgEvent {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: Write,
+    {
+        Ok(self.nonce.write_to(writer)?
+            + self.previous_message_id.write_to(writer)?
+            + self.previous_block_number.write_to(writer)?
+            + self.message_id.write_to(writer)?
+            + self.block_number.write_to(writer)?
+            + self.timestamp.write_to(writer)?
+            + self.acknowledged.write_to(writer)?)
+    }
+}
+
+impl Decode for ReorgEvent {
+    fn read_from<R>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>
+    where
+        R: Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            nonce: u32::read_from(reader)?,
+            previous_message_id: H256::read_from(reader)?,
+            previous_block_number: u64::read_from(reader)?,
+            message_id: H256::read_from(reader)?,
+            block_number: u64::read_from(reader)?,
+            timestamp: u64::read_from(reader)?,
+            acknowledged: bool::read_from(reader)?,
+        })
+    }
+}
+
+fn write_signed_checkpoint<T: SignedCheckpointValue, W: Write>(
+    signed: &SignedType<T>,
+    writer: &mut W,
+) -> std::io::Result<usize> {
+    Ok(signed.value.write_value(writer)?
+        + signed.signature.r.write_to(writer)?
+        + signed.signature.s.write_to(writer)?
+        + signed.signature.v.write_to(writer)?)
+}
+
+fn read_signed_checkpoint<T: SignedCheckpointValue, R: Read>(
+    reader: &mut R,
+) -> Result<SignedType<T>, HyperlaneProtocolError> {
+    Ok(SignedType {
+        value: T::read_value(reader)?,
+        signature: Signature {
+            r: U256::read_from(reader)?,
+            s: U256::read_from(reader)?,
+            v: u64::read_from(reader)?,
+        },
+    })
+}
@@ -31,11 +31,14 @@ where
 #[cfg(test)]
 mod test {
     use hyperlane_core::{
-        HyperlaneDomain, HyperlaneLogStore, HyperlaneMessage, LogMeta, RawHyperlaneMessage, H256,
-        H512, U256,
+        Checkpoint, CheckpointWithMessageId, HyperlaneDomain, HyperlaneLogStore, HyperlaneMessage,
+        LogMeta, RawHyperlaneMessage, Signature, SignedCheckpointWithMessageId, H160, H256, H512,
+        U256,
     };
 
-    use crate::db::HyperlaneRocksDB;
+    use hyperlane_core::{InterchainGasExpenditure, InterchainGasPayment};
+
+    use crate::db::{DeadLetter, Equivocation, GasLedgerEntryKind, HyperlaneRocksDB};
 
     use super::*;
 
@@ -75,4 +78,215 @@ mod test {
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn db_stores_lists_and_removes_dead_letters() {
+        run_test_db(|db| async move {
+            let db_a =
+                HyperlaneRocksDB::new(&HyperlaneDomain::new_test_domain("origin_a"), db.clone());
+            let db_b = HyperlaneRocksDB::new(&HyperlaneDomain::new_test_domain("origin_b"), db);
+
+            let dead_letter = |nonce: u32, last_error: Option<&str>| DeadLetter {
+                message_id: H256::from_low_u64_be(nonce as u64),
+                nonce,
+                reason: "recipient is not a contract".into(),
+                last_error: last_error.map(Into::into),
+                timestamp: 1_700_000_000,
+            };
+            let first = dead_letter(1, None);
+            let second = dead_letter(2, Some("Error when estimating costs"));
+            db_a.store_dead_letter_by_nonce(&second.nonce, &second)
+                .unwrap();
+            db_a.store_dead_letter_by_nonce(&first.nonce, &first)
+                .unwrap();
+            db_b.store_dead_letter_by_nonce(&7, &dead_letter(7, None))
+                .unwrap();
+
+            assert_eq!(
+                db_a.retrieve_dead_letters(),
+                vec![first.clone(), second.clone()]
+            );
+
+            assert_eq!(db_a.remove_dead_letter_by_nonce(1).unwrap(), Some(first));
+            assert_eq!(db_a.remove_dead_letter_by_nonce(1).unwrap(), None);
+            assert_eq!(db_a.retrieve_dead_letters(), vec![second]);
+            assert_eq!(db_b.retrieve_dead_letters().len(), 1);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn db_records_gas_ledger_entries() {
+        run_test_db(|db| async move {
+            let db = HyperlaneRocksDB::new(&HyperlaneDomain::new_test_domain("test"), db);
+            let message_id = H256::from_low_u64_be(1);
+            let payment = |log_index: u64, payment: u64| {
+                let meta = LogMeta {
+                    address: H256::zero(),
+                    block_number: 1,
+                    block_hash: H256::zero(),
+                    transaction_id: H512::from_low_u64_be(10),
+                    transaction_index: 0,
+                    log_index: U256::from(log_index),
+                };
+                let payment = InterchainGasPayment {
+                    message_id,
+                    payment: U256::from(payment),
+                    gas_amount: U256::from(payment * 2),
+                };
+                (payment, meta)
+            };
+
+            // A payment, a top-up in the same transaction and a duplicate of the first
+            for (p, meta) in [payment(0, 100), payment(1, 50), payment(0, 100)] {
+                db.process_gas_payment(p, &meta).unwrap();
+            }
+            db.process_gas_expenditure(
+                InterchainGasExpenditure {
+                    message_id,
+                    tokens_used: U256::from(30),
+                    gas_used: U256::from(3),
+                },
+                H512::from_low_u64_be(20),
+            )
+            .unwrap();
+
+            let ledger = db.retrieve_gas_ledger();
+            assert_eq!(ledger.len(), 3);
+            let (payments, expenditures): (Vec<_>, Vec<_>) = ledger
+                .iter()
+                .partition(|e| e.kind == GasLedgerEntryKind::Payment);
+            assert_eq!(
+                payments.iter().map(|e| e.tokens).collect::<Vec<_>>(),
+                vec![U256::from(100), U256::from(50)]
+            );
+            assert_eq!(expenditures[0].transaction_id, H512::from_low_u64_be(20));
+            assert_eq!(expenditures[0].gas, U256::from(3));
+            assert_eq!(
+                db.retrieve_gas_payment_by_message_id(message_id)
+                    .unwrap()
+                    .payment,
+                U256::from(150)
+            );
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn db_stores_equivocations() {
+        run_test_db(|db| async move {
+            let db = HyperlaneRocksDB::new(&HyperlaneDomain::new_test_domain("test"), db);
+            let signed = |message_id: u64| SignedCheckpointWithMessageId {
+                value: CheckpointWithMessageId {
+                    checkpoint: Checkpoint {
+                        mailbox_address: H256::from_low_u64_be(2),
+                        mailbox_domain: 1,
+                        root: H256::from_low_u64_be(3),
+                        index: 4,
+                    },
+                    message_id: H256::from_low_u64_be(message_id),
+                },
+                signature: Signature {
+                    r: U256::from(5),
+                    s: U256::from(6),
+                    v: 27,
+                },
+            };
+            let equivocation = Equivocation {
+                validator: H160::from_low_u64_be(7),
+                first: signed(8),
+                second: signed(9),
+                timestamp: 10,
+            };
+            db.store_equivocation(&equivocation).unwrap();
+            assert_eq!(db.retrieve_equivocations(), vec![equivocation]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn db_refuses_conflicting_signing_ledger_entries() {
+        run_test_db(|db| async move {
+            let db = HyperlaneRocksDB::new(&HyperlaneDomain::new_test_domain("test"), db);
+            let checkpoint = |root: u64| Checkpoint {
+                mailbox_address: H256::from_low_u64_be(2),
+                mailbox_domain: 1,
+                root: H256::from_low_u64_be(root),
+                index: 4,
+            };
+            assert_eq!(db.record_signed_checkpoint(&checkpoint(3)).unwrap(), None);
+            // signing the same checkpoint again is fine
+            assert_eq!(db.record_signed_checkpoint(&checkpoint(3)).unwrap(), None);
+
+            let conflict = db.record_signed_checkpoint(&checkpoint(5)).unwrap();
+            assert_eq!(conflict.map(|entry| entry.checkpoint), Some(checkpoint(3)));
+            // the conflicting checkpoint was not recorded
+            let conflict = db.record_signed_checkpoint(&checkpoint(5)).unwrap();
+            assert_eq!(conflict.map(|entry| entry.checkpoint), Some(checkpoint(3)));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn db_records_and_acknowledges_reorgs() {
+        run_test_db(|db| async move {
+            let db = HyperlaneRocksDB::new(&HyperlaneDomain::new_test_domain("test"), db)
+                .with_reorg_detection();
+            let message = |body: u8| HyperlaneMessage {
+                nonce: 0,
+                body: vec![body],
+                ..Default::default()
+            };
+            let (stored, reorged) = (message(1), message(2));
+            db.store_message(&stored, 10).unwrap();
+            assert!(!db.has_unacknowledged_reorgs());
+
+            assert!(!db.store_message(&reorged, 11).unwrap());
+            assert!(db.has_unacknowledged_reorgs());
+            let events = db.retrieve_reorg_events();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].previous_message_id, stored.id());
+            assert_eq!(events[0].previous_block_number, 10);
+            assert_eq!(events[0].message_id, reorged.id());
+            assert_eq!(events[0].block_number, 11);
+            // the stored message is kept until the reorg is acknowledged
+            let by_nonce = db.retrieve_message_by_nonce(0).unwrap();
+            assert_eq!(by_nonce.map(|m| m.id()), Some(stored.id()));
+            let by_id = |id| {
+                db.retrieve_indexed_message_by_id(&id)
+                    .unwrap()
+                    .map(|m| m.id())
+            };
+            assert_eq!(by_id(stored.id()), Some(stored.id()));
+            assert_eq!(by_id(reorged.id()), None);
+
+            let acknowledged = db.acknowledge_reorgs().unwrap();
+            assert_eq!(acknowledged.len(), 1);
+            assert!(!db.has_unacknowledged_reorgs());
+            let by_nonce = db.retrieve_message_by_nonce(0).unwrap();
+            assert_eq!(by_nonce.map(|m| m.id()), Some(reorged.id()));
+            assert_eq!(by_id(stored.id()), None);
+            assert_eq!(by_id(reorged.id()), Some(reorged.id()));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn db_ignores_reorgs_without_reorg_detection() {
+        run_test_db(|db| async move {
+            let db = HyperlaneRocksDB::new(&HyperlaneDomain::new_test_domain("test"), db);
+            let message = |body: u8| HyperlaneMessage {
+                nonce: 0,
+                body: vec![body],
+                ..Default::default()
+            };
+            let (stored, reorged) = (message(1), message(2));
+            db.store_message(&stored, 10).unwrap();
+            assert!(!db.store_message(&reorged, 11).unwrap());
+            assert!(db.retrieve_reorg_events().is_empty());
+            let by_nonce = db.retrieve_message_by_nonce(0).unwrap();
+            assert_eq!(by_nonce.map(|m| m.id()), Some(stored.id()));
+        })
+        .await;
+    }
 }
@@ -1,6 +1,6 @@
 use hyperlane_core::{Decode, Encode, HyperlaneDomain};
 
-use crate::db::{DbError, DB};
+use crate::db::{iterator::PrefixIterator, DbError, DB};
 
 type Result<T> = std::result::Result<T, DbError>;
 
@@ -67,6 +67,29 @@ impl TypedDB {
             .map_err(Into::into)
     }
 
+    /// Remove a value
+    pub fn delete(&self, prefix: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Result<()> {
+        self.db
+            .delete(&self.prefixed_key(prefix.as_ref(), key.as_ref()))
+    }
+
+    /// Remove the values of all keys with the given prefix for which `remove`
+    /// returns true given the rest of the key. Returns how many were removed.
+    pub fn delete_where(
+        &self,
+        prefix: impl AsRef<[u8]>,
+        remove: impl FnMut(&[u8]) -> bool,
+    ) -> Result<usize> {
+        self.db
+            .delete_prefixed_where(&self.prefixed_key(prefix.as_ref(), &[]), remove)
+    }
+
+    /// Iterate over the values of all keys with the given prefix
+    pub fn iterate_decodable<V: Decode>(&self, prefix: impl AsRef<[u8]>) -> PrefixIterator<'_, V> {
+        self.db
+            .prefix_iterator(&self.prefixed_key(prefix.as_ref(), &[]))
+    }
+
     /// Store encodable kv pair
     pub fn store_keyed_encodable<K: Encode, V: Encode>(
         &self,
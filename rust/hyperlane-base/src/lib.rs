@@ -15,6 +15,10 @@ pub use agent::*;
 mod metrics;
 pub use metrics::*;
 
+/// Helpers for the HTTP APIs agents serve alongside the metrics
+mod server;
+pub use server::*;
+
 mod contract_sync;
 pub use contract_sync::*;
 
@@ -11,6 +11,7 @@ use prometheus::{
 };
 use tokio::task::JoinHandle;
 use tracing::warn;
+use warp::{filters::BoxedFilter, Reply};
 
 use ethers_prometheus::{json_rpc_client::JsonRpcClientMetrics, middleware::MiddlewareMetrics};
 
@@ -39,12 +40,25 @@ pub struct CoreMetrics {
     last_known_message_nonce: IntGaugeVec,
     validator_checkpoint_index: IntGaugeVec,
     submitter_queue_length: IntGaugeVec,
+    submitter_lane_queue_length: IntGaugeVec,
 
     operations_processed_count: IntCounterVec,
+    batched_operations_count: IntCounterVec,
     messages_processed_count: IntCounterVec,
 
     latest_checkpoint: IntGaugeVec,
 
+    transaction_escalations: IntCounterVec,
+    transaction_escalation_final_gas_price: IntGaugeVec,
+
+    rate_limited_messages: IntCounterVec,
+    rate_limit_deferred_messages: IntGaugeVec,
+
+    merkle_root_multisig_proofs: IntCounterVec,
+    validator_equivocations: IntCounterVec,
+    refused_conflicting_signatures: IntCounterVec,
+    unacknowledged_reorgs: IntGaugeVec,
+
     /// Set of metrics that tightly wrap the JsonRpcClient for use with the
     /// quorum provider.
     json_rpc_client_metrics: OnceLock<JsonRpcClientMetrics>,
@@ -129,6 +143,16 @@ impl CoreMetrics {
             registry
         )?;
 
+        let submitter_lane_queue_length = register_int_gauge_vec_with_registry!(
+            opts!(
+                namespaced!("submitter_lane_queue_length"),
+                "Submitter queue length of each submission lane",
+                const_labels_ref
+            ),
+            &["remote", "queue_name", "lane"],
+            registry
+        )?;
+
         let latest_checkpoint = register_int_gauge_vec_with_registry!(
             opts!(
                 namespaced!("latest_checkpoint"),
@@ -149,6 +173,16 @@ impl CoreMetrics {
             registry
         )?;
 
+        let batched_operations_count = register_int_counter_vec_with_registry!(
+            opts!(
+                namespaced!("batched_operations_count"),
+                "Number of operations submitted as part of a batch transaction",
+                const_labels_ref
+            ),
+            &["outcome", "chain"],
+            registry
+        )?;
+
         let messages_processed_count = register_int_counter_vec_with_registry!(
             opts!(
                 namespaced!("messages_processed_count"),
@@ -159,6 +193,86 @@ impl CoreMetrics {
             registry
         )?;
 
+        let transaction_escalations = register_int_counter_vec_with_registry!(
+            opts!(
+                namespaced!("transaction_escalations"),
+                "Number of times a stuck transaction was replaced with a higher gas price",
+                const_labels_ref
+            ),
+            &["chain"],
+            registry
+        )?;
+
+        let transaction_escalation_final_gas_price = register_int_gauge_vec_with_registry!(
+            opts!(
+                namespaced!("transaction_escalation_final_gas_price"),
+                "Effective gas price paid by the last escalated transaction to be included",
+                const_labels_ref
+            ),
+            &["chain"],
+            registry
+        )?;
+
+        let rate_limited_messages = register_int_counter_vec_with_registry!(
+            opts!(
+                namespaced!("rate_limited_messages"),
+                "Number of messages deferred because a rate limit was exceeded",
+                const_labels_ref
+            ),
+            &["limiter", "origin"],
+            registry
+        )?;
+
+        let rate_limit_deferred_messages = register_int_gauge_vec_with_registry!(
+            opts!(
+                namespaced!("rate_limit_deferred_messages"),
+                "Number of messages currently deferred because a rate limit was exceeded",
+                const_labels_ref
+            ),
+            &["limiter", "origin"],
+            registry
+        )?;
+
+        let merkle_root_multisig_proofs = register_int_counter_vec_with_registry!(
+            opts!(
+                namespaced!("merkle_root_multisig_proofs"),
+                "Number of messages proven for a merkle root multisig ISM, by the checkpoint they were proven against",
+                const_labels_ref
+            ),
+            &["origin", "destination", "checkpoint"],
+            registry
+        )?;
+
+        let validator_equivocations = register_int_counter_vec_with_registry!(
+            opts!(
+                namespaced!("validator_equivocations"),
+                "Number of times a validator was found to have signed conflicting checkpoints for the same index",
+                const_labels_ref
+            ),
+            &["origin", "validator"],
+            registry
+        )?;
+
+        let refused_conflicting_signatures = register_int_counter_vec_with_registry!(
+            opts!(
+                namespaced!("refused_conflicting_signatures"),
+                "Number of times the validator refused to sign a checkpoint conflicting with one it already signed for the same index",
+                const_labels_ref
+            ),
+            &["origin"],
+            registry
+        )?;
+
+        let unacknowledged_reorgs = register_int_gauge_vec_with_registry!(
+            opts!(
+                namespaced!("unacknowledged_reorgs"),
+                "Number of message nonces the origin was found to have reorged that an operator has not acknowledged yet",
+                const_labels_ref
+            ),
+            &["origin"],
+            registry
+        )?;
+
         Ok(Self {
             agent_name: for_agent.into(),
             registry,
@@ -172,12 +286,25 @@ impl CoreMetrics {
             validator_checkpoint_index,
 
             submitter_queue_length,
+            submitter_lane_queue_length,
 
             operations_processed_count,
+            batched_operations_count,
             messages_processed_count,
 
             latest_checkpoint,
 
+            transaction_escalations,
+            transaction_escalation_final_gas_price,
+
+            rate_limited_messages,
+            rate_limit_deferred_messages,
+
+            merkle_root_multisig_proofs,
+            validator_equivocations,
+            refused_conflicting_signatures,
+            unacknowledged_reorgs,
+
             json_rpc_client_metrics: OnceLock::new(),
             provider_metrics: OnceLock::new(),
         })
@@ -325,6 +452,17 @@ impl CoreMetrics {
         self.submitter_queue_length.clone()
     }
 
+    /// Measure of the queue lengths of each submission lane in Submitter
+    /// instances. `submitter_queue_length` is their sum over the lanes.
+    ///
+    /// Labels:
+    /// - `remote`: Remote chain the queue is for.
+    /// - `queue_name`: Which queue the message is in.
+    /// - `lane`: Index of the submission lane the queue belongs to.
+    pub fn submitter_lane_queue_length(&self) -> IntGaugeVec {
+        self.submitter_lane_queue_length.clone()
+    }
+
     /// The number of operations successfully submitted by this process during
     /// its lifetime.
     ///
@@ -352,6 +490,19 @@ impl CoreMetrics {
         self.operations_processed_count.clone()
     }
 
+    /// The number of operations submitted as part of a batch transaction. The
+    /// operations of executed batches are also counted as `submitted` by
+    /// `operations_processed_count`.
+    ///
+    /// Labels:
+    /// - `outcome`: `executed` when the batch transaction was executed, or
+    ///   `failed` when it failed and its operations are submitted individually
+    ///   instead.
+    /// - `chain`: Chain the batch was submitted to.
+    pub fn batched_operations_count(&self) -> IntCounterVec {
+        self.batched_operations_count.clone()
+    }
+
     /// The number of messages successfully submitted by this process during its
     /// lifetime.
     ///
@@ -369,6 +520,93 @@ impl CoreMetrics {
         self.messages_processed_count.clone()
     }
 
+    /// The number of times a transaction which was not included in time was
+    /// replaced with one using the same nonce and a higher gas price.
+    ///
+    /// Labels:
+    /// - `chain`: Chain the transaction was submitted to.
+    pub fn transaction_escalations(&self) -> IntCounterVec {
+        self.transaction_escalations.clone()
+    }
+
+    /// The effective gas price, in wei, paid by the most recent escalated
+    /// transaction to be included.
+    ///
+    /// Labels:
+    /// - `chain`: Chain the transaction was submitted to.
+    pub fn transaction_escalation_final_gas_price(&self) -> IntGaugeVec {
+        self.transaction_escalation_final_gas_price.clone()
+    }
+
+    /// The number of messages deferred because sending them to a submitter
+    /// would have exceeded a rate limit. Each message is counted once, when it
+    /// is first deferred.
+    ///
+    /// Labels:
+    /// - `limiter`: Name of the rate limit that was exceeded.
+    /// - `origin`: Chain the message came from.
+    pub fn rate_limited_messages(&self) -> IntCounterVec {
+        self.rate_limited_messages.clone()
+    }
+
+    /// The number of messages currently deferred because sending them to a
+    /// submitter would exceed a rate limit.
+    ///
+    /// Labels:
+    /// - `limiter`: Name of the rate limit that is exceeded.
+    /// - `origin`: Chain the messages came from.
+    pub fn rate_limit_deferred_messages(&self) -> IntGaugeVec {
+        self.rate_limit_deferred_messages.clone()
+    }
+
+    /// The number of messages proven for merkle root multisig ISMs. Messages
+    /// are proven against the checkpoint at their own index if a quorum of
+    /// validators signed it, and otherwise against the lowest later checkpoint
+    /// with a quorum.
+    ///
+    /// Labels:
+    /// - `origin`: Chain the message came from.
+    /// - `destination`: Chain the message is being delivered to.
+    /// - `checkpoint`: `exact` if proven against the checkpoint at the index of
+    ///   the message, `fallback` if proven against a later one.
+    pub fn merkle_root_multisig_proofs(&self) -> IntCounterVec {
+        self.merkle_root_multisig_proofs.clone()
+    }
+
+    /// The number of checkpoint indices a validator was found to have signed
+    /// two different checkpoints for. Any non-zero value should be
+    /// investigated, the evidence is stored in the relayer database.
+    ///
+    /// Labels:
+    /// - `origin`: Chain the validator signs checkpoints for.
+    /// - `validator`: Address of the validator.
+    pub fn validator_equivocations(&self) -> IntCounterVec {
+        self.validator_equivocations.clone()
+    }
+
+    /// The number of times this validator refused to sign a checkpoint
+    /// because its signing ledger holds a different checkpoint for the same
+    /// index. Any non-zero value needs an operator's attention, as the
+    /// validator stops signing checkpoints for the origin until it is
+    /// resolved.
+    ///
+    /// Labels:
+    /// - `origin`: Chain the checkpoint is for.
+    pub fn refused_conflicting_signatures(&self) -> IntCounterVec {
+        self.refused_conflicting_signatures.clone()
+    }
+
+    /// The number of message nonces a different message was indexed for than
+    /// the one stored, which an operator has not acknowledged yet. The
+    /// validator does not sign checkpoints of an origin while this is
+    /// non-zero.
+    ///
+    /// Labels:
+    /// - `origin`: Chain that reorged.
+    pub fn unacknowledged_reorgs(&self) -> IntGaugeVec {
+        self.unacknowledged_reorgs.clone()
+    }
+
     /// Measure of span durations provided by tracing.
     ///
     /// Labels:
@@ -412,34 +650,38 @@ impl CoreMetrics {
     }
 
     /// Run an HTTP server serving OpenMetrics format reports on `/metrics`
+    /// and any additional `routes` the agent provides.
     ///
     /// This is compatible with Prometheus, which ought to be configured to
     /// scrape me!
-    pub fn run_http_server(self: Arc<Self>) -> JoinHandle<()> {
+    pub fn run_http_server(
+        self: Arc<Self>,
+        routes: Option<BoxedFilter<(Box<dyn Reply>,)>>,
+    ) -> JoinHandle<()> {
         use warp::Filter;
         let port = self.listen_port;
         tracing::info!(port, "starting prometheus server on 0.0.0.0");
         tokio::spawn(async move {
-            warp::serve(
-                warp::path!("metrics")
-                    .map(move || {
-                        warp::reply::with_header(
-                            self.gather().expect("failed to encode metrics"),
-                            "Content-Type",
-                            // OpenMetrics specs demands "application/openmetrics-text;
-                            // version=1.0.0; charset=utf-8"
-                            // but the prometheus scraper itself doesn't seem to care?
-                            // try text/plain to make web browsers happy.
-                            "text/plain; charset=utf-8",
-                        )
-                    })
-                    .or(warp::any().map(|| {
-                        warp::reply::with_status(
-                            "go look at /metrics",
-                            warp::http::StatusCode::NOT_FOUND,
-                        )
-                    })),
-            )
+            let metrics = warp::path!("metrics")
+                .map(move || -> Box<dyn Reply> {
+                    Box::new(warp::reply::with_header(
+                        self.gather().expect("failed to encode metrics"),
+                        "Content-Type",
+                        // OpenMetrics specs demands "application/openmetrics-text;
+                        // version=1.0.0; charset=utf-8"
+                        // but the prometheus scraper itself doesn't seem to care?
+                        // try text/plain to make web browsers happy.
+                        "text/plain; charset=utf-8",
+                    ))
+                })
+                .boxed();
+            let routes = match routes {
+                Some(routes) => metrics.or(routes).unify().boxed(),
+                None => metrics,
+            };
+            warp::serve(routes.or(warp::any().map(|| {
+                warp::reply::with_status("go look at /metrics", warp::http::StatusCode::NOT_FOUND)
+            })))
             .try_bind(([0, 0, 0, 0], port))
             .await;
             warn!("Prometheus server could not be started or exited early");
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use warp::{filters::BoxedFilter, http::StatusCode, reply::with_status, Filter, Rejection, Reply};
+
+/// Only serve `routes` to requests bearing `token` in their `Authorization`
+/// header. Other requests are answered with `401 Unauthorized`, whatever their
+/// path.
+pub fn with_bearer_token(
+    token: &str,
+    routes: BoxedFilter<(Box<dyn Reply>,)>,
+) -> BoxedFilter<(Box<dyn Reply>,)> {
+    let expected = Arc::new(format!("Bearer {token}"));
+    let unauthorized = warp::header::optional::<String>("authorization").and_then(
+        move |header: Option<String>| {
+            let expected = expected.clone();
+            async move {
+                match header {
+                    Some(header) if constant_time_eq(header.as_bytes(), expected.as_bytes()) => {
+                        Err(warp::reject())
+                    }
+                    _ => Ok::<_, Rejection>(Box::new(with_status(
+                        "Missing or invalid bearer token",
+                        StatusCode::UNAUTHORIZED,
+                    )) as Box<dyn Reply>),
+                }
+            }
+        },
+    );
+    unauthorized.or(routes).unify().boxed()
+}
+
+/// Compare secrets in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -3,9 +3,9 @@ use std::{collections::HashMap, fmt::Debug, sync::Arc};
 use eyre::{eyre, Context, Result};
 use futures_util::future::try_join_all;
 use hyperlane_core::{
-    Delivery, HyperlaneChain, HyperlaneDomain, HyperlaneMessageStore, HyperlaneProvider,
-    HyperlaneWatermarkedLogStore, InterchainGasPaymaster, InterchainGasPayment, Mailbox,
-    MultisigIsm, ValidatorAnnounce, H256,
+    Delivery, DeploymentKey, HyperlaneChain, HyperlaneDomain, HyperlaneMessageStore,
+    HyperlaneProvider, HyperlaneWatermarkedLogStore, InterchainGasPaymaster, InterchainGasPayment,
+    Mailbox, MultisigIsm, ValidatorAnnounce, H256,
 };
 
 use crate::{
@@ -118,16 +118,17 @@ macro_rules! build_contract_fns {
             setup.$singular(metrics).await
         }
 
-        /// Builds a contract for each domain
+        /// Builds a contract for each domain, keyed by the mailbox deployment
+        /// it belongs to
         pub async fn $plural(
             &self,
             domains: impl Iterator<Item = &HyperlaneDomain>,
             metrics: &CoreMetrics,
-        ) -> Result<HashMap<HyperlaneDomain, Arc<$ret>>> {
+        ) -> Result<HashMap<DeploymentKey, Arc<$ret>>> {
             try_join_all(domains.map(|d| self.$singular(d, metrics)))
                 .await?
                 .into_iter()
-                .map(|i| Ok((i.domain().clone(), Arc::from(i))))
+                .map(|i| Ok((i.domain().deployment_key(), Arc::from(i))))
                 .collect()
         }
     };
@@ -156,21 +157,22 @@ macro_rules! build_indexer_fns {
             Ok(Box::new(sync))
         }
 
-        /// Builds a contract for each domain
+        /// Builds a contract for each domain, keyed by the mailbox deployment
+        /// it belongs to
         pub async fn $plural(
             &self,
             domains: impl Iterator<Item = &HyperlaneDomain>,
             metrics: &CoreMetrics,
             sync_metrics: &ContractSyncMetrics,
-            dbs: HashMap<HyperlaneDomain, Arc<$db>>,
-        ) -> Result<HashMap<HyperlaneDomain, Arc<$ret>>> {
-            try_join_all(
-                domains
-                    .map(|d| self.$singular(d, metrics, sync_metrics, dbs.get(d).unwrap().clone())),
-            )
+            dbs: HashMap<DeploymentKey, Arc<$db>>,
+        ) -> Result<HashMap<DeploymentKey, Arc<$ret>>> {
+            try_join_all(domains.map(|d| {
+                let db = dbs.get(&d.deployment_key()).unwrap().clone();
+                self.$singular(d, metrics, sync_metrics, db)
+            }))
             .await?
             .into_iter()
-            .map(|i| Ok((i.domain().clone(), Arc::from(i))))
+            .map(|i| Ok((i.domain().deployment_key(), Arc::from(i))))
             .collect()
         }
     };
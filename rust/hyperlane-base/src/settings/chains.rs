@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use ethers::prelude::Selector;
 use ethers_prometheus::middleware::{
@@ -8,8 +9,9 @@ use eyre::{eyre, Context, Result};
 use hyperlane_core::{
     AggregationIsm, CcipReadIsm, ContractLocator, HyperlaneAbi, HyperlaneDomain,
     HyperlaneDomainProtocol, HyperlaneProvider, HyperlaneSigner, IndexMode, Indexer,
-    InterchainGasPaymaster, InterchainGasPayment, InterchainSecurityModule, Mailbox,
-    MessageIndexer, MultisigIsm, RoutingIsm, ValidatorAnnounce, H256,
+    InflightTransactionStore, InterchainGasPaymaster, InterchainGasPayment,
+    InterchainSecurityModule, Mailbox, MessageIndexer, MultisigIsm, OptimisticIsm, RoutingIsm,
+    ValidatorAnnounce, H256,
 };
 use hyperlane_ethereum::{
     self as h_eth, BuildableWithProvider, EthereumInterchainGasPaymasterAbi, EthereumMailboxAbi,
@@ -35,6 +37,9 @@ pub struct ChainConf {
     pub finality_blocks: u32,
     /// Addresses of contracts on the chain
     pub addresses: CoreContractAddresses,
+    /// Addresses of the contracts of additional mailbox deployments on the
+    /// chain, by deployment name
+    pub deployments: HashMap<String, CoreContractAddresses>,
     /// The chain connection details
     pub connection: ChainConnectionConf,
     /// Configure chain-specific metrics information. This will automatically
@@ -43,6 +48,13 @@ pub struct ChainConf {
     pub metrics_conf: PrometheusMiddlewareConf,
     /// Settings for event indexing
     pub index: IndexSettings,
+    /// Replace stuck transactions with ones paying a higher gas price. Only
+    /// supported for Ethereum chains.
+    pub tx_escalation: Option<h_eth::TxEscalationConf>,
+    /// Address of the Multicall3 contract messages are batched through,
+    /// defaults to the canonical Multicall3 address. Only used for Ethereum
+    /// chains.
+    pub multicall_address: Option<H256>,
 }
 
 /// A connection to _some_ blockchain.
@@ -90,6 +102,18 @@ pub struct IndexSettings {
 }
 
 impl ChainConf {
+    /// The chain setups of the additional mailbox deployments on the chain.
+    /// They only differ from this one in their domain, which names the
+    /// deployment, and their contract addresses.
+    pub fn deployment_chains(&self) -> impl Iterator<Item = ChainConf> + '_ {
+        self.deployments.iter().map(|(name, addresses)| ChainConf {
+            domain: self.domain.with_deployment(name),
+            addresses: addresses.clone(),
+            deployments: HashMap::new(),
+            ..self.clone()
+        })
+    }
+
     /// Try to convert the chain settings into an HyperlaneProvider.
     pub async fn build_provider(
         &self,
@@ -115,8 +139,11 @@ impl ChainConf {
 
         match &self.connection {
             ChainConnectionConf::Ethereum(conf) => {
-                self.build_ethereum(conf, &locator, metrics, h_eth::MailboxBuilder {})
-                    .await
+                let builder = h_eth::MailboxBuilder {
+                    tx_escalator: self.tx_escalator(metrics),
+                    multicall_address: self.multicall_address.map(Into::into),
+                };
+                self.build_ethereum(conf, &locator, metrics, builder).await
             }
 
             ChainConnectionConf::Fuel(conf) => {
@@ -135,6 +162,47 @@ impl ChainConf {
         .context(ctx)
     }
 
+    /// Try to convert the chain setting into the Mailbox contract of the
+    /// chain followed by those of the additional deployments on it. On
+    /// Ethereum chains the mailboxes share one signing provider, so
+    /// transactions to any of them are sent through the same nonce manager.
+    /// Transactions being replaced with a higher gas price are persisted to
+    /// `tx_store`, if given, so they are tracked again after a restart.
+    pub async fn build_deployment_mailboxes(
+        &self,
+        metrics: &CoreMetrics,
+        tx_store: Option<Arc<dyn InflightTransactionStore>>,
+    ) -> Result<Vec<Box<dyn Mailbox>>> {
+        match &self.connection {
+            ChainConnectionConf::Ethereum(conf) => {
+                let locator = self.locator(self.addresses.mailbox);
+                let deployments = self
+                    .deployment_chains()
+                    .map(|chain| (chain.domain, chain.addresses.mailbox))
+                    .collect();
+                let tx_escalator = self.tx_escalator(metrics).map(|escalator| match tx_store {
+                    Some(store) => escalator.with_store(store),
+                    None => escalator,
+                });
+                let builder = h_eth::DeploymentMailboxesBuilder {
+                    tx_escalator,
+                    multicall_address: self.multicall_address.map(Into::into),
+                    deployments,
+                };
+                self.build_ethereum(conf, &locator, metrics, builder)
+                    .await
+                    .context("Building mailboxes")
+            }
+            ChainConnectionConf::Fuel(_) | ChainConnectionConf::Sealevel(_) => {
+                let mut mailboxes = vec![self.build_mailbox(metrics).await?];
+                for chain in self.deployment_chains() {
+                    mailboxes.push(chain.build_mailbox(metrics).await?);
+                }
+                Ok(mailboxes)
+            }
+        }
+    }
+
     /// Try to convert the chain settings into a message indexer
     pub async fn build_message_indexer(
         &self,
@@ -416,6 +484,32 @@ impl ChainConf {
         .context(ctx)
     }
 
+    /// Try to convert the chain setting into an Optimistic Ism contract
+    pub async fn build_optimistic_ism(
+        &self,
+        address: H256,
+        metrics: &CoreMetrics,
+    ) -> Result<Box<dyn OptimisticIsm>> {
+        let ctx = "Building optimistic ISM";
+        let locator = ContractLocator {
+            domain: &self.domain,
+            address,
+        };
+
+        match &self.connection {
+            ChainConnectionConf::Ethereum(conf) => {
+                self.build_ethereum(conf, &locator, metrics, h_eth::OptimisticIsmBuilder {})
+                    .await
+            }
+
+            ChainConnectionConf::Fuel(_) => todo!(),
+            ChainConnectionConf::Sealevel(_) => {
+                Err(eyre!("Sealevel does not support optimistic ISM yet")).context(ctx)
+            }
+        }
+        .context(ctx)
+    }
+
     async fn signer<S: BuildableWithSignerConf>(&self) -> Result<Option<S>> {
         if let Some(conf) = &self.signer {
             Ok(Some(conf.build::<S>().await?))
@@ -499,6 +593,23 @@ impl ChainConf {
         }
     }
 
+    fn tx_escalator(&self, metrics: &CoreMetrics) -> Option<h_eth::TxEscalator> {
+        self.tx_escalation.clone().map(|conf| {
+            let chain = self.domain.name();
+            h_eth::TxEscalator::new(
+                conf,
+                h_eth::TxEscalationMetrics {
+                    escalations: metrics
+                        .transaction_escalations()
+                        .with_label_values(&[chain]),
+                    final_gas_price: metrics
+                        .transaction_escalation_final_gas_price()
+                        .with_label_values(&[chain]),
+                },
+            )
+        })
+    }
+
     async fn build_ethereum<B>(
         &self,
         conf: &h_eth::ConnectionConf,
@@ -99,6 +99,11 @@ impl MultisigCheckpointSyncerConf {
                 continue;
             }
         }
-        Ok(MultisigCheckpointSyncer::new(checkpoint_syncers))
+        Ok(MultisigCheckpointSyncer::new(
+            checkpoint_syncers,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ))
     }
 }
@@ -59,6 +59,15 @@ impl FromRawConf<DeprecatedRawSettings, Option<&HashSet<&str>>> for Settings {
                         None
                     }
                 })
+                // additional mailbox deployments are set up as chains of their own so
+                // they can be looked up by their domain like any other chain
+                .flat_map(|(k, v)| {
+                    let deployments: Vec<_> = v
+                        .deployment_chains()
+                        .map(|d| (d.domain.name().to_owned(), d))
+                        .collect();
+                    std::iter::once((k, v)).chain(deployments)
+                })
                 .collect()
         } else {
             Default::default()
@@ -206,6 +215,7 @@ pub struct DeprecatedRawChainConf {
     pub(super) signer: Option<RawSignerConf>,
     finality_blocks: Option<StrOrInt>,
     addresses: Option<DeprecatedRawCoreContractAddresses>,
+    deployments: Option<HashMap<String, DeprecatedRawCoreContractAddresses>>,
     #[serde(flatten, default)]
     connection: Option<DeprecatedRawChainConnectionConf>,
     // TODO: if people actually use the metrics conf we should also add a raw form.
@@ -213,6 +223,8 @@ pub struct DeprecatedRawChainConf {
     metrics_conf: Option<PrometheusMiddlewareConf>,
     #[serde(default)]
     index: Option<DeprecatedRawIndexSettings>,
+    tx_escalation: Option<h_eth::RawTxEscalationConf>,
+    multicall_address: Option<String>,
 }
 
 impl FromRawConf<DeprecatedRawChainConf> for ChainConf {
@@ -258,6 +270,18 @@ impl FromRawConf<DeprecatedRawChainConf> for ChainConf {
                     .take_config_err(&mut err)
             });
 
+        let deployments = raw
+            .deployments
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(name, v)| {
+                let addresses = v
+                    .parse_config(&cwp.join("deployments").join(&name))
+                    .take_config_err(&mut err)?;
+                Some((name.to_ascii_lowercase(), addresses))
+            })
+            .collect();
+
         let signer = raw.signer.and_then(|v| -> Option<SignerConf> {
             v.parse_config(&cwp.join("signer"))
                 .take_config_err(&mut err)
@@ -279,16 +303,28 @@ impl FromRawConf<DeprecatedRawChainConf> for ChainConf {
 
         let metrics_conf = raw.metrics_conf.unwrap_or_default();
 
+        let tx_escalation = raw.tx_escalation.and_then(|v| {
+            v.parse_config(&cwp.join("tx_escalation"))
+                .take_config_err(&mut err)
+        });
+
+        let multicall_address = raw.multicall_address.and_then(|v| {
+            hex_or_base58_to_h256(&v).take_err(&mut err, || cwp + "multicall_address")
+        });
+
         cfg_unwrap_all!(cwp, err: [connection, domain, addresses]);
 
         err.into_result(Self {
             connection,
             domain,
             addresses,
+            deployments,
             signer,
             finality_blocks,
             index,
             metrics_conf,
+            tx_escalation,
+            multicall_address,
         })
     }
 }
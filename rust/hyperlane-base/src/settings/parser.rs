@@ -10,6 +10,7 @@ use std::{
     cmp::Reverse,
     collections::{HashMap, HashSet},
     path::PathBuf,
+    time::Duration,
 };
 
 use eyre::{eyre, Context};
@@ -48,6 +49,8 @@ struct RawAgentChainMetadataConf {
     signer: Option<RawSignerConf>,
     #[serde(default)]
     index: RawAgentChainMetadataIndexConf,
+    tx_escalation: Option<h_eth::RawTxEscalationConf>,
+    multicall_address: Option<String>,
 
     // -- ChainMetadata --
     protocol: Option<String>,
@@ -77,6 +80,18 @@ struct RawAgentChainMetadataConf {
     interchain_gas_paymaster: Option<String>,
     validator_announce: Option<String>,
     interchain_security_module: Option<String>,
+    /// Additional mailbox deployments on the chain, by deployment name
+    #[serde(default)]
+    deployments: HashMap<String, RawAgentDeploymentConf>,
+}
+
+/// Contract addresses of an additional mailbox deployment on a chain.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAgentDeploymentConf {
+    mailbox: Option<String>,
+    interchain_gas_paymaster: Option<String>,
+    validator_announce: Option<String>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -151,6 +166,13 @@ pub struct RawSignerConf {
     key: Option<String>,
     id: Option<String>,
     region: Option<String>,
+    url: Option<String>,
+    token: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+    ca_cert: Option<String>,
+    /// Seconds to wait for a remote signer to respond. Defaults to 30.
+    timeout: Option<StrOrInt>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -235,6 +257,15 @@ impl FromRawConf<RawAgentConf, Option<&HashSet<&str>>> for Settings {
                             .take_err(&mut err, || &cwp + "name")
                     })
             })
+            // additional mailbox deployments are set up as chains of their own so
+            // they can be looked up by their domain like any other chain
+            .flat_map(|(name, chain)| {
+                let deployments: Vec<_> = chain
+                    .deployment_chains()
+                    .map(|d| (d.domain.name().to_owned(), d))
+                    .collect();
+                std::iter::once((name, chain)).chain(deployments)
+            })
             .collect();
 
         cfg_unwrap_all!(cwp, err: [tracing]);
@@ -280,6 +311,17 @@ impl FromRawConf<RawAgentChainMetadataConf> for ChainConf {
         let domain = (&raw).parse_config(cwp).take_config_err(&mut err);
         let addresses = (&raw).parse_config(cwp).take_config_err(&mut err);
 
+        let deployments = raw
+            .deployments
+            .iter()
+            .filter_map(|(name, deployment)| {
+                let addresses = deployment
+                    .parse_config(&cwp.join("deployments").join(name))
+                    .take_config_err(&mut err)?;
+                Some((name.to_ascii_lowercase(), addresses))
+            })
+            .collect();
+
         let signer = raw.signer.and_then(|s| {
             s.parse_config(&cwp.join("signer"))
                 .take_config_err(&mut err)
@@ -303,6 +345,15 @@ impl FromRawConf<RawAgentChainMetadataConf> for ChainConf {
             .parse_config_with_filter(&cwp.join("index"), domain.as_ref())
             .take_config_err(&mut err);
 
+        let tx_escalation = raw.tx_escalation.and_then(|v| {
+            v.parse_config(&cwp.join("tx_escalation"))
+                .take_config_err(&mut err)
+        });
+
+        let multicall_address = raw.multicall_address.and_then(|v| {
+            hex_or_base58_to_h256(&v).take_err(&mut err, || cwp + "multicall_address")
+        });
+
         let rpcs: Vec<(ConfigPath, RawRpcUrlConf)> = if raw.custom_rpc_urls.is_empty() {
             let cwp = cwp + "rpc_urls";
             // if no custom rpc urls are set, use the default rpc urls
@@ -410,9 +461,12 @@ impl FromRawConf<RawAgentChainMetadataConf> for ChainConf {
             signer,
             finality_blocks,
             addresses,
+            deployments,
             connection,
             metrics_conf: Default::default(),
             index,
+            tx_escalation,
+            multicall_address,
         })
     }
 }
@@ -480,6 +534,21 @@ impl FromRawConf<&RawAgentChainMetadataConf> for CoreContractAddresses {
         raw: &RawAgentChainMetadataConf,
         cwp: &ConfigPath,
         _filter: (),
+    ) -> ConfigResult<Self> {
+        let addresses = RawAgentDeploymentConf {
+            mailbox: raw.mailbox.clone(),
+            interchain_gas_paymaster: raw.interchain_gas_paymaster.clone(),
+            validator_announce: raw.validator_announce.clone(),
+        };
+        (&addresses).parse_config(cwp)
+    }
+}
+
+impl FromRawConf<&RawAgentDeploymentConf> for CoreContractAddresses {
+    fn from_config_filtered(
+        raw: &RawAgentDeploymentConf,
+        cwp: &ConfigPath,
+        _filter: (),
     ) -> ConfigResult<Self> {
         let mut err = ConfigParsingError::default();
 
@@ -569,6 +638,7 @@ impl FromRawConf<RawSignerConf> for SignerConf {
     ) -> ConfigResult<Self> {
         let key_path = || cwp + "key";
         let region_path = || cwp + "region";
+        let url_path = || cwp + "url";
 
         match raw.signer_type.as_deref() {
             Some("hexKey") => Ok(Self::HexKey {
@@ -591,6 +661,33 @@ impl FromRawConf<RawSignerConf> for SignerConf {
                     .parse()
                     .into_config_result(region_path)?,
             }),
+            Some("remote") => {
+                if raw.client_cert.is_some() != raw.client_key.is_some() {
+                    return Err(eyre!(
+                        "`clientCert` and `clientKey` must be set together for Remote signer"
+                    ))
+                    .into_config_result(|| cwp + "clientKey");
+                }
+                let timeout = match raw.timeout {
+                    Some(timeout) => Duration::from_secs(
+                        timeout.try_into().into_config_result(|| cwp + "timeout")?,
+                    ),
+                    None => Duration::from_secs(30),
+                };
+                Ok(Self::Remote {
+                    url: raw
+                        .url
+                        .ok_or_else(|| eyre!("Missing `url` for Remote signer"))
+                        .into_config_result(url_path)?
+                        .parse()
+                        .into_config_result(url_path)?,
+                    token: raw.token,
+                    client_cert: raw.client_cert.map(PathBuf::from),
+                    client_key: raw.client_key.map(PathBuf::from),
+                    ca_cert: raw.ca_cert.map(PathBuf::from),
+                    timeout,
+                })
+            }
             Some(t) => Err(eyre!("Unknown signer type `{t}`")).into_config_result(|| cwp + "type"),
             None if raw.key.is_some() => Ok(Self::HexKey {
                 key: raw.key.unwrap().parse().into_config_result(key_path)?,
@@ -657,3 +754,56 @@ impl FromRawConf<RawCheckpointSyncerConf> for CheckpointSyncerConf {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use hyperlane_core::{KnownHyperlaneDomain, H256};
+
+    use super::*;
+
+    fn addresses(byte: u8) -> serde_json::Value {
+        let address = format!("{:?}", H256::repeat_byte(byte));
+        json!({
+            "mailbox": address,
+            "interchainGasPaymaster": address,
+            "validatorAnnounce": address,
+        })
+    }
+
+    #[test]
+    fn test_parses_two_deployments_on_one_chain() {
+        let mut chain = json!({
+            "name": "ethereum",
+            "chainId": 1,
+            "protocol": "ethereum",
+            "rpcUrls": [{ "http": "http://localhost:8545" }],
+            "blocks": { "confirmations": 1 },
+            "deployments": { "Private": addresses(2), "Public": addresses(3) },
+        });
+        chain
+            .as_object_mut()
+            .unwrap()
+            .extend(addresses(1).as_object().unwrap().clone());
+        let raw: RawAgentConf = serde_json::from_value(json!({
+            "metricsPort": 9090,
+            "defaultSigner": {},
+            "chains": { "ethereum": chain },
+        }))
+        .unwrap();
+        let settings = Settings::from_config(raw, &ConfigPath::default()).unwrap();
+
+        let ethereum = HyperlaneDomain::Known(KnownHyperlaneDomain::Ethereum);
+        let mut names: Vec<_> = settings.chains.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, ["ethereum", "ethereum@private", "ethereum@public"]);
+        for (deployment, byte) in [(None, 1), (Some("private"), 2), (Some("public"), 3)] {
+            let domain = match deployment {
+                Some(deployment) => ethereum.with_deployment(deployment),
+                None => ethereum.clone(),
+            };
+            let chain = settings.chain_setup(&domain).unwrap();
+            assert_eq!(chain.domain.deployment_key(), domain.deployment_key());
+            assert_eq!(chain.addresses.mailbox, H256::repeat_byte(byte));
+        }
+    }
+}
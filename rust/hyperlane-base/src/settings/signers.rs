@@ -1,19 +1,21 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use async_trait::async_trait;
 use ed25519_dalek::SecretKey;
 use ethers::prelude::{AwsSigner, LocalWallet};
 use eyre::{bail, Context, Report};
 use hyperlane_core::H256;
+use hyperlane_ethereum::{RemoteSigner, RemoteSignerTls};
 use hyperlane_sealevel::Keypair;
 use rusoto_core::{HttpClient, HttpConfig, Region};
 use rusoto_kms::KmsClient;
 use tracing::instrument;
+use url::Url;
 
 use super::aws_credentials::AwsChainCredentialsProvider;
 
 /// Signer types
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub enum SignerConf {
     /// A local hex key
     HexKey {
@@ -28,6 +30,24 @@ pub enum SignerConf {
         /// The AWS region
         region: Region,
     },
+    /// A signer using a key held by a signing service reached over HTTP, e.g.
+    /// one in front of an HSM or Vault
+    Remote {
+        /// Base URL of the signing service
+        url: Url,
+        /// Token to authenticate to the service with as a bearer token
+        token: Option<String>,
+        /// Path to the PEM encoded client certificate to authenticate to the
+        /// service with over mutual TLS
+        client_cert: Option<PathBuf>,
+        /// Path to the PEM encoded private key of the client certificate
+        client_key: Option<PathBuf>,
+        /// Path to the PEM encoded CA certificate to trust the service's
+        /// certificate with, in addition to the system roots
+        ca_cert: Option<PathBuf>,
+        /// How long to wait for the service to respond
+        timeout: Duration,
+    },
     /// Assume the local node will sign on RPC calls automatically
     #[default]
     Node,
@@ -73,6 +93,36 @@ impl BuildableWithSignerConf for hyperlane_ethereum::Signers {
                 let signer = AwsSigner::new(client, id, 0).await?;
                 hyperlane_ethereum::Signers::Aws(signer)
             }
+            SignerConf::Remote {
+                url,
+                token,
+                client_cert,
+                client_key,
+                ca_cert,
+                timeout,
+            } => {
+                let read = |path: &PathBuf| {
+                    std::fs::read(path).with_context(|| format!("Failed to read {path:?}"))
+                };
+                let identity_pem = match (client_cert, client_key) {
+                    (Some(cert), Some(key)) => {
+                        let mut pem = read(cert)?;
+                        pem.push(b'\n');
+                        pem.extend(read(key)?);
+                        Some(pem)
+                    }
+                    (None, None) => None,
+                    _ => bail!("A client certificate and its key must be configured together"),
+                };
+                let tls = RemoteSignerTls {
+                    identity_pem,
+                    ca_cert_pem: ca_cert.as_ref().map(read).transpose()?,
+                };
+                let signer = RemoteSigner::connect(url.clone(), token.clone(), tls, *timeout)
+                    .await
+                    .context("Failed to connect to the remote signer")?;
+                hyperlane_ethereum::Signers::Remote(signer)
+            }
             SignerConf::Node => bail!("Node signer"),
         })
     }
@@ -88,6 +138,7 @@ impl BuildableWithSignerConf for fuels::prelude::WalletUnlocked {
                 fuels::prelude::WalletUnlocked::new_from_private_key(key, None)
             }
             SignerConf::Aws { .. } => bail!("Aws signer is not supported by fuel"),
+            SignerConf::Remote { .. } => bail!("Remote signer is not supported by fuel"),
             SignerConf::Node => bail!("Node signer is not supported by fuel"),
         })
     }
@@ -104,6 +155,7 @@ impl BuildableWithSignerConf for Keypair {
                     .context("Unable to create Keypair")?
             }
             SignerConf::Aws { .. } => bail!("Aws signer is not supported by fuel"),
+            SignerConf::Remote { .. } => bail!("Remote signer is not supported by sealevel"),
             SignerConf::Node => bail!("Node signer is not supported by fuel"),
         })
     }
@@ -0,0 +1,364 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Formatter},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use prometheus::IntCounterVec;
+use tracing::error;
+
+use hyperlane_core::{
+    Checkpoint, CheckpointWithMessageId, SignedCheckpoint, SignedCheckpointWithMessageId,
+    SignedType, H160, H256,
+};
+
+use crate::db::{Equivocation, HyperlaneRocksDB, SignedCheckpointValue};
+
+/// Checkpoints this many indices below the highest index seen are forgotten.
+const SEEN_CHECKPOINT_WINDOW: u32 = 100_000;
+/// Forgotten checkpoints are pruned whenever the highest index seen crosses a
+/// multiple of this.
+const PRUNE_INTERVAL: u32 = 1000;
+
+/// A checkpoint a validator may only sign one value for: the validator, the
+/// mailbox domain and address, and the index.
+type CheckpointSlot = (H160, u32, H256, u32);
+
+/// Detects validators which sign two different checkpoints for the same index
+/// of a mailbox, by remembering the checkpoints fetched from each validator.
+/// Legacy checkpoints, signed without a message id, are checked separately.
+///
+/// If there is a database, the checkpoints seen are stored in it so they are
+/// remembered across restarts, and equivocations are stored as fraud evidence.
+/// Equivocations are always logged and counted.
+#[derive(Default)]
+pub struct EquivocationDetector {
+    seen: Mutex<SeenCheckpoints<CheckpointWithMessageId>>,
+    legacy_seen: Mutex<SeenCheckpoints<Checkpoint>>,
+    db: Option<HyperlaneRocksDB>,
+    metric: Option<IntCounterVec>,
+}
+
+/// The checkpoints recently seen from each validator.
+#[derive(Debug)]
+struct SeenCheckpoints<T: SignedCheckpointValue> {
+    checkpoints: HashMap<CheckpointSlot, SignedType<T>>,
+    /// Slots a validator has signed conflicting checkpoints for.
+    equivocated: HashSet<CheckpointSlot>,
+    highest_index: u32,
+}
+
+impl<T: SignedCheckpointValue> Default for SeenCheckpoints<T> {
+    fn default() -> Self {
+        Self {
+            checkpoints: HashMap::new(),
+            equivocated: HashSet::new(),
+            highest_index: 0,
+        }
+    }
+}
+
+impl Debug for EquivocationDetector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EquivocationDetector {{ db: {:?} }}", self.db)
+    }
+}
+
+impl EquivocationDetector {
+    /// Detect equivocations by the validators of the domain of `db`, storing
+    /// them in it.
+    ///
+    /// - `metric`: the `validator_equivocations` metric.
+    pub fn new(db: HyperlaneRocksDB, metric: IntCounterVec) -> Self {
+        Self {
+            seen: Default::default(),
+            legacy_seen: Default::default(),
+            db: Some(db),
+            metric: Some(metric),
+        }
+    }
+
+    /// Check a checkpoint signed by `validator` against those it signed
+    /// before. Returns false if the validator has signed a different checkpoint
+    /// for the same index, in which case neither can be trusted.
+    pub fn observe(&self, validator: H160, signed: &SignedCheckpointWithMessageId) -> bool {
+        self.observe_in(&self.seen, validator, signed)
+    }
+
+    /// Check a legacy checkpoint signed by `validator` against the legacy
+    /// checkpoints it signed before, like `observe`.
+    pub fn observe_legacy(&self, validator: H160, signed: &SignedCheckpoint) -> bool {
+        self.observe_in(&self.legacy_seen, validator, signed)
+    }
+
+    fn observe_in<T: SignedCheckpointValue>(
+        &self,
+        seen: &Mutex<SeenCheckpoints<T>>,
+        validator: H160,
+        signed: &SignedType<T>,
+    ) -> bool {
+        let checkpoint = signed.value.checkpoint();
+        let slot = (
+            validator,
+            checkpoint.mailbox_domain,
+            checkpoint.mailbox_address,
+            checkpoint.index,
+        );
+        let first = {
+            let mut seen = seen.lock().unwrap();
+            if seen.equivocated.contains(&slot) {
+                return false;
+            }
+            let first = match seen.checkpoints.get(&slot) {
+                Some(first) => Some(first.clone()),
+                None => self.retrieve_seen(validator, checkpoint),
+            };
+            match first {
+                Some(first) if first.value == signed.value => {
+                    if let Some(min_index) = seen.insert(slot, first) {
+                        self.prune_persisted::<T>(min_index);
+                    }
+                    return true;
+                }
+                Some(first) => {
+                    seen.equivocated.insert(slot);
+                    first
+                }
+                None => {
+                    if let Some(min_index) = seen.insert(slot, signed.clone()) {
+                        self.prune_persisted::<T>(min_index);
+                    }
+                    self.persist_seen(validator, signed);
+                    return true;
+                }
+            }
+        };
+        self.report(Equivocation {
+            validator,
+            first,
+            second: signed.clone(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        });
+        false
+    }
+
+    fn retrieve_seen<T: SignedCheckpointValue>(
+        &self,
+        validator: H160,
+        checkpoint: &Checkpoint,
+    ) -> Option<SignedType<T>> {
+        let db = self.db.as_ref()?;
+        db.retrieve_seen_checkpoint(validator, checkpoint)
+            .map_err(|err| error!(?err, "Failed to retrieve seen checkpoint"))
+            .ok()
+            .flatten()
+    }
+
+    fn persist_seen<T: SignedCheckpointValue>(&self, validator: H160, signed: &SignedType<T>) {
+        if let Some(db) = &self.db {
+            if let Err(err) = db.store_seen_checkpoint(validator, signed) {
+                error!(?err, "Failed to store seen checkpoint");
+            }
+        }
+    }
+
+    fn prune_persisted<T: SignedCheckpointValue>(&self, min_index: u32) {
+        if let Some(db) = &self.db {
+            if let Err(err) = db.prune_seen_checkpoints::<T>(min_index) {
+                error!(?err, "Failed to prune seen checkpoints");
+            }
+        }
+    }
+
+    fn report<T: SignedCheckpointValue>(&self, equivocation: Equivocation<T>) {
+        let (first, second) = (&equivocation.first.value, &equivocation.second.value);
+        error!(
+            validator = format!("{:#x}", equivocation.validator),
+            index = first.checkpoint().index,
+            first = ?first,
+            second = ?second,
+            legacy = T::LEGACY,
+            "Validator signed conflicting checkpoints for the same index"
+        );
+        if let Some(metric) = &self.metric {
+            let origin = self.db.as_ref().map(|db| db.domain().name()).unwrap_or("");
+            metric
+                .with_label_values(&[origin, &format!("{:#x}", equivocation.validator)])
+                .inc();
+        }
+        if let Some(db) = &self.db {
+            if let Err(err) = db.store_equivocation(&equivocation) {
+                error!(?err, "Failed to store equivocation");
+            }
+        }
+    }
+}
+
+impl<T: SignedCheckpointValue> SeenCheckpoints<T> {
+    /// Remember a checkpoint, pruning those outside of the window if the
+    /// highest index crosses an interval. Returns the index checkpoints below
+    /// were pruned, so the persisted ones can be pruned as well.
+    fn insert(&mut self, slot: CheckpointSlot, signed: SignedType<T>) -> Option<u32> {
+        let index = slot.3;
+        let mut pruned = None;
+        if index > self.highest_index {
+            // Indices may be skipped, so prune whenever a multiple of the
+            // interval is crossed rather than reached.
+            let crossed = index / PRUNE_INTERVAL > self.highest_index / PRUNE_INTERVAL;
+            self.highest_index = index;
+            if crossed {
+                let min_index = index.saturating_sub(SEEN_CHECKPOINT_WINDOW);
+                self.checkpoints.retain(|slot, _| slot.3 >= min_index);
+                self.equivocated.retain(|slot| slot.3 >= min_index);
+                pruned = Some(min_index);
+            }
+        }
+        self.checkpoints.insert(slot, signed);
+        pruned
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyperlane_core::{HyperlaneDomain, Signature, U256};
+    use prometheus::Opts;
+
+    use super::*;
+    use crate::db::test_utils;
+
+    fn signed(index: u32, message_id: u64) -> SignedCheckpointWithMessageId {
+        SignedCheckpointWithMessageId {
+            value: CheckpointWithMessageId {
+                checkpoint: Checkpoint {
+                    mailbox_address: H256::zero(),
+                    mailbox_domain: 1,
+                    root: H256::zero(),
+                    index,
+                },
+                message_id: H256::from_low_u64_be(message_id),
+            },
+            signature: Signature {
+                r: U256::zero(),
+                s: U256::zero(),
+                v: 27,
+            },
+        }
+    }
+
+    #[test]
+    fn test_detects_conflicting_checkpoints() {
+        let detector = EquivocationDetector::default();
+        let validator = H160::from_low_u64_be(1);
+        assert!(detector.observe(validator, &signed(5, 1)));
+        assert!(detector.observe(validator, &signed(5, 1)));
+        // Another validator or another index is independent
+        assert!(detector.observe(H160::from_low_u64_be(2), &signed(5, 2)));
+        assert!(detector.observe(validator, &signed(6, 2)));
+
+        assert!(!detector.observe(validator, &signed(5, 2)));
+        // Neither checkpoint is trusted once the validator has equivocated
+        assert!(!detector.observe(validator, &signed(5, 1)));
+    }
+
+    fn signed_legacy(index: u32, root: u64) -> SignedCheckpoint {
+        SignedCheckpoint {
+            value: Checkpoint {
+                mailbox_address: H256::zero(),
+                mailbox_domain: 1,
+                root: H256::from_low_u64_be(root),
+                index,
+            },
+            signature: Signature {
+                r: U256::zero(),
+                s: U256::zero(),
+                v: 27,
+            },
+        }
+    }
+
+    #[test]
+    fn test_detects_conflicting_legacy_checkpoints() {
+        let detector = EquivocationDetector::default();
+        let validator = H160::from_low_u64_be(1);
+        assert!(detector.observe_legacy(validator, &signed_legacy(5, 1)));
+        assert!(detector.observe_legacy(validator, &signed_legacy(5, 1)));
+        // Legacy checkpoints are independent of those with a message id
+        assert!(detector.observe(validator, &signed(5, 2)));
+
+        assert!(!detector.observe_legacy(validator, &signed_legacy(5, 2)));
+        assert!(!detector.observe_legacy(validator, &signed_legacy(5, 1)));
+    }
+
+    #[test]
+    fn test_prunes_when_crossing_an_interval() {
+        let validator = H160::from_low_u64_be(1);
+        let slot = |index| (validator, 1, H256::zero(), index);
+        let mut seen = SeenCheckpoints::default();
+        assert_eq!(seen.insert(slot(5), signed(5, 1)), None);
+        // Indices are skipped, so no multiple of the interval is ever reached
+        let index = SEEN_CHECKPOINT_WINDOW + 1500;
+        assert_eq!(seen.insert(slot(index), signed(index, 1)), Some(1500));
+        assert!(!seen.checkpoints.contains_key(&slot(5)));
+        assert!(seen.checkpoints.contains_key(&slot(index)));
+    }
+
+    #[tokio::test]
+    async fn test_seen_checkpoints_are_persisted() {
+        test_utils::run_test_db(|db| async move {
+            let db = HyperlaneRocksDB::new(&HyperlaneDomain::new_test_domain("test"), db);
+            let detector = || {
+                let metric = IntCounterVec::new(
+                    Opts::new("validator_equivocations", "help string"),
+                    &["origin", "validator"],
+                )
+                .unwrap();
+                EquivocationDetector::new(db.clone(), metric)
+            };
+            let validator = H160::from_low_u64_be(1);
+            assert!(detector().observe(validator, &signed(5, 1)));
+            assert!(detector().observe_legacy(validator, &signed_legacy(5, 1)));
+
+            // A detector started later remembers the checkpoints seen before
+            let restarted = detector();
+            assert!(restarted.observe(validator, &signed(5, 1)));
+            assert!(!restarted.observe(validator, &signed(5, 2)));
+            assert!(!restarted.observe_legacy(validator, &signed_legacy(5, 2)));
+
+            let equivocations = db.retrieve_equivocations();
+            assert_eq!(equivocations.len(), 1);
+            assert_eq!(equivocations[0].first, signed(5, 1));
+            assert_eq!(equivocations[0].second, signed(5, 2));
+            let legacy_equivocations = db.retrieve_legacy_equivocations();
+            assert_eq!(legacy_equivocations.len(), 1);
+            assert_eq!(legacy_equivocations[0].second, signed_legacy(5, 2));
+
+            // Persisted checkpoints are pruned along with the remembered ones
+            let index = SEEN_CHECKPOINT_WINDOW + 1500;
+            let detector = detector();
+            assert!(detector.observe(validator, &signed(1499, 1)));
+            assert!(detector.observe(validator, &signed(1500, 1)));
+            assert!(detector.observe(validator, &signed(index, 1)));
+            let retrieve = |index| {
+                db.retrieve_seen_checkpoint::<CheckpointWithMessageId>(
+                    validator,
+                    &signed(index, 1).value.checkpoint,
+                )
+                .unwrap()
+            };
+            assert_eq!(retrieve(5), None);
+            assert_eq!(retrieve(1499), None);
+            assert_eq!(retrieve(1500), Some(signed(1500, 1)));
+            assert_eq!(retrieve(index), Some(signed(index, 1)));
+            // Legacy checkpoints are pruned separately
+            assert!(db
+                .retrieve_seen_checkpoint::<Checkpoint>(validator, &signed_legacy(5, 1).value)
+                .unwrap()
+                .is_some());
+        })
+        .await;
+    }
+}
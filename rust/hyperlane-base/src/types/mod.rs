@@ -1,7 +1,11 @@
+mod equivocation;
 mod local_storage;
 mod multisig;
 mod s3_storage;
+mod validator_health;
 
+pub use equivocation::*;
 pub use local_storage::*;
 pub use multisig::*;
 pub use s3_storage::*;
+pub use validator_health::*;
@@ -1,16 +1,54 @@
 use std::collections::{hash_map::Entry, HashMap};
+use std::fmt::Debug;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 
 use derive_new::new;
 use eyre::Result;
+use futures_util::{stream::FuturesUnordered, StreamExt};
+use tokio::time::{error::Elapsed, sleep, timeout, timeout_at, Instant};
 use tracing::{debug, instrument, trace};
 
 use hyperlane_core::{
-    Checkpoint, CheckpointWithMessageId, MultisigSignedCheckpoint, SignedCheckpointWithSigner,
-    H160, H256,
+    Checkpoint, CheckpointWithMessageId, MultisigSignedCheckpoint, Signable,
+    SignedCheckpointWithSigner, SignedType, H160, H256,
 };
 
-use crate::CheckpointSyncer;
+use crate::{CheckpointSyncer, EquivocationDetector, ValidatorHealth};
+
+/// How long to wait for the checkpoint syncers of validators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointFetchConf {
+    /// How long to wait for a validator's checkpoint syncer to respond, unless
+    /// overridden for the validator.
+    pub timeout: Duration,
+    /// How long to wait for the checkpoint syncers of specific validators to
+    /// respond, e.g. those storing checkpoints far away from the relayer.
+    pub validator_timeouts: HashMap<H160, Duration>,
+    /// How long to wait for responses before also querying another validator.
+    pub hedge_delay: Duration,
+}
+
+impl Default for CheckpointFetchConf {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            validator_timeouts: HashMap::new(),
+            hedge_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl CheckpointFetchConf {
+    /// How long to wait for the checkpoint syncer of `validator` to respond.
+    pub fn timeout(&self, validator: &H160) -> Duration {
+        self.validator_timeouts
+            .get(validator)
+            .copied()
+            .unwrap_or(self.timeout)
+    }
+}
 
 /// Fetches signed checkpoints from multiple validators to create
 /// MultisigSignedCheckpoints
@@ -18,9 +56,64 @@ use crate::CheckpointSyncer;
 pub struct MultisigCheckpointSyncer {
     /// The checkpoint syncer for each valid validator signer address
     checkpoint_syncers: HashMap<H160, Arc<dyn CheckpointSyncer>>,
+    /// Health of the validators, shared with other syncers for the same
+    /// validators so healthy validators are queried first.
+    health: Arc<ValidatorHealth>,
+    /// Checkpoints previously fetched from the validators, to discard those of
+    /// validators which sign conflicting checkpoints.
+    equivocations: Arc<EquivocationDetector>,
+    /// How long to wait for the checkpoint syncers.
+    fetch_conf: Arc<CheckpointFetchConf>,
+}
+
+/// A signed value which contains a checkpoint.
+trait CheckpointValue: Signable + Copy + Eq + Debug + Send + Sync {
+    fn checkpoint(&self) -> &Checkpoint;
+
+    /// Check the signed value against those the validator signed before, see
+    /// `EquivocationDetector::observe`.
+    fn observe(
+        equivocations: &EquivocationDetector,
+        validator: H160,
+        signed: &SignedType<Self>,
+    ) -> bool;
+}
+
+impl CheckpointValue for Checkpoint {
+    fn checkpoint(&self) -> &Checkpoint {
+        self
+    }
+
+    fn observe(
+        equivocations: &EquivocationDetector,
+        validator: H160,
+        signed: &SignedType<Self>,
+    ) -> bool {
+        equivocations.observe_legacy(validator, signed)
+    }
+}
+
+impl CheckpointValue for CheckpointWithMessageId {
+    fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    fn observe(
+        equivocations: &EquivocationDetector,
+        validator: H160,
+        signed: &SignedType<Self>,
+    ) -> bool {
+        equivocations.observe(validator, signed)
+    }
 }
 
 impl MultisigCheckpointSyncer {
+    /// The checkpoint syncer of a validator, if one could be built from the
+    /// storage locations it announced.
+    pub fn checkpoint_syncer(&self, validator: &H160) -> Option<&Arc<dyn CheckpointSyncer>> {
+        self.checkpoint_syncers.get(validator)
+    }
+
     /// Attempts to get the latest checkpoint with a quorum of signatures among
     /// validators.
     ///
@@ -41,52 +134,24 @@ impl MultisigCheckpointSyncer {
         minimum_index: u32,
         maximum_index: u32,
     ) -> Result<Option<MultisigSignedCheckpoint<Checkpoint>>> {
-        // Get the latest_index from each validator's checkpoint syncer.
-        let mut latest_indices = Vec::with_capacity(validators.len());
-        for validator in validators {
-            let address = H160::from(*validator);
-            if let Some(checkpoint_syncer) = self.checkpoint_syncers.get(&address) {
-                // Gracefully handle errors getting the latest_index
-                match checkpoint_syncer.latest_index().await {
-                    Ok(Some(index)) => {
-                        trace!(?address, ?index, "Validator returned latest index");
-                        latest_indices.push(index);
-                    }
-                    err => {
-                        debug!(?address, ?err, "Failed to get latest index from validator");
-                    }
-                }
-            }
-        }
-        debug!(
-            ?latest_indices,
-            "Fetched latest indices from checkpoint syncers"
-        );
-
-        if latest_indices.is_empty() {
-            debug!("No validators returned a latest index");
+        let Some(highest_quorum_index) = self.highest_quorum_index(validators, threshold).await
+        else {
+            return Ok(None);
+        };
+        // The highest viable checkpoint index is the minimum of the highest index
+        // we (supposedly) have a quorum for, and the maximum index for which we can
+        // generate a proof.
+        let start_index = highest_quorum_index.min(maximum_index);
+        if minimum_index > start_index {
+            debug!(%start_index, %highest_quorum_index, "Highest quorum index is below the minimum index");
             return Ok(None);
         }
-
-        // Sort in descending order. The n'th index will represent
-        // the highest index for which we (supposedly) have (n+1) signed checkpoints
-        latest_indices.sort_by(|a, b| b.cmp(a));
-        if let Some(&highest_quorum_index) = latest_indices.get(threshold - 1) {
-            // The highest viable checkpoint index is the minimum of the highest index
-            // we (supposedly) have a quorum for, and the maximum index for which we can
-            // generate a proof.
-            let start_index = highest_quorum_index.min(maximum_index);
-            if minimum_index > start_index {
-                debug!(%start_index, %highest_quorum_index, "Highest quorum index is below the minimum index");
-                return Ok(None);
-            }
-            for index in (minimum_index..=start_index).rev() {
-                if let Ok(Some(checkpoint)) = self
-                    .legacy_fetch_checkpoint(index, validators, threshold)
-                    .await
-                {
-                    return Ok(Some(checkpoint));
-                }
+        for index in (minimum_index..=start_index).rev() {
+            if let Ok(Some(checkpoint)) = self
+                .legacy_fetch_checkpoint(index, validators, threshold)
+                .await
+            {
+                return Ok(Some(checkpoint));
             }
         }
         debug!("No checkpoint found in range");
@@ -102,91 +167,10 @@ impl MultisigCheckpointSyncer {
         validators: &[H256],
         threshold: usize,
     ) -> Result<Option<MultisigSignedCheckpoint<Checkpoint>>> {
-        // Keeps track of signed validator checkpoints for a particular root.
-        // In practice, it's likely that validators will all sign the same root for a
-        // particular index, but we'd like to be robust to this not being the case
-        let mut signed_checkpoints_per_root: HashMap<
-            H256,
-            Vec<SignedCheckpointWithSigner<Checkpoint>>,
-        > = HashMap::new();
-
-        for validator in validators {
-            let addr = H160::from(*validator);
-            if let Some(checkpoint_syncer) = self.checkpoint_syncers.get(&addr) {
-                // Gracefully ignore an error fetching the checkpoint from a validator's
-                // checkpoint syncer, which can happen if the validator has not
-                // signed the checkpoint at `index`.
-                if let Ok(Some(signed_checkpoint)) =
-                    checkpoint_syncer.legacy_fetch_checkpoint(index).await
-                {
-                    // If the signed checkpoint is for a different index, ignore it
-                    if signed_checkpoint.value.index != index {
-                        debug!(
-                            validator = format!("{:#x}", validator),
-                            index = index,
-                            checkpoint_index = signed_checkpoint.value.index,
-                            "Checkpoint index mismatch"
-                        );
-                        continue;
-                    }
-                    // Ensure that the signature is actually by the validator
-                    let signer = signed_checkpoint.recover()?;
-                    if H256::from(signer) != *validator {
-                        debug!(
-                            validator = format!("{:#x}", validator),
-                            index = index,
-                            "Checkpoint signature mismatch"
-                        );
-                        continue;
-                    }
-
-                    // Insert the SignedCheckpointWithSigner into signed_checkpoints_per_root
-                    let signed_checkpoint_with_signer = SignedCheckpointWithSigner {
-                        signer,
-                        signed_checkpoint,
-                    };
-                    let root = signed_checkpoint_with_signer.signed_checkpoint.value.root;
-
-                    let signature_count = match signed_checkpoints_per_root.entry(root) {
-                        Entry::Occupied(mut entry) => {
-                            let vec = entry.get_mut();
-                            vec.push(signed_checkpoint_with_signer);
-                            vec.len()
-                        }
-                        Entry::Vacant(entry) => {
-                            entry.insert(vec![signed_checkpoint_with_signer]);
-                            1 // length of 1
-                        }
-                    };
-                    debug!(
-                        validator = format!("{validator:#x}"),
-                        index,
-                        root = format!("{root:#x}"),
-                        signature_count,
-                        "Found signed checkpoint"
-                    );
-                    // If we've hit a quorum, create a MultisigSignedCheckpoint
-                    if signature_count >= threshold {
-                        if let Some(signed_checkpoints) = signed_checkpoints_per_root.get(&root) {
-                            let checkpoint =
-                                MultisigSignedCheckpoint::try_from(signed_checkpoints)?;
-                            debug!(?checkpoint, "Fetched multisig checkpoint");
-                            return Ok(Some(checkpoint));
-                        }
-                    }
-                } else {
-                    debug!(
-                        validator = format!("{validator:#x}"),
-                        index = index,
-                        "Unable to find signed checkpoint"
-                    );
-                }
-            } else {
-                debug!(%validator, "Unable to find checkpoint syncer");
-                continue;
-            }
-        }
-        Ok(None)
+        self.fetch_quorum(validators, threshold, index, |syncer| async move {
+            syncer.legacy_fetch_checkpoint(index).await
+        })
+        .await
     }
 
     /// Attempts to get the latest checkpoint with a quorum of signatures among
@@ -209,152 +193,674 @@ impl MultisigCheckpointSyncer {
         minimum_index: u32,
         maximum_index: u32,
     ) -> Result<Option<MultisigSignedCheckpoint<CheckpointWithMessageId>>> {
-        // Get the latest_index from each validator's checkpoint syncer.
-        let mut latest_indices = Vec::with_capacity(validators.len());
-        for validator in validators {
-            let address = H160::from(*validator);
-            if let Some(checkpoint_syncer) = self.checkpoint_syncers.get(&address) {
-                // Gracefully handle errors getting the latest_index
-                match checkpoint_syncer.latest_index().await {
-                    Ok(Some(index)) => {
-                        trace!(?address, ?index, "Validator returned latest index");
-                        latest_indices.push(index);
-                    }
-                    err => {
-                        debug!(?address, ?err, "Failed to get latest index from validator");
-                    }
+        let Some(highest_quorum_index) = self.highest_quorum_index(validators, threshold).await
+        else {
+            return Ok(None);
+        };
+        // The highest viable checkpoint index is the minimum of the highest index
+        // we (supposedly) have a quorum for, and the maximum index for which we can
+        // generate a proof.
+        let start_index = highest_quorum_index.min(maximum_index);
+        if minimum_index > start_index {
+            debug!(%start_index, %highest_quorum_index, "Highest quorum index is below the minimum index");
+            return Ok(None);
+        }
+        for index in (minimum_index..=start_index).rev() {
+            if let Ok(Some(checkpoint)) = self.fetch_checkpoint(validators, threshold, index).await
+            {
+                return Ok(Some(checkpoint));
+            }
+        }
+        debug!("No checkpoint found in range");
+        Ok(None)
+    }
+
+    /// Attempts to get the lowest checkpoint at or above `minimum_index` with
+    /// a quorum of signatures among validators.
+    ///
+    /// Iterates forwards from `minimum_index` up to the highest index that
+    /// >= `threshold` validators have returned as their `latest_index`, or
+    /// `maximum_index` if it is lower. This finds a checkpoint to prove a
+    /// message against when validators did not sign the checkpoint at its
+    /// index.
+    ///
+    /// Note it's possible to not find a quorum.
+    #[instrument(err, skip(self))]
+    pub async fn fetch_lowest_checkpoint_in_range(
+        &self,
+        validators: &[H256],
+        threshold: usize,
+        minimum_index: u32,
+        maximum_index: u32,
+    ) -> Result<Option<MultisigSignedCheckpoint<CheckpointWithMessageId>>> {
+        let Some(highest_quorum_index) = self.highest_quorum_index(validators, threshold).await
+        else {
+            return Ok(None);
+        };
+        let end_index = highest_quorum_index.min(maximum_index);
+        if minimum_index > end_index {
+            debug!(%end_index, %highest_quorum_index, "Highest quorum index is below the minimum index");
+            return Ok(None);
+        }
+        for index in minimum_index..=end_index {
+            if let Ok(Some(checkpoint)) = self.fetch_checkpoint(validators, threshold, index).await
+            {
+                return Ok(Some(checkpoint));
+            }
+        }
+        debug!("No checkpoint found in range");
+        Ok(None)
+    }
+
+    /// Fetches a MultisigSignedCheckpointWithMessageId if there is a quorum.
+    /// Returns Ok(None) if there is no quorum.
+    #[instrument(err, skip(self))]
+    pub async fn fetch_checkpoint(
+        &self,
+        validators: &[H256],
+        threshold: usize,
+        index: u32,
+    ) -> Result<Option<MultisigSignedCheckpoint<CheckpointWithMessageId>>> {
+        self.fetch_quorum(validators, threshold, index, |syncer| async move {
+            syncer.fetch_checkpoint(index).await
+        })
+        .await
+    }
+
+    /// The highest index for which `threshold` validators have (supposedly)
+    /// signed a checkpoint, going by the `latest_index` of each validator's
+    /// checkpoint syncer, which are fetched concurrently.
+    ///
+    /// Validators which have been failing are only queried if there cannot be
+    /// a quorum without them. Once there is a quorum, the other validators are
+    /// only waited on for the hedge delay, as they may raise the index.
+    async fn highest_quorum_index(&self, validators: &[H256], threshold: usize) -> Option<u32> {
+        let (preferred, failing) = self.health.order(
+            validators
+                .iter()
+                .map(|validator| H160::from(*validator))
+                .filter(|address| self.checkpoint_syncers.contains_key(address)),
+        );
+        let mut failing = failing.into_iter();
+
+        let fetch = |syncer: Arc<dyn CheckpointSyncer>| async move { syncer.latest_index().await };
+        let query = |validator| self.query(validator, &fetch);
+        let mut in_flight: FuturesUnordered<_> = preferred.into_iter().map(query).collect();
+
+        let mut latest_indices = Vec::with_capacity(in_flight.len());
+        let mut deadline = None;
+        loop {
+            while in_flight.len() + latest_indices.len() < threshold {
+                let Some(validator) = failing.next() else {
+                    break;
+                };
+                in_flight.push(query(validator));
+            }
+            let response = match deadline {
+                Some(deadline) => timeout_at(deadline, in_flight.next()).await.ok().flatten(),
+                None => in_flight.next().await,
+            };
+            let Some((address, latency, result)) = response else {
+                break;
+            };
+
+            // Gracefully handle errors getting the latest_index
+            match result {
+                Ok(Ok(Some(index))) => {
+                    trace!(?address, ?index, "Validator returned latest index");
+                    self.health.record_response(address, latency);
+                    latest_indices.push((address, index));
+                }
+                Ok(Ok(None)) => {
+                    debug!(?address, "Validator has not signed any checkpoints");
+                    self.health.record_response(address, latency);
                 }
+                err => {
+                    debug!(?address, ?err, "Failed to get latest index from validator");
+                    self.health.record_failure(address);
+                }
+            }
+            if deadline.is_none() && latest_indices.len() >= threshold {
+                deadline = Some(Instant::now() + self.fetch_conf.hedge_delay);
             }
         }
         debug!(
             ?latest_indices,
             "Fetched latest indices from checkpoint syncers"
         );
+        self.health.record_latest_indices(&latest_indices);
 
         if latest_indices.is_empty() {
             debug!("No validators returned a latest index");
-            return Ok(None);
+            return None;
         }
 
         // Sort in descending order. The n'th index will represent
         // the highest index for which we (supposedly) have (n+1) signed checkpoints
+        let mut latest_indices: Vec<u32> = latest_indices.into_iter().map(|(_, i)| i).collect();
         latest_indices.sort_by(|a, b| b.cmp(a));
-        if let Some(&highest_quorum_index) = latest_indices.get(threshold - 1) {
-            // The highest viable checkpoint index is the minimum of the highest index
-            // we (supposedly) have a quorum for, and the maximum index for which we can
-            // generate a proof.
-            let start_index = highest_quorum_index.min(maximum_index);
-            if minimum_index > start_index {
-                debug!(%start_index, %highest_quorum_index, "Highest quorum index is below the minimum index");
-                return Ok(None);
-            }
-            for index in (minimum_index..=start_index).rev() {
-                if let Ok(Some(checkpoint)) =
-                    self.fetch_checkpoint(validators, threshold, index).await
-                {
-                    return Ok(Some(checkpoint));
-                }
-            }
-        }
-        debug!("No checkpoint found in range");
-        Ok(None)
+        latest_indices.get(threshold.checked_sub(1)?).copied()
     }
 
-    /// Fetches a MultisigSignedCheckpointWithMessageId if there is a quorum.
-    /// Returns Ok(None) if there is no quorum.
-    #[instrument(err, skip(self))]
-    pub async fn fetch_checkpoint(
+    /// Fetches the checkpoint at `index` from validators until `threshold` of
+    /// them have signed the same checkpoint.
+    ///
+    /// The healthiest `threshold` validators are queried concurrently first.
+    /// Another validator is queried whenever one fails to provide a valid
+    /// checkpoint, or whenever the hedge delay passes without a response.
+    /// Validators which have been failing are only queried once there are no
+    /// others left. Requests which are still in flight when a quorum is reached
+    /// are dropped.
+    async fn fetch_quorum<T, F, Fut>(
         &self,
         validators: &[H256],
         threshold: usize,
         index: u32,
-    ) -> Result<Option<MultisigSignedCheckpoint<CheckpointWithMessageId>>> {
+        fetch: F,
+    ) -> Result<Option<MultisigSignedCheckpoint<T>>>
+    where
+        T: CheckpointValue,
+        F: Fn(Arc<dyn CheckpointSyncer>) -> Fut,
+        Fut: Future<Output = Result<Option<SignedType<T>>>>,
+    {
         // Keeps track of signed validator checkpoints for a particular root.
         // In practice, it's likely that validators will all sign the same root for a
         // particular index, but we'd like to be robust to this not being the case
-        let mut signed_checkpoints_per_root: HashMap<
-            H256,
-            Vec<SignedCheckpointWithSigner<CheckpointWithMessageId>>,
-        > = HashMap::new();
-
-        for validator in validators.iter() {
-            let addr = H160::from(*validator);
-            if let Some(checkpoint_syncer) = self.checkpoint_syncers.get(&addr) {
+        let mut signed_checkpoints_per_root: HashMap<H256, Vec<SignedCheckpointWithSigner<T>>> =
+            HashMap::new();
+
+        let (preferred, failing) = self.health.order(validators.iter().filter_map(|validator| {
+            let address = H160::from(*validator);
+            if self.checkpoint_syncers.contains_key(&address) {
+                Some(address)
+            } else {
+                debug!(%validator, "Unable to find checkpoint syncer");
+                None
+            }
+        }));
+        let mut preferred = preferred.into_iter();
+        let mut failing = failing.into_iter();
+
+        let query = |validator| self.query(validator, &fetch);
+        let mut in_flight: FuturesUnordered<_> =
+            preferred.by_ref().take(threshold).map(query).collect();
+
+        loop {
+            if in_flight.is_empty() {
+                match preferred.next().or_else(|| failing.next()) {
+                    Some(validator) => in_flight.push(query(validator)),
+                    None => break,
+                }
+            }
+            let (validator, latency, result) = tokio::select! {
+                Some(response) = in_flight.next() => response,
+                _ = sleep(self.fetch_conf.hedge_delay), if preferred.len() > 0 => {
+                    if let Some(validator) = preferred.next() {
+                        debug!(validator = format!("{validator:#x}"), index, "Hedging checkpoint request");
+                        in_flight.push(query(validator));
+                    }
+                    continue;
+                }
+                else => break,
+            };
+
+            let signed_checkpoint = match result {
+                Ok(Ok(Some(signed_checkpoint))) => {
+                    self.health.record_response(validator, latency);
+                    signed_checkpoint
+                }
                 // Gracefully ignore an error fetching the checkpoint from a validator's
                 // checkpoint syncer, which can happen if the validator has not
                 // signed the checkpoint at `index`.
-                if let Ok(Some(signed_checkpoint)) = checkpoint_syncer.fetch_checkpoint(index).await
-                {
-                    // If the signed checkpoint is for a different index, ignore it
-                    if signed_checkpoint.value.index != index {
-                        debug!(
-                            validator = format!("{:#x}", validator),
-                            index = index,
-                            checkpoint_index = signed_checkpoint.value.index,
-                            "Checkpoint index mismatch"
-                        );
-                        continue;
-                    }
-                    // Ensure that the signature is actually by the validator
-                    let signer = signed_checkpoint.recover()?;
-                    if H256::from(signer) != *validator {
-                        debug!(
-                            validator = format!("{:#x}", validator),
-                            index = index,
-                            "Checkpoint signature mismatch"
-                        );
-                        continue;
-                    }
-
-                    // Insert the SignedCheckpointWithSigner into signed_checkpoints_per_root
-                    let signed_checkpoint_with_signer =
-                        SignedCheckpointWithSigner::<CheckpointWithMessageId> {
-                            signer,
-                            signed_checkpoint,
-                        };
-                    let root = signed_checkpoint_with_signer.signed_checkpoint.value.root;
-
-                    let signature_count = match signed_checkpoints_per_root.entry(root) {
-                        Entry::Occupied(mut entry) => {
-                            let vec = entry.get_mut();
-                            vec.push(signed_checkpoint_with_signer);
-                            vec.len()
-                        }
-                        Entry::Vacant(entry) => {
-                            entry.insert(vec![signed_checkpoint_with_signer]);
-                            1 // length of 1
-                        }
-                    };
+                Ok(Ok(None)) => {
+                    self.health.record_response(validator, latency);
                     debug!(
-                        validator = format!("{:#x}", validator),
-                        index = index,
-                        root = format!("{:#x}", root),
-                        signature_count = signature_count,
-                        "Found signed checkpoint"
+                        validator = format!("{validator:#x}"),
+                        index, "Unable to find signed checkpoint"
                     );
-                    // If we've hit a quorum, create a MultisigSignedCheckpoint
-                    if signature_count >= threshold {
-                        if let Some(signed_checkpoints) = signed_checkpoints_per_root.get(&root) {
-                            let checkpoint =
-                                MultisigSignedCheckpoint::<CheckpointWithMessageId>::try_from(
-                                    signed_checkpoints,
-                                )?;
-                            debug!(checkpoint=?checkpoint, "Fetched multisig checkpoint");
-                            return Ok(Some(checkpoint));
-                        }
-                    }
-                } else {
+                    query_next(&mut in_flight, &mut preferred, &mut failing, &query);
+                    continue;
+                }
+                Ok(Err(err)) => {
+                    self.health.record_failure(validator);
                     debug!(
-                        validator = format!("{:#x}", validator),
-                        index = index,
-                        "Unable to find signed checkpoint"
+                        validator = format!("{validator:#x}"),
+                        index,
+                        ?err,
+                        "Failed to fetch signed checkpoint"
                     );
+                    query_next(&mut in_flight, &mut preferred, &mut failing, &query);
+                    continue;
                 }
-            } else {
-                debug!(%validator, "Unable to find checkpoint syncer");
+                Err(_) => {
+                    self.health.record_failure(validator);
+                    debug!(
+                        validator = format!("{validator:#x}"),
+                        index, "Timed out fetching signed checkpoint"
+                    );
+                    query_next(&mut in_flight, &mut preferred, &mut failing, &query);
+                    continue;
+                }
+            };
+
+            // If the signed checkpoint is for a different index, ignore it
+            if signed_checkpoint.value.checkpoint().index != index {
+                debug!(
+                    validator = format!("{validator:#x}"),
+                    index,
+                    checkpoint_index = signed_checkpoint.value.checkpoint().index,
+                    "Checkpoint index mismatch"
+                );
+                self.health.record_index_mismatch(validator);
+                query_next(&mut in_flight, &mut preferred, &mut failing, &query);
+                continue;
+            }
+            // Ensure that the signature is actually by the validator
+            if !matches!(signed_checkpoint.recover(), Ok(signer) if signer == validator) {
+                debug!(
+                    validator = format!("{validator:#x}"),
+                    index, "Checkpoint signature mismatch"
+                );
+                self.health.record_signature_failure(validator);
+                query_next(&mut in_flight, &mut preferred, &mut failing, &query);
                 continue;
             }
+            // Ensure that the validator has not signed a different checkpoint
+            // for this index before
+            if !T::observe(&self.equivocations, validator, &signed_checkpoint) {
+                self.health.record_signature_failure(validator);
+                query_next(&mut in_flight, &mut preferred, &mut failing, &query);
+                continue;
+            }
+            self.health.record_valid_checkpoint(validator);
+
+            // Insert the SignedCheckpointWithSigner into signed_checkpoints_per_root
+            let root = signed_checkpoint.value.checkpoint().root;
+            let signed_checkpoint_with_signer = SignedCheckpointWithSigner {
+                signer: validator,
+                signed_checkpoint,
+            };
+            let signature_count = match signed_checkpoints_per_root.entry(root) {
+                Entry::Occupied(mut entry) => {
+                    let vec = entry.get_mut();
+                    vec.push(signed_checkpoint_with_signer);
+                    vec.len()
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(vec![signed_checkpoint_with_signer]);
+                    1 // length of 1
+                }
+            };
+            debug!(
+                validator = format!("{validator:#x}"),
+                index,
+                root = format!("{root:#x}"),
+                signature_count,
+                "Found signed checkpoint"
+            );
+            // If we've hit a quorum, create a MultisigSignedCheckpoint
+            if signature_count >= threshold {
+                let checkpoint =
+                    MultisigSignedCheckpoint::try_from(&signed_checkpoints_per_root[&root])?;
+                debug!(?checkpoint, "Fetched multisig checkpoint");
+                return Ok(Some(checkpoint));
+            }
+            // A validator may have signed a different root than the others, in
+            // which case more are needed for a quorum.
+            if in_flight.len() + signature_count < threshold {
+                query_next(&mut in_flight, &mut preferred, &mut failing, &query);
+            }
         }
         Ok(None)
     }
+
+    /// Makes a request to the checkpoint syncer of `validator`, waiting for as
+    /// long as configured for it. Resolves to the validator, the latency of
+    /// the request and its result.
+    fn query<T, F, Fut>(
+        &self,
+        validator: H160,
+        fetch: &F,
+    ) -> impl Future<Output = (H160, Duration, Result<Result<T>, Elapsed>)>
+    where
+        F: Fn(Arc<dyn CheckpointSyncer>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let request = fetch(self.checkpoint_syncers[&validator].clone());
+        let duration = self.fetch_conf.timeout(&validator);
+        async move {
+            let start = Instant::now();
+            let result = timeout(duration, request).await;
+            (validator, start.elapsed(), result)
+        }
+    }
+}
+
+/// Query the next healthiest validator, if there are any left.
+fn query_next<Q, Fut>(
+    in_flight: &mut FuturesUnordered<Fut>,
+    preferred: &mut impl Iterator<Item = H160>,
+    failing: &mut impl Iterator<Item = H160>,
+    query: &Q,
+) where
+    Q: Fn(H160) -> Fut,
+{
+    if let Some(validator) = preferred.next().or_else(|| failing.next()) {
+        in_flight.push(query(validator));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use ethers::signers::LocalWallet;
+    use hyperlane_core::{
+        HyperlaneSigner, HyperlaneSignerExt, SignedAnnouncement, SignedCheckpoint,
+        SignedCheckpointWithMessageId,
+    };
+    use hyperlane_ethereum::Signers;
+
+    use super::*;
+
+    const INDEX: u32 = 10;
+
+    /// A checkpoint syncer which responds after a delay.
+    #[derive(Debug)]
+    struct MockCheckpointSyncer {
+        latest_index: Option<u32>,
+        checkpoint: Option<SignedCheckpointWithMessageId>,
+        delay: Duration,
+        requests: AtomicUsize,
+    }
+
+    impl MockCheckpointSyncer {
+        fn requests(&self) -> usize {
+            self.requests.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl CheckpointSyncer for MockCheckpointSyncer {
+        async fn latest_index(&self) -> Result<Option<u32>> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            sleep(self.delay).await;
+            Ok(self.latest_index)
+        }
+
+        async fn legacy_fetch_checkpoint(&self, _index: u32) -> Result<Option<SignedCheckpoint>> {
+            unimplemented!()
+        }
+
+        async fn fetch_checkpoint(
+            &self,
+            index: u32,
+        ) -> Result<Option<SignedCheckpointWithMessageId>> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            sleep(self.delay).await;
+            Ok(self
+                .checkpoint
+                .clone()
+                .filter(|_| self.latest_index >= Some(index)))
+        }
+
+        async fn legacy_write_checkpoint(&self, _signed: &SignedCheckpoint) -> Result<()> {
+            unimplemented!()
+        }
+
+        async fn write_checkpoint(&self, _signed: &SignedCheckpointWithMessageId) -> Result<()> {
+            unimplemented!()
+        }
+
+        async fn write_announcement(&self, _signed: &SignedAnnouncement) -> Result<()> {
+            unimplemented!()
+        }
+
+        fn announcement_location(&self) -> String {
+            unimplemented!()
+        }
+    }
+
+    fn signer(i: u64) -> Signers {
+        let key = H256::from_low_u64_be(i + 1);
+        Signers::Local(LocalWallet::from_bytes(key.as_bytes()).unwrap())
+    }
+
+    fn checkpoint(index: u32) -> CheckpointWithMessageId {
+        CheckpointWithMessageId {
+            checkpoint: Checkpoint {
+                mailbox_address: H256::zero(),
+                mailbox_domain: 1,
+                root: H256::repeat_byte(1),
+                index,
+            },
+            message_id: H256::repeat_byte(2),
+        }
+    }
+
+    /// Validators whose checkpoint syncers respond after the given delays in
+    /// milliseconds, with the checkpoint at `INDEX` signed by them.
+    struct Validators(Vec<(H160, Arc<MockCheckpointSyncer>)>);
+
+    impl Validators {
+        async fn new(delays_ms: &[u64]) -> Self {
+            let mut validators = Self(vec![]);
+            for &delay_ms in delays_ms {
+                validators
+                    .push(Some(INDEX), Some(checkpoint(INDEX)), None, delay_ms)
+                    .await;
+            }
+            validators
+        }
+
+        /// Add a validator, which signs `checkpoint` with the key of the
+        /// validator at `signed_by` if set, or its own key otherwise.
+        async fn push(
+            &mut self,
+            latest_index: Option<u32>,
+            checkpoint: Option<CheckpointWithMessageId>,
+            signed_by: Option<u64>,
+            delay_ms: u64,
+        ) {
+            let i = self.0.len() as u64;
+            let signer = signer(i);
+            let checkpoint = match checkpoint {
+                Some(checkpoint) => Some(
+                    signer_or(&signer, signed_by)
+                        .sign(checkpoint)
+                        .await
+                        .unwrap(),
+                ),
+                None => None,
+            };
+            let syncer = MockCheckpointSyncer {
+                latest_index,
+                checkpoint,
+                delay: Duration::from_millis(delay_ms),
+                requests: AtomicUsize::new(0),
+            };
+            self.0.push((signer.eth_address(), Arc::new(syncer)));
+        }
+
+        fn address(&self, i: usize) -> H160 {
+            self.0[i].0
+        }
+
+        fn requests(&self, i: usize) -> usize {
+            self.0[i].1.requests()
+        }
+
+        fn addresses(&self) -> Vec<H256> {
+            self.0
+                .iter()
+                .map(|(address, _)| (*address).into())
+                .collect()
+        }
+
+        fn syncer(
+            &self,
+            health: Arc<ValidatorHealth>,
+            fetch_conf: CheckpointFetchConf,
+        ) -> MultisigCheckpointSyncer {
+            let checkpoint_syncers = self
+                .0
+                .iter()
+                .map(|(address, syncer)| (*address, syncer.clone() as Arc<dyn CheckpointSyncer>))
+                .collect();
+            MultisigCheckpointSyncer::new(
+                checkpoint_syncers,
+                health,
+                Default::default(),
+                Arc::new(fetch_conf),
+            )
+        }
+    }
+
+    fn signer_or(signer: &Signers, other: Option<u64>) -> Signers {
+        match other {
+            Some(i) => self::signer(i),
+            None => signer.clone(),
+        }
+    }
+
+    fn signers<T>(checkpoint: &MultisigSignedCheckpoint<T>) -> Vec<H160> {
+        let mut signers: Vec<_> = checkpoint.signatures.iter().map(|s| s.signer).collect();
+        signers.sort();
+        signers
+    }
+
+    fn sorted(mut addresses: Vec<H160>) -> Vec<H160> {
+        addresses.sort();
+        addresses
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hedges_slow_validators() {
+        let validators = Validators::new(&[2000, 10, 10]).await;
+        let syncer = validators.syncer(Default::default(), Default::default());
+
+        let start = Instant::now();
+        let checkpoint = syncer
+            .fetch_checkpoint(&validators.addresses(), 2, INDEX)
+            .await
+            .unwrap()
+            .unwrap();
+        // The third validator is queried once the first has not responded for
+        // the hedge delay, rather than waiting for the first
+        assert_eq!(
+            signers(&checkpoint),
+            sorted(vec![validators.address(1), validators.address(2)])
+        );
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_uses_validator_timeouts() {
+        let validators = Validators::new(&[1000, 10]).await;
+        let fetch_conf = CheckpointFetchConf {
+            validator_timeouts: [(validators.address(0), Duration::from_millis(100))].into(),
+            hedge_delay: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let syncer = validators.syncer(Default::default(), fetch_conf);
+
+        let start = Instant::now();
+        let checkpoint = syncer
+            .fetch_checkpoint(&validators.addresses(), 1, INDEX)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(signers(&checkpoint), vec![validators.address(1)]);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_replaces_invalid_checkpoints_until_quorum() {
+        let mut validators = Validators(vec![]);
+        // A checkpoint for another index
+        validators
+            .push(Some(INDEX), Some(checkpoint(INDEX - 1)), None, 10)
+            .await;
+        // A checkpoint signed by another validator
+        validators
+            .push(Some(INDEX), Some(checkpoint(INDEX)), Some(9), 10)
+            .await;
+        // No checkpoint at all
+        validators.push(Some(INDEX), None, None, 10).await;
+        for _ in 0..3 {
+            validators
+                .push(Some(INDEX), Some(checkpoint(INDEX)), None, 10)
+                .await;
+        }
+        let health = Arc::new(ValidatorHealth::default());
+        let syncer = validators.syncer(health.clone(), Default::default());
+
+        let checkpoint = syncer
+            .fetch_checkpoint(&validators.addresses(), 2, INDEX)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            signers(&checkpoint),
+            sorted(vec![validators.address(3), validators.address(4)])
+        );
+        // Validators are not queried once there is a quorum
+        assert_eq!(validators.requests(5), 0);
+
+        // The index mismatch is penalized less than the invalid signature
+        let (preferred, _) = health.order([validators.address(1), validators.address(0)]);
+        assert_eq!(
+            preferred,
+            vec![validators.address(0), validators.address(1)]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_quorum() {
+        let mut validators = Validators::new(&[10]).await;
+        validators.push(Some(INDEX), None, None, 10).await;
+        let syncer = validators.syncer(Default::default(), Default::default());
+
+        let checkpoint = syncer
+            .fetch_checkpoint(&validators.addresses(), 2, INDEX)
+            .await
+            .unwrap();
+        assert!(checkpoint.is_none());
+        assert_eq!(validators.requests(0), 1);
+        assert_eq!(validators.requests(1), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_highest_quorum_index_does_not_wait_for_every_validator() {
+        let mut validators = Validators(vec![]);
+        for (latest_index, delay_ms) in [(12, 2000), (11, 10), (10, 10), (13, 10)] {
+            validators
+                .push(Some(latest_index), None, None, delay_ms)
+                .await;
+        }
+        // The last validator has been failing
+        let health = Arc::new(ValidatorHealth::default());
+        for _ in 0..10 {
+            health.record_failure(validators.address(3));
+        }
+        let syncer = validators.syncer(health, Default::default());
+
+        // The slow validator is only waited on for the hedge delay once there
+        // is a quorum, and the failing one is not queried
+        let start = Instant::now();
+        let index = syncer
+            .highest_quorum_index(&validators.addresses(), 2)
+            .await;
+        assert_eq!(index, Some(10));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(validators.requests(3), 0);
+
+        // The failing validator is queried when a quorum needs it
+        let index = syncer
+            .highest_quorum_index(&validators.addresses(), 4)
+            .await;
+        assert_eq!(index, Some(10));
+        assert_eq!(validators.requests(3), 1);
+    }
 }
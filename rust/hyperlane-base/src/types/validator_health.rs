@@ -0,0 +1,219 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hyperlane_core::H160;
+
+/// How much a new latency sample moves the average latency of a validator.
+const LATENCY_SAMPLE_WEIGHT: f64 = 0.2;
+/// Added to the score of a validator for each consecutive failure to respond,
+/// in seconds of latency.
+const FAILURE_PENALTY_S: f64 = 2.;
+/// Added to the score of a validator for each recent checkpoint with an
+/// invalid signature, in seconds of latency.
+const SIGNATURE_FAILURE_PENALTY_S: f64 = 10.;
+/// Added to the score of a validator for each recent checkpoint which was not
+/// for the requested index, in seconds of latency.
+const INDEX_MISMATCH_PENALTY_S: f64 = 1.;
+/// Added to the score of a validator for each index its latest checkpoint is
+/// behind the latest checkpoint of the most up to date validator.
+const STALENESS_PENALTY_S: f64 = 0.01;
+/// Validators which failed this many times in a row are only queried if a
+/// quorum cannot be reached without them.
+const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+/// How long validators are skipped for after failing too many times in a row,
+/// before they are given another chance.
+const SKIP_DURATION: Duration = Duration::from_secs(5 * 60);
+
+/// Health scores of validators based on how their checkpoint syncers have
+/// responded, used to query healthy validators first. A lower score is
+/// better.
+#[derive(Debug, Default)]
+pub struct ValidatorHealth(Mutex<HashMap<H160, HealthStats>>);
+
+#[derive(Debug, Default)]
+struct HealthStats {
+    /// Moving average of the latency of successful requests, in seconds.
+    latency_s: f64,
+    consecutive_failures: u32,
+    last_failure: Option<Instant>,
+    /// Decremented for every valid checkpoint, so it only counts recent
+    /// invalid signatures.
+    signature_failures: u32,
+    /// Decremented for every valid checkpoint, so it only counts recent
+    /// checkpoints for the wrong index.
+    index_mismatches: u32,
+    /// Number of indices the latest checkpoint of the validator was behind
+    /// the most up to date validator when last checked.
+    staleness: u32,
+}
+
+impl HealthStats {
+    fn score(&self) -> f64 {
+        self.latency_s
+            + FAILURE_PENALTY_S * self.consecutive_failures as f64
+            + SIGNATURE_FAILURE_PENALTY_S * self.signature_failures as f64
+            + INDEX_MISMATCH_PENALTY_S * self.index_mismatches as f64
+            + STALENESS_PENALTY_S * self.staleness as f64
+    }
+
+    fn is_skipped(&self, now: Instant) -> bool {
+        self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES
+            && matches!(self.last_failure, Some(t) if now.saturating_duration_since(t) < SKIP_DURATION)
+    }
+}
+
+impl ValidatorHealth {
+    /// Record a response from a validator, which may or may not have included
+    /// a checkpoint.
+    pub fn record_response(&self, validator: H160, latency: Duration) {
+        let mut validators = self.0.lock().unwrap();
+        let stats = validators.entry(validator).or_default();
+        let latency_s = latency.as_secs_f64();
+        stats.latency_s = if stats.latency_s == 0. {
+            latency_s
+        } else {
+            stats.latency_s * (1. - LATENCY_SAMPLE_WEIGHT) + latency_s * LATENCY_SAMPLE_WEIGHT
+        };
+        stats.consecutive_failures = 0;
+    }
+
+    /// Record a valid checkpoint from a validator.
+    pub fn record_valid_checkpoint(&self, validator: H160) {
+        let mut validators = self.0.lock().unwrap();
+        let stats = validators.entry(validator).or_default();
+        stats.signature_failures = stats.signature_failures.saturating_sub(1);
+        stats.index_mismatches = stats.index_mismatches.saturating_sub(1);
+    }
+
+    /// Record a validator failing to respond, e.g. by timing out.
+    pub fn record_failure(&self, validator: H160) {
+        self.record_failure_at(validator, Instant::now())
+    }
+
+    fn record_failure_at(&self, validator: H160, now: Instant) {
+        let mut validators = self.0.lock().unwrap();
+        let stats = validators.entry(validator).or_default();
+        stats.consecutive_failures += 1;
+        stats.last_failure = Some(now);
+    }
+
+    /// Record a checkpoint from a validator which was not signed by it or
+    /// conflicts with one it signed before.
+    pub fn record_signature_failure(&self, validator: H160) {
+        let mut validators = self.0.lock().unwrap();
+        validators.entry(validator).or_default().signature_failures += 1;
+    }
+
+    /// Record a checkpoint from a validator which was not for the requested
+    /// index, e.g. because its checkpoint syncer is misconfigured.
+    pub fn record_index_mismatch(&self, validator: H160) {
+        let mut validators = self.0.lock().unwrap();
+        validators.entry(validator).or_default().index_mismatches += 1;
+    }
+
+    /// Record the latest checkpoint indices of a set of validators to score
+    /// how far behind each one is.
+    pub fn record_latest_indices(&self, latest_indices: &[(H160, u32)]) {
+        let Some(highest) = latest_indices.iter().map(|(_, index)| *index).max() else {
+            return;
+        };
+        let mut validators = self.0.lock().unwrap();
+        for (validator, index) in latest_indices {
+            validators.entry(*validator).or_default().staleness = highest - index;
+        }
+    }
+
+    /// Order validators from most to least healthy. Validators which have
+    /// failed too many times in a row are returned separately, to only be
+    /// queried if a quorum cannot be reached without them.
+    pub fn order(&self, validators: impl IntoIterator<Item = H160>) -> (Vec<H160>, Vec<H160>) {
+        self.order_at(validators, Instant::now())
+    }
+
+    fn order_at(
+        &self,
+        validators: impl IntoIterator<Item = H160>,
+        now: Instant,
+    ) -> (Vec<H160>, Vec<H160>) {
+        let stats = self.0.lock().unwrap();
+        let mut scored: Vec<_> = validators
+            .into_iter()
+            .map(|validator| match stats.get(&validator) {
+                Some(s) => (validator, s.score(), s.is_skipped(now)),
+                None => (validator, 0., false),
+            })
+            .collect();
+        scored.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (skipped, preferred): (Vec<_>, Vec<_>) = scored.into_iter().partition(|v| v.2);
+        (
+            preferred.into_iter().map(|v| v.0).collect(),
+            skipped.into_iter().map(|v| v.0).collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn validator(i: u64) -> H160 {
+        H160::from_low_u64_be(i)
+    }
+
+    #[test]
+    fn test_slow_and_stale_validators_are_ordered_last() {
+        let health = ValidatorHealth::default();
+        health.record_response(validator(1), Duration::from_millis(900));
+        health.record_response(validator(2), Duration::from_millis(100));
+        health.record_response(validator(3), Duration::from_millis(100));
+        health.record_latest_indices(&[(validator(2), 10), (validator(3), 1000)]);
+
+        let (preferred, skipped) =
+            health.order([validator(1), validator(2), validator(3), validator(4)]);
+        // Validator 4 has not been queried yet so is assumed to be healthy
+        assert_eq!(
+            preferred,
+            vec![validator(4), validator(3), validator(1), validator(2)]
+        );
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn test_failing_validators_are_skipped_for_a_while() {
+        let health = ValidatorHealth::default();
+        let now = Instant::now();
+        for _ in 0..=MAX_CONSECUTIVE_FAILURES {
+            health.record_failure_at(validator(1), now);
+        }
+        health.record_signature_failure(validator(2));
+
+        let (preferred, skipped) = health.order_at([validator(1), validator(2)], now);
+        assert_eq!(preferred, vec![validator(2)]);
+        assert_eq!(skipped, vec![validator(1)]);
+
+        let (preferred, _) = health.order_at([validator(1), validator(2)], now + SKIP_DURATION);
+        assert_eq!(preferred, vec![validator(2), validator(1)]);
+
+        health.record_response(validator(1), Duration::from_millis(100));
+        let (preferred, _) = health.order_at([validator(1), validator(2)], now);
+        assert_eq!(preferred, vec![validator(1), validator(2)]);
+    }
+
+    #[test]
+    fn test_index_mismatches_are_penalized_less_than_signature_failures() {
+        let health = ValidatorHealth::default();
+        health.record_signature_failure(validator(1));
+        health.record_index_mismatch(validator(2));
+
+        let (preferred, _) = health.order([validator(1), validator(2), validator(3)]);
+        assert_eq!(preferred, vec![validator(3), validator(2), validator(1)]);
+
+        // Valid checkpoints make up for recent mismatches
+        health.record_valid_checkpoint(validator(2));
+        let (preferred, _) = health.order([validator(2), validator(3)]);
+        assert_eq!(preferred, vec![validator(2), validator(3)]);
+    }
+}
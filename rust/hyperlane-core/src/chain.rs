@@ -286,6 +286,21 @@ impl Debug for HyperlaneDomain {
     }
 }
 
+/// Separates the chain name from the deployment name in the names of domains
+/// of additional mailbox deployments.
+const DEPLOYMENT_SEPARATOR: char = '@';
+
+/// Identifies a mailbox deployment on a chain. The domains of the deployments
+/// on a chain are equal as they share its domain id, so collections holding
+/// several deployments of a chain are keyed by this instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeploymentKey {
+    /// The domain id of the chain
+    pub domain_id: u32,
+    /// The name of an additional deployment, `None` for the default one
+    pub deployment: Option<String>,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum HyperlaneDomainConfigError {
     #[error("Domain name (`{0}`) does not match the name of a known domain id; the name is probably misspelled.")]
@@ -351,6 +366,42 @@ impl HyperlaneDomain {
         }
     }
 
+    /// The domain of another mailbox deployment on this chain. It shares the
+    /// domain id of the chain but is named `<chain>@<deployment>`, so that it
+    /// gets a DB namespace and metrics labels of its own.
+    #[cfg(feature = "strum")]
+    pub fn with_deployment(&self, deployment: &str) -> Self {
+        HyperlaneDomain::Unknown {
+            domain_id: self.id(),
+            domain_name: format!(
+                "{}{DEPLOYMENT_SEPARATOR}{}",
+                self.name(),
+                deployment.to_ascii_lowercase()
+            ),
+            domain_type: self.domain_type(),
+            domain_protocol: self.domain_protocol(),
+        }
+    }
+
+    /// The name of the mailbox deployment this domain is for, `None` for the
+    /// default deployment of the chain.
+    pub fn deployment(&self) -> Option<&str> {
+        match self {
+            HyperlaneDomain::Known(_) => None,
+            HyperlaneDomain::Unknown { domain_name, .. } => domain_name
+                .split_once(DEPLOYMENT_SEPARATOR)
+                .map(|(_, deployment)| deployment),
+        }
+    }
+
+    /// The key of the mailbox deployment this domain is for.
+    pub fn deployment_key(&self) -> DeploymentKey {
+        DeploymentKey {
+            domain_id: self.id(),
+            deployment: self.deployment().map(str::to_owned),
+        }
+    }
+
     /// Backend implementation for this domain
     pub const fn domain_protocol(&self) -> HyperlaneDomainProtocol {
         match self {
@@ -367,7 +418,7 @@ impl HyperlaneDomain {
 mod tests {
     use std::str::FromStr;
 
-    use crate::KnownHyperlaneDomain;
+    use crate::{HyperlaneDomain, KnownHyperlaneDomain};
 
     #[test]
     fn domain_strings() {
@@ -420,4 +471,21 @@ mod tests {
         );
         assert!("foo".parse::<KnownHyperlaneDomain>().is_err());
     }
+
+    #[test]
+    fn test_deployment_domain() {
+        let ethereum = HyperlaneDomain::Known(KnownHyperlaneDomain::Ethereum);
+        let private = ethereum.with_deployment("Private");
+        assert_eq!(private.id(), 1);
+        assert_eq!(private.name(), "ethereum@private");
+        assert_eq!(private.deployment(), Some("private"));
+        assert_eq!(ethereum.deployment(), None);
+        // the deployments share the domain of the chain but not its key
+        assert_eq!(private, ethereum);
+        assert_ne!(private.deployment_key(), ethereum.deployment_key());
+        assert_eq!(
+            private.deployment_key(),
+            ethereum.with_deployment("private").deployment_key()
+        );
+    }
 }
@@ -4,6 +4,7 @@ use std::fmt::{Debug, Display, Formatter};
 use std::ops::Deref;
 
 use crate::HyperlaneProviderError;
+use crate::ProcessRevert;
 use crate::H256;
 
 /// The result of interacting with a chain.
@@ -74,6 +75,13 @@ pub enum ChainCommunicationError {
     /// No signer is available and was required for the operation
     #[error("Signer unavailable")]
     SignerUnavailable,
+    /// The chain does not support submitting several operations in a single
+    /// transaction
+    #[error("Batch submission is not supported")]
+    BatchingUnsupported,
+    /// Processing a message would revert
+    #[error("Process call reverted with {0}")]
+    ProcessReverted(ProcessRevert),
 }
 
 impl ChainCommunicationError {
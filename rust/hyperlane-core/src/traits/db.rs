@@ -4,7 +4,7 @@ use async_trait::async_trait;
 use auto_impl::auto_impl;
 use eyre::Result;
 
-use crate::{HyperlaneMessage, LogMeta};
+use crate::{HyperlaneMessage, InflightTransaction, LogMeta, H256};
 
 /// Interface for a HyperlaneLogStore that ingests logs.
 #[async_trait]
@@ -34,3 +34,17 @@ pub trait HyperlaneWatermarkedLogStore<T>: HyperlaneLogStore<T> {
     /// Stores the block number high watermark
     async fn store_high_watermark(&self, block_number: u32) -> Result<()>;
 }
+
+/// Interface for storing the transactions an agent is waiting on to be
+/// included, so it keeps tracking them after a restart instead of sending new
+/// ones.
+#[auto_impl(&, Box, Arc)]
+pub trait InflightTransactionStore: Send + Sync + Debug {
+    /// Stores the transaction in flight for the call identified by `call_id`,
+    /// e.g. the id of the message it processes.
+    fn store_inflight_transaction(&self, call_id: H256, tx: &InflightTransaction) -> Result<()>;
+    /// Gets the transaction in flight for a call.
+    fn retrieve_inflight_transaction(&self, call_id: H256) -> Result<Option<InflightTransaction>>;
+    /// Removes the transaction in flight for a call once it is resolved.
+    fn remove_inflight_transaction(&self, call_id: H256) -> Result<()>;
+}
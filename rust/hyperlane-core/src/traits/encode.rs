@@ -151,6 +151,50 @@ impl Decode for u64 {
     }
 }
 
+/// The longest string, in bytes, which is encoded. Lengths are read before the
+/// bytes, so this bounds what is allocated when decoding corrupt data.
+const MAX_ENCODED_STRING_LEN: u32 = 1 << 20;
+
+/// Encoded as a u32 byte length followed by the UTF-8 bytes.
+impl Encode for String {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let len = u32::try_from(self.len())
+            .ok()
+            .filter(|len| *len <= MAX_ENCODED_STRING_LEN)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "string too long to encode"))?;
+        len.write_to(writer)?;
+        writer.write_all(self.as_bytes())?;
+        Ok(4 + self.len())
+    }
+}
+
+impl Decode for String {
+    fn read_from<R>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let len = u32::read_from(reader)?;
+        if len > MAX_ENCODED_STRING_LEN {
+            return Err(HyperlaneProtocolError::IoError(Error::new(
+                ErrorKind::InvalidData,
+                "decoded string length exceeds the maximum",
+            )));
+        }
+        let mut buf = vec![0; len as usize];
+        reader.read_exact(&mut buf)?;
+        String::from_utf8(buf).map_err(|_| {
+            HyperlaneProtocolError::IoError(Error::new(
+                ErrorKind::InvalidData,
+                "decoded string is not valid UTF-8",
+            ))
+        })
+    }
+}
+
 impl Encode for bool {
     fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
     where
@@ -179,3 +223,28 @@ impl Decode for bool {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_string_round_trip() {
+        let string = "delivered".to_owned();
+        let encoded = string.to_vec();
+        assert_eq!(encoded.len(), 4 + string.len());
+        assert_eq!(String::read_from(&mut encoded.as_slice()).unwrap(), string);
+    }
+
+    #[test]
+    fn test_string_length_is_bounded() {
+        let too_long = "a".repeat(MAX_ENCODED_STRING_LEN as usize + 1);
+        assert!(too_long.write_to(&mut vec![]).is_err());
+
+        // A corrupt length is rejected rather than allocated
+        let encoded = u32::MAX.to_vec();
+        assert!(String::read_from(&mut encoded.as_slice()).is_err());
+        let encoded = (MAX_ENCODED_STRING_LEN + 1).to_vec();
+        assert!(String::read_from(&mut encoded.as_slice()).is_err());
+    }
+}
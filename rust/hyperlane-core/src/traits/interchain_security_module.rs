@@ -30,6 +30,9 @@ pub enum ModuleType {
     Null,
     /// Ccip Read ISM (accepts offchain signature information)
     CcipRead,
+    /// Optimistic ISM (pre-verified by a submodule, then delivered after a
+    /// fraud window)
+    Optimistic,
 }
 
 /// Interface for the InterchainSecurityModule chain contract. Allows abstraction over
@@ -41,6 +44,12 @@ pub trait InterchainSecurityModule: HyperlaneContract + Send + Sync + Debug {
     /// metadata offchain fetching and onchain formatting standard.
     async fn module_type(&self) -> ChainResult<ModuleType>;
 
+    /// Returns the module type as reported by the ISM, including types that
+    /// are not known to `ModuleType`.
+    async fn raw_module_type(&self) -> ChainResult<u8> {
+        Ok(self.module_type().await? as u8)
+    }
+
     /// Dry runs the `verify()` ISM call and returns `Some(gas_estimate)` if the call
     /// succeeds.
     async fn dry_run_verify(
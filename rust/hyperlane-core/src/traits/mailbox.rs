@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::num::NonZeroU64;
 
 use async_trait::async_trait;
@@ -6,7 +6,8 @@ use auto_impl::auto_impl;
 
 use crate::{
     accumulator::incremental::IncrementalMerkle, traits::TxOutcome, utils::domain_hash,
-    ChainResult, Checkpoint, HyperlaneContract, HyperlaneMessage, TxCostEstimate, H256, U256,
+    ChainCommunicationError, ChainResult, Checkpoint, HyperlaneContract, HyperlaneMessage,
+    TxCostEstimate, H256, U256,
 };
 
 /// Interface for the Mailbox chain contract. Allows abstraction over different
@@ -54,7 +55,23 @@ pub trait Mailbox: HyperlaneContract + Send + Sync + Debug {
         tx_gas_limit: Option<U256>,
     ) -> ChainResult<TxOutcome>;
 
-    /// Estimate transaction costs to process a message.
+    /// Process several messages in a single transaction. Each entry is a
+    /// message and the metadata to process it with.
+    ///
+    /// The batch is atomic: if any message fails to process the whole
+    /// transaction reverts and callers should fall back to processing the
+    /// messages individually. Chains without batching support return an error.
+    async fn process_batch(
+        &self,
+        _messages: &[(HyperlaneMessage, Vec<u8>)],
+        _tx_gas_limit: Option<U256>,
+    ) -> ChainResult<TxOutcome> {
+        Err(ChainCommunicationError::BatchingUnsupported)
+    }
+
+    /// Estimate transaction costs to process a message. Fails with
+    /// `ChainCommunicationError::ProcessReverted` if processing the message
+    /// would revert and the chain can tell why.
     async fn process_estimate_costs(
         &self,
         message: &HyperlaneMessage,
@@ -65,3 +82,51 @@ pub trait Mailbox: HyperlaneContract + Send + Sync + Debug {
     /// against the provided signed checkpoint
     fn process_calldata(&self, message: &HyperlaneMessage, metadata: &[u8]) -> Vec<u8>;
 }
+
+/// The kind of failure which makes processing a message revert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProcessRevertClass {
+    /// The message has already been delivered
+    AlreadyDelivered,
+    /// The ISM does not verify the message with the metadata
+    IsmRejected,
+    /// The recipient reverted when handling the message
+    RecipientReverted,
+    /// The message can never be processed by the mailbox, e.g. because it is
+    /// for another destination
+    InvalidMessage,
+}
+
+/// Why processing a message reverted when it was simulated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessRevert {
+    /// The kind of failure
+    pub class: ProcessRevertClass,
+    /// The decoded revert reason, empty if there was none
+    pub reason: String,
+}
+
+impl ProcessRevert {
+    /// Classify a revert of the mailbox's `process` by its reason. Reasons
+    /// which are not the mailbox's own come from the ISM or the recipient, and
+    /// are attributed to the recipient.
+    pub fn from_mailbox_reason(reason: String) -> Self {
+        let class = match reason.as_str() {
+            "delivered" => ProcessRevertClass::AlreadyDelivered,
+            "!module" => ProcessRevertClass::IsmRejected,
+            "!version" | "!destination" => ProcessRevertClass::InvalidMessage,
+            _ => ProcessRevertClass::RecipientReverted,
+        };
+        Self { class, reason }
+    }
+}
+
+impl Display for ProcessRevert {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.reason.is_empty() {
+            write!(f, "{:?} without a reason", self.class)
+        } else {
+            write!(f, "{:?}: {}", self.class, self.reason)
+        }
+    }
+}
@@ -9,6 +9,7 @@ pub use interchain_gas::*;
 pub use interchain_security_module::*;
 pub use mailbox::*;
 pub use multisig_ism::*;
+pub use optimistic_ism::*;
 pub use provider::*;
 pub use routing_ism::*;
 pub use signing::*;
@@ -25,6 +26,7 @@ mod interchain_gas;
 mod interchain_security_module;
 mod mailbox;
 mod multisig_ism;
+mod optimistic_ism;
 mod provider;
 mod routing_ism;
 mod signing;
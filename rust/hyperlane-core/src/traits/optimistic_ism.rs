@@ -0,0 +1,45 @@
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use auto_impl::auto_impl;
+
+use crate::{
+    ChainResult, HyperlaneContract, HyperlaneMessage, TxCostEstimate, TxOutcome, H256, U256,
+};
+
+/// Interface for the OptimisticIsm chain contract. Allows abstraction over
+/// different chains.
+///
+/// A message is pre-verified with the metadata of the submodule of the
+/// optimistic ISM, after which it can only be delivered once the fraud window
+/// has elapsed without the submodule being marked fraudulent.
+#[async_trait]
+#[auto_impl(&, Box, Arc)]
+pub trait OptimisticIsm: HyperlaneContract + Send + Sync + Debug {
+    /// Returns the ISM which pre-verifies the message
+    async fn submodule(&self, message: &HyperlaneMessage) -> ChainResult<H256>;
+
+    /// Returns the number of seconds after a message is pre-verified before it
+    /// can be delivered
+    async fn fraud_window(&self) -> ChainResult<u64>;
+
+    /// Returns the unix timestamp at which the message was pre-verified, or
+    /// `None` if it has not been
+    async fn pre_verified_at(&self, message: &HyperlaneMessage) -> ChainResult<Option<u64>>;
+
+    /// Pre-verify a message with metadata for the submodule, starting its
+    /// fraud window
+    async fn pre_verify(
+        &self,
+        message: &HyperlaneMessage,
+        metadata: &[u8],
+        tx_gas_limit: Option<U256>,
+    ) -> ChainResult<TxOutcome>;
+
+    /// Estimate transaction costs to pre-verify a message
+    async fn pre_verify_estimate_costs(
+        &self,
+        message: &HyperlaneMessage,
+        metadata: &[u8],
+    ) -> ChainResult<TxCostEstimate>;
+}
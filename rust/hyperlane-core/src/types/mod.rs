@@ -222,3 +222,66 @@ impl TxCostEstimate {
         self.l2_gas_limit.unwrap_or(self.gas_limit)
     }
 }
+
+/// A dispatched transaction that has not been included yet, along with the
+/// replacements sent for it. They all share its nonce.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InflightTransaction {
+    /// The nonce of the transactions.
+    pub nonce: U256,
+    /// The gas price of the last transaction sent, or its max fee per gas if
+    /// it is an EIP-1559 transaction.
+    pub gas_price: U256,
+    /// The max priority fee per gas of the last transaction sent if it is an
+    /// EIP-1559 transaction.
+    pub max_priority_fee_per_gas: Option<U256>,
+    /// Hashes of the transactions sent, oldest first.
+    pub tx_hashes: Vec<H256>,
+}
+
+impl Encode for InflightTransaction {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: Write,
+    {
+        let mut written = self.nonce.write_to(writer)?
+            + self.gas_price.write_to(writer)?
+            + self.max_priority_fee_per_gas.is_some().write_to(writer)?;
+        if let Some(max_priority_fee_per_gas) = self.max_priority_fee_per_gas {
+            written += max_priority_fee_per_gas.write_to(writer)?;
+        }
+        written += (self.tx_hashes.len() as u32).write_to(writer)?;
+        for tx_hash in &self.tx_hashes {
+            written += tx_hash.write_to(writer)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Decode for InflightTransaction {
+    fn read_from<R>(reader: &mut R) -> Result<Self, HyperlaneProtocolError>
+    where
+        R: Read,
+        Self: Sized,
+    {
+        let nonce = U256::read_from(reader)?;
+        let gas_price = U256::read_from(reader)?;
+        let max_priority_fee_per_gas = if bool::read_from(reader)? {
+            Some(U256::read_from(reader)?)
+        } else {
+            None
+        };
+        let len = u32::read_from(reader)?;
+        // Not preallocated, a corrupt length fails on reading past the end
+        let mut tx_hashes = vec![];
+        for _ in 0..len {
+            tx_hashes.push(H256::read_from(reader)?);
+        }
+        Ok(Self {
+            nonce,
+            gas_price,
+            max_priority_fee_per_gas,
+            tx_hashes,
+        })
+    }
+}
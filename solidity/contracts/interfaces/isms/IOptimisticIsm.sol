@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+pragma solidity >=0.8.0;
+
+import {IInterchainSecurityModule} from "../IInterchainSecurityModule.sol";
+
+interface IOptimisticIsm is IInterchainSecurityModule {
+    /**
+     * @notice Pre-verifies _message using the submodule, starting its fraud
+     * window
+     * @param _metadata Metadata for the submodule verifying _message
+     * @param _message Formatted Hyperlane message (see Message.sol).
+     * @return True if the message was pre-verified
+     */
+    function preVerify(bytes calldata _metadata, bytes calldata _message)
+        external
+        returns (bool);
+
+    /**
+     * @notice Marks a submodule as fraudulent, so messages it pre-verified
+     * can no longer be verified
+     * @param _submodule The submodule to mark as fraudulent
+     */
+    function markFraudulent(address _submodule) external;
+
+    /**
+     * @notice Returns the ISM responsible for pre-verifying _message
+     * @param _message Formatted Hyperlane message (see Message.sol).
+     * @return module The ISM to pre-verify _message with
+     */
+    function submodule(bytes calldata _message)
+        external
+        view
+        returns (IInterchainSecurityModule);
+
+    /**
+     * @notice Returns the number of seconds after a message is pre-verified
+     * before it can be verified
+     */
+    function fraudWindow() external view returns (uint256);
+
+    /**
+     * @notice Returns the timestamp at which a message was pre-verified
+     * @param _id The id of the message
+     * @return The timestamp, or 0 if the message has not been pre-verified
+     */
+    function preVerifiedAt(bytes32 _id) external view returns (uint256);
+}
@@ -70,6 +70,39 @@ export const AgentSignerSchema = z.union([
     .describe(
       'An AWS signer. Note that AWS credentials must be inserted into the env separately.',
     ),
+  z
+    .object({
+      type: z.literal('remote'),
+      url: z.string().url().describe('The base URL of the signing service'),
+      token: z
+        .string()
+        .optional()
+        .describe('A bearer token to authenticate to the service with'),
+      clientCert: z
+        .string()
+        .optional()
+        .describe(
+          'Path to the PEM encoded client certificate to authenticate with over mutual TLS',
+        ),
+      clientKey: z
+        .string()
+        .optional()
+        .describe(
+          'Path to the PEM encoded private key of the client certificate',
+        ),
+      caCert: z
+        .string()
+        .optional()
+        .describe(
+          "Path to the PEM encoded CA certificate to trust the service's certificate with",
+        ),
+      timeout: ZNzUint.optional().describe(
+        'Seconds to wait for the service to respond, 30 by default',
+      ),
+    })
+    .describe(
+      'A signer using a key held by a signing service reached over HTTP, e.g. one in front of an HSM or Vault',
+    ),
   z
     .object({
       type: z.literal('node'),
@@ -120,6 +153,40 @@ export const AgentChainMetadataSchema = ChainMetadataSchema.merge(
         'The indexing method to use for this chain; will attempt to choose a suitable default if not specified.',
       ),
   }),
+  txEscalation: z
+    .object({
+      timeout: ZNzUint.optional().describe(
+        'Seconds to wait for a transaction to be included before replacing it; defaults to 180.',
+      ),
+      bumpPercent: ZNzUint.optional().describe(
+        'Percentage to increase the gas price by with each replacement; defaults to 12.',
+      ),
+      maxFeePerGas: ZUWei.describe(
+        'The highest max fee per gas, in wei, a replacement transaction may pay.',
+      ),
+      maxEscalations: ZUint.optional().describe(
+        'Max number of times a transaction will be replaced; defaults to 5.',
+      ),
+    })
+    .optional()
+    .describe(
+      'Replace transactions which are not included in time with ones using the same nonce and a higher gas price. Only supported for Ethereum chains.',
+    ),
+  multicallAddress: ZHash.optional().describe(
+    'The address of the Multicall3 contract messages are batched through; defaults to the canonical Multicall3 address. Only used for Ethereum chains.',
+  ),
+  deployments: z
+    .record(
+      HyperlaneDeploymentArtifactsSchema.pick({
+        mailbox: true,
+        interchainGasPaymaster: true,
+        validatorAnnounce: true,
+      }),
+    )
+    .optional()
+    .describe(
+      'Contract addresses of additional mailbox deployments on this chain, by deployment name. Messages are relayed between the mailboxes of the same deployment.',
+    ),
 });
 
 export type AgentChainMetadata = z.infer<typeof AgentChainMetadataSchema>;
@@ -192,6 +259,30 @@ const GasPaymentEnforcementSchema = z.union([
       'An optional matching list, any message that matches will use this policy. By default all messages will match.',
     ),
   }),
+  GasPaymentEnforcementBaseSchema.extend({
+    type: z.literal('profitability'),
+    priceSource: z
+      .discriminatedUnion('type', [
+        z.object({
+          type: z.literal('file'),
+          path: z.string().nonempty(),
+        }),
+        z.object({
+          type: z.literal('http'),
+          url: z.string().url(),
+        }),
+      ])
+      .describe(
+        'Where to get native token prices from, as a JSON object keyed by domain id, e.g. `{"1": {"priceUsd": 1850.5, "decimals": 18}}`.',
+      ),
+    margin: z
+      .number()
+      .nonnegative()
+      .optional()
+      .describe(
+        'Fraction of the delivery cost that must be paid on top of it. Defaults to 0.',
+      ),
+  }),
 ]);
 
 export type GasPaymentEnforcement = z.infer<typeof GasPaymentEnforcementSchema>;